@@ -1,4 +1,55 @@
+#[path = "build/hid.rs"]
+mod hid;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Input reports of `pedalbox_hid.xml`, with the names of their structs and fields.
+const INPUT_REPORTS: &[hid::InputReport] = &[
+    hid::InputReport {
+        id: 0x01,
+        id_const: "PEDALBOX_REPORT_ID",
+        name: "PedalboxReport",
+        fields: &["x", "y", "z", "buttons"],
+    },
+    hid::InputReport {
+        id: 0x02,
+        id_const: "STATUS_REPORT_ID",
+        name: "StatusReport",
+        fields: &[
+            "faults_x",
+            "faults_y",
+            "faults_z",
+            "temperature",
+            "version_major",
+            "version_minor",
+            "version_patch",
+            "uptime_s",
+        ],
+    },
+    hid::InputReport {
+        id: 0x03,
+        id_const: "DIAGNOSTICS_REPORT_ID",
+        name: "DiagnosticsReport",
+        fields: &["raw_x", "raw_y", "raw_z"],
+    },
+];
+
 fn main() {
+    let out = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    fs::write(out.join("memory.x"), include_bytes!("memory.x")).unwrap();
+    println!("cargo:rustc-link-search={}", out.display());
+    println!("cargo:rerun-if-changed=memory.x");
+    println!("cargo:rerun-if-changed=build.rs");
+
+    let xml = fs::read_to_string("pedalbox_hid.xml").unwrap();
+    let code = hid::generate(&xml, "PEDALBOX_REPORT_DESCRIPTOR", INPUT_REPORTS)
+        .unwrap_or_else(|error| panic!("pedalbox_hid.xml: {error}"));
+    fs::write(out.join("pedalbox_hid.rs"), code).unwrap();
+    println!("cargo:rerun-if-changed=pedalbox_hid.xml");
+    println!("cargo:rerun-if-changed=build/hid.rs");
+
     println!("cargo:rustc-link-arg-bins=--nmagic");
     println!("cargo:rustc-link-arg-bins=-Tlink.x");
     #[cfg(feature = "defmt")]
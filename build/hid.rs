@@ -0,0 +1,796 @@
+//! Compiles `pedalbox_hid.xml`, written against the hidrd XML schema (`hidrd.xsd`), into
+//! calls of the `hid_descriptor` builder and the structs of its input reports, each checked
+//! against the descriptor by a static assertion.
+//!
+//! Only the items the pedalbox needs are supported, anything else fails the build instead of
+//! being silently dropped.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// An input report of the descriptor with the names of its struct and fields.
+pub struct InputReport {
+    pub id: u8,
+    pub id_const: &'static str,
+    pub name: &'static str,
+    /// One name per field, a field being a value of 8, 16 or 32 bits or a whole input item
+    /// of smaller values packed into 8, 16 or 32 bits.
+    pub fields: &'static [&'static str],
+}
+
+/// Compiles `xml` to Rust code holding `descriptor_const` and the structs of `reports`.
+pub fn generate(
+    xml: &str,
+    descriptor_const: &str,
+    reports: &[InputReport],
+) -> Result<String, String> {
+    let root = Parser::new(xml).document()?;
+    if root.name != "descriptor" {
+        return Err(format!(
+            "line {}: the root element is `{}`, not `descriptor`",
+            root.line, root.name
+        ));
+    }
+    let mut compiler = Compiler::default();
+    for element in &root.children {
+        compiler.element(element)?;
+    }
+    compiler.finish()?;
+
+    let mut code =
+        String::from("// Generated by build.rs from pedalbox_hid.xml, edit that file instead.\n\n");
+    let builder = format!("{descriptor_const}_BUILDER");
+    writeln!(code, "use crate::hid_descriptor as hid;\n").unwrap();
+    writeln!(
+        code,
+        "const {builder}: hid::ReportDescriptor = hid::ReportDescriptor::new()"
+    )
+    .unwrap();
+    for item in &compiler.items {
+        writeln!(code, "    .{} // {}", item.call, item.description).unwrap();
+    }
+    writeln!(code, "    .finish();\n").unwrap();
+    writeln!(
+        code,
+        "/// Report descriptor compiled from `pedalbox_hid.xml`."
+    )
+    .unwrap();
+    writeln!(
+        code,
+        "pub const {descriptor_const}: &[u8] = &{builder}.to_array::<{{ {builder}.len() }}>();"
+    )
+    .unwrap();
+
+    for (kind, id) in compiler.reports.keys() {
+        if *kind == MainKind::Input && !reports.iter().any(|report| report.id == *id) {
+            return Err(format!("input report {id} has no struct in build.rs"));
+        }
+    }
+    for report in reports {
+        code.push('\n');
+        code.push_str(&compiler.report_struct(report, &builder)?);
+    }
+    Ok(code)
+}
+
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Element>,
+    text: String,
+    line: usize,
+}
+
+impl Element {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(attribute, _)| attribute == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn error(&self, message: impl std::fmt::Display) -> String {
+        format!("line {}: <{}>: {message}", self.line, self.name)
+    }
+}
+
+/// Just enough XML for the descriptor: elements, attributes, text, comments and the
+/// declaration.
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Self {
+        Self { text, position: 0 }
+    }
+
+    fn document(&mut self) -> Result<Element, String> {
+        self.skip_misc()?;
+        let root = self.element()?;
+        self.skip_misc()?;
+        if !self.rest().is_empty() {
+            return Err(self.error("content after the root element"));
+        }
+        Ok(root)
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn line(&self) -> usize {
+        self.text[..self.position].matches('\n').count() + 1
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("line {}: {message}", self.line())
+    }
+
+    /// Skips whitespace, comments and processing instructions.
+    fn skip_misc(&mut self) -> Result<(), String> {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.position += rest.len() - trimmed.len();
+            if trimmed.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if trimmed.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Moves past `end` and returns what came before it.
+    fn skip_past(&mut self, end: &str) -> Result<&'a str, String> {
+        let rest = self.rest();
+        let length = rest
+            .find(end)
+            .ok_or_else(|| self.error(&format!("missing `{end}`")))?;
+        self.position += length + end.len();
+        Ok(&rest[..length])
+    }
+
+    fn element(&mut self) -> Result<Element, String> {
+        let line = self.line();
+        if !self.rest().starts_with('<') {
+            return Err(self.error("expected an element"));
+        }
+        self.position += 1;
+        let name_length = self
+            .rest()
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .ok_or_else(|| self.error("unterminated tag"))?;
+        let name = self.rest()[..name_length].to_string();
+        self.position += name_length;
+
+        let mut attributes = Vec::new();
+        let empty = loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.position += rest.len() - trimmed.len();
+            if let Some(after) = trimmed.strip_prefix("/>") {
+                self.position = self.text.len() - after.len();
+                break true;
+            }
+            if let Some(after) = trimmed.strip_prefix('>') {
+                self.position = self.text.len() - after.len();
+                break false;
+            }
+            let attribute = self.skip_past("=")?.trim().to_string();
+            let quote = self
+                .rest()
+                .chars()
+                .next()
+                .filter(|c| *c == '"' || *c == '\'')
+                .ok_or_else(|| self.error("attribute values must be quoted"))?;
+            self.position += 1;
+            let value = decode_entities(self.skip_past(&quote.to_string())?);
+            attributes.push((attribute, value));
+        };
+
+        let mut element = Element {
+            name,
+            attributes,
+            children: Vec::new(),
+            text: String::new(),
+            line,
+        };
+        if empty {
+            return Ok(element);
+        }
+        loop {
+            let rest = self.rest();
+            let text_length = rest
+                .find('<')
+                .ok_or_else(|| self.error(&format!("<{}> isn't closed", element.name)))?;
+            element
+                .text
+                .push_str(&decode_entities(&rest[..text_length]));
+            self.position += text_length;
+            if self.rest().starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if self.rest().starts_with("</") {
+                self.position += 2;
+                let closing = self.skip_past(">")?.trim();
+                if closing != element.name {
+                    return Err(
+                        self.error(&format!("<{}> is closed by </{closing}>", element.name))
+                    );
+                }
+                element.text = element.text.trim().to_string();
+                return Ok(element);
+            } else {
+                element.children.push(self.element()?);
+            }
+        }
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Usage page tokens of `hidrd.xsd`.
+const USAGE_PAGES: &[(&str, u16)] = &[
+    ("undefined", 0x00),
+    ("desktop", 0x01),
+    ("simulation", 0x02),
+    ("vr", 0x03),
+    ("sport", 0x04),
+    ("game", 0x05),
+    ("device", 0x06),
+    ("keyboard", 0x07),
+    ("led", 0x08),
+    ("button", 0x09),
+    ("ordinal", 0x0A),
+    ("telephony", 0x0B),
+    ("consumer", 0x0C),
+    ("digitizer", 0x0D),
+    ("pid", 0x0F),
+    ("unicode", 0x10),
+    ("alnum_display", 0x14),
+    ("medical", 0x40),
+    ("monitor", 0x80),
+    ("monitor_enum", 0x81),
+    ("monitor_vesa_vcp", 0x82),
+    ("power_device", 0x84),
+    ("power_batsys", 0x85),
+    ("pos_bcs", 0x8C),
+    ("pos_scale", 0x8D),
+    ("pos_msr", 0x8E),
+    ("camera", 0x90),
+    ("arcade", 0x91),
+];
+
+/// The usage tokens of the generic desktop page the build script knows, other usages have to
+/// be given in hex.
+const DESKTOP_USAGES: &[(&str, u16)] = &[
+    ("desktop_pointer", 0x01),
+    ("desktop_mouse", 0x02),
+    ("desktop_joystick", 0x04),
+    ("desktop_gamepad", 0x05),
+    ("desktop_keyboard", 0x06),
+    ("desktop_keypad", 0x07),
+    ("desktop_multi_axis_ctrl", 0x08),
+    ("desktop_x", 0x30),
+    ("desktop_y", 0x31),
+    ("desktop_z", 0x32),
+    ("desktop_rx", 0x33),
+    ("desktop_ry", 0x34),
+    ("desktop_rz", 0x35),
+    ("desktop_slider", 0x36),
+    ("desktop_dial", 0x37),
+    ("desktop_wheel", 0x38),
+    ("desktop_hat_switch", 0x39),
+];
+
+const COLLECTION_TYPES: &[(&str, u8)] = &[
+    ("physical", 0x00),
+    ("application", 0x01),
+    ("logical", 0x02),
+    ("report", 0x03),
+    ("named_array", 0x04),
+    ("usage_switch", 0x05),
+    ("usage_modifier", 0x06),
+];
+
+/// Bits of the main items, in the order `hidrd.xsd` wants them. Each bit is set by its
+/// `bitN` element or by the second of its names, and cleared by the first one.
+const MAIN_BITS: &[(&str, &str)] = &[
+    ("data", "constant"),
+    ("array", "variable"),
+    ("absolute", "relative"),
+    ("no_wrap", "wrap"),
+    ("linear", "non_linear"),
+    ("preferred_state", "no_preferred"),
+    ("no_null_position", "null_state"),
+    ("non_volatile", "volatile"),
+    ("bit_field", "buffered_bytes"),
+];
+const CONSTANT: u32 = 1 << 0;
+const VARIABLE: u32 = 1 << 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum MainKind {
+    Input,
+    Output,
+    Feature,
+}
+
+/// A call of the descriptor builder.
+struct Item {
+    call: String,
+    description: String,
+}
+
+/// A main item of a report.
+struct Field {
+    offset: u32,
+    size: u32,
+    count: u32,
+    flags: u32,
+    logical_minimum: i32,
+    logical_maximum: i32,
+    usages: Vec<String>,
+    line: usize,
+}
+
+#[derive(Default)]
+struct Compiler {
+    items: Vec<Item>,
+    usage_page: Option<u16>,
+    logical_minimum: Option<i32>,
+    logical_maximum: Option<i32>,
+    report_size: Option<u32>,
+    report_count: Option<u32>,
+    report_id: Option<u8>,
+    /// Local items, cleared by every main item.
+    usages: Vec<String>,
+    usage_minimum: Option<u32>,
+    usage_maximum: Option<u32>,
+    depth: usize,
+    reports: BTreeMap<(MainKind, u8), Vec<Field>>,
+}
+
+impl Compiler {
+    fn element(&mut self, element: &Element) -> Result<(), String> {
+        let name = element.name.as_str();
+        if name != "COLLECTION" && !element.children.is_empty() && !is_main_data(name) {
+            return Err(element.error("unexpected child elements"));
+        }
+        match name {
+            "usage_page" => {
+                let page = parse_usage_page(element)?;
+                self.usage_page = Some(page);
+                self.call(element, format!("usage_page(hid::UsagePage({page:#06X}))"));
+            }
+            "usage" => {
+                let usage = self.parse_usage(element)?;
+                self.usages.push(element.text.clone());
+                self.call(element, format!("usage({usage:#04X})"));
+            }
+            "usage_minimum" => {
+                let usage = self.parse_usage(element)?;
+                self.usage_minimum = Some(usage);
+                self.call(element, format!("usage_minimum({usage:#04X})"));
+            }
+            "usage_maximum" => {
+                let usage = self.parse_usage(element)?;
+                self.usage_maximum = Some(usage);
+                self.call(element, format!("usage_maximum({usage:#04X})"));
+            }
+            "logical_minimum" => {
+                let value = parse_number(element)?;
+                self.logical_minimum = Some(value);
+                self.call(element, format!("logical_minimum({value})"));
+            }
+            "logical_maximum" => {
+                let value = parse_number(element)?;
+                self.logical_maximum = Some(value);
+                self.call(element, format!("logical_maximum({value})"));
+            }
+            "physical_minimum" | "physical_maximum" => {
+                let value = parse_number(element)?;
+                self.call(element, format!("{name}({value})"));
+            }
+            "report_size" => {
+                let value = parse_number(element)?;
+                if !(1..=32).contains(&value) {
+                    return Err(element.error("report sizes go from 1 to 32 bits"));
+                }
+                self.report_size = Some(value as u32);
+                self.call(element, format!("report_size({value})"));
+            }
+            "report_id" => {
+                let value = parse_number(element)?;
+                let id = u8::try_from(value)
+                    .ok()
+                    .filter(|id| *id >= 1)
+                    .ok_or_else(|| element.error("report IDs go from 1 to 255"))?;
+                self.report_id = Some(id);
+                self.call(element, format!("report_id({id})"));
+            }
+            "report_count" => {
+                let value = parse_number(element)?;
+                if value < 1 {
+                    return Err(element.error("the report count must be at least 1"));
+                }
+                self.report_count = Some(value as u32);
+                self.call(element, format!("report_count({value})"));
+            }
+            "input" => self.main_data(element, MainKind::Input)?,
+            "output" => self.main_data(element, MainKind::Output)?,
+            "feature" => self.main_data(element, MainKind::Feature)?,
+            "collection" => self.collection(element)?,
+            "end_collection" => self.end_collection(element)?,
+            "COLLECTION" => {
+                self.collection(element)?;
+                for child in &element.children {
+                    self.element(child)?;
+                }
+                self.end_collection(element)?;
+            }
+            _ => return Err(element.error("isn't supported by the build script")),
+        }
+        Ok(())
+    }
+
+    fn finish(&self) -> Result<(), String> {
+        if self.depth != 0 {
+            return Err(format!("{} collections aren't closed", self.depth));
+        }
+        let numbered = self.reports.keys().any(|(_, id)| *id != 0);
+        if numbered && self.reports.keys().any(|(_, id)| *id == 0) {
+            return Err("main items come before the first report ID".to_string());
+        }
+        for ((kind, id), fields) in &self.reports {
+            let bits = fields.last().map_or(0, |f| f.offset + f.size * f.count);
+            if bits % 8 != 0 {
+                return Err(format!(
+                    "{kind:?} report {id} has {bits} bits, which aren't whole bytes"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn report_struct(&self, report: &InputReport, builder: &str) -> Result<String, String> {
+        let fields = self
+            .reports
+            .get(&(MainKind::Input, report.id))
+            .ok_or_else(|| {
+                format!(
+                    "{} is input report {}, which isn't in the descriptor",
+                    report.name, report.id
+                )
+            })?;
+        let mut names = report.fields.iter();
+        let mut next_name = |field: &Field| {
+            names.next().ok_or_else(|| {
+                format!(
+                    "line {}: {} has fewer field names than input report {} has fields",
+                    field.line, report.name, report.id
+                )
+            })
+        };
+
+        let mut code = String::new();
+        writeln!(
+            code,
+            "pub const {}: u8 = 0x{:02X};\n",
+            report.id_const, report.id
+        )
+        .unwrap();
+        writeln!(
+            code,
+            "/// Layout of input report {} including its report ID, see the report descriptor.",
+            report.id
+        )
+        .unwrap();
+        writeln!(code, "#[repr(C, packed)]").unwrap();
+        writeln!(
+            code,
+            "#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]"
+        )
+        .unwrap();
+        writeln!(code, "pub struct {} {{", report.name).unwrap();
+        writeln!(code, "    pub id: u8,").unwrap();
+        for field in fields {
+            if field.offset % 8 != 0 {
+                return Err(format!(
+                    "line {}: a field of {} starts at bit {}, not on a byte",
+                    field.line, report.name, field.offset
+                ));
+            }
+            let signedness = if field.logical_minimum < 0 { 'i' } else { 'u' };
+            let range = format!("{} to {}", field.logical_minimum, field.logical_maximum);
+            if field.flags & CONSTANT != 0 {
+                let bits = field.size * field.count;
+                if bits % 8 != 0 {
+                    return Err(format!(
+                        "line {}: padding of {bits} bits isn't whole bytes",
+                        field.line
+                    ));
+                }
+                writeln!(code, "    /// Padding.").unwrap();
+                writeln!(code, "    pub {}: [u8; {}],", next_name(field)?, bits / 8).unwrap();
+            } else if matches!(field.size, 8 | 16 | 32) {
+                for index in 0..field.count as usize {
+                    let usage = field.usages.get(index).or(field.usages.last());
+                    let usage = usage.map_or(String::new(), |usage| format!("`{usage}`, "));
+                    writeln!(code, "    /// {usage}{range}.").unwrap();
+                    writeln!(
+                        code,
+                        "    pub {}: {signedness}{},",
+                        next_name(field)?,
+                        field.size
+                    )
+                    .unwrap();
+                }
+            } else {
+                let bits = field.size * field.count;
+                if !matches!(bits, 8 | 16 | 32) {
+                    return Err(format!(
+                        "line {}: {} values of {} bits don't pack into 8, 16 or 32 bits",
+                        field.line, field.count, field.size
+                    ));
+                }
+                writeln!(
+                    code,
+                    "    /// {} values of {} bits each, {range}, the first one in the lowest bits.",
+                    field.count, field.size
+                )
+                .unwrap();
+                writeln!(code, "    pub {}: u{bits},", next_name(field)?).unwrap();
+            }
+        }
+        if names.next().is_some() {
+            return Err(format!(
+                "{} has more field names than input report {} has fields",
+                report.name, report.id
+            ));
+        }
+        writeln!(code, "}}\n").unwrap();
+        writeln!(code, "const _: () = assert!(").unwrap();
+        writeln!(
+            code,
+            "    {builder}.report_length(hid::ReportKind::Input, {}) == core::mem::size_of::<{}>(),",
+            report.id_const, report.name
+        )
+        .unwrap();
+        writeln!(
+            code,
+            "    \"{} doesn't match input report {} of the descriptor\"",
+            report.name, report.id
+        )
+        .unwrap();
+        writeln!(code, ");").unwrap();
+        Ok(code)
+    }
+
+    fn main_data(&mut self, element: &Element, kind: MainKind) -> Result<(), String> {
+        let mut flags = 0;
+        let mut last_bit = None;
+        for child in &element.children {
+            let (bit, set) = parse_main_bit(child, kind)?;
+            if last_bit.is_some_and(|last| bit <= last) {
+                return Err(child.error("main item bits must be given once and in order"));
+            }
+            last_bit = Some(bit);
+            if set {
+                flags |= 1 << bit;
+            }
+        }
+
+        let size = self
+            .report_size
+            .ok_or_else(|| element.error("comes before any report_size"))?;
+        let count = self
+            .report_count
+            .ok_or_else(|| element.error("comes before any report_count"))?;
+        let minimum = self.logical_minimum.unwrap_or(0);
+        let maximum = self.logical_maximum.unwrap_or(0);
+        if flags & CONSTANT == 0 {
+            if self.logical_minimum.is_none() || self.logical_maximum.is_none() {
+                return Err(element.error("needs a logical_minimum and a logical_maximum"));
+            }
+            if minimum > maximum {
+                return Err(element.error(format!(
+                    "the logical minimum {minimum} is above the maximum {maximum}"
+                )));
+            }
+            let (low, high) = if minimum < 0 {
+                (-(1_i64 << (size - 1)), (1_i64 << (size - 1)) - 1)
+            } else {
+                (0, (1_i64 << size) - 1)
+            };
+            if (minimum as i64) < low || (maximum as i64) > high {
+                return Err(
+                    element.error(format!("{minimum} to {maximum} doesn't fit in {size} bits"))
+                );
+            }
+            if self.usages.is_empty() && self.usage_minimum.is_none() {
+                return Err(element.error("has no usage"));
+            }
+            if self.usage_minimum.is_some() != self.usage_maximum.is_some() {
+                return Err(element.error("needs both usage_minimum and usage_maximum"));
+            }
+            if flags & VARIABLE != 0 && self.usages.len() > count as usize {
+                return Err(element.error(format!(
+                    "has {} usages for {count} values",
+                    self.usages.len()
+                )));
+            }
+        }
+
+        let fields = self
+            .reports
+            .entry((kind, self.report_id.unwrap_or(0)))
+            .or_default();
+        let offset = fields.last().map_or(0, |f| f.offset + f.size * f.count);
+        fields.push(Field {
+            offset,
+            size,
+            count,
+            flags,
+            logical_minimum: minimum,
+            logical_maximum: maximum,
+            usages: std::mem::take(&mut self.usages),
+            line: element.line,
+        });
+        self.clear_locals();
+
+        let mut call = format!("{}(hid::MainFlags::DATA", element.name);
+        for (bit, (_, set)) in MAIN_BITS.iter().enumerate() {
+            if flags & 1 << bit != 0 {
+                call.push_str(&format!(".{set}()"));
+            }
+        }
+        if flags >> MAIN_BITS.len() != 0 {
+            return Err(element.error("sets a reserved bit"));
+        }
+        call.push(')');
+        self.call(element, call);
+        Ok(())
+    }
+
+    fn collection(&mut self, element: &Element) -> Result<(), String> {
+        let kind = element
+            .attribute("type")
+            .ok_or_else(|| element.error("needs a type"))?;
+        let variant = COLLECTION_TYPES
+            .iter()
+            .find(|(name, value)| *name == kind || kind.parse() == Ok(*value))
+            .map(|(name, _)| name.split('_').map(capitalize).collect::<String>())
+            .ok_or_else(|| element.error(format!("unknown collection type `{kind}`")))?;
+        self.depth += 1;
+        self.clear_locals();
+        self.items.push(Item {
+            call: format!("collection(hid::Collection::{variant})"),
+            description: format!("collection ({kind})"),
+        });
+        Ok(())
+    }
+
+    fn end_collection(&mut self, element: &Element) -> Result<(), String> {
+        self.depth = self
+            .depth
+            .checked_sub(1)
+            .ok_or_else(|| element.error("closes a collection that isn't open"))?;
+        self.items.push(Item {
+            call: "end_collection()".to_string(),
+            description: "end_collection".to_string(),
+        });
+        Ok(())
+    }
+
+    fn clear_locals(&mut self) {
+        self.usages.clear();
+        self.usage_minimum = None;
+        self.usage_maximum = None;
+    }
+
+    fn parse_usage(&self, element: &Element) -> Result<u32, String> {
+        let text = element.text.as_str();
+        if let Some((_, usage)) = DESKTOP_USAGES.iter().find(|(name, _)| *name == text) {
+            if self.usage_page != Some(0x01) {
+                return Err(element.error(format!("`{text}` needs the desktop usage page")));
+            }
+            return Ok(*usage as u32);
+        }
+        if text.is_empty() || text.len() > 8 || !text.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(element.error(format!(
+                "`{text}` is neither a known usage token nor 1 to 8 hex digits"
+            )));
+        }
+        if self.usage_page.is_none() && text.len() <= 4 {
+            return Err(element.error("comes before any usage_page"));
+        }
+        Ok(u32::from_str_radix(text, 16).unwrap())
+    }
+
+    fn call(&mut self, element: &Element, call: String) {
+        let flags: Vec<&str> = element.children.iter().map(|c| c.name.as_str()).collect();
+        let value = if is_main_data(&element.name) {
+            flags.join(", ")
+        } else {
+            element.text.clone()
+        };
+        self.items.push(Item {
+            call,
+            description: format!("{} ({value})", element.name),
+        });
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut characters = word.chars();
+    characters.next().map_or(String::new(), |first| {
+        first.to_ascii_uppercase().to_string() + characters.as_str()
+    })
+}
+
+fn is_main_data(name: &str) -> bool {
+    matches!(name, "input" | "output" | "feature")
+}
+
+fn parse_usage_page(element: &Element) -> Result<u16, String> {
+    let text = element.text.as_str();
+    if let Some((_, page)) = USAGE_PAGES.iter().find(|(name, _)| *name == text) {
+        return Ok(*page);
+    }
+    if text.is_empty() || text.len() > 4 || !text.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(element.error(format!(
+            "`{text}` is neither a usage page token nor 1 to 4 hex digits"
+        )));
+    }
+    Ok(u16::from_str_radix(text, 16).unwrap())
+}
+
+fn parse_number(element: &Element) -> Result<i32, String> {
+    element
+        .text
+        .parse()
+        .map_err(|_| element.error(format!("`{}` isn't a 32-bit integer", element.text)))
+}
+
+fn parse_boolean(element: &Element) -> Result<bool, String> {
+    match element.text.as_str() {
+        "" | "1" | "true" => Ok(true),
+        "0" | "false" => Ok(false),
+        text => Err(element.error(format!("`{text}` isn't a boolean"))),
+    }
+}
+
+/// Returns the bit a child of a main item sets, and whether it is set.
+fn parse_main_bit(element: &Element, kind: MainKind) -> Result<(u32, bool), String> {
+    let value = parse_boolean(element)?;
+    if let Some(bit) = element.name.strip_prefix("bit") {
+        let bit: u32 = bit
+            .parse()
+            .ok()
+            .filter(|bit| *bit < 32)
+            .ok_or_else(|| element.error("isn't a main item bit"))?;
+        return Ok((bit, value));
+    }
+    for (bit, (cleared, set)) in MAIN_BITS.iter().enumerate() {
+        if bit == 7 && kind == MainKind::Input {
+            continue;
+        }
+        if element.name == *cleared {
+            return Ok((bit as u32, !value));
+        }
+        if element.name == *set {
+            return Ok((bit as u32, value));
+        }
+    }
+    Err(element.error(format!("isn't a bit of {kind:?} items")))
+}
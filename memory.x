@@ -0,0 +1,7 @@
+/* STM32F407VG. The last two 128K flash sectors (0x080C0000 and 0x080E0000) hold the
+   calibration records, so they are left out of the program area. */
+MEMORY
+{
+  FLASH : ORIGIN = 0x08000000, LENGTH = 768K
+  RAM   : ORIGIN = 0x20000000, LENGTH = 128K
+}
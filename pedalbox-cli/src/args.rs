@@ -0,0 +1,270 @@
+//! Command line parsing.
+
+use rusty_pedalbox::axis::Axis;
+use rusty_pedalbox::calibration::CommitTarget;
+use rusty_pedalbox::curve::{CurvePoint, CurvePoints, ResponseCurve};
+use rusty_pedalbox::feature_report::SessionCommand;
+use rusty_pedalbox::report_mode::ReportMode;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: pedalbox-cli [--device <path>] <command>
+
+Commands:
+  list                              List the connected pedalboxes
+  monitor                           Show the live axis values
+  status                            Show the faults, temperature, firmware version and uptime
+  diagnostics                       Show the live unmapped sensor readings
+  show [gas|brake|clutch]           Show the settings of the axes
+  set-range <axis> <min> <max>      Set the calibrated range of an axis
+  set-curve <axis> <curve>          Set the response curve of an axis
+  calibrate <start|commit|save|cancel|status>
+                                    Drive the interactive calibration
+  report-mode [periodic <interval_ms> | on-change <threshold> <heartbeat_ms>]
+                                    Show or select when the pedalbox sends reports
+  latency [reset]                   Show the report statistics, or reset the maximum latency
+  reset-cause                       Show why the pedalbox was last reset
+  update <firmware.bin>             Flash a firmware image through the bootloader
+  export <file.toml|file.json>      Save the settings of every axis to a profile
+  import <file.toml|file.json>      Load a profile into the pedalbox
+
+Curves: linear, progressive:<strength>, s-curve:<strength>,
+        piecewise:<x>/<y>,<x>/<y>,..., spline:<x>/<y>,<x>/<y>,...
+Axes can be given as gas, brake, clutch or x, y, z.";
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Command {
+    List,
+    Monitor,
+    Status,
+    Diagnostics,
+    Show(Option<Axis>),
+    SetRange { axis: Axis, min: i32, max: i32 },
+    SetCurve { axis: Axis, curve: ResponseCurve },
+    Calibrate(Option<SessionCommand>),
+    ReportMode(Option<ReportMode>),
+    Latency { reset: bool },
+    ResetCause,
+    Update(PathBuf),
+    Export(PathBuf),
+    Import(PathBuf),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Options {
+    pub device: Option<PathBuf>,
+    pub command: Command,
+}
+
+pub fn parse<I>(args: I) -> Result<Options, String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter().peekable();
+    let mut device = None;
+    if args.peek().map(String::as_str) == Some("--device") {
+        args.next();
+        device = Some(PathBuf::from(args.next().ok_or("--device needs a path")?));
+    }
+    let command = args.next().ok_or("missing command")?;
+    let mut next = |what: &str| args.next().ok_or(format!("missing {what}"));
+
+    let command = match command.as_str() {
+        "list" => Command::List,
+        "monitor" => Command::Monitor,
+        "status" => Command::Status,
+        "diagnostics" => Command::Diagnostics,
+        "show" => Command::Show(
+            next("axis")
+                .ok()
+                .map(|axis| parse_axis(&axis))
+                .transpose()?,
+        ),
+        "set-range" => Command::SetRange {
+            axis: parse_axis(&next("axis")?)?,
+            min: parse_number(&next("minimum")?)?,
+            max: parse_number(&next("maximum")?)?,
+        },
+        "set-curve" => Command::SetCurve {
+            axis: parse_axis(&next("axis")?)?,
+            curve: parse_curve(&next("curve")?)?,
+        },
+        "calibrate" => Command::Calibrate(match next("calibration command")?.as_str() {
+            "start" => Some(SessionCommand::Start),
+            "commit" => Some(SessionCommand::Commit(CommitTarget::Ram)),
+            "save" => Some(SessionCommand::Commit(CommitTarget::Persistent)),
+            "cancel" => Some(SessionCommand::Cancel),
+            "status" => None,
+            other => return Err(format!("unknown calibration command `{other}`")),
+        }),
+        "report-mode" => Command::ReportMode(match next("report mode").ok().as_deref() {
+            None => None,
+            Some("periodic") => Some(ReportMode::Periodic {
+                interval_ms: parse_number(&next("interval")?)?,
+            }),
+            Some("on-change") => Some(ReportMode::OnChange {
+                threshold: parse_number(&next("threshold")?)?,
+                heartbeat_ms: parse_number(&next("heartbeat")?)?,
+            }),
+            Some(other) => return Err(format!("unknown report mode `{other}`")),
+        }),
+        "latency" => Command::Latency {
+            reset: match next("latency command").ok().as_deref() {
+                None => false,
+                Some("reset") => true,
+                Some(other) => return Err(format!("unknown latency command `{other}`")),
+            },
+        },
+        "reset-cause" => Command::ResetCause,
+        "update" => Command::Update(next("firmware image")?.into()),
+        "export" => Command::Export(next("file")?.into()),
+        "import" => Command::Import(next("file")?.into()),
+        other => return Err(format!("unknown command `{other}`")),
+    };
+    Ok(Options { device, command })
+}
+
+pub fn parse_axis(text: &str) -> Result<Axis, String> {
+    match text {
+        "gas" | "x" => Ok(Axis::X),
+        "brake" | "y" => Ok(Axis::Y),
+        "clutch" | "z" => Ok(Axis::Z),
+        _ => Err(format!("unknown axis `{text}`")),
+    }
+}
+
+pub fn parse_curve(text: &str) -> Result<ResponseCurve, String> {
+    let (kind, parameter) = text.split_once(':').unwrap_or((text, ""));
+    let points = || -> Result<CurvePoints, String> {
+        let points = parameter
+            .split(',')
+            .map(|point| {
+                let (x, y) = point
+                    .split_once('/')
+                    .ok_or(format!("curve point `{point}` isn't <x>/<y>"))?;
+                Ok(CurvePoint::new(parse_number(x)?, parse_number(y)?))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        CurvePoints::new(&points).map_err(|e| format!("invalid curve points: {e:?}"))
+    };
+    match kind {
+        "linear" => Ok(ResponseCurve::Linear),
+        "progressive" => Ok(ResponseCurve::Progressive(parse_number(parameter)?)),
+        "s-curve" => Ok(ResponseCurve::SCurve(parse_number(parameter)?)),
+        "piecewise" => Ok(ResponseCurve::PiecewiseLinear(points()?)),
+        "spline" => Ok(ResponseCurve::CubicSpline(points()?)),
+        _ => Err(format!("unknown curve `{kind}`")),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(text: &str) -> Result<T, String> {
+    text.trim()
+        .parse()
+        .map_err(|_| format!("`{text}` isn't a valid number"))
+}
+
+#[cfg(test)]
+mod args_testing {
+    use crate::args::{parse, parse_curve, Command, Options};
+    use rstest::rstest;
+    use rusty_pedalbox::axis::Axis;
+    use rusty_pedalbox::calibration::CommitTarget;
+    use rusty_pedalbox::curve::{CurvePoint, CurvePoints, ResponseCurve};
+    use rusty_pedalbox::feature_report::SessionCommand;
+    use rusty_pedalbox::report_mode::ReportMode;
+    use std::path::PathBuf;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[rstest]
+    #[case("list", Command::List)]
+    #[case("show", Command::Show(None))]
+    #[case("show brake", Command::Show(Some(Axis::Y)))]
+    #[case("set-range gas 1820 3100", Command::SetRange { axis: Axis::X, min: 1820, max: 3100 })]
+    #[case("set-curve z progressive:-20", Command::SetCurve { axis: Axis::Z, curve: ResponseCurve::Progressive(-20) })]
+    #[case(
+        "calibrate save",
+        Command::Calibrate(Some(SessionCommand::Commit(CommitTarget::Persistent)))
+    )]
+    #[case("calibrate status", Command::Calibrate(None))]
+    #[case("report-mode", Command::ReportMode(None))]
+    #[case("report-mode periodic 4", Command::ReportMode(Some(ReportMode::Periodic { interval_ms: 4 })))]
+    #[case("report-mode on-change 16 100", Command::ReportMode(Some(ReportMode::OnChange { threshold: 16, heartbeat_ms: 100 })))]
+    #[case("latency reset", Command::Latency { reset: true })]
+    #[case("reset-cause", Command::ResetCause)]
+    #[case("status", Command::Status)]
+    #[case("diagnostics", Command::Diagnostics)]
+    #[case("update pedalbox.bin", Command::Update(PathBuf::from("pedalbox.bin")))]
+    #[case("export profile.toml", Command::Export(PathBuf::from("profile.toml")))]
+    fn when_command_line_is_parsed(#[case] line: &str, #[case] expected: Command) {
+        // When
+        let result = parse(args(line));
+
+        // Then
+        assert_eq!(
+            result,
+            Ok(Options {
+                device: None,
+                command: expected
+            })
+        );
+    }
+
+    #[test]
+    fn when_device_is_given_then_it_is_used() {
+        // When
+        let result = parse(args("--device /dev/hidraw4 monitor"));
+
+        // Then
+        assert_eq!(
+            result,
+            Ok(Options {
+                device: Some(PathBuf::from("/dev/hidraw4")),
+                command: Command::Monitor
+            })
+        );
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("fly")]
+    #[case("set-range gas 1820")]
+    #[case("set-range pedal 0 1")]
+    #[case("calibrate later")]
+    #[case("report-mode on-change 16")]
+    #[case("report-mode burst")]
+    #[case("update")]
+    #[case("--device")]
+    fn when_command_line_is_invalid(#[case] line: &str) {
+        // When
+        let result = parse(args(line));
+
+        // Then
+        assert!(result.is_err());
+    }
+
+    #[rstest]
+    #[case("linear", Ok(ResponseCurve::Linear))]
+    #[case("s-curve:35", Ok(ResponseCurve::SCurve(35)))]
+    #[case(
+        "spline:0/0,20000/10000,65535/65535",
+        Ok(ResponseCurve::CubicSpline(CurvePoints::new(&[
+            CurvePoint::new(0, 0),
+            CurvePoint::new(20_000, 10_000),
+            CurvePoint::new(65_535, 65_535),
+        ]).unwrap()))
+    )]
+    #[case("piecewise:0/0", Err(()))]
+    #[case("piecewise:0-0,10/10", Err(()))]
+    #[case("progressive", Err(()))]
+    #[case("exponential:3", Err(()))]
+    fn when_curve_is_parsed(#[case] text: &str, #[case] expected: Result<ResponseCurve, ()>) {
+        // When
+        let result = parse_curve(text);
+
+        // Then
+        assert_eq!(result.map_err(|_| ()), expected);
+    }
+}
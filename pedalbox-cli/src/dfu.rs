@@ -0,0 +1,546 @@
+//! Firmware updates through the DfuSe protocol of the STM32 ROM bootloader.
+
+use crate::usbdevfs::{ControlDevice, CLASS_INTERFACE_IN, CLASS_INTERFACE_OUT};
+use rusty_pedalbox::dfu::{
+    DfuRequest, DfuState, DfuStatus, BOOTLOADER_TRANSFER_SIZE, DFU_STATUS_LENGTH,
+};
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+/// Start of the internal flash, where the firmware image goes.
+pub const FLASH_START: u32 = 0x0800_0000;
+/// End of the program area. The last two sectors hold the calibration and an update never
+/// touches them.
+pub const PROGRAM_END: u32 = 0x080C_0000;
+
+/// Sizes of the flash sectors of the STM32F407, from sector 0.
+const SECTOR_SIZES: [u32; 12] = [
+    16 * 1024,
+    16 * 1024,
+    16 * 1024,
+    16 * 1024,
+    64 * 1024,
+    128 * 1024,
+    128 * 1024,
+    128 * 1024,
+    128 * 1024,
+    128 * 1024,
+    128 * 1024,
+    128 * 1024,
+];
+
+/// DfuSe commands, sent in block 0 of a download.
+const SET_ADDRESS_POINTER: u8 = 0x21;
+const ERASE_SECTOR: u8 = 0x41;
+/// First block carrying data, block `n` lands at the address pointer plus
+/// `(n - 2) * BOOTLOADER_TRANSFER_SIZE`.
+const FIRST_DATA_BLOCK: u16 = 2;
+
+/// Time the firmware gets to detach, sent with `DETACH`.
+const DETACH_TIMEOUT_MS: u16 = 1_000;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Step {
+    Erase,
+    Write,
+    Verify,
+}
+
+/// Asks a running firmware to reboot into the bootloader through its DFU runtime
+/// interface.
+pub fn detach<D: ControlDevice>(device: &mut D, interface: u8) -> io::Result<()> {
+    device.control_out(
+        CLASS_INTERFACE_OUT,
+        DfuRequest::Detach as u8,
+        DETACH_TIMEOUT_MS,
+        interface as u16,
+        &[],
+    )
+}
+
+/// Fails if `image` is empty or would overwrite the calibration.
+pub fn check_image(image: &[u8]) -> io::Result<()> {
+    let capacity = (PROGRAM_END - FLASH_START) as usize;
+    if image.is_empty() || image.len() > capacity {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "the image has {} bytes, it must have 1 to {capacity}",
+                image.len()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Start addresses of the sectors an image of `length` bytes overlaps.
+pub fn sectors_for(length: usize) -> Vec<u32> {
+    let end = FLASH_START + length as u32;
+    let mut sectors = Vec::new();
+    let mut start = FLASH_START;
+    for size in SECTOR_SIZES {
+        if start >= end {
+            break;
+        }
+        sectors.push(start);
+        start += size;
+    }
+    sectors
+}
+
+pub struct Dfuse<D> {
+    device: D,
+    interface: u16,
+}
+
+impl<D> Dfuse<D>
+where
+    D: ControlDevice,
+{
+    pub fn new(device: D, interface: u8) -> Self {
+        Self {
+            device,
+            interface: interface as u16,
+        }
+    }
+
+    /// Erases the sectors `image` needs, writes it from `FLASH_START` and reads it back.
+    /// `progress` gets the current step with the work done and to do.
+    pub fn update(
+        &mut self,
+        image: &[u8],
+        mut progress: impl FnMut(Step, usize, usize),
+    ) -> io::Result<()> {
+        check_image(image)?;
+        self.ensure_idle()?;
+
+        let sectors = sectors_for(image.len());
+        for (index, sector) in sectors.iter().enumerate() {
+            self.command(ERASE_SECTOR, *sector)?;
+            progress(Step::Erase, index + 1, sectors.len());
+        }
+
+        self.command(SET_ADDRESS_POINTER, FLASH_START)?;
+        let mut written = 0;
+        for (block, chunk) in
+            (FIRST_DATA_BLOCK..).zip(image.chunks(BOOTLOADER_TRANSFER_SIZE as usize))
+        {
+            self.request_out(DfuRequest::Download, block, chunk)?;
+            self.wait()?;
+            written += chunk.len();
+            progress(Step::Write, written, image.len());
+        }
+
+        self.command(SET_ADDRESS_POINTER, FLASH_START)?;
+        self.request_out(DfuRequest::Abort, 0, &[])?;
+        let mut buffer = [0; BOOTLOADER_TRANSFER_SIZE as usize];
+        let mut verified = 0;
+        for (block, chunk) in
+            (FIRST_DATA_BLOCK..).zip(image.chunks(BOOTLOADER_TRANSFER_SIZE as usize))
+        {
+            let length = self.request_in(DfuRequest::Upload, block, &mut buffer[..chunk.len()])?;
+            if let Some(offset) = (0..chunk.len()).find(|i| *i >= length || buffer[*i] != chunk[*i])
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "verification failed at {:#010x}",
+                        FLASH_START as usize + verified + offset
+                    ),
+                ));
+            }
+            verified += chunk.len();
+            progress(Step::Verify, verified, image.len());
+        }
+        self.request_out(DfuRequest::Abort, 0, &[])
+    }
+
+    /// Leaves the bootloader and starts the firmware at `FLASH_START`.
+    pub fn leave(&mut self) -> io::Result<()> {
+        self.command(SET_ADDRESS_POINTER, FLASH_START)?;
+        self.request_out(DfuRequest::Download, FIRST_DATA_BLOCK, &[])?;
+        // The bootloader resets while answering, so the answer may never come.
+        let _ = self.status();
+        Ok(())
+    }
+
+    /// Brings the bootloader back to idle from whatever an earlier session left it in.
+    fn ensure_idle(&mut self) -> io::Result<()> {
+        match self.status()?.state {
+            DfuState::Idle => return Ok(()),
+            DfuState::Error => self.request_out(DfuRequest::ClearStatus, 0, &[])?,
+            _ => self.request_out(DfuRequest::Abort, 0, &[])?,
+        }
+        match self.status()? {
+            DfuStatus {
+                state: DfuState::Idle,
+                ..
+            } => Ok(()),
+            status => Err(bootloader_error(status)),
+        }
+    }
+
+    fn command(&mut self, command: u8, address: u32) -> io::Result<()> {
+        let mut data = [command, 0, 0, 0, 0];
+        data[1..].copy_from_slice(&address.to_le_bytes());
+        self.request_out(DfuRequest::Download, 0, &data)?;
+        self.wait()
+    }
+
+    /// Polls the status until the bootloader is done with the last download.
+    fn wait(&mut self) -> io::Result<()> {
+        loop {
+            let status = self.status()?;
+            if status.status != 0 || status.state == DfuState::Error {
+                return Err(bootloader_error(status));
+            }
+            if status.state != DfuState::DownloadBusy {
+                return Ok(());
+            }
+            thread::sleep(Duration::from_millis(status.poll_timeout_ms as u64));
+        }
+    }
+
+    fn status(&mut self) -> io::Result<DfuStatus> {
+        let mut buffer = [0; DFU_STATUS_LENGTH];
+        let length = self.request_in(DfuRequest::GetStatus, 0, &mut buffer)?;
+        DfuStatus::decode(&buffer[..length]).ok_or(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid DFU status",
+        ))
+    }
+
+    fn request_out(&mut self, request: DfuRequest, value: u16, data: &[u8]) -> io::Result<()> {
+        self.device.control_out(
+            CLASS_INTERFACE_OUT,
+            request as u8,
+            value,
+            self.interface,
+            data,
+        )
+    }
+
+    fn request_in(
+        &mut self,
+        request: DfuRequest,
+        value: u16,
+        buffer: &mut [u8],
+    ) -> io::Result<usize> {
+        self.device.control_in(
+            CLASS_INTERFACE_IN,
+            request as u8,
+            value,
+            self.interface,
+            buffer,
+        )
+    }
+}
+
+fn bootloader_error(status: DfuStatus) -> io::Error {
+    io::Error::other(format!(
+        "the bootloader reported error {} in state {:?}",
+        status.status, status.state
+    ))
+}
+
+#[cfg(test)]
+mod dfu_testing {
+    use crate::dfu::{check_image, detach, sectors_for, Dfuse, Step, FLASH_START, PROGRAM_END};
+    use crate::usbdevfs::{ControlDevice, CLASS_INTERFACE_IN, CLASS_INTERFACE_OUT};
+    use rstest::rstest;
+    use rusty_pedalbox::dfu::{DfuRequest, DfuState, DfuStatus, BOOTLOADER_TRANSFER_SIZE};
+    use std::io;
+
+    const FLASH_SIZE: usize = 1024 * 1024;
+    const TRANSFER_SIZE: usize = BOOTLOADER_TRANSFER_SIZE as usize;
+
+    enum Pending {
+        SetAddress(u32),
+        Erase(u32),
+        Write(u32, Vec<u8>),
+    }
+
+    /// The DfuSe state machine of the ROM bootloader over a flash that only clears bits
+    /// when written.
+    struct FakeBootloader {
+        flash: Vec<u8>,
+        state: DfuState,
+        address: u32,
+        pending: Option<Pending>,
+        erased: Vec<u32>,
+        left: bool,
+        /// A flash byte that doesn't take writes.
+        broken: Option<u32>,
+        requests: Vec<(u8, u8, u16)>,
+    }
+
+    impl FakeBootloader {
+        fn new() -> Self {
+            Self {
+                flash: vec![0xff; FLASH_SIZE],
+                state: DfuState::Idle,
+                address: 0,
+                pending: None,
+                erased: Vec::new(),
+                left: false,
+                broken: None,
+                requests: Vec::new(),
+            }
+        }
+
+        fn offset(address: u32) -> usize {
+            (address - FLASH_START) as usize
+        }
+
+        fn stall(&mut self) -> io::Error {
+            self.state = DfuState::Error;
+            io::Error::from(io::ErrorKind::BrokenPipe)
+        }
+
+        fn execute(&mut self, pending: Pending) {
+            match pending {
+                Pending::SetAddress(address) => self.address = address,
+                Pending::Erase(address) => {
+                    let size = sectors_for(FLASH_SIZE)
+                        .windows(2)
+                        .find(|pair| pair[0] == address)
+                        .map_or(128 * 1024, |pair| (pair[1] - pair[0]) as usize);
+                    let start = Self::offset(address);
+                    self.flash[start..start + size].fill(0xff);
+                    self.erased.push(address);
+                }
+                Pending::Write(address, data) => {
+                    let start = Self::offset(address);
+                    for (index, byte) in data.iter().enumerate() {
+                        if self.broken != Some(address + index as u32) {
+                            self.flash[start + index] &= byte;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    impl ControlDevice for FakeBootloader {
+        fn control_out(
+            &mut self,
+            request_type: u8,
+            request: u8,
+            value: u16,
+            _index: u16,
+            data: &[u8],
+        ) -> io::Result<()> {
+            self.requests.push((request_type, request, value));
+            assert_eq!(request_type, CLASS_INTERFACE_OUT);
+            match DfuRequest::try_from(request) {
+                Ok(DfuRequest::Download)
+                    if matches!(self.state, DfuState::Idle | DfuState::DownloadIdle) =>
+                {
+                    let address = |data: &[u8]| u32::from_le_bytes(data[1..5].try_into().unwrap());
+                    if data.is_empty() {
+                        self.state = DfuState::ManifestSync;
+                        return Ok(());
+                    }
+                    self.pending = Some(match (value, data) {
+                        (0, [0x21, ..]) => Pending::SetAddress(address(data)),
+                        (0, [0x41, ..]) => Pending::Erase(address(data)),
+                        (0, _) => return Err(self.stall()),
+                        (block, data) => Pending::Write(
+                            self.address + (block as u32 - 2) * TRANSFER_SIZE as u32,
+                            data.to_vec(),
+                        ),
+                    });
+                    self.state = DfuState::DownloadSync;
+                }
+                Ok(DfuRequest::ClearStatus) | Ok(DfuRequest::Abort) => self.state = DfuState::Idle,
+                Ok(DfuRequest::Detach) if self.state == DfuState::AppIdle => {
+                    self.state = DfuState::AppDetach
+                }
+                _ => return Err(self.stall()),
+            }
+            Ok(())
+        }
+
+        fn control_in(
+            &mut self,
+            request_type: u8,
+            request: u8,
+            value: u16,
+            _index: u16,
+            buffer: &mut [u8],
+        ) -> io::Result<usize> {
+            self.requests.push((request_type, request, value));
+            assert_eq!(request_type, CLASS_INTERFACE_IN);
+            match DfuRequest::try_from(request) {
+                Ok(DfuRequest::GetStatus) => {
+                    self.state = match self.state {
+                        DfuState::DownloadSync => DfuState::DownloadBusy,
+                        DfuState::DownloadBusy => {
+                            let pending = self.pending.take().unwrap();
+                            self.execute(pending);
+                            DfuState::DownloadIdle
+                        }
+                        DfuState::ManifestSync => {
+                            self.left = true;
+                            DfuState::Manifest
+                        }
+                        state => state,
+                    };
+                    let status = DfuStatus {
+                        status: 0,
+                        poll_timeout_ms: 0,
+                        state: self.state,
+                    };
+                    buffer[..6].copy_from_slice(&status.encode());
+                    Ok(6)
+                }
+                Ok(DfuRequest::Upload)
+                    if value >= 2
+                        && matches!(self.state, DfuState::Idle | DfuState::UploadIdle) =>
+                {
+                    let start = Self::offset(self.address) + (value as usize - 2) * TRANSFER_SIZE;
+                    buffer.copy_from_slice(&self.flash[start..start + buffer.len()]);
+                    self.state = DfuState::UploadIdle;
+                    Ok(buffer.len())
+                }
+                _ => Err(self.stall()),
+            }
+        }
+    }
+
+    fn image(length: usize) -> Vec<u8> {
+        (0..length).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    #[rstest]
+    #[case(1, vec![0x0800_0000])]
+    #[case(16 * 1024, vec![0x0800_0000])]
+    #[case(40 * 1024, vec![0x0800_0000, 0x0800_4000, 0x0800_8000])]
+    #[case(100 * 1024, vec![0x0800_0000, 0x0800_4000, 0x0800_8000, 0x0800_C000, 0x0801_0000])]
+    fn when_image_is_flashed_then_the_sectors_it_overlaps_are_erased(
+        #[case] length: usize,
+        #[case] expected: Vec<u32>,
+    ) {
+        // When
+        let result = sectors_for(length);
+
+        // Then
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn when_image_is_updated_then_it_is_written_verified_and_started() {
+        // Given
+        let image = image(40 * 1024 + 100);
+        let mut dfuse = Dfuse::new(FakeBootloader::new(), 0);
+        let mut steps = Vec::new();
+
+        // When
+        dfuse
+            .update(&image, |step, done, total| steps.push((step, done, total)))
+            .unwrap();
+        dfuse.leave().unwrap();
+
+        // Then
+        let bootloader = &dfuse.device;
+        assert_eq!(&bootloader.flash[..image.len()], &image[..]);
+        assert_eq!(bootloader.erased, sectors_for(image.len()));
+        assert!(bootloader.left);
+        assert_eq!(steps.first(), Some(&(Step::Erase, 1, 3)));
+        assert!(steps.contains(&(Step::Write, image.len(), image.len())));
+        assert_eq!(
+            steps.last(),
+            Some(&(Step::Verify, image.len(), image.len()))
+        );
+    }
+
+    #[test]
+    fn when_image_is_updated_then_the_calibration_is_kept() {
+        // Given
+        let mut bootloader = FakeBootloader::new();
+        let calibration = FakeBootloader::offset(PROGRAM_END);
+        bootloader.flash[calibration..calibration + 4].copy_from_slice(&[1, 2, 3, 4]);
+        let mut dfuse = Dfuse::new(bootloader, 0);
+        let image = image((PROGRAM_END - FLASH_START) as usize);
+
+        // When
+        dfuse.update(&image, |_, _, _| {}).unwrap();
+
+        // Then
+        let bootloader = &dfuse.device;
+        assert_eq!(bootloader.erased.last(), Some(&0x080A_0000));
+        assert_eq!(
+            &bootloader.flash[calibration..calibration + 4],
+            &[1, 2, 3, 4]
+        );
+    }
+
+    #[rstest]
+    #[case(0)]
+    #[case((PROGRAM_END - FLASH_START) as usize + 1)]
+    fn when_image_does_not_fit_then_nothing_is_sent(#[case] length: usize) {
+        // Given
+        let mut dfuse = Dfuse::new(FakeBootloader::new(), 0);
+
+        // When
+        let result = dfuse.update(&image(length), |_, _, _| {});
+
+        // Then
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert!(check_image(&image(length)).is_err());
+        assert!(dfuse.device.requests.is_empty());
+    }
+
+    #[test]
+    fn when_flash_does_not_take_the_image_then_verification_fails() {
+        // Given
+        let mut bootloader = FakeBootloader::new();
+        bootloader.broken = Some(0x0800_1234);
+        let mut dfuse = Dfuse::new(bootloader, 0);
+
+        // When
+        let result = dfuse.update(&image(8 * 1024), |_, _, _| {});
+
+        // Then
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "verification failed at 0x08001234");
+    }
+
+    #[test]
+    fn when_bootloader_is_left_in_error_then_the_error_is_cleared_first() {
+        // Given
+        let mut bootloader = FakeBootloader::new();
+        bootloader.state = DfuState::Error;
+        let mut dfuse = Dfuse::new(bootloader, 0);
+
+        // When
+        let result = dfuse.update(&image(100), |_, _, _| {});
+
+        // Then
+        assert!(result.is_ok());
+        assert_eq!(
+            dfuse.device.requests[1],
+            (CLASS_INTERFACE_OUT, DfuRequest::ClearStatus as u8, 0)
+        );
+    }
+
+    #[test]
+    fn when_firmware_is_detached_then_the_timeout_is_sent() {
+        // Given
+        let mut firmware = FakeBootloader::new();
+        firmware.state = DfuState::AppIdle;
+
+        // When
+        let result = detach(&mut firmware, 3);
+
+        // Then
+        assert!(result.is_ok());
+        assert_eq!(firmware.state, DfuState::AppDetach);
+        assert_eq!(
+            firmware.requests,
+            vec![(CLASS_INTERFACE_OUT, DfuRequest::Detach as u8, 1_000)]
+        );
+    }
+}
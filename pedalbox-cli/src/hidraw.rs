@@ -0,0 +1,151 @@
+//! Access to the pedalbox through the Linux hidraw interface.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+
+/// Operations the tool needs from a HID device, so the device can be faked in tests.
+pub trait HidDevice {
+    /// Reads the feature report whose ID is in `buffer[0]`. Returns the length of the
+    /// report including its ID.
+    fn get_feature(&mut self, buffer: &mut [u8]) -> io::Result<usize>;
+    /// Writes a feature report, `report[0]` is its ID.
+    fn set_feature(&mut self, report: &[u8]) -> io::Result<()>;
+    /// Blocks until the next input report arrives.
+    fn read_input(&mut self, buffer: &mut [u8]) -> io::Result<usize>;
+}
+
+/// `_IOC(_IOC_READ | _IOC_WRITE, 'H', number, length)` from `linux/hidraw.h`.
+const fn hidraw_ioctl(number: u8, length: usize) -> libc::c_ulong {
+    const IOC_READ_WRITE: libc::c_ulong = 3;
+    (IOC_READ_WRITE << 30)
+        | ((length as libc::c_ulong) << 16)
+        | ((b'H' as libc::c_ulong) << 8)
+        | number as libc::c_ulong
+}
+
+const HIDIOCSFEATURE: u8 = 0x06;
+const HIDIOCGFEATURE: u8 = 0x07;
+
+pub struct Hidraw {
+    file: File,
+}
+
+impl Hidraw {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        Ok(Self { file })
+    }
+
+    fn ioctl(&mut self, number: u8, buffer: *mut u8, length: usize) -> io::Result<usize> {
+        // SAFETY: the kernel reads and writes at most `length` bytes of `buffer`.
+        let result = unsafe {
+            libc::ioctl(
+                self.file.as_raw_fd(),
+                hidraw_ioctl(number, length) as _,
+                buffer,
+            )
+        };
+        if result < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(result as usize)
+        }
+    }
+}
+
+impl HidDevice for Hidraw {
+    fn get_feature(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.ioctl(HIDIOCGFEATURE, buffer.as_mut_ptr(), buffer.len())
+    }
+
+    fn set_feature(&mut self, report: &[u8]) -> io::Result<()> {
+        let mut report = report.to_vec();
+        self.ioctl(HIDIOCSFEATURE, report.as_mut_ptr(), report.len())
+            .map(|_| ())
+    }
+
+    fn read_input(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buffer)
+    }
+}
+
+/// Returns the `/dev` nodes of the hidraw devices with the given USB IDs, found through
+/// `sys_root/class/hidraw`.
+pub fn find_devices(sys_root: &Path, vendor_id: u16, product_id: u16) -> io::Result<Vec<PathBuf>> {
+    let mut devices = Vec::new();
+    for entry in fs::read_dir(sys_root.join("class/hidraw"))? {
+        let entry = entry?;
+        let Ok(uevent) = fs::read_to_string(entry.path().join("device/uevent")) else {
+            continue;
+        };
+        if hid_id(&uevent) == Some((vendor_id, product_id)) {
+            devices.push(Path::new("/dev").join(entry.file_name()));
+        }
+    }
+    devices.sort();
+    Ok(devices)
+}
+
+/// Parses the vendor and product ID from the `HID_ID=bus:vendor:product` line of a uevent.
+fn hid_id(uevent: &str) -> Option<(u16, u16)> {
+    let id = uevent
+        .lines()
+        .find_map(|line| line.strip_prefix("HID_ID="))?;
+    let mut parts = id.split(':').skip(1);
+    let vendor = u32::from_str_radix(parts.next()?, 16).ok()?;
+    let product = u32::from_str_radix(parts.next()?, 16).ok()?;
+    Some((u16::try_from(vendor).ok()?, u16::try_from(product).ok()?))
+}
+
+#[cfg(test)]
+mod hidraw_testing {
+    use crate::hidraw::{find_devices, hid_id, hidraw_ioctl};
+    use rstest::rstest;
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[rstest]
+    #[case("HID_ID=0003:0000CAFE:00002025\nHID_NAME=8 BitHunters Rusty Pedalbox\n", Some((0xcafe, 0x2025)))]
+    #[case("DRIVER=hid-generic\nHID_ID=0003:0000046D:0000C52B\n", Some((0x046d, 0xc52b)))]
+    #[case("DRIVER=hid-generic\n", None)]
+    #[case("HID_ID=0003:garbage\n", None)]
+    fn when_uevent_is_parsed(#[case] uevent: &str, #[case] expected: Option<(u16, u16)>) {
+        // When
+        let result = hid_id(uevent);
+
+        // Then
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn when_ioctl_number_is_built_then_it_matches_the_kernel_header() {
+        // When
+        let result = hidraw_ioctl(0x07, 48);
+
+        // Then
+        assert_eq!(result, 0xC030_4807);
+    }
+
+    #[test]
+    fn when_devices_are_listed_then_only_the_pedalbox_is_returned() {
+        // Given
+        let root = std::env::temp_dir().join(format!("pedalbox-sysfs-{}", std::process::id()));
+        for (name, uevent) in [
+            ("hidraw0", "HID_ID=0003:0000046D:0000C52B\n"),
+            ("hidraw3", "HID_ID=0003:0000CAFE:00002025\n"),
+        ] {
+            let device = root.join("class/hidraw").join(name).join("device");
+            fs::create_dir_all(&device).unwrap();
+            fs::write(device.join("uevent"), uevent).unwrap();
+        }
+
+        // When
+        let result = find_devices(&root, 0xcafe, 0x2025);
+
+        // Then
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(result.unwrap(), [PathBuf::from("/dev/hidraw3")]);
+    }
+}
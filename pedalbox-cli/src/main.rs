@@ -0,0 +1,282 @@
+//! Configuration tool for the rusty-pedalbox.
+
+mod args;
+mod dfu;
+mod hidraw;
+mod pedalbox;
+mod profile;
+mod usbdevfs;
+
+use crate::args::{Command, USAGE};
+use crate::dfu::{Dfuse, Step};
+use crate::hidraw::{find_devices, HidDevice, Hidraw};
+use crate::pedalbox::Pedalbox;
+use crate::profile::{Format, Profile};
+use crate::usbdevfs::{find_device, find_interface, UsbDevice};
+use rusty_pedalbox::axis::Axis;
+use rusty_pedalbox::dfu::{BOOTLOADER_PRODUCT_ID, BOOTLOADER_VENDOR_ID, DFU_CLASS, DFU_SUBCLASS};
+use rusty_pedalbox::fault::FaultFlags;
+use rusty_pedalbox::feature_report::{FeatureReport, SessionStatus};
+use rusty_pedalbox::input_report::{DiagnosticsReport, PedalboxReport, StatusReport};
+use rusty_pedalbox::report_mode::ReportMode;
+use rusty_pedalbox::settings::AxisSettings;
+use rusty_pedalbox::{USB_PRODUCT_ID, USB_VENDOR_ID};
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::thread;
+use std::time::Duration;
+
+const SYS_ROOT: &str = "/sys";
+/// How long the pedalbox gets to come back as the bootloader after `DETACH`.
+const BOOTLOADER_WAIT: Duration = Duration::from_secs(5);
+const BOOTLOADER_POLL: Duration = Duration::from_millis(100);
+
+fn main() -> ExitCode {
+    let options = match args::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("error: {message}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match run(options.device, options.command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(device: Option<PathBuf>, command: Command) -> Result<(), Box<dyn Error>> {
+    if command == Command::List {
+        for device in find_devices(Path::new(SYS_ROOT), USB_VENDOR_ID, USB_PRODUCT_ID)? {
+            println!("{}", device.display());
+        }
+        return Ok(());
+    }
+    if let Command::Update(path) = command {
+        return update(&path);
+    }
+
+    let device = match device {
+        Some(device) => device,
+        None => find_devices(Path::new(SYS_ROOT), USB_VENDOR_ID, USB_PRODUCT_ID)?
+            .into_iter()
+            .next()
+            .ok_or("no pedalbox found, is it plugged in?")?,
+    };
+    let mut pedalbox = Pedalbox::new(Hidraw::open(&device)?);
+    execute(&mut pedalbox, command)
+}
+
+fn execute<D: HidDevice>(
+    pedalbox: &mut Pedalbox<D>,
+    command: Command,
+) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::List | Command::Update(_) => unreachable!("handled without opening a device"),
+        Command::Monitor => loop {
+            let report: PedalboxReport = pedalbox.read_input()?;
+            let (x, y, z, buttons) = (report.x, report.y, report.z, report.buttons);
+            print!("\rgas {x:>6}  brake {y:>6}  clutch {z:>6}  buttons {buttons:08b}");
+            std::io::stdout().flush()?;
+        },
+        Command::Status => {
+            let report: StatusReport = pedalbox.read_input()?;
+            let [major, minor, patch] = report.version();
+            let (temperature, uptime_s) = (report.temperature, report.uptime_s);
+            println!("firmware     {major}.{minor}.{patch}");
+            println!("uptime       {uptime_s} s");
+            println!("temperature  {:.1} °C", temperature as f32 / 10.0);
+            for axis in Axis::ALL {
+                println!(
+                    "{:<12} {}",
+                    axis_name(axis),
+                    fault_names(report.faults(axis))
+                );
+            }
+        }
+        Command::Diagnostics => loop {
+            let report: DiagnosticsReport = pedalbox.read_input()?;
+            let [x, y, z] = Axis::ALL.map(|axis| report.raw(axis));
+            print!("\rgas {x:>6}  brake {y:>9}  clutch {z:>6}");
+            std::io::stdout().flush()?;
+        },
+        Command::Show(axis) => {
+            let axes = axis.map_or(Axis::ALL.to_vec(), |axis| vec![axis]);
+            for axis in axes {
+                print_settings(axis, &pedalbox.settings(axis)?);
+            }
+        }
+        Command::SetRange { axis, min, max } => {
+            let mut settings = pedalbox.settings(axis)?;
+            settings.calibration.range_min = min;
+            settings.calibration.range_max = max;
+            pedalbox.write_report(&FeatureReport::Calibration {
+                axis,
+                calibration: settings.calibration,
+                deadzone: settings.deadzone,
+            })?;
+        }
+        Command::SetCurve { axis, curve } => {
+            pedalbox.write_report(&FeatureReport::Curve { axis, curve })?;
+        }
+        Command::Calibrate(Some(command)) => {
+            pedalbox.session_command(command)?;
+            print_status(&pedalbox.session_status()?);
+        }
+        Command::Calibrate(None) => print_status(&pedalbox.session_status()?),
+        Command::ReportMode(Some(mode)) => pedalbox.set_report_mode(mode)?,
+        Command::ReportMode(None) => match pedalbox.report_mode()? {
+            ReportMode::Periodic { interval_ms } => println!("periodic, every {interval_ms} ms"),
+            ReportMode::OnChange {
+                threshold,
+                heartbeat_ms,
+            } => println!("on change above {threshold}, heartbeat every {heartbeat_ms} ms"),
+        },
+        Command::Latency { reset: true } => pedalbox.reset_max_latency()?,
+        Command::Latency { reset: false } => {
+            let status = pedalbox.report_status()?;
+            println!("reports      {}", status.reports);
+            println!("heartbeats   {}", status.heartbeats);
+            println!("last latency {} us", status.last_latency_us);
+            println!("max latency  {} us", status.max_latency_us);
+        }
+        Command::ResetCause => {
+            let cause = pedalbox.reset_cause()?;
+            match cause.culprit {
+                Some(task) => println!("{:?}, the {} task hung", cause.reason, task.name()),
+                None => println!("{:?}", cause.reason),
+            }
+        }
+        Command::Export(path) => {
+            let format = Format::from_path(&path).ok_or("profiles must be .toml or .json")?;
+            let mut settings = [AxisSettings::DEFAULT; 3];
+            for axis in Axis::ALL {
+                settings[axis.index()] = pedalbox.settings(axis)?;
+            }
+            let profile = Profile::from_settings(|axis| settings[axis.index()]);
+            fs::write(&path, profile.render(format))?;
+        }
+        Command::Import(path) => {
+            let format = Format::from_path(&path).ok_or("profiles must be .toml or .json")?;
+            let profile = Profile::parse(&fs::read_to_string(&path)?, format)?;
+            for axis in Axis::ALL {
+                let settings = AxisSettings::try_from(profile.axis(axis))?;
+                pedalbox.write_settings(axis, &settings)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reboots the pedalbox into the ROM bootloader unless it already runs it, then flashes
+/// `path`.
+fn update(path: &Path) -> Result<(), Box<dyn Error>> {
+    let image = fs::read(path)?;
+    dfu::check_image(&image)?;
+    let sys_root = Path::new(SYS_ROOT);
+
+    if find_device(sys_root, BOOTLOADER_VENDOR_ID, BOOTLOADER_PRODUCT_ID)?.is_none() {
+        let pedalbox = find_device(sys_root, USB_VENDOR_ID, USB_PRODUCT_ID)?
+            .ok_or("no pedalbox found, is it plugged in?")?;
+        let interface = find_interface(&pedalbox, DFU_CLASS, DFU_SUBCLASS)?
+            .ok_or("the pedalbox has no DFU interface, start its bootloader with BOOT0")?;
+        let mut device = UsbDevice::open(&pedalbox.node)?;
+        device.claim_interface(interface)?;
+        dfu::detach(&mut device, interface)?;
+        println!("rebooting the pedalbox into its bootloader");
+    }
+
+    let mut waited = Duration::ZERO;
+    let bootloader = loop {
+        if let Some(bootloader) =
+            find_device(sys_root, BOOTLOADER_VENDOR_ID, BOOTLOADER_PRODUCT_ID)?
+        {
+            break bootloader;
+        }
+        if waited >= BOOTLOADER_WAIT {
+            return Err("the bootloader didn't show up".into());
+        }
+        thread::sleep(BOOTLOADER_POLL);
+        waited += BOOTLOADER_POLL;
+    };
+    let mut device = UsbDevice::open(&bootloader.node)?;
+    device.claim_interface(0)?;
+    let mut dfuse = Dfuse::new(device, 0);
+    dfuse.update(&image, |step, done, total| {
+        let step = match step {
+            Step::Erase => "erasing  ",
+            Step::Write => "writing  ",
+            Step::Verify => "verifying",
+        };
+        print!("\r{step} {done:>7}/{total}");
+        let _ = std::io::stdout().flush();
+        if done == total {
+            println!();
+        }
+    })?;
+    dfuse.leave()?;
+    println!("done, the pedalbox restarts with the new firmware");
+    Ok(())
+}
+
+fn axis_name(axis: Axis) -> &'static str {
+    match axis {
+        Axis::X => "gas",
+        Axis::Y => "brake",
+        Axis::Z => "clutch",
+    }
+}
+
+fn fault_names(faults: FaultFlags) -> String {
+    const NAMES: [(FaultFlags, &str); 5] = [
+        (FaultFlags::RAIL_LOW, "rail low"),
+        (FaultFlags::RAIL_HIGH, "rail high"),
+        (FaultFlags::STUCK, "stuck"),
+        (FaultFlags::NOT_READY, "not ready"),
+        (FaultFlags::READ_ERRORS, "read errors"),
+    ];
+    if faults.is_empty() {
+        return "ok".to_string();
+    }
+    NAMES
+        .iter()
+        .filter(|(flag, _)| faults.contains(*flag))
+        .map(|(_, name)| *name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn print_settings(axis: Axis, settings: &AxisSettings) {
+    println!("{}:", axis_name(axis));
+    println!(
+        "  range     {}..{}",
+        settings.calibration.range_min, settings.calibration.range_max
+    );
+    println!("  deadzone  {:?}", settings.deadzone);
+    println!("  curve     {:?}", settings.curve);
+    println!(
+        "  filters   {:?}",
+        settings.filter.filters().collect::<Vec<_>>()
+    );
+}
+
+fn print_status(status: &SessionStatus) {
+    println!("calibration {:?}", status.state);
+    for axis in Axis::ALL {
+        match status.captured[axis.index()] {
+            Some(range) => println!(
+                "  {:<7} {}..{}",
+                axis_name(axis),
+                range.range_min,
+                range.range_max
+            ),
+            None => println!("  {:<7} not moved yet", axis_name(axis)),
+        }
+    }
+}
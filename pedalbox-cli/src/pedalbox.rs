@@ -0,0 +1,374 @@
+//! Settings and calibration of a pedalbox, on top of the shared report layouts.
+
+use crate::hidraw::HidDevice;
+use rusty_pedalbox::axis::Axis;
+use rusty_pedalbox::feature_report::{
+    FeatureKind, FeatureReport, FeatureReportId, ReportError, ReportStatus, SessionCommand,
+    SessionStatus, CALIBRATION_SESSION_REPORT_ID, MAX_FEATURE_REPORT_SIZE, REPORT_MODE_REPORT_ID,
+    REPORT_STATISTICS_REPORT_ID, RESET_CAUSE_REPORT_ID,
+};
+use rusty_pedalbox::input_report::InputReport;
+use rusty_pedalbox::report_mode::ReportMode;
+use rusty_pedalbox::settings::AxisSettings;
+use rusty_pedalbox::supervisor::ResetCause;
+use std::io;
+
+pub struct Pedalbox<D> {
+    device: D,
+}
+
+impl<D> Pedalbox<D>
+where
+    D: HidDevice,
+{
+    pub fn new(device: D) -> Self {
+        Self { device }
+    }
+
+    pub fn settings(&mut self, axis: Axis) -> io::Result<AxisSettings> {
+        let mut settings = AxisSettings::DEFAULT;
+        for kind in FeatureKind::ALL {
+            let report = self.get_feature(FeatureReportId::new(kind, axis).value())?;
+            FeatureReport::decode(&report)
+                .map_err(invalid_report)?
+                .apply(&mut settings);
+        }
+        Ok(settings)
+    }
+
+    pub fn write_settings(&mut self, axis: Axis, settings: &AxisSettings) -> io::Result<()> {
+        for kind in FeatureKind::ALL {
+            let id = FeatureReportId::new(kind, axis);
+            self.write_report(&FeatureReport::from_settings(id, settings))?;
+        }
+        Ok(())
+    }
+
+    pub fn write_report(&mut self, report: &FeatureReport) -> io::Result<()> {
+        let mut buffer = [0; MAX_FEATURE_REPORT_SIZE];
+        let length = report.encode(&mut buffer).map_err(invalid_report)?;
+        self.device.set_feature(&buffer[..length])
+    }
+
+    pub fn session_status(&mut self) -> io::Result<SessionStatus> {
+        let report = self.get_feature(CALIBRATION_SESSION_REPORT_ID)?;
+        SessionStatus::decode(&report).map_err(invalid_report)
+    }
+
+    pub fn session_command(&mut self, command: SessionCommand) -> io::Result<()> {
+        let mut buffer = [0; MAX_FEATURE_REPORT_SIZE];
+        let length = command.encode(&mut buffer).map_err(invalid_report)?;
+        self.device.set_feature(&buffer[..length])
+    }
+
+    pub fn report_mode(&mut self) -> io::Result<ReportMode> {
+        let report = self.get_feature(REPORT_MODE_REPORT_ID)?;
+        ReportMode::decode(&report).map_err(invalid_report)
+    }
+
+    pub fn set_report_mode(&mut self, mode: ReportMode) -> io::Result<()> {
+        let mut buffer = [0; MAX_FEATURE_REPORT_SIZE];
+        let length = mode.encode(&mut buffer).map_err(invalid_report)?;
+        self.device.set_feature(&buffer[..length])
+    }
+
+    pub fn report_status(&mut self) -> io::Result<ReportStatus> {
+        let report = self.get_feature(REPORT_STATISTICS_REPORT_ID)?;
+        ReportStatus::decode(&report).map_err(invalid_report)
+    }
+
+    /// Starts measuring the maximum report latency again.
+    pub fn reset_max_latency(&mut self) -> io::Result<()> {
+        self.device.set_feature(&[REPORT_STATISTICS_REPORT_ID])
+    }
+
+    pub fn reset_cause(&mut self) -> io::Result<ResetCause> {
+        let report = self.get_feature(RESET_CAUSE_REPORT_ID)?;
+        ResetCause::decode(&report).map_err(invalid_report)
+    }
+
+    /// Waits for the next input report `R`, skipping reports of other kinds.
+    pub fn read_input<R: InputReport>(&mut self) -> io::Result<R> {
+        let mut buffer = [0; 64];
+        loop {
+            let length = self.device.read_input(&mut buffer)?;
+            if let Some(report) = R::from_bytes(&buffer[..length]) {
+                return Ok(report);
+            }
+        }
+    }
+
+    fn get_feature(&mut self, id: u8) -> io::Result<Vec<u8>> {
+        let mut buffer = [0; MAX_FEATURE_REPORT_SIZE];
+        buffer[0] = id;
+        let length = self.device.get_feature(&mut buffer)?;
+        Ok(buffer[..length].to_vec())
+    }
+}
+
+fn invalid_report(error: ReportError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{error:?}"))
+}
+
+#[cfg(test)]
+pub(crate) mod pedalbox_testing {
+    use crate::hidraw::HidDevice;
+    use crate::pedalbox::Pedalbox;
+    use rusty_pedalbox::axis::Axis;
+    use rusty_pedalbox::calibration::{
+        AxisCalibration, CalibrationSession, CalibrationState, CommitTarget,
+    };
+    use rusty_pedalbox::curve::ResponseCurve;
+    use rusty_pedalbox::deadzone::{Deadzone, DeadzoneWidth};
+    use rusty_pedalbox::feature_report::{
+        FeatureReport, FeatureReportId, ReportStatus, SessionCommand, SessionStatus,
+        CALIBRATION_SESSION_REPORT_ID, REPORT_MODE_REPORT_ID, REPORT_STATISTICS_REPORT_ID,
+        RESET_CAUSE_REPORT_ID,
+    };
+    use rusty_pedalbox::filters::{Filter, FilterChain};
+    use rusty_pedalbox::input_report::{DiagnosticsReport, PedalboxReport, StatusReport};
+    use rusty_pedalbox::pedal_state::PedalSnapshot;
+    use rusty_pedalbox::report_mode::{ReportMode, ReportReason, ReportStatistics};
+    use rusty_pedalbox::settings::AxisSettings;
+    use rusty_pedalbox::supervisor::{ResetCause, ResetReason, SupervisedTask};
+    use std::collections::VecDeque;
+    use std::io;
+
+    /// Answers the reports like the firmware does.
+    pub(crate) struct FakeHidraw {
+        pub settings: [AxisSettings; 3],
+        pub session: CalibrationSession,
+        pub report_mode: ReportMode,
+        pub report_statistics: ReportStatistics,
+        pub reset_cause: ResetCause,
+        pub inputs: VecDeque<Vec<u8>>,
+    }
+
+    impl FakeHidraw {
+        pub fn new() -> Self {
+            Self {
+                settings: [AxisSettings::DEFAULT; 3],
+                session: CalibrationSession::new(),
+                report_mode: ReportMode::DEFAULT,
+                report_statistics: ReportStatistics::new(),
+                reset_cause: ResetCause {
+                    reason: ResetReason::PowerOn,
+                    culprit: None,
+                },
+                inputs: VecDeque::new(),
+            }
+        }
+    }
+
+    fn broken_pipe() -> io::Error {
+        io::Error::from(io::ErrorKind::BrokenPipe)
+    }
+
+    impl HidDevice for FakeHidraw {
+        fn get_feature(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            match buffer[0] {
+                CALIBRATION_SESSION_REPORT_ID => {
+                    SessionStatus::from_session(&self.session).encode(buffer)
+                }
+                REPORT_MODE_REPORT_ID => self.report_mode.encode(buffer),
+                REPORT_STATISTICS_REPORT_ID => {
+                    ReportStatus::from_statistics(&self.report_statistics).encode(buffer)
+                }
+                RESET_CAUSE_REPORT_ID => self.reset_cause.encode(buffer),
+                id => {
+                    let id = FeatureReportId::try_from(id).map_err(|_| broken_pipe())?;
+                    FeatureReport::from_settings(id, &self.settings[id.axis.index()]).encode(buffer)
+                }
+            }
+            .map_err(|_| broken_pipe())
+        }
+
+        fn set_feature(&mut self, report: &[u8]) -> io::Result<()> {
+            if report[0] == REPORT_MODE_REPORT_ID {
+                self.report_mode = ReportMode::decode(report).map_err(|_| broken_pipe())?;
+                return Ok(());
+            }
+            if report[0] == REPORT_STATISTICS_REPORT_ID {
+                self.report_statistics.reset_max_latency();
+                return Ok(());
+            }
+            if report[0] == CALIBRATION_SESSION_REPORT_ID {
+                let command = SessionCommand::decode(report).map_err(|_| broken_pipe())?;
+                return match command.execute(&self.session) {
+                    true => Ok(()),
+                    false => Err(broken_pipe()),
+                };
+            }
+            let report = FeatureReport::decode(report).map_err(|_| broken_pipe())?;
+            report.apply(&mut self.settings[report.id().axis.index()]);
+            Ok(())
+        }
+
+        fn read_input(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            let input = self
+                .inputs
+                .pop_front()
+                .ok_or(io::Error::from(io::ErrorKind::UnexpectedEof))?;
+            buffer[..input.len()].copy_from_slice(&input);
+            Ok(input.len())
+        }
+    }
+
+    fn brake_settings() -> AxisSettings {
+        AxisSettings {
+            calibration: AxisCalibration::new(0, 230_000),
+            deadzone: Deadzone {
+                lower: DeadzoneWidth::Percent(2),
+                upper: DeadzoneWidth::Raw(1_000),
+                saturation: 3,
+            },
+            curve: ResponseCurve::Progressive(40),
+            filter: FilterChain::new(&[Filter::Median { window: 3 }]).unwrap(),
+        }
+    }
+
+    #[test]
+    fn when_settings_are_written_then_they_are_read_back() {
+        // Given
+        let mut pedalbox = Pedalbox::new(FakeHidraw::new());
+
+        // When
+        pedalbox.write_settings(Axis::Y, &brake_settings()).unwrap();
+        let result = pedalbox.settings(Axis::Y).unwrap();
+
+        // Then
+        assert_eq!(result, brake_settings());
+        assert_eq!(pedalbox.device.settings[Axis::Y.index()], brake_settings());
+        assert_eq!(
+            pedalbox.device.settings[Axis::X.index()],
+            AxisSettings::DEFAULT
+        );
+    }
+
+    #[test]
+    fn when_calibration_is_driven_then_the_status_follows() {
+        // Given
+        let mut pedalbox = Pedalbox::new(FakeHidraw::new());
+
+        // When
+        pedalbox.session_command(SessionCommand::Start).unwrap();
+        pedalbox.device.session.observe(Axis::Z, 100);
+        pedalbox.device.session.observe(Axis::Z, 64_000);
+        pedalbox
+            .session_command(SessionCommand::Commit(CommitTarget::Ram))
+            .unwrap();
+        let repeated_commit = pedalbox.session_command(SessionCommand::Commit(CommitTarget::Ram));
+        let result = pedalbox.session_status().unwrap();
+
+        // Then
+        assert!(repeated_commit.is_err());
+        assert_eq!(result.state, CalibrationState::Committed);
+        assert_eq!(result.captured[2], Some(AxisCalibration::new(100, 64_000)));
+    }
+
+    #[test]
+    fn when_input_reports_arrive_then_unknown_ones_are_skipped() {
+        // Given
+        let mut device = FakeHidraw::new();
+        device.inputs.push_back(vec![0x7F, 0, 0]);
+        device
+            .inputs
+            .push_back(bytemuck::bytes_of(&DiagnosticsReport::new([1, 2, 3])).to_vec());
+        device
+            .inputs
+            .push_back(bytemuck::bytes_of(&PedalboxReport::new(1, 2, 3, 4)).to_vec());
+        let mut pedalbox = Pedalbox::new(device);
+
+        // When
+        let result: PedalboxReport = pedalbox.read_input().unwrap();
+
+        // Then
+        assert_eq!((result.x, result.y, result.z, result.buttons), (1, 2, 3, 4));
+    }
+
+    #[test]
+    fn when_status_is_read_then_the_pedal_reports_before_it_are_skipped() {
+        // Given
+        let mut device = FakeHidraw::new();
+        device
+            .inputs
+            .push_back(bytemuck::bytes_of(&PedalboxReport::new(1, 2, 3, 4)).to_vec());
+        device.inputs.push_back(
+            bytemuck::bytes_of(&StatusReport::new(
+                &PedalSnapshot::UNKNOWN,
+                312,
+                [0, 2, 1],
+                3_600,
+            ))
+            .to_vec(),
+        );
+        let mut pedalbox = Pedalbox::new(device);
+
+        // When
+        let result: StatusReport = pedalbox.read_input().unwrap();
+
+        // Then
+        assert_eq!(
+            (result.temperature, result.version(), result.uptime_s),
+            (312, [0, 2, 1], 3_600)
+        );
+    }
+
+    #[test]
+    fn when_report_mode_is_written_then_it_is_read_back() {
+        // Given
+        let mut pedalbox = Pedalbox::new(FakeHidraw::new());
+        let mode = ReportMode::Periodic { interval_ms: 4 };
+
+        // When
+        pedalbox.set_report_mode(mode).unwrap();
+        let result = pedalbox.report_mode().unwrap();
+
+        // Then
+        assert_eq!(result, mode);
+    }
+
+    #[test]
+    fn when_max_latency_is_reset_then_the_statistics_start_over() {
+        // Given
+        let device = FakeHidraw::new();
+        device.report_statistics.record(ReportReason::Change, 1_200);
+        device.report_statistics.record(ReportReason::Timeout, 0);
+        let mut pedalbox = Pedalbox::new(device);
+        let before_reset = pedalbox.report_status().unwrap();
+
+        // When
+        pedalbox.reset_max_latency().unwrap();
+        let result = pedalbox.report_status().unwrap();
+
+        // Then
+        assert_eq!(before_reset.max_latency_us, 1_200);
+        assert_eq!(
+            result,
+            ReportStatus {
+                reports: 2,
+                heartbeats: 1,
+                last_latency_us: 1_200,
+                max_latency_us: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn when_watchdog_reset_the_pedalbox_then_the_culprit_is_read() {
+        // Given
+        let mut device = FakeHidraw::new();
+        device.reset_cause = ResetCause {
+            reason: ResetReason::IndependentWatchdog,
+            culprit: Some(SupervisedTask::Adc),
+        };
+        let mut pedalbox = Pedalbox::new(device);
+
+        // When
+        let result = pedalbox.reset_cause().unwrap();
+
+        // Then
+        assert_eq!(result.reason, ResetReason::IndependentWatchdog);
+        assert_eq!(result.culprit, Some(SupervisedTask::Adc));
+    }
+}
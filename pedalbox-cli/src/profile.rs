@@ -0,0 +1,404 @@
+//! Profiles holding the settings of every axis, stored as TOML or JSON.
+
+use rusty_pedalbox::axis::Axis;
+use rusty_pedalbox::calibration::AxisCalibration;
+use rusty_pedalbox::curve::{CurvePoint, CurvePoints, ResponseCurve};
+use rusty_pedalbox::deadzone::{Deadzone, DeadzoneWidth};
+use rusty_pedalbox::filters::{Filter, FilterChain, OneEuroConfig};
+use rusty_pedalbox::settings::AxisSettings;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Format {
+    Toml,
+    Json,
+}
+
+impl Format {
+    /// Picks the format from the file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "toml" => Some(Format::Toml),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ProfileError {
+    Parse(String),
+    /// The profile parsed, but describes settings the firmware would refuse.
+    Invalid(&'static str),
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProfileError::Parse(message) => write!(f, "failed to parse profile: {message}"),
+            ProfileError::Invalid(message) => write!(f, "invalid profile: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ProfileError {}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub gas: AxisProfile,
+    pub brake: AxisProfile,
+    pub clutch: AxisProfile,
+}
+
+impl Profile {
+    pub fn axis(&self, axis: Axis) -> &AxisProfile {
+        match axis {
+            Axis::X => &self.gas,
+            Axis::Y => &self.brake,
+            Axis::Z => &self.clutch,
+        }
+    }
+
+    pub fn from_settings(settings: impl Fn(Axis) -> AxisSettings) -> Self {
+        Self {
+            gas: AxisProfile::from(&settings(Axis::X)),
+            brake: AxisProfile::from(&settings(Axis::Y)),
+            clutch: AxisProfile::from(&settings(Axis::Z)),
+        }
+    }
+
+    pub fn parse(text: &str, format: Format) -> Result<Self, ProfileError> {
+        match format {
+            Format::Toml => toml::from_str(text).map_err(|e| ProfileError::Parse(e.to_string())),
+            Format::Json => {
+                serde_json::from_str(text).map_err(|e| ProfileError::Parse(e.to_string()))
+            }
+        }
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Toml => toml::to_string_pretty(self).expect("Profile is valid TOML"),
+            Format::Json => serde_json::to_string_pretty(self).expect("Profile is valid JSON"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct AxisProfile {
+    pub range_min: i32,
+    pub range_max: i32,
+    #[serde(default)]
+    pub deadzone: DeadzoneProfile,
+    #[serde(default)]
+    pub curve: CurveProfile,
+    #[serde(default)]
+    pub filters: Vec<FilterProfile>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct DeadzoneProfile {
+    pub lower: WidthProfile,
+    pub upper: WidthProfile,
+    pub saturation: u8,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WidthProfile {
+    Percent(u8),
+    Raw(u32),
+}
+
+impl Default for WidthProfile {
+    fn default() -> Self {
+        WidthProfile::Raw(0)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum CurveProfile {
+    #[default]
+    Linear,
+    Progressive {
+        strength: i8,
+    },
+    SCurve {
+        strength: i8,
+    },
+    PiecewiseLinear {
+        points: Vec<[u16; 2]>,
+    },
+    CubicSpline {
+        points: Vec<[u16; 2]>,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum FilterProfile {
+    Ema {
+        alpha: u16,
+    },
+    Median {
+        window: u8,
+    },
+    OneEuro {
+        min_cutoff_mhz: u32,
+        beta_uhz: u32,
+        derivative_cutoff_mhz: u32,
+        sample_rate_hz: u16,
+    },
+}
+
+impl From<&AxisSettings> for AxisProfile {
+    fn from(settings: &AxisSettings) -> Self {
+        let width = |width| match width {
+            DeadzoneWidth::Percent(percent) => WidthProfile::Percent(percent),
+            DeadzoneWidth::Raw(raw) => WidthProfile::Raw(raw),
+        };
+        let points = |points: &CurvePoints| {
+            points
+                .as_slice()
+                .iter()
+                .map(|point| [point.x, point.y])
+                .collect()
+        };
+        Self {
+            range_min: settings.calibration.range_min,
+            range_max: settings.calibration.range_max,
+            deadzone: DeadzoneProfile {
+                lower: width(settings.deadzone.lower),
+                upper: width(settings.deadzone.upper),
+                saturation: settings.deadzone.saturation,
+            },
+            curve: match &settings.curve {
+                ResponseCurve::Linear => CurveProfile::Linear,
+                ResponseCurve::Progressive(strength) => CurveProfile::Progressive {
+                    strength: *strength,
+                },
+                ResponseCurve::SCurve(strength) => CurveProfile::SCurve {
+                    strength: *strength,
+                },
+                ResponseCurve::PiecewiseLinear(knots) => CurveProfile::PiecewiseLinear {
+                    points: points(knots),
+                },
+                ResponseCurve::CubicSpline(knots) => CurveProfile::CubicSpline {
+                    points: points(knots),
+                },
+            },
+            filters: settings
+                .filter
+                .filters()
+                .map(|filter| match *filter {
+                    Filter::Ema { alpha } => FilterProfile::Ema { alpha },
+                    Filter::Median { window } => FilterProfile::Median { window },
+                    Filter::OneEuro(config) => FilterProfile::OneEuro {
+                        min_cutoff_mhz: config.min_cutoff_mhz,
+                        beta_uhz: config.beta_uhz,
+                        derivative_cutoff_mhz: config.derivative_cutoff_mhz,
+                        sample_rate_hz: config.sample_rate_hz,
+                    },
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<&AxisProfile> for AxisSettings {
+    type Error = ProfileError;
+
+    fn try_from(profile: &AxisProfile) -> Result<Self, Self::Error> {
+        if profile.range_min >= profile.range_max {
+            return Err(ProfileError::Invalid("range_min must be below range_max"));
+        }
+        let width = |width| match width {
+            WidthProfile::Percent(percent) => DeadzoneWidth::Percent(percent),
+            WidthProfile::Raw(raw) => DeadzoneWidth::Raw(raw),
+        };
+        let points = |points: &[[u16; 2]]| {
+            let points: Vec<_> = points
+                .iter()
+                .map(|[x, y]| CurvePoint::new(*x, *y))
+                .collect();
+            CurvePoints::new(&points).map_err(|_| ProfileError::Invalid("invalid curve points"))
+        };
+        let filters: Vec<_> = profile
+            .filters
+            .iter()
+            .map(|filter| match *filter {
+                FilterProfile::Ema { alpha } => Filter::Ema { alpha },
+                FilterProfile::Median { window } => Filter::Median { window },
+                FilterProfile::OneEuro {
+                    min_cutoff_mhz,
+                    beta_uhz,
+                    derivative_cutoff_mhz,
+                    sample_rate_hz,
+                } => Filter::OneEuro(OneEuroConfig {
+                    min_cutoff_mhz,
+                    beta_uhz,
+                    derivative_cutoff_mhz,
+                    sample_rate_hz,
+                }),
+            })
+            .collect();
+
+        Ok(AxisSettings {
+            calibration: AxisCalibration::new(profile.range_min, profile.range_max),
+            deadzone: Deadzone {
+                lower: width(profile.deadzone.lower),
+                upper: width(profile.deadzone.upper),
+                saturation: profile.deadzone.saturation,
+            },
+            curve: match &profile.curve {
+                CurveProfile::Linear => ResponseCurve::Linear,
+                CurveProfile::Progressive { strength } => ResponseCurve::Progressive(*strength),
+                CurveProfile::SCurve { strength } => ResponseCurve::SCurve(*strength),
+                CurveProfile::PiecewiseLinear { points: knots } => {
+                    ResponseCurve::PiecewiseLinear(points(knots)?)
+                }
+                CurveProfile::CubicSpline { points: knots } => {
+                    ResponseCurve::CubicSpline(points(knots)?)
+                }
+            },
+            filter: FilterChain::new(&filters)
+                .map_err(|_| ProfileError::Invalid("invalid filter chain"))?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod profile_testing {
+    use crate::profile::{
+        AxisProfile, CurveProfile, DeadzoneProfile, FilterProfile, Format, Profile, ProfileError,
+        WidthProfile,
+    };
+    use rstest::rstest;
+    use rusty_pedalbox::settings::AxisSettings;
+    use std::path::Path;
+
+    fn profile() -> Profile {
+        Profile {
+            gas: AxisProfile {
+                range_min: 1820,
+                range_max: 3100,
+                deadzone: DeadzoneProfile {
+                    lower: WidthProfile::Percent(2),
+                    upper: WidthProfile::Percent(2),
+                    saturation: 0,
+                },
+                curve: CurveProfile::Progressive { strength: 30 },
+                filters: vec![FilterProfile::Ema { alpha: 16_383 }],
+            },
+            brake: AxisProfile {
+                range_min: 0,
+                range_max: 230_000,
+                deadzone: DeadzoneProfile::default(),
+                curve: CurveProfile::CubicSpline {
+                    points: vec![[0, 0], [20_000, 10_000], [65_535, 65_535]],
+                },
+                filters: vec![
+                    FilterProfile::Median { window: 3 },
+                    FilterProfile::OneEuro {
+                        min_cutoff_mhz: 1_000,
+                        beta_uhz: 7_000,
+                        derivative_cutoff_mhz: 1_000,
+                        sample_rate_hz: 100,
+                    },
+                ],
+            },
+            clutch: AxisProfile {
+                range_min: 0,
+                range_max: 65_535,
+                deadzone: DeadzoneProfile::default(),
+                curve: CurveProfile::Linear,
+                filters: vec![],
+            },
+        }
+    }
+
+    #[rstest]
+    #[case("profile.toml", Some(Format::Toml))]
+    #[case("/tmp/profile.json", Some(Format::Json))]
+    #[case("profile.yaml", None)]
+    #[case("profile", None)]
+    fn when_format_is_picked_from_path(#[case] path: &str, #[case] expected: Option<Format>) {
+        // When
+        let result = Format::from_path(Path::new(path));
+
+        // Then
+        assert_eq!(result, expected);
+    }
+
+    #[rstest]
+    #[case(Format::Toml)]
+    #[case(Format::Json)]
+    fn when_profile_is_rendered_then_it_parses_back(#[case] format: Format) {
+        // Given
+        let profile = profile();
+
+        // When
+        let result = Profile::parse(&profile.render(format), format);
+
+        // Then
+        assert_eq!(result, Ok(profile));
+    }
+
+    #[test]
+    fn when_profile_is_converted_then_the_settings_survive_the_round_trip() {
+        // Given
+        let profile = profile();
+
+        // When
+        let settings = AxisSettings::try_from(&profile.brake).unwrap();
+        let result = AxisProfile::from(&settings);
+
+        // Then
+        assert_eq!(result, profile.brake);
+    }
+
+    #[test]
+    fn when_handwritten_toml_is_parsed_then_missing_sections_use_defaults() {
+        // Given
+        let text = r#"
+            [gas]
+            range_min = 1820
+            range_max = 3100
+            curve = { kind = "s-curve", strength = 50 }
+
+            [brake]
+            range_min = 0
+            range_max = 230000
+            deadzone = { lower = { raw = 500 }, upper = { percent = 5 }, saturation = 2 }
+
+            [clutch]
+            range_min = 0
+            range_max = 65535
+            filters = [{ kind = "median", window = 5 }]
+        "#;
+
+        // When
+        let result = Profile::parse(text, Format::Toml).unwrap();
+
+        // Then
+        assert_eq!(result.gas.curve, CurveProfile::SCurve { strength: 50 });
+        assert_eq!(result.gas.filters, []);
+        assert_eq!(result.brake.deadzone.lower, WidthProfile::Raw(500));
+        assert_eq!(result.clutch.filters, [FilterProfile::Median { window: 5 }]);
+    }
+
+    #[rstest]
+    #[case(AxisProfile { range_min: 10, range_max: 10, ..profile().clutch })]
+    #[case(AxisProfile { curve: CurveProfile::PiecewiseLinear { points: vec![[0, 0]] }, ..profile().clutch })]
+    #[case(AxisProfile { filters: vec![FilterProfile::Median { window: 20 }], ..profile().clutch })]
+    fn when_profile_is_invalid_then_it_is_refused(#[case] profile: AxisProfile) {
+        // When
+        let result = AxisSettings::try_from(&profile);
+
+        // Then
+        assert!(matches!(result, Err(ProfileError::Invalid(_))));
+    }
+}
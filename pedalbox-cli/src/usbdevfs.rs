@@ -0,0 +1,329 @@
+//! Raw USB access through the Linux usbdevfs interface, used for DFU.
+
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+
+/// Control transfers on the default endpoint, so the device can be faked in tests.
+pub trait ControlDevice {
+    /// Sends a control request with `data` in its data stage.
+    fn control_out(
+        &mut self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        data: &[u8],
+    ) -> io::Result<()>;
+    /// Sends a control request and reads its data stage into `buffer`. Returns the number
+    /// of bytes read.
+    fn control_in(
+        &mut self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        buffer: &mut [u8],
+    ) -> io::Result<usize>;
+}
+
+/// `_IOC(direction, 'U', number, length)` from `linux/usbdevice_fs.h`.
+const fn usbdevfs_ioctl(direction: libc::c_ulong, number: u8, length: usize) -> libc::c_ulong {
+    (direction << 30)
+        | ((length as libc::c_ulong) << 16)
+        | ((b'U' as libc::c_ulong) << 8)
+        | number as libc::c_ulong
+}
+
+const IOC_READ: libc::c_ulong = 2;
+const IOC_READ_WRITE: libc::c_ulong = 3;
+const USBDEVFS_CONTROL: u8 = 0;
+const USBDEVFS_CLAIMINTERFACE: u8 = 15;
+const USBDEVFS_RELEASEINTERFACE: u8 = 16;
+
+/// Class request to an interface, host to device.
+pub const CLASS_INTERFACE_OUT: u8 = 0x21;
+/// Class request to an interface, device to host.
+pub const CLASS_INTERFACE_IN: u8 = 0xa1;
+
+/// `struct usbdevfs_ctrltransfer`.
+#[repr(C)]
+struct ControlTransfer {
+    request_type: u8,
+    request: u8,
+    value: u16,
+    index: u16,
+    length: u16,
+    timeout_ms: u32,
+    data: *mut libc::c_void,
+}
+
+/// Timeout of a control transfer. Erasing a 128K sector takes up to 2 s, but the
+/// bootloader answers the request first and erases during the poll timeout.
+const CONTROL_TIMEOUT_MS: u32 = 5_000;
+
+pub struct UsbDevice {
+    file: File,
+    claimed: Option<u32>,
+}
+
+impl UsbDevice {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        Ok(Self {
+            file,
+            claimed: None,
+        })
+    }
+
+    pub fn claim_interface(&mut self, interface: u8) -> io::Result<()> {
+        let mut interface = interface as u32;
+        self.ioctl(
+            usbdevfs_ioctl(IOC_READ, USBDEVFS_CLAIMINTERFACE, 4),
+            &mut interface as *mut u32 as _,
+        )?;
+        self.claimed = Some(interface);
+        Ok(())
+    }
+
+    fn control(
+        &mut self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        data: &mut [u8],
+    ) -> io::Result<usize> {
+        let mut transfer = ControlTransfer {
+            request_type,
+            request,
+            value,
+            index,
+            length: u16::try_from(data.len())
+                .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?,
+            timeout_ms: CONTROL_TIMEOUT_MS,
+            data: data.as_mut_ptr() as _,
+        };
+        self.ioctl(
+            usbdevfs_ioctl(
+                IOC_READ_WRITE,
+                USBDEVFS_CONTROL,
+                size_of::<ControlTransfer>(),
+            ),
+            &mut transfer as *mut ControlTransfer as _,
+        )
+    }
+
+    fn ioctl(&mut self, request: libc::c_ulong, argument: *mut libc::c_void) -> io::Result<usize> {
+        // SAFETY: `argument` points to the structure the kernel expects for `request`, and
+        // a control transfer only touches the `length` bytes of its data buffer.
+        let result = unsafe { libc::ioctl(self.file.as_raw_fd(), request as _, argument) };
+        if result < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(result as usize)
+        }
+    }
+}
+
+impl ControlDevice for UsbDevice {
+    fn control_out(
+        &mut self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        data: &[u8],
+    ) -> io::Result<()> {
+        let mut data = data.to_vec();
+        self.control(request_type, request, value, index, &mut data)
+            .map(|_| ())
+    }
+
+    fn control_in(
+        &mut self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        buffer: &mut [u8],
+    ) -> io::Result<usize> {
+        self.control(request_type, request, value, index, buffer)
+    }
+}
+
+impl Drop for UsbDevice {
+    fn drop(&mut self) {
+        if let Some(mut interface) = self.claimed.take() {
+            let _ = self.ioctl(
+                usbdevfs_ioctl(IOC_READ, USBDEVFS_RELEASEINTERFACE, 4),
+                &mut interface as *mut u32 as _,
+            );
+        }
+    }
+}
+
+/// A USB device found in `sys_root/bus/usb/devices`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FoundDevice {
+    /// The usbdevfs node, `/dev/bus/usb/<bus>/<device>`.
+    pub node: PathBuf,
+    /// The sysfs directory of the device.
+    pub sysfs: PathBuf,
+}
+
+/// Returns the first USB device with the given IDs.
+pub fn find_device(
+    sys_root: &Path,
+    vendor_id: u16,
+    product_id: u16,
+) -> io::Result<Option<FoundDevice>> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(sys_root.join("bus/usb/devices"))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<_>>()?;
+    entries.sort();
+    for sysfs in entries {
+        let id = |name: &str| read_hex(&sysfs.join(name));
+        if id("idVendor") != Some(vendor_id as u32) || id("idProduct") != Some(product_id as u32) {
+            continue;
+        }
+        let number = |name: &str| -> Option<u32> {
+            fs::read_to_string(sysfs.join(name))
+                .ok()?
+                .trim()
+                .parse()
+                .ok()
+        };
+        let (Some(bus), Some(device)) = (number("busnum"), number("devnum")) else {
+            continue;
+        };
+        return Ok(Some(FoundDevice {
+            node: PathBuf::from(format!("/dev/bus/usb/{bus:03}/{device:03}")),
+            sysfs,
+        }));
+    }
+    Ok(None)
+}
+
+/// Returns the number of the first interface of `device` with the given class and
+/// subclass.
+pub fn find_interface(device: &FoundDevice, class: u8, subclass: u8) -> io::Result<Option<u8>> {
+    let name = device
+        .sysfs
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+        .to_owned();
+    for entry in fs::read_dir(&device.sysfs)? {
+        let path = entry?.path();
+        let is_interface = path
+            .file_name()
+            .and_then(|file| file.to_str())
+            .is_some_and(|file| file.starts_with(&format!("{name}:")));
+        if !is_interface {
+            continue;
+        }
+        if read_hex(&path.join("bInterfaceClass")) == Some(class as u32)
+            && read_hex(&path.join("bInterfaceSubClass")) == Some(subclass as u32)
+        {
+            return Ok(read_hex(&path.join("bInterfaceNumber")).map(|number| number as u8));
+        }
+    }
+    Ok(None)
+}
+
+fn read_hex(path: &Path) -> Option<u32> {
+    u32::from_str_radix(fs::read_to_string(path).ok()?.trim(), 16).ok()
+}
+
+#[cfg(test)]
+mod usbdevfs_testing {
+    use crate::usbdevfs::{
+        find_device, find_interface, usbdevfs_ioctl, ControlTransfer, FoundDevice, IOC_READ,
+        IOC_READ_WRITE, USBDEVFS_CLAIMINTERFACE, USBDEVFS_CONTROL,
+    };
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    fn write_attributes(directory: &Path, attributes: &[(&str, &str)]) {
+        fs::create_dir_all(directory).unwrap();
+        for (name, value) in attributes {
+            fs::write(directory.join(name), format!("{value}\n")).unwrap();
+        }
+    }
+
+    #[test]
+    fn when_ioctl_numbers_are_built_then_they_match_the_kernel_header() {
+        // When
+        let control = usbdevfs_ioctl(
+            IOC_READ_WRITE,
+            USBDEVFS_CONTROL,
+            size_of::<ControlTransfer>(),
+        );
+        let claim = usbdevfs_ioctl(IOC_READ, USBDEVFS_CLAIMINTERFACE, 4);
+
+        // Then
+        assert_eq!(control, 0xC018_5500);
+        assert_eq!(claim, 0x8004_550F);
+    }
+
+    #[test]
+    fn when_devices_are_searched_then_the_pedalbox_and_its_dfu_interface_are_found() {
+        // Given
+        let root = std::env::temp_dir().join(format!("pedalbox-usb-{}", std::process::id()));
+        let devices = root.join("bus/usb/devices");
+        write_attributes(
+            &devices.join("1-1"),
+            &[
+                ("idVendor", "046d"),
+                ("idProduct", "c52b"),
+                ("busnum", "1"),
+                ("devnum", "2"),
+            ],
+        );
+        write_attributes(
+            &devices.join("1-2"),
+            &[
+                ("idVendor", "cafe"),
+                ("idProduct", "2025"),
+                ("busnum", "1"),
+                ("devnum", "7"),
+            ],
+        );
+        for (interface, class, subclass) in
+            [("00", "03", "00"), ("01", "02", "02"), ("03", "fe", "01")]
+        {
+            write_attributes(
+                &devices.join(format!(
+                    "1-2/1-2:1.{}",
+                    interface.trim_start_matches('0').max("0")
+                )),
+                &[
+                    ("bInterfaceNumber", interface),
+                    ("bInterfaceClass", class),
+                    ("bInterfaceSubClass", subclass),
+                ],
+            );
+        }
+
+        // When
+        let device = find_device(&root, 0xcafe, 0x2025).unwrap();
+        let interface = device
+            .as_ref()
+            .map(|device| find_interface(device, 0xfe, 0x01).unwrap());
+        let missing = find_device(&root, 0x0483, 0xdf11).unwrap();
+
+        // Then
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(
+            device,
+            Some(FoundDevice {
+                node: PathBuf::from("/dev/bus/usb/001/007"),
+                sysfs: devices.join("1-2"),
+            })
+        );
+        assert_eq!(interface, Some(Some(3)));
+        assert_eq!(missing, None);
+    }
+}
@@ -0,0 +1,30 @@
+/// Number of pedal axes reported to the host.
+pub const AXIS_COUNT: usize = 3;
+
+/// Axes of the HID report, in report order.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Axis {
+    /// Gas pedal.
+    X,
+    /// Brake pedal.
+    Y,
+    /// Clutch pedal.
+    Z,
+}
+
+impl Axis {
+    pub const ALL: [Axis; AXIS_COUNT] = [Axis::X, Axis::Y, Axis::Z];
+
+    pub const fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl TryFrom<u8> for Axis {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Axis::ALL.get(value as usize).copied().ok_or(())
+    }
+}
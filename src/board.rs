@@ -1,6 +1,9 @@
+use embassy_stm32::exti::ExtiInput;
 use embassy_stm32::gpio::{Input, Level, Output, Pull, Speed};
-use embassy_stm32::peripherals::{ADC1, ADC2, PA11, PA12, PA5, PA7, USB_OTG_FS};
+use embassy_stm32::peripherals::{ADC1, DMA2_CH0, FLASH, IWDG, PA11, PA12, PA5, PA7, USB_OTG_FS};
 use embassy_stm32::{bind_interrupts, usb, Peri, Peripherals};
+use embedded_hal::blocking::delay::DelayUs;
+use rusty_pedalbox::temperature::TemperatureCalibration;
 
 bind_interrupts!(pub struct Irqs {
     OTG_FS => usb::InterruptHandler<USB_OTG_FS>;
@@ -11,29 +14,89 @@ pub struct Board {
     pub usb_interrupt: Irqs,
     pub usb_d_plus: Peri<'static, PA12>,
     pub usb_d_minus: Peri<'static, PA11>,
-    pub gas_adc: Peri<'static, ADC1>,
+    /// Scans the potentiometers of the gas and the clutch.
+    pub analog_adc: Peri<'static, ADC1>,
+    pub analog_dma: Peri<'static, DMA2_CH0>,
     pub gas_potentiometer: Peri<'static, PA7>,
-    pub clutch_adc: Peri<'static, ADC2>,
     pub clutch_potentiometer: Peri<'static, PA5>,
-    pub brake_data: Input<'static>,
+    /// DOUT of the HX711, falls when a conversion is ready.
+    pub brake_data: ExtiInput<'static>,
     pub brake_clock: Output<'static>,
+    /// RATE pin of the HX711, high for 80 samples per second.
+    pub brake_rate: Output<'static>,
+    pub flash: Peri<'static, FLASH>,
+    pub watchdog: Peri<'static, IWDG>,
+    pub user_button: ExtiInput<'static>,
+    /// Wheel and shifter buttons, closing to ground.
+    pub buttons: [Input<'static>; 8],
 }
 
 impl Board {
     pub fn new(peripherals: Peripherals) -> Self {
-        let brake_data = Input::new(peripherals.PC11, Pull::None);
+        let brake_data = ExtiInput::new(peripherals.PC11, peripherals.EXTI11, Pull::None);
         let brake_clock = Output::new(peripherals.PC12, Level::Low, Speed::High);
+        let brake_rate = Output::new(peripherals.PC10, Level::Low, Speed::Low);
+        let user_button = ExtiInput::new(peripherals.PA0, peripherals.EXTI0, Pull::Down);
+        let buttons = [
+            Input::new(peripherals.PE7, Pull::Up),
+            Input::new(peripherals.PE8, Pull::Up),
+            Input::new(peripherals.PE9, Pull::Up),
+            Input::new(peripherals.PE10, Pull::Up),
+            Input::new(peripherals.PE11, Pull::Up),
+            Input::new(peripherals.PE12, Pull::Up),
+            Input::new(peripherals.PE13, Pull::Up),
+            Input::new(peripherals.PE14, Pull::Up),
+        ];
         Self {
             usb_peripheral: peripherals.USB_OTG_FS,
             usb_interrupt: Irqs,
             usb_d_plus: peripherals.PA12,
             usb_d_minus: peripherals.PA11,
-            gas_adc: peripherals.ADC1,
+            analog_adc: peripherals.ADC1,
+            analog_dma: peripherals.DMA2_CH0,
             gas_potentiometer: peripherals.PA7,
-            clutch_adc: peripherals.ADC2,
             clutch_potentiometer: peripherals.PA5,
             brake_data,
             brake_clock,
+            brake_rate,
+            flash: peripherals.FLASH,
+            watchdog: peripherals.IWDG,
+            user_button,
+            buttons,
         }
     }
 }
+
+/// Readings of the temperature sensor at 30 °C and 110 °C the factory stored in the system
+/// memory, see the datasheet.
+const TS_CAL1: *const u16 = 0x1FFF_7A2C as *const u16;
+const TS_CAL2: *const u16 = 0x1FFF_7A2E as *const u16;
+
+/// Factory calibration of the temperature sensor, or the typical one if it looks erased.
+pub fn temperature_calibration() -> TemperatureCalibration {
+    // SAFETY: the system memory is always mapped and readable.
+    let calibration = unsafe {
+        TemperatureCalibration {
+            at_30: TS_CAL1.read_volatile(),
+            at_110: TS_CAL2.read_volatile(),
+        }
+    };
+    if calibration.is_valid() {
+        calibration
+    } else {
+        TemperatureCalibration::TYPICAL
+    }
+}
+
+/// Core clock set up by `usb_configuration`.
+const SYSCLK_MHZ: u32 = 168;
+
+/// Busy waits by counting core cycles. Unlike the embassy timer it can wait for a single
+/// microsecond, as the HX711 clock needs.
+pub struct CycleDelay;
+
+impl DelayUs<u32> for CycleDelay {
+    fn delay_us(&mut self, us: u32) {
+        cortex_m::asm::delay(us * SYSCLK_MHZ);
+    }
+}
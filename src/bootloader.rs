@@ -0,0 +1,30 @@
+use embassy_stm32::pac;
+
+/// RTC backup register asking the next boot to start the bootloader.
+const REQUEST_REGISTER: usize = 1;
+const REQUEST_MAGIC: u32 = 0xb007_10ad;
+/// Vector table of the ROM bootloader of the STM32F407, which offers DFU on USB OTG FS.
+const SYSTEM_MEMORY: u32 = 0x1fff_0000;
+
+/// Resets the chip into the ROM bootloader. The jump happens on the next boot, before any
+/// clock or peripheral is set up.
+pub fn reboot_into_bootloader() -> ! {
+    pac::RTC
+        .bkpr(REQUEST_REGISTER)
+        .write(|w| w.set_bkp(REQUEST_MAGIC));
+    cortex_m::peripheral::SCB::sys_reset()
+}
+
+/// Starts the ROM bootloader if the last boot asked for it. Has to run first thing after
+/// the reset.
+pub fn start_if_requested() {
+    if pac::RTC.bkpr(REQUEST_REGISTER).read().bkp() != REQUEST_MAGIC {
+        return;
+    }
+    pac::RCC.apb1enr().modify(|w| w.set_pwren(true));
+    pac::PWR.cr1().modify(|w| w.set_dbp(true));
+    pac::RTC.bkpr(REQUEST_REGISTER).write(|w| w.set_bkp(0));
+    // SAFETY: the ROM holds a valid vector table and nothing was set up yet that the
+    // bootloader could trip over.
+    unsafe { cortex_m::asm::bootload(SYSTEM_MEMORY as *const u32) }
+}
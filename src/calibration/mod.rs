@@ -0,0 +1,82 @@
+//! Calibration of the pedal axes and its persistent storage.
+
+mod record;
+mod session;
+mod store;
+
+pub use record::{DecodeError, StoredCalibration, RECORD_MAGIC, RECORD_SIZE, RECORD_VERSION};
+pub use session::{CalibrationSession, CalibrationState, CommitTarget};
+pub use store::{CalibrationStore, StoreError};
+
+use crate::axis::{Axis, AXIS_COUNT};
+
+/// Raw sensor values belonging to the released and the fully pressed pedal.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AxisCalibration {
+    pub range_min: i32,
+    pub range_max: i32,
+}
+
+impl AxisCalibration {
+    pub const fn new(range_min: i32, range_max: i32) -> Self {
+        Self {
+            range_min,
+            range_max,
+        }
+    }
+
+    /// Returns the range converted to the sample type of a monitor, or `None` when it
+    /// doesn't fit into it or `range_min` isn't below `range_max`.
+    pub fn range<T>(&self) -> Option<(T, T)>
+    where
+        T: TryFrom<i32>,
+    {
+        if self.range_min >= self.range_max {
+            return None;
+        }
+        let min = T::try_from(self.range_min).ok()?;
+        let max = T::try_from(self.range_max).ok()?;
+        Some((min, max))
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Calibration {
+    pub axes: [AxisCalibration; AXIS_COUNT],
+}
+
+impl Calibration {
+    pub fn axis(&self, axis: Axis) -> AxisCalibration {
+        self.axes[axis.index()]
+    }
+
+    pub fn set_axis(&mut self, axis: Axis, calibration: AxisCalibration) {
+        self.axes[axis.index()] = calibration;
+    }
+}
+
+#[cfg(test)]
+mod calibration_testing {
+    use crate::calibration::AxisCalibration;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(AxisCalibration::new(1820, 3100), Some((1820, 3100)))]
+    #[case(AxisCalibration::new(0, 65_535), Some((0, 65_535)))]
+    #[case(AxisCalibration::new(-1, 3100), None)]
+    #[case(AxisCalibration::new(0, 65_536), None)]
+    #[case(AxisCalibration::new(3100, 1820), None)]
+    #[case(AxisCalibration::new(3100, 3100), None)]
+    fn when_converting_the_range(
+        #[case] calibration: AxisCalibration,
+        #[case] expected: Option<(u16, u16)>,
+    ) {
+        // When
+        let result = calibration.range::<u16>();
+
+        // Then
+        assert_eq!(result, expected);
+    }
+}
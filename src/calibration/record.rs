@@ -0,0 +1,303 @@
+//! Layout of a calibration record in flash. All fields are little endian.
+//!
+//! | Offset | Size | Field                                   |
+//! |--------|------|-----------------------------------------|
+//! | 0      | 4    | Magic, `PBCL`                           |
+//! | 4      | 2    | Layout version                          |
+//! | 6      | 2    | Payload length                          |
+//! | 8      | 4    | Sequence number, incremented every save |
+//! | 12     | n    | Payload                                 |
+//! | 12 + n | 4    | CRC-32 of everything before it          |
+//!
+//! Version 1 payload: `range_min` and `range_max` as `i32` for every axis.
+//!
+//! Version 2 payload: the version 1 payload followed by the name of the pedalbox in
+//! [`MAX_NAME_LENGTH`] bytes, padded with zeros. All zeros when it has no name. Version 1
+//! records are still read, without a name.
+
+use crate::axis::AXIS_COUNT;
+use crate::calibration::{AxisCalibration, Calibration};
+use crate::crc::crc32;
+use crate::serial_number::{DeviceName, MAX_NAME_LENGTH};
+
+pub const RECORD_MAGIC: [u8; 4] = *b"PBCL";
+pub const RECORD_VERSION: u16 = 2;
+
+const HEADER_SIZE: usize = 12;
+const CRC_SIZE: usize = 4;
+const AXIS_SIZE: usize = 8;
+const AXES_SIZE: usize = AXIS_COUNT * AXIS_SIZE;
+const PAYLOAD_SIZE: usize = AXES_SIZE + MAX_NAME_LENGTH;
+
+/// Size of an encoded record.
+pub const RECORD_SIZE: usize = HEADER_SIZE + PAYLOAD_SIZE + CRC_SIZE;
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DecodeError {
+    /// The magic is missing, the slot is most likely erased.
+    NoRecord,
+    UnsupportedVersion(u16),
+    InvalidLength,
+    InvalidCrc,
+    /// The name has characters a name can't have.
+    InvalidName,
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct StoredCalibration {
+    pub sequence: u32,
+    pub calibration: Calibration,
+    /// Name of the pedalbox, its USB serial number.
+    pub name: Option<DeviceName>,
+}
+
+impl StoredCalibration {
+    pub fn encode(&self) -> [u8; RECORD_SIZE] {
+        let mut bytes = [0u8; RECORD_SIZE];
+        bytes[0..4].copy_from_slice(&RECORD_MAGIC);
+        bytes[4..6].copy_from_slice(&RECORD_VERSION.to_le_bytes());
+        bytes[6..8].copy_from_slice(&(PAYLOAD_SIZE as u16).to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.sequence.to_le_bytes());
+
+        let axes = &mut bytes[HEADER_SIZE..HEADER_SIZE + AXES_SIZE];
+        for (chunk, axis) in axes.chunks_exact_mut(AXIS_SIZE).zip(self.calibration.axes) {
+            chunk[0..4].copy_from_slice(&axis.range_min.to_le_bytes());
+            chunk[4..8].copy_from_slice(&axis.range_max.to_le_bytes());
+        }
+        if let Some(name) = self.name {
+            let name = name.as_str().as_bytes();
+            let offset = HEADER_SIZE + AXES_SIZE;
+            bytes[offset..offset + name.len()].copy_from_slice(name);
+        }
+
+        let crc = crc32(&bytes[..HEADER_SIZE + PAYLOAD_SIZE]);
+        bytes[HEADER_SIZE + PAYLOAD_SIZE..].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        if bytes.len() < HEADER_SIZE || bytes[0..4] != RECORD_MAGIC {
+            return Err(DecodeError::NoRecord);
+        }
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        let payload_size = match version {
+            1 => AXES_SIZE,
+            RECORD_VERSION => PAYLOAD_SIZE,
+            _ => return Err(DecodeError::UnsupportedVersion(version)),
+        };
+        let length = u16::from_le_bytes([bytes[6], bytes[7]]) as usize;
+        if length != payload_size || bytes.len() < HEADER_SIZE + length + CRC_SIZE {
+            return Err(DecodeError::InvalidLength);
+        }
+        let crc_offset = HEADER_SIZE + length;
+        let stored_crc = u32::from_le_bytes(read_array(bytes, crc_offset));
+        if crc32(&bytes[..crc_offset]) != stored_crc {
+            return Err(DecodeError::InvalidCrc);
+        }
+
+        let sequence = u32::from_le_bytes(read_array(bytes, 8));
+        let mut axes = [AxisCalibration::new(0, 0); AXIS_COUNT];
+        for (index, axis) in axes.iter_mut().enumerate() {
+            let offset = HEADER_SIZE + index * AXIS_SIZE;
+            axis.range_min = i32::from_le_bytes(read_array(bytes, offset));
+            axis.range_max = i32::from_le_bytes(read_array(bytes, offset + 4));
+        }
+        let name = match version {
+            1 => None,
+            _ => decode_name(&bytes[HEADER_SIZE + AXES_SIZE..HEADER_SIZE + PAYLOAD_SIZE])?,
+        };
+        Ok(Self {
+            sequence,
+            calibration: Calibration { axes },
+            name,
+        })
+    }
+}
+
+fn decode_name(bytes: &[u8]) -> Result<Option<DeviceName>, DecodeError> {
+    let length = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    if length == 0 {
+        return Ok(None);
+    }
+    core::str::from_utf8(&bytes[..length])
+        .ok()
+        .and_then(DeviceName::new)
+        .map(Some)
+        .ok_or(DecodeError::InvalidName)
+}
+
+fn read_array<const N: usize>(bytes: &[u8], offset: usize) -> [u8; N] {
+    let mut array = [0u8; N];
+    array.copy_from_slice(&bytes[offset..offset + N]);
+    array
+}
+
+#[cfg(test)]
+mod record_testing {
+    use crate::calibration::{
+        AxisCalibration, Calibration, DecodeError, StoredCalibration, RECORD_SIZE,
+    };
+    use crate::crc::crc32;
+    use crate::serial_number::DeviceName;
+    use rstest::rstest;
+
+    fn stored() -> StoredCalibration {
+        StoredCalibration {
+            sequence: 7,
+            calibration: Calibration {
+                axes: [
+                    AxisCalibration::new(1820, 3100),
+                    AxisCalibration::new(-1_000, 230_000),
+                    AxisCalibration::new(0, 65_535),
+                ],
+            },
+            name: DeviceName::new("left-rig"),
+        }
+    }
+
+    /// Encodes `record` the way firmware before the name did.
+    fn encode_version_1(record: &StoredCalibration) -> [u8; 40] {
+        let mut bytes = [0; 40];
+        bytes[..36].copy_from_slice(&record.encode()[..36]);
+        bytes[4] = 1;
+        bytes[6] = 24;
+        let crc = crc32(&bytes[..36]);
+        bytes[36..].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn when_record_is_encoded_then_it_decodes_to_the_same_calibration() {
+        // Given
+        let record = stored();
+
+        // When
+        let result = StoredCalibration::decode(&record.encode());
+
+        // Then
+        assert_eq!(result, Ok(record));
+    }
+
+    #[test]
+    fn when_record_is_encoded_then_the_header_is_in_place() {
+        // When
+        let result = stored().encode();
+
+        // Then
+        assert_eq!(result.len(), RECORD_SIZE);
+        assert_eq!(&result[0..4], b"PBCL");
+        assert_eq!(&result[4..6], &[2, 0]);
+        assert_eq!(&result[6..8], &[40, 0]);
+        assert_eq!(&result[8..12], &[7, 0, 0, 0]);
+        assert_eq!(&result[12..16], &1820i32.to_le_bytes());
+        assert_eq!(&result[36..52], b"left-rig\0\0\0\0\0\0\0\0");
+    }
+
+    #[test]
+    fn when_pedalbox_has_no_name_then_it_decodes_without_one() {
+        // Given
+        let record = StoredCalibration {
+            name: None,
+            ..stored()
+        };
+
+        // When
+        let result = StoredCalibration::decode(&record.encode());
+
+        // Then
+        assert_eq!(result, Ok(record));
+    }
+
+    #[test]
+    fn when_record_is_version_1_then_it_decodes_without_a_name() {
+        // Given
+        let bytes = encode_version_1(&stored());
+
+        // When
+        let result = StoredCalibration::decode(&bytes);
+
+        // Then
+        assert_eq!(
+            result,
+            Ok(StoredCalibration {
+                name: None,
+                ..stored()
+            })
+        );
+    }
+
+    #[test]
+    fn when_name_has_invalid_characters_then_the_record_is_rejected() {
+        // Given
+        let mut bytes = stored().encode();
+        bytes[40] = b' ';
+        let crc = crc32(&bytes[..RECORD_SIZE - 4]);
+        bytes[RECORD_SIZE - 4..].copy_from_slice(&crc.to_le_bytes());
+
+        // When
+        let result = StoredCalibration::decode(&bytes);
+
+        // Then
+        assert_eq!(result, Err(DecodeError::InvalidName));
+    }
+
+    #[rstest]
+    #[case(0, DecodeError::NoRecord)]
+    #[case(4, DecodeError::UnsupportedVersion(3))]
+    #[case(6, DecodeError::InvalidLength)]
+    #[case(9, DecodeError::InvalidCrc)]
+    #[case(20, DecodeError::InvalidCrc)]
+    #[case(RECORD_SIZE - 1, DecodeError::InvalidCrc)]
+    fn when_a_byte_is_corrupted(#[case] offset: usize, #[case] expected: DecodeError) {
+        // Given
+        let mut bytes = stored().encode();
+        bytes[offset] ^= 0x01;
+
+        // When
+        let result = StoredCalibration::decode(&bytes);
+
+        // Then
+        assert_eq!(result, Err(expected));
+    }
+
+    #[test]
+    fn when_slot_is_erased_then_there_is_no_record() {
+        // Given
+        let bytes = [0xFF; RECORD_SIZE];
+
+        // When
+        let result = StoredCalibration::decode(&bytes);
+
+        // Then
+        assert_eq!(result, Err(DecodeError::NoRecord));
+    }
+
+    #[test]
+    fn when_version_is_unknown_then_the_record_is_rejected_even_with_valid_crc() {
+        // Given
+        let mut bytes = stored().encode();
+        bytes[4] = 3;
+        let crc = crc32(&bytes[..RECORD_SIZE - 4]);
+        bytes[RECORD_SIZE - 4..].copy_from_slice(&crc.to_le_bytes());
+
+        // When
+        let result = StoredCalibration::decode(&bytes);
+
+        // Then
+        assert_eq!(result, Err(DecodeError::UnsupportedVersion(3)));
+    }
+
+    #[test]
+    fn when_record_is_truncated_then_it_is_rejected() {
+        // Given
+        let bytes = stored().encode();
+
+        // When
+        let result = StoredCalibration::decode(&bytes[..RECORD_SIZE - 1]);
+
+        // Then
+        assert_eq!(result, Err(DecodeError::InvalidLength));
+    }
+}
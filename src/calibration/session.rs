@@ -0,0 +1,266 @@
+//! Interactive calibration, where the range of every axis is learned while the user
+//! sweeps the pedals.
+//!
+//! The monitors feed their readings into the session while it is capturing. When the
+//! capture is committed, the observed minimum and maximum of every swept axis become its
+//! new range. Committing only marks the session, applying the ranges (and saving them)
+//! is done by whoever calls [`CalibrationSession::take_commit`].
+
+use crate::axis::{Axis, AXIS_COUNT};
+use crate::calibration::AxisCalibration;
+use core::sync::atomic::{AtomicI32, AtomicU8, Ordering};
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum CalibrationState {
+    Idle = 0,
+    Capturing = 1,
+    /// The last capture was committed.
+    Committed = 2,
+}
+
+impl TryFrom<u8> for CalibrationState {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(CalibrationState::Idle),
+            1 => Ok(CalibrationState::Capturing),
+            2 => Ok(CalibrationState::Committed),
+            _ => Err(()),
+        }
+    }
+}
+
+/// What happens with the captured ranges once they are committed.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum CommitTarget {
+    /// The ranges are used until the next reset.
+    Ram = 1,
+    /// The ranges are also saved to the calibration store.
+    Persistent = 2,
+}
+
+/// Observed range of a single axis.
+#[derive(Debug)]
+struct CapturedRange {
+    min: AtomicI32,
+    max: AtomicI32,
+}
+
+impl CapturedRange {
+    const fn new() -> Self {
+        Self {
+            min: AtomicI32::new(i32::MAX),
+            max: AtomicI32::new(i32::MIN),
+        }
+    }
+
+    fn clear(&self) {
+        self.min.store(i32::MAX, Ordering::Relaxed);
+        self.max.store(i32::MIN, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug)]
+pub struct CalibrationSession {
+    state: AtomicU8,
+    pending_commit: AtomicU8,
+    axes: [CapturedRange; AXIS_COUNT],
+}
+
+impl CalibrationSession {
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(CalibrationState::Idle as u8),
+            pending_commit: AtomicU8::new(0),
+            axes: [const { CapturedRange::new() }; AXIS_COUNT],
+        }
+    }
+
+    pub fn state(&self) -> CalibrationState {
+        CalibrationState::try_from(self.state.load(Ordering::Acquire))
+            .unwrap_or(CalibrationState::Idle)
+    }
+
+    /// Starts a new capture, dropping whatever was captured before.
+    pub fn start(&self) {
+        self.axes.iter().for_each(CapturedRange::clear);
+        self.pending_commit.store(0, Ordering::Relaxed);
+        self.state
+            .store(CalibrationState::Capturing as u8, Ordering::Release);
+    }
+
+    /// Stops the capture without using its result.
+    pub fn cancel(&self) {
+        self.state
+            .store(CalibrationState::Idle as u8, Ordering::Release);
+    }
+
+    /// Ends the capture and marks it for being applied. Returns `false` when there is no
+    /// capture in progress.
+    pub fn commit(&self, target: CommitTarget) -> bool {
+        let committed = self
+            .state
+            .compare_exchange(
+                CalibrationState::Capturing as u8,
+                CalibrationState::Committed as u8,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_ok();
+        if committed {
+            self.pending_commit.store(target as u8, Ordering::Release);
+        }
+        committed
+    }
+
+    /// Returns the target of a commit that wasn't applied yet and clears it.
+    pub fn take_commit(&self) -> Option<CommitTarget> {
+        match self.pending_commit.swap(0, Ordering::AcqRel) {
+            1 => Some(CommitTarget::Ram),
+            2 => Some(CommitTarget::Persistent),
+            _ => None,
+        }
+    }
+
+    /// Records a reading of the axis. Does nothing unless a capture is in progress.
+    pub fn observe(&self, axis: Axis, reading: i64) {
+        if self.state() != CalibrationState::Capturing {
+            return;
+        }
+        let reading = reading.clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+        let range = &self.axes[axis.index()];
+        range.min.fetch_min(reading, Ordering::Relaxed);
+        range.max.fetch_max(reading, Ordering::Relaxed);
+    }
+
+    /// Range observed on the axis so far, or `None` when the axis wasn't moved.
+    pub fn captured(&self, axis: Axis) -> Option<AxisCalibration> {
+        let range = &self.axes[axis.index()];
+        let min = range.min.load(Ordering::Relaxed);
+        let max = range.max.load(Ordering::Relaxed);
+        (min < max).then_some(AxisCalibration::new(min, max))
+    }
+}
+
+impl Default for CalibrationSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod session_testing {
+    use crate::axis::Axis;
+    use crate::calibration::{AxisCalibration, CalibrationSession, CalibrationState, CommitTarget};
+
+    #[test]
+    fn when_session_is_idle_then_readings_are_ignored() {
+        // Given
+        let session = CalibrationSession::new();
+
+        // When
+        session.observe(Axis::X, 100);
+        session.observe(Axis::X, 200);
+
+        // Then
+        assert_eq!(session.state(), CalibrationState::Idle);
+        assert_eq!(session.captured(Axis::X), None);
+    }
+
+    #[test]
+    fn when_pedal_is_swept_then_its_range_is_captured() {
+        // Given
+        let session = CalibrationSession::new();
+        session.start();
+
+        // When
+        [2000, 1830, 2500, 3090, 2700].iter().for_each(|&reading| {
+            session.observe(Axis::X, reading);
+        });
+        session.observe(Axis::Y, 5_000);
+
+        // Then
+        assert_eq!(session.state(), CalibrationState::Capturing);
+        assert_eq!(
+            session.captured(Axis::X),
+            Some(AxisCalibration::new(1830, 3090))
+        );
+        assert_eq!(session.captured(Axis::Y), None);
+        assert_eq!(session.captured(Axis::Z), None);
+    }
+
+    #[test]
+    fn when_capture_is_committed_then_the_commit_is_taken_once() {
+        // Given
+        let session = CalibrationSession::new();
+        session.start();
+
+        // When
+        let committed = session.commit(CommitTarget::Persistent);
+        let first = session.take_commit();
+        let second = session.take_commit();
+
+        // Then
+        assert!(committed);
+        assert_eq!(session.state(), CalibrationState::Committed);
+        assert_eq!(first, Some(CommitTarget::Persistent));
+        assert_eq!(second, None);
+    }
+
+    #[test]
+    fn when_not_capturing_then_commit_is_refused() {
+        // Given
+        let session = CalibrationSession::new();
+
+        // When
+        let result = session.commit(CommitTarget::Ram);
+
+        // Then
+        assert!(!result);
+        assert_eq!(session.state(), CalibrationState::Idle);
+        assert_eq!(session.take_commit(), None);
+    }
+
+    #[test]
+    fn when_capture_is_restarted_then_the_previous_range_is_dropped() {
+        // Given
+        let session = CalibrationSession::new();
+        session.start();
+        session.observe(Axis::Z, 0);
+        session.observe(Axis::Z, 60_000);
+        session.cancel();
+
+        // When
+        session.start();
+        session.observe(Axis::Z, 100);
+        session.observe(Axis::Z, 200);
+
+        // Then
+        assert_eq!(
+            session.captured(Axis::Z),
+            Some(AxisCalibration::new(100, 200))
+        );
+    }
+
+    #[test]
+    fn when_reading_does_not_fit_then_it_is_clamped() {
+        // Given
+        let session = CalibrationSession::new();
+        session.start();
+
+        // When
+        session.observe(Axis::Y, i64::MIN);
+        session.observe(Axis::Y, i64::MAX);
+
+        // Then
+        assert_eq!(
+            session.captured(Axis::Y),
+            Some(AxisCalibration::new(i32::MIN, i32::MAX))
+        );
+    }
+}
@@ -0,0 +1,394 @@
+use crate::calibration::{Calibration, StoredCalibration, RECORD_SIZE};
+use crate::serial_number::DeviceName;
+use embedded_storage::nor_flash::NorFlash;
+
+/// Room for a record padded to the write size of the flash.
+const BUFFER_SIZE: usize = RECORD_SIZE.next_multiple_of(32);
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum StoreError<E> {
+    Flash(E),
+    /// The record read back after the write doesn't match the saved one.
+    Verification,
+}
+
+/// Calibration storage in two flash slots used alternately.
+///
+/// Every save goes to the slot that doesn't hold the newest record, so a power loss
+/// during a save leaves the previous record intact. On load the valid record with the
+/// highest sequence number wins.
+pub struct CalibrationStore<F> {
+    flash: F,
+    slots: [u32; 2],
+}
+
+impl<F> CalibrationStore<F>
+where
+    F: NorFlash,
+{
+    /// Each slot must start at an erase boundary of the flash and span one erase unit.
+    pub fn new(flash: F, slots: [u32; 2]) -> Self {
+        Self { flash, slots }
+    }
+
+    /// Returns the newest valid record.
+    pub fn load(&mut self) -> Option<StoredCalibration> {
+        self.newest().map(|(_, record)| record)
+    }
+
+    /// Returns the newest valid calibration or `defaults` when there is none.
+    pub fn load_or(&mut self, defaults: Calibration) -> Calibration {
+        self.load()
+            .map(|record| record.calibration)
+            .unwrap_or(defaults)
+    }
+
+    /// Saves the calibration, keeping the saved name, and returns the sequence number of the
+    /// new record.
+    pub fn save(&mut self, calibration: &Calibration) -> Result<u32, StoreError<F::Error>> {
+        let name = self.newest().and_then(|(_, record)| record.name);
+        self.save_record(calibration, name)
+    }
+
+    /// Saves the name of the pedalbox, keeping the saved calibration or saving `fallback`
+    /// when there is none, and returns the sequence number of the new record.
+    pub fn save_name(
+        &mut self,
+        name: Option<DeviceName>,
+        fallback: &Calibration,
+    ) -> Result<u32, StoreError<F::Error>> {
+        let calibration = self
+            .newest()
+            .map_or(*fallback, |(_, record)| record.calibration);
+        self.save_record(&calibration, name)
+    }
+
+    fn save_record(
+        &mut self,
+        calibration: &Calibration,
+        name: Option<DeviceName>,
+    ) -> Result<u32, StoreError<F::Error>> {
+        let (slot, sequence) = match self.newest() {
+            Some((newest, record)) => (1 - newest, record.sequence.wrapping_add(1)),
+            None => (0, 1),
+        };
+        let record = StoredCalibration {
+            sequence,
+            calibration: *calibration,
+            name,
+        };
+
+        let mut buffer = [0xFF; BUFFER_SIZE];
+        buffer[..RECORD_SIZE].copy_from_slice(&record.encode());
+        let length = RECORD_SIZE.next_multiple_of(F::WRITE_SIZE);
+        let offset = self.slots[slot];
+        self.flash
+            .erase(offset, offset + F::ERASE_SIZE as u32)
+            .map_err(StoreError::Flash)?;
+        self.flash
+            .write(offset, &buffer[..length])
+            .map_err(StoreError::Flash)?;
+
+        match self.read_slot(slot) {
+            Some(stored) if stored == record => Ok(sequence),
+            _ => Err(StoreError::Verification),
+        }
+    }
+
+    fn read_slot(&mut self, slot: usize) -> Option<StoredCalibration> {
+        let mut bytes = [0u8; RECORD_SIZE];
+        self.flash.read(self.slots[slot], &mut bytes).ok()?;
+        StoredCalibration::decode(&bytes).ok()
+    }
+
+    fn newest(&mut self) -> Option<(usize, StoredCalibration)> {
+        match (self.read_slot(0), self.read_slot(1)) {
+            (Some(first), Some(second)) => {
+                if (second.sequence.wrapping_sub(first.sequence) as i32) > 0 {
+                    Some((1, second))
+                } else {
+                    Some((0, first))
+                }
+            }
+            (Some(first), None) => Some((0, first)),
+            (None, Some(second)) => Some((1, second)),
+            (None, None) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod store_testing {
+    use crate::calibration::{
+        AxisCalibration, Calibration, CalibrationStore, StoreError, StoredCalibration, RECORD_SIZE,
+    };
+    use crate::serial_number::DeviceName;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use embedded_storage::nor_flash::{
+        check_erase, check_read, check_write, ErrorType, NorFlash, NorFlashErrorKind, ReadNorFlash,
+    };
+
+    const SLOTS: [u32; 2] = [0, 256];
+
+    /// In-memory NOR flash. Writes can only clear bits, like on the real thing, and the
+    /// number of bytes written before a simulated power loss can be limited.
+    struct MockFlash {
+        memory: Vec<u8>,
+        power_loss_after: Option<usize>,
+    }
+
+    impl MockFlash {
+        fn new() -> Self {
+            Self {
+                memory: vec![0xFF; 512],
+                power_loss_after: None,
+            }
+        }
+    }
+
+    impl ErrorType for MockFlash {
+        type Error = NorFlashErrorKind;
+    }
+
+    impl ReadNorFlash for MockFlash {
+        const READ_SIZE: usize = 1;
+
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            check_read(self, offset, bytes.len())?;
+            let offset = offset as usize;
+            bytes.copy_from_slice(&self.memory[offset..offset + bytes.len()]);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            self.memory.len()
+        }
+    }
+
+    impl NorFlash for MockFlash {
+        const WRITE_SIZE: usize = 4;
+        const ERASE_SIZE: usize = 256;
+
+        fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            check_erase(self, from, to)?;
+            self.memory[from as usize..to as usize].fill(0xFF);
+            Ok(())
+        }
+
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            check_write(self, offset, bytes.len())?;
+            for (index, byte) in bytes.iter().enumerate() {
+                if let Some(budget) = self.power_loss_after.as_mut() {
+                    if *budget == 0 {
+                        return Err(NorFlashErrorKind::Other);
+                    }
+                    *budget -= 1;
+                }
+                self.memory[offset as usize + index] &= byte;
+            }
+            Ok(())
+        }
+    }
+
+    fn calibration(range_max: i32) -> Calibration {
+        Calibration {
+            axes: [
+                AxisCalibration::new(1820, range_max),
+                AxisCalibration::new(0, 230_000),
+                AxisCalibration::new(0, 65_535),
+            ],
+        }
+    }
+
+    #[test]
+    fn when_flash_is_empty_then_defaults_are_used() {
+        // Given
+        let mut store = CalibrationStore::new(MockFlash::new(), SLOTS);
+
+        // When
+        let loaded = store.load();
+        let result = store.load_or(calibration(3100));
+
+        // Then
+        assert_eq!(loaded, None);
+        assert_eq!(result, calibration(3100));
+    }
+
+    #[test]
+    fn when_calibration_is_saved_then_it_is_loaded_back() {
+        // Given
+        let mut store = CalibrationStore::new(MockFlash::new(), SLOTS);
+
+        // When
+        let sequence = store.save(&calibration(3000));
+        let result = store.load();
+
+        // Then
+        assert_eq!(sequence, Ok(1));
+        assert_eq!(
+            result,
+            Some(StoredCalibration {
+                sequence: 1,
+                calibration: calibration(3000),
+                name: None
+            })
+        );
+    }
+
+    #[test]
+    fn when_saved_repeatedly_then_the_slots_are_used_alternately() {
+        // Given
+        let mut store = CalibrationStore::new(MockFlash::new(), SLOTS);
+
+        // When
+        let sequences: Vec<_> = (0..3)
+            .map(|i| store.save(&calibration(3000 + i)).unwrap())
+            .collect();
+
+        // Then
+        assert_eq!(sequences, [1, 2, 3]);
+        assert_eq!(store.read_slot(0).unwrap().calibration, calibration(3002));
+        assert_eq!(store.read_slot(1).unwrap().calibration, calibration(3001));
+        assert_eq!(store.load_or(calibration(0)), calibration(3002));
+    }
+
+    #[test]
+    fn when_power_is_lost_during_save_then_the_previous_record_survives() {
+        // Given
+        let mut store = CalibrationStore::new(MockFlash::new(), SLOTS);
+        store.save(&calibration(3000)).unwrap();
+        store.flash.power_loss_after = Some(RECORD_SIZE / 2);
+
+        // When
+        let saved = store.save(&calibration(2000));
+        let result = store.load_or(calibration(0));
+
+        // Then
+        assert_eq!(saved, Err(StoreError::Flash(NorFlashErrorKind::Other)));
+        assert_eq!(result, calibration(3000));
+    }
+
+    #[test]
+    fn when_newest_record_is_corrupted_then_the_older_one_is_used() {
+        // Given
+        let mut store = CalibrationStore::new(MockFlash::new(), SLOTS);
+        store.save(&calibration(3000)).unwrap();
+        store.save(&calibration(2000)).unwrap();
+
+        // When
+        store.flash.memory[SLOTS[1] as usize + 14] ^= 0x10;
+        let result = store.load();
+
+        // Then
+        assert_eq!(
+            result,
+            Some(StoredCalibration {
+                sequence: 1,
+                calibration: calibration(3000),
+                name: None
+            })
+        );
+    }
+
+    #[test]
+    fn when_sequence_number_wraps_around_then_the_newer_record_still_wins() {
+        // Given
+        let mut flash = MockFlash::new();
+        let old = StoredCalibration {
+            sequence: u32::MAX,
+            calibration: calibration(3000),
+            name: None,
+        };
+        let new = StoredCalibration {
+            sequence: 0,
+            calibration: calibration(2000),
+            name: None,
+        };
+        flash.write(SLOTS[0], &old.encode()).unwrap();
+        flash.write(SLOTS[1], &new.encode()).unwrap();
+        let mut store = CalibrationStore::new(flash, SLOTS);
+
+        // When
+        let result = store.load();
+        let sequence = store.save(&calibration(1000));
+
+        // Then
+        assert_eq!(result, Some(new));
+        assert_eq!(sequence, Ok(1));
+        assert_eq!(store.read_slot(0).unwrap().calibration, calibration(1000));
+    }
+
+    #[test]
+    fn when_name_is_saved_then_the_calibration_is_kept() {
+        // Given
+        let mut store = CalibrationStore::new(MockFlash::new(), SLOTS);
+        store.save(&calibration(3000)).unwrap();
+
+        // When
+        let sequence = store.save_name(DeviceName::new("left-rig"), &calibration(0));
+        let result = store.load();
+
+        // Then
+        assert_eq!(sequence, Ok(2));
+        assert_eq!(
+            result,
+            Some(StoredCalibration {
+                sequence: 2,
+                calibration: calibration(3000),
+                name: DeviceName::new("left-rig")
+            })
+        );
+    }
+
+    #[test]
+    fn when_nothing_was_saved_then_the_name_is_saved_with_the_fallback() {
+        // Given
+        let mut store = CalibrationStore::new(MockFlash::new(), SLOTS);
+
+        // When
+        store
+            .save_name(DeviceName::new("left-rig"), &calibration(2500))
+            .unwrap();
+        let result = store.load().unwrap();
+
+        // Then
+        assert_eq!(result.calibration, calibration(2500));
+        assert_eq!(result.name, DeviceName::new("left-rig"));
+    }
+
+    #[test]
+    fn when_calibration_is_saved_then_the_name_is_kept() {
+        // Given
+        let mut store = CalibrationStore::new(MockFlash::new(), SLOTS);
+        store
+            .save_name(DeviceName::new("left-rig"), &calibration(3000))
+            .unwrap();
+
+        // When
+        store.save(&calibration(2000)).unwrap();
+        let result = store.load().unwrap();
+
+        // Then
+        assert_eq!(result.calibration, calibration(2000));
+        assert_eq!(result.name, DeviceName::new("left-rig"));
+    }
+
+    #[test]
+    fn when_name_is_cleared_then_the_record_has_none() {
+        // Given
+        let mut store = CalibrationStore::new(MockFlash::new(), SLOTS);
+        store
+            .save_name(DeviceName::new("left-rig"), &calibration(3000))
+            .unwrap();
+
+        // When
+        store.save_name(None, &calibration(0)).unwrap();
+        let result = store.load().unwrap();
+
+        // Then
+        assert_eq!(result.name, None);
+        assert_eq!(result.calibration, calibration(3000));
+    }
+}
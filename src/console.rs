@@ -0,0 +1,663 @@
+//! Line based console on the USB serial port.
+//!
+//! Bytes from the host are collected by a [`LineBuffer`] until the end of the line, parsed
+//! into a [`ConsoleCommand`] and run by the [`Console`], which writes its answer as text.
+//! Commands that need the hardware, like saving to flash or jumping to the bootloader,
+//! come back as a [`ConsoleAction`] for the firmware to carry out.
+
+use crate::axis::Axis;
+use crate::calibration::AxisCalibration;
+use crate::pedal_state::{AxisState, PedalState};
+use crate::report_mode::{ReportMode, SharedReportMode};
+use crate::serial_number::DeviceName;
+use crate::settings::SharedSettings;
+use crate::tare::TareRequest;
+use core::fmt::{self, Write};
+
+/// Longest line the console accepts.
+pub const MAX_LINE_LENGTH: usize = 64;
+
+pub const HELP: &str = "\
+help                      show this help\r
+values                    show the raw and mapped value of every pedal\r
+watch <on|off>            keep showing the values\r
+range <axis> <min> <max>  set the calibrated range of a pedal\r
+save                      save the calibration to flash\r
+name <name|clear>         save the USB serial number, used from the next start\r
+tare                      tare the brake load cell\r
+config                    show the settings of every pedal\r
+bootloader                reboot into the USB bootloader\r
+axes: gas, brake, clutch\r
+";
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConsoleCommand {
+    Help,
+    Values,
+    Watch(bool),
+    Range {
+        axis: Axis,
+        min: i32,
+        max: i32,
+    },
+    Save,
+    /// `None` goes back to the unique ID of the chip.
+    Name(Option<DeviceName>),
+    Tare,
+    Config,
+    Bootloader,
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConsoleError {
+    UnknownCommand,
+    MissingArgument,
+    InvalidArgument,
+    LineTooLong,
+}
+
+impl fmt::Display for ConsoleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            ConsoleError::UnknownCommand => "unknown command, try `help`",
+            ConsoleError::MissingArgument => "missing argument",
+            ConsoleError::InvalidArgument => "invalid argument",
+            ConsoleError::LineTooLong => "line too long",
+        };
+        f.write_str(message)
+    }
+}
+
+impl ConsoleCommand {
+    /// Parses a line, `None` for an empty one.
+    pub fn parse(line: &str) -> Result<Option<Self>, ConsoleError> {
+        let mut words = line.split_ascii_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(None);
+        };
+        let mut next = || words.next().ok_or(ConsoleError::MissingArgument);
+        let command = match command {
+            "help" | "?" => ConsoleCommand::Help,
+            "values" => ConsoleCommand::Values,
+            "watch" => ConsoleCommand::Watch(match next()? {
+                "on" => true,
+                "off" => false,
+                _ => return Err(ConsoleError::InvalidArgument),
+            }),
+            "range" => {
+                let axis = parse_axis(next()?)?;
+                let min = parse_number(next()?)?;
+                let max = parse_number(next()?)?;
+                if min >= max {
+                    return Err(ConsoleError::InvalidArgument);
+                }
+                ConsoleCommand::Range { axis, min, max }
+            }
+            "save" => ConsoleCommand::Save,
+            "name" => ConsoleCommand::Name(match next()? {
+                "clear" => None,
+                name => Some(DeviceName::new(name).ok_or(ConsoleError::InvalidArgument)?),
+            }),
+            "tare" => ConsoleCommand::Tare,
+            "config" => ConsoleCommand::Config,
+            "bootloader" => ConsoleCommand::Bootloader,
+            _ => return Err(ConsoleError::UnknownCommand),
+        };
+        Ok(Some(command))
+    }
+}
+
+fn parse_axis(word: &str) -> Result<Axis, ConsoleError> {
+    match word {
+        "gas" | "x" => Ok(Axis::X),
+        "brake" | "y" => Ok(Axis::Y),
+        "clutch" | "z" => Ok(Axis::Z),
+        _ => Err(ConsoleError::InvalidArgument),
+    }
+}
+
+fn parse_number(word: &str) -> Result<i32, ConsoleError> {
+    word.parse().map_err(|_| ConsoleError::InvalidArgument)
+}
+
+fn axis_name(axis: Axis) -> &'static str {
+    match axis {
+        Axis::X => "gas",
+        Axis::Y => "brake",
+        Axis::Z => "clutch",
+    }
+}
+
+/// Collects the bytes of a line. Backspace removes the last byte and a line that doesn't
+/// fit is dropped as a whole.
+#[derive(Debug)]
+pub struct LineBuffer {
+    bytes: [u8; MAX_LINE_LENGTH],
+    length: usize,
+    overflow: bool,
+}
+
+impl LineBuffer {
+    pub const fn new() -> Self {
+        Self {
+            bytes: [0; MAX_LINE_LENGTH],
+            length: 0,
+            overflow: false,
+        }
+    }
+
+    /// Adds a byte and returns the line once `\r` or `\n` ends it.
+    pub fn push(&mut self, byte: u8) -> Option<Result<&str, ConsoleError>> {
+        match byte {
+            b'\r' | b'\n' => {
+                let length = core::mem::take(&mut self.length);
+                if core::mem::take(&mut self.overflow) {
+                    return Some(Err(ConsoleError::LineTooLong));
+                }
+                Some(
+                    core::str::from_utf8(&self.bytes[..length])
+                        .map_err(|_| ConsoleError::InvalidArgument),
+                )
+            }
+            0x08 | 0x7f => {
+                self.length = self.length.saturating_sub(1);
+                None
+            }
+            _ if self.length == MAX_LINE_LENGTH => {
+                self.overflow = true;
+                None
+            }
+            _ => {
+                self.bytes[self.length] = byte;
+                self.length += 1;
+                None
+            }
+        }
+    }
+}
+
+impl Default for LineBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Echoes a received byte, so a terminal shows what is typed.
+pub fn echo(byte: u8, out: &mut impl Write) -> fmt::Result {
+    match byte {
+        b'\r' | b'\n' => out.write_str("\r\n"),
+        0x08 | 0x7f => out.write_str("\x08 \x08"),
+        0x20..=0x7e => out.write_char(byte as char),
+        _ => Ok(()),
+    }
+}
+
+/// Text written by the console, cut off when it doesn't fit.
+#[derive(Debug)]
+pub struct ResponseBuffer<const N: usize> {
+    bytes: [u8; N],
+    length: usize,
+}
+
+impl<const N: usize> ResponseBuffer<N> {
+    pub const fn new() -> Self {
+        Self {
+            bytes: [0; N],
+            length: 0,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.length]
+    }
+
+    pub fn clear(&mut self) {
+        self.length = 0;
+    }
+}
+
+impl<const N: usize> Default for ResponseBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Write for ResponseBuffer<N> {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        let length = text.len().min(N - self.length);
+        self.bytes[self.length..self.length + length].copy_from_slice(&text.as_bytes()[..length]);
+        self.length += length;
+        match length == text.len() {
+            true => Ok(()),
+            false => Err(fmt::Error),
+        }
+    }
+}
+
+/// What the firmware has to do after a command.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConsoleAction {
+    None,
+    SaveCalibration,
+    SaveName(Option<DeviceName>),
+    Bootloader,
+}
+
+/// Runs the commands against the shared state of the firmware.
+pub struct Console<'a> {
+    settings: &'a SharedSettings,
+    pedal_state: &'a PedalState,
+    report_mode: &'a SharedReportMode,
+    brake_tare: &'a TareRequest,
+    watching: bool,
+}
+
+impl<'a> Console<'a> {
+    pub fn new(
+        settings: &'a SharedSettings,
+        pedal_state: &'a PedalState,
+        report_mode: &'a SharedReportMode,
+        brake_tare: &'a TareRequest,
+    ) -> Self {
+        Self {
+            settings,
+            pedal_state,
+            report_mode,
+            brake_tare,
+            watching: false,
+        }
+    }
+
+    /// Whether the values should be shown periodically.
+    pub fn is_watching(&self) -> bool {
+        self.watching
+    }
+
+    /// Parses and runs a line, writing the answer to `out`.
+    pub fn run_line(
+        &mut self,
+        line: Result<&str, ConsoleError>,
+        out: &mut impl Write,
+    ) -> ConsoleAction {
+        match line.and_then(ConsoleCommand::parse) {
+            Ok(Some(command)) => self.execute(command, out),
+            Ok(None) => ConsoleAction::None,
+            Err(e) => {
+                let _ = write!(out, "error: {}\r\n", e);
+                ConsoleAction::None
+            }
+        }
+    }
+
+    pub fn execute(&mut self, command: ConsoleCommand, out: &mut impl Write) -> ConsoleAction {
+        let result = match command {
+            ConsoleCommand::Help => out.write_str(HELP),
+            ConsoleCommand::Values => self.write_values(out),
+            ConsoleCommand::Watch(on) => {
+                self.watching = on;
+                Ok(())
+            }
+            ConsoleCommand::Range { axis, min, max } => {
+                self.settings.update(axis, |settings| {
+                    settings.calibration = AxisCalibration::new(min, max)
+                });
+                write!(out, "{} range {}..{}\r\n", axis_name(axis), min, max)
+            }
+            ConsoleCommand::Save => return ConsoleAction::SaveCalibration,
+            ConsoleCommand::Name(name) => {
+                let _ = match name {
+                    Some(name) => write!(out, "serial number {} from the next start\r\n", name),
+                    None => out.write_str("serial number of the chip from the next start\r\n"),
+                };
+                return ConsoleAction::SaveName(name);
+            }
+            ConsoleCommand::Tare => {
+                self.brake_tare.request();
+                out.write_str("brake tare requested\r\n")
+            }
+            ConsoleCommand::Config => self.write_config(out),
+            ConsoleCommand::Bootloader => {
+                let _ = out.write_str("rebooting into the bootloader\r\n");
+                return ConsoleAction::Bootloader;
+            }
+        };
+        if result.is_err() {
+            let _ = out.write_str("\r\n...\r\n");
+        }
+        ConsoleAction::None
+    }
+
+    /// One line with the raw and mapped value of every pedal and the buttons.
+    pub fn write_values(&self, out: &mut impl Write) -> fmt::Result {
+        let snapshot = self.pedal_state.snapshot();
+        for axis in Axis::ALL {
+            write_axis_state(out, axis, &snapshot.axis(axis))?;
+        }
+        write!(out, "buttons {:08b}\r\n", snapshot.buttons)
+    }
+
+    fn write_config(&self, out: &mut impl Write) -> fmt::Result {
+        for axis in Axis::ALL {
+            let settings = self.settings.get(axis);
+            write!(
+                out,
+                "{}: range {}..{}, deadzone {:?}, curve {:?}, filters",
+                axis_name(axis),
+                settings.calibration.range_min,
+                settings.calibration.range_max,
+                settings.deadzone,
+                settings.curve,
+            )?;
+            for filter in settings.filter.filters() {
+                write!(out, " {:?}", filter)?;
+            }
+            out.write_str("\r\n")?;
+        }
+        match self.report_mode.get() {
+            ReportMode::Periodic { interval_ms } => {
+                write!(out, "reports: periodic, every {} ms\r\n", interval_ms)
+            }
+            ReportMode::OnChange {
+                threshold,
+                heartbeat_ms,
+            } => write!(
+                out,
+                "reports: on change above {}, heartbeat every {} ms\r\n",
+                threshold, heartbeat_ms
+            ),
+        }
+    }
+}
+
+fn write_axis_state(out: &mut impl Write, axis: Axis, state: &AxisState) -> fmt::Result {
+    write!(out, "{} {} -> {}", axis_name(axis), state.raw, state.value)?;
+    if !state.faults.is_empty() {
+        write!(out, " fault {:#04x}", state.faults.0)?;
+    }
+    out.write_str(", ")
+}
+
+#[cfg(test)]
+mod console_testing {
+    use crate::axis::Axis;
+    use crate::calibration::AxisCalibration;
+    use crate::console::{
+        echo, Console, ConsoleAction, ConsoleCommand, ConsoleError, LineBuffer, ResponseBuffer,
+        MAX_LINE_LENGTH,
+    };
+    use crate::fault::FaultFlags;
+    use crate::pedal_state::PedalState;
+    use crate::report_mode::{ReportMode, SharedReportMode};
+    use crate::serial_number::DeviceName;
+    use crate::settings::SharedSettings;
+    use crate::tare::TareRequest;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    use core::fmt::Write;
+    use rstest::rstest;
+
+    struct Fixture {
+        settings: &'static SharedSettings,
+        pedal_state: &'static PedalState,
+        brake_tare: &'static TareRequest,
+        console: Console<'static>,
+    }
+
+    fn fixture() -> Fixture {
+        let settings: &'static SharedSettings =
+            alloc::boxed::Box::leak(alloc::boxed::Box::new(SharedSettings::new()));
+        let pedal_state: &'static PedalState =
+            alloc::boxed::Box::leak(alloc::boxed::Box::new(PedalState::new(|| 0)));
+        let report_mode: &'static SharedReportMode = alloc::boxed::Box::leak(
+            alloc::boxed::Box::new(SharedReportMode::new(ReportMode::DEFAULT)),
+        );
+        let brake_tare: &'static TareRequest =
+            alloc::boxed::Box::leak(alloc::boxed::Box::new(TareRequest::new()));
+        Fixture {
+            settings,
+            pedal_state,
+            brake_tare,
+            console: Console::new(settings, pedal_state, report_mode, brake_tare),
+        }
+    }
+
+    fn lines(buffer: &mut LineBuffer, bytes: &[u8]) -> Vec<Result<String, ConsoleError>> {
+        bytes
+            .iter()
+            .filter_map(|&byte| buffer.push(byte).map(|line| line.map(String::from)))
+            .collect()
+    }
+
+    #[rstest]
+    #[case("help", ConsoleCommand::Help)]
+    #[case("  values ", ConsoleCommand::Values)]
+    #[case("watch on", ConsoleCommand::Watch(true))]
+    #[case("watch off", ConsoleCommand::Watch(false))]
+    #[case("range brake -100 230000", ConsoleCommand::Range { axis: Axis::Y, min: -100, max: 230_000 })]
+    #[case("range z 0 16383", ConsoleCommand::Range { axis: Axis::Z, min: 0, max: 16_383 })]
+    #[case("save", ConsoleCommand::Save)]
+    #[case("name left-rig", ConsoleCommand::Name(DeviceName::new("left-rig")))]
+    #[case("name clear", ConsoleCommand::Name(None))]
+    #[case("tare", ConsoleCommand::Tare)]
+    #[case("config", ConsoleCommand::Config)]
+    #[case("bootloader", ConsoleCommand::Bootloader)]
+    fn when_command_is_parsed(#[case] line: &str, #[case] expected: ConsoleCommand) {
+        // When
+        let result = ConsoleCommand::parse(line);
+
+        // Then
+        assert_eq!(result, Ok(Some(expected)));
+    }
+
+    #[rstest]
+    #[case("reboot", ConsoleError::UnknownCommand)]
+    #[case("watch", ConsoleError::MissingArgument)]
+    #[case("watch maybe", ConsoleError::InvalidArgument)]
+    #[case("range gas 100", ConsoleError::MissingArgument)]
+    #[case("range throttle 0 100", ConsoleError::InvalidArgument)]
+    #[case("range gas 0 x", ConsoleError::InvalidArgument)]
+    #[case("range gas 500 100", ConsoleError::InvalidArgument)]
+    #[case("name", ConsoleError::MissingArgument)]
+    #[case("name far-too-long-for-a-name", ConsoleError::InvalidArgument)]
+    fn when_command_is_invalid(#[case] line: &str, #[case] expected: ConsoleError) {
+        // When
+        let result = ConsoleCommand::parse(line);
+
+        // Then
+        assert_eq!(result, Err(expected));
+    }
+
+    #[test]
+    fn when_line_is_empty_then_there_is_no_command() {
+        // When
+        let result = ConsoleCommand::parse("   ");
+
+        // Then
+        assert_eq!(result, Ok(None));
+    }
+
+    #[test]
+    fn when_bytes_arrive_then_lines_are_split_at_line_endings() {
+        // Given
+        let mut buffer = LineBuffer::new();
+
+        // When
+        let result = lines(&mut buffer, b"help\r\nvalx\x08ues\r");
+
+        // Then
+        assert_eq!(
+            result,
+            [Ok("help".into()), Ok("".into()), Ok("values".into())]
+        );
+    }
+
+    #[test]
+    fn when_line_is_too_long_then_it_is_dropped() {
+        // Given
+        let mut buffer = LineBuffer::new();
+        let mut bytes = [b'a'; MAX_LINE_LENGTH + 1].to_vec();
+        bytes.extend(b"\nsave\n");
+
+        // When
+        let result = lines(&mut buffer, &bytes);
+
+        // Then
+        assert_eq!(result, [Err(ConsoleError::LineTooLong), Ok("save".into())]);
+    }
+
+    #[test]
+    fn when_bytes_are_echoed_then_the_terminal_sees_the_typing() {
+        // Given
+        let mut out = String::new();
+
+        // When
+        for &byte in b"ta\x7fx\x1b\r" {
+            echo(byte, &mut out).unwrap();
+        }
+
+        // Then
+        assert_eq!(out, "ta\x08 \x08x\r\n");
+    }
+
+    #[test]
+    fn when_range_is_set_then_the_settings_change() {
+        // Given
+        let mut fixture = fixture();
+        let mut out = String::new();
+
+        // When
+        fixture
+            .console
+            .run_line(Ok("range clutch 100 16000"), &mut out);
+
+        // Then
+        assert_eq!(
+            fixture.settings.get(Axis::Z).calibration,
+            AxisCalibration::new(100, 16_000)
+        );
+        assert_eq!(out, "clutch range 100..16000\r\n");
+    }
+
+    #[test]
+    fn when_values_are_shown_then_raw_and_mapped_values_are_written() {
+        // Given
+        let mut fixture = fixture();
+        fixture
+            .pedal_state
+            .axis_channel(Axis::X)
+            .publish(-32_768, 7_280, true);
+        let brake = fixture.pedal_state.axis_channel(Axis::Y);
+        brake.publish(1_000, 12_345, true);
+        brake.set_faults(FaultFlags::NOT_READY);
+        fixture.pedal_state.button_channel().publish(0b101);
+        let mut out = String::new();
+
+        // When
+        fixture.console.run_line(Ok("values"), &mut out);
+
+        // Then
+        assert_eq!(
+            out,
+            "gas 7280 -> -32768, brake 12345 -> 1000 fault 0x08, clutch 0 -> -32768, buttons 00000101\r\n"
+        );
+    }
+
+    #[test]
+    fn when_hardware_commands_run_then_the_firmware_is_asked_to_act() {
+        // Given
+        let mut fixture = fixture();
+        let mut out = String::new();
+
+        // When
+        let save = fixture.console.run_line(Ok("save"), &mut out);
+        let tare = fixture.console.run_line(Ok("tare"), &mut out);
+        let bootloader = fixture.console.run_line(Ok("bootloader"), &mut out);
+
+        // Then
+        assert_eq!(save, ConsoleAction::SaveCalibration);
+        assert_eq!(tare, ConsoleAction::None);
+        assert!(fixture.brake_tare.take());
+        assert_eq!(bootloader, ConsoleAction::Bootloader);
+    }
+
+    #[test]
+    fn when_name_is_given_then_the_firmware_is_asked_to_save_it() {
+        // Given
+        let mut fixture = fixture();
+        let mut out = String::new();
+
+        // When
+        let named = fixture.console.run_line(Ok("name left-rig"), &mut out);
+        let cleared = fixture.console.run_line(Ok("name clear"), &mut out);
+
+        // Then
+        assert_eq!(named, ConsoleAction::SaveName(DeviceName::new("left-rig")));
+        assert_eq!(cleared, ConsoleAction::SaveName(None));
+        assert_eq!(
+            out,
+            "serial number left-rig from the next start\r\nserial number of the chip from the next start\r\n"
+        );
+    }
+
+    #[test]
+    fn when_watch_is_toggled_then_the_console_follows() {
+        // Given
+        let mut fixture = fixture();
+        let mut out = String::new();
+
+        // When
+        fixture.console.run_line(Ok("watch on"), &mut out);
+        let watching = fixture.console.is_watching();
+        fixture.console.run_line(Ok("watch off"), &mut out);
+
+        // Then
+        assert!(watching);
+        assert!(!fixture.console.is_watching());
+    }
+
+    #[test]
+    fn when_command_fails_then_the_error_is_written() {
+        // Given
+        let mut fixture = fixture();
+        let mut out = String::new();
+
+        // When
+        let result = fixture.console.run_line(Ok("dance"), &mut out);
+
+        // Then
+        assert_eq!(result, ConsoleAction::None);
+        assert_eq!(out, "error: unknown command, try `help`\r\n");
+    }
+
+    #[test]
+    fn when_config_is_shown_then_every_axis_and_the_report_mode_are_listed() {
+        // Given
+        let mut fixture = fixture();
+        let mut out = String::new();
+
+        // When
+        fixture.console.run_line(Ok("config"), &mut out);
+
+        // Then
+        let lines: Vec<&str> = out.split("\r\n").collect();
+        assert!(lines[0].starts_with("gas: range 0..0"));
+        assert!(lines[2].starts_with("clutch: "));
+        assert!(lines[3].starts_with("reports: "));
+    }
+
+    #[test]
+    fn when_response_does_not_fit_then_it_is_cut_off() {
+        // Given
+        let mut buffer = ResponseBuffer::<8>::new();
+
+        // When
+        let result = buffer.write_str("brake tare requested");
+
+        // Then
+        assert!(result.is_err());
+        assert_eq!(buffer.as_bytes(), b"brake ta");
+    }
+}
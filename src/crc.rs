@@ -0,0 +1,86 @@
+//! CRC-32 (IEEE 802.3) used to protect records stored in flash.
+
+const POLYNOMIAL: u32 = 0xEDB8_8320;
+
+const TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+};
+
+/// Incremental CRC-32 calculation.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub struct Crc32(u32);
+
+impl Crc32 {
+    pub const fn new() -> Self {
+        Self(0xFFFF_FFFF)
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = TABLE[((self.0 ^ *byte as u32) & 0xFF) as usize] ^ (self.0 >> 8);
+        }
+    }
+
+    pub fn finish(&self) -> u32 {
+        !self.0
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(bytes);
+    crc.finish()
+}
+
+#[cfg(test)]
+mod crc_testing {
+    use crate::crc::{crc32, Crc32};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(b"", 0x0000_0000)]
+    #[case(b"123456789", 0xCBF4_3926)]
+    #[case(b"The quick brown fox jumps over the lazy dog", 0x414F_A339)]
+    fn when_calculating_the_checksum(#[case] bytes: &[u8], #[case] expected: u32) {
+        // When
+        let result = crc32(bytes);
+
+        // Then
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn when_updated_in_chunks_then_checksum_is_the_same() {
+        // Given
+        let mut crc = Crc32::new();
+
+        // When
+        crc.update(b"12345");
+        crc.update(b"6789");
+
+        // Then
+        assert_eq!(crc.finish(), 0xCBF4_3926);
+    }
+}
@@ -0,0 +1,426 @@
+//! Response curves applied on top of [`Mapping::map_to_i16`](crate::Mapping::map_to_i16).
+//!
+//! Curves work on the pedal travel expressed as a `u16` position, where `0` is the fully
+//! released pedal (`i16::MIN` on the axis) and `u16::MAX` is the fully pressed one
+//! (`i16::MAX` on the axis). Every calculation is done with integers, so the curves can
+//! be evaluated on the target without a floating point unit being involved.
+
+/// Maximum number of knots a custom curve can hold.
+pub const MAX_CURVE_POINTS: usize = 11;
+/// Minimum number of knots a custom curve needs.
+pub const MIN_CURVE_POINTS: usize = 2;
+/// Limit of the strength parameter of the parametric curves.
+pub const MAX_CURVE_STRENGTH: i8 = 100;
+
+const FULL_SCALE: i64 = u16::MAX as i64;
+const SLOPE_ONE: i128 = 1 << 16;
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CurveError {
+    TooFewPoints,
+    TooManyPoints,
+    /// The x coordinates must be strictly increasing.
+    NotIncreasing,
+    /// The y coordinates must not decrease.
+    NotMonotonic,
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CurvePoint {
+    pub x: u16,
+    pub y: u16,
+}
+
+impl CurvePoint {
+    pub const fn new(x: u16, y: u16) -> Self {
+        Self { x, y }
+    }
+}
+
+/// Validated, fixed capacity list of knots for the custom curves.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CurvePoints {
+    points: [CurvePoint; MAX_CURVE_POINTS],
+    len: u8,
+}
+
+impl CurvePoints {
+    pub fn new(points: &[CurvePoint]) -> Result<Self, CurveError> {
+        if points.len() < MIN_CURVE_POINTS {
+            return Err(CurveError::TooFewPoints);
+        }
+        if points.len() > MAX_CURVE_POINTS {
+            return Err(CurveError::TooManyPoints);
+        }
+        for pair in points.windows(2) {
+            if pair[1].x <= pair[0].x {
+                return Err(CurveError::NotIncreasing);
+            }
+            if pair[1].y < pair[0].y {
+                return Err(CurveError::NotMonotonic);
+            }
+        }
+
+        let mut storage = [CurvePoint::default(); MAX_CURVE_POINTS];
+        storage[..points.len()].copy_from_slice(points);
+        Ok(Self {
+            points: storage,
+            len: points.len() as u8,
+        })
+    }
+
+    pub fn as_slice(&self) -> &[CurvePoint] {
+        &self.points[..self.len as usize]
+    }
+
+    /// Returns the index of the segment containing `position`, or `None` when the
+    /// position is outside of the knots.
+    fn segment(&self, position: u16) -> Option<usize> {
+        let points = self.as_slice();
+        if position <= points[0].x || position >= points[points.len() - 1].x {
+            return None;
+        }
+        points.windows(2).position(|pair| position < pair[1].x)
+    }
+
+    /// Value of the curve outside of the knots.
+    fn clamped(&self, position: u16) -> u16 {
+        let points = self.as_slice();
+        if position <= points[0].x {
+            points[0].y
+        } else {
+            points[points.len() - 1].y
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ResponseCurve {
+    /// The mapped value is passed through unchanged.
+    #[default]
+    Linear,
+    /// Blend between linear and quadratic response. Positive strength makes the start
+    /// of the travel less sensitive, negative strength makes it more sensitive.
+    /// The strength is clamped to `-100..=100`.
+    Progressive(i8),
+    /// Blend between linear and smoothstep response. Positive strength flattens both
+    /// ends of the travel, negative strength flattens the middle.
+    /// The strength is clamped to `-100..=100`.
+    SCurve(i8),
+    /// Straight lines between the knots.
+    PiecewiseLinear(CurvePoints),
+    /// Monotone cubic (Fritsch-Carlson) interpolation between the knots.
+    CubicSpline(CurvePoints),
+}
+
+impl ResponseCurve {
+    pub fn apply(&self, value: i16) -> i16 {
+        from_position(self.apply_position(to_position(value)))
+    }
+
+    pub fn apply_position(&self, position: u16) -> u16 {
+        match self {
+            ResponseCurve::Linear => position,
+            ResponseCurve::Progressive(strength) => progressive(position, *strength),
+            ResponseCurve::SCurve(strength) => s_curve(position, *strength),
+            ResponseCurve::PiecewiseLinear(points) => piecewise_linear(points, position),
+            ResponseCurve::CubicSpline(points) => cubic_spline(points, position),
+        }
+    }
+}
+
+fn to_position(value: i16) -> u16 {
+    (value as i32 - i16::MIN as i32) as u16
+}
+
+fn from_position(position: u16) -> i16 {
+    (position as i32 + i16::MIN as i32) as i16
+}
+
+fn clamp_strength(strength: i8) -> i64 {
+    strength.clamp(-MAX_CURVE_STRENGTH, MAX_CURVE_STRENGTH) as i64
+}
+
+/// y = x + k * (x^2 - x), evaluated exactly over the common denominator `100 * F`.
+fn progressive(position: u16, strength: i8) -> u16 {
+    let k = clamp_strength(strength);
+    let p = position as i64;
+    let numerator = 100 * p * FULL_SCALE + k * (p * p - p * FULL_SCALE);
+    (numerator / (100 * FULL_SCALE)) as u16
+}
+
+/// y = x + k * (3x^2 - 2x^3 - x), evaluated exactly over the common denominator `100 * F^2`.
+fn s_curve(position: u16, strength: i8) -> u16 {
+    let k = clamp_strength(strength);
+    let p = position as i64;
+    let f2 = FULL_SCALE * FULL_SCALE;
+    let smoothstep = 3 * p * p * FULL_SCALE - 2 * p * p * p;
+    let numerator = 100 * p * f2 + k * (smoothstep - p * f2);
+    (numerator / (100 * f2)) as u16
+}
+
+fn piecewise_linear(points: &CurvePoints, position: u16) -> u16 {
+    let Some(index) = points.segment(position) else {
+        return points.clamped(position);
+    };
+    let start = points.as_slice()[index];
+    let end = points.as_slice()[index + 1];
+
+    let dx = (end.x - start.x) as i64;
+    let dy = (end.y - start.y) as i64;
+    let offset = (position - start.x) as i64;
+    (start.y as i64 + dy * offset / dx) as u16
+}
+
+/// Secant slope of a segment in Q16 fixed point.
+fn secant(start: CurvePoint, end: CurvePoint) -> i128 {
+    (end.y - start.y) as i128 * SLOPE_ONE / (end.x - start.x) as i128
+}
+
+/// Tangent at a knot in Q16 fixed point. The harmonic mean of the neighbouring secants
+/// keeps the tangent below three times either secant, which keeps the spline monotone.
+fn tangent(points: &[CurvePoint], index: usize) -> i128 {
+    if index == 0 {
+        return secant(points[0], points[1]);
+    }
+    if index == points.len() - 1 {
+        return secant(points[index - 1], points[index]);
+    }
+    let before = secant(points[index - 1], points[index]);
+    let after = secant(points[index], points[index + 1]);
+    if before == 0 || after == 0 {
+        0
+    } else {
+        2 * before * after / (before + after)
+    }
+}
+
+fn cubic_spline(points: &CurvePoints, position: u16) -> u16 {
+    let Some(index) = points.segment(position) else {
+        return points.clamped(position);
+    };
+    let knots = points.as_slice();
+    let start = knots[index];
+    let end = knots[index + 1];
+    let start_tangent = tangent(knots, index);
+    let end_tangent = tangent(knots, index + 1);
+
+    // Cubic Hermite basis functions multiplied through by h^3 with u = t * h.
+    let h = (end.x - start.x) as i128;
+    let u = (position - start.x) as i128;
+    let (u2, u3, h2, h3) = (u * u, u * u * u, h * h, h * h * h);
+    let h00 = 2 * u3 - 3 * u2 * h + h3;
+    let h01 = 3 * u2 * h - 2 * u3;
+    let h10 = u3 - 2 * u2 * h + u * h2;
+    let h11 = u3 - u2 * h;
+
+    let numerator = SLOPE_ONE * (start.y as i128 * h00 + end.y as i128 * h01)
+        + h * (start_tangent * h10 + end_tangent * h11);
+    let value = numerator / (SLOPE_ONE * h3);
+    value.clamp(start.y as i128, end.y as i128) as u16
+}
+
+#[cfg(test)]
+mod curve_testing {
+    use crate::curve::{CurveError, CurvePoint, CurvePoints, ResponseCurve, MAX_CURVE_POINTS};
+    use rstest::rstest;
+
+    fn knots(points: &[(u16, u16)]) -> CurvePoints {
+        let points: alloc::vec::Vec<CurvePoint> =
+            points.iter().map(|&(x, y)| CurvePoint::new(x, y)).collect();
+        CurvePoints::new(&points).unwrap()
+    }
+
+    fn five_knots() -> CurvePoints {
+        knots(&[
+            (0, 0),
+            (16_384, 4_000),
+            (32_768, 20_000),
+            (49_152, 52_000),
+            (u16::MAX, u16::MAX),
+        ])
+    }
+
+    fn eleven_knots() -> CurvePoints {
+        knots(&[
+            (0, 0),
+            (6_553, 500),
+            (13_107, 2_000),
+            (19_660, 2_000),
+            (26_214, 9_000),
+            (32_767, 30_000),
+            (39_321, 31_000),
+            (45_874, 50_000),
+            (52_428, 60_000),
+            (58_981, 65_000),
+            (u16::MAX, u16::MAX),
+        ])
+    }
+
+    fn all_curves() -> [ResponseCurve; 9] {
+        [
+            ResponseCurve::Linear,
+            ResponseCurve::Progressive(100),
+            ResponseCurve::Progressive(-100),
+            ResponseCurve::SCurve(100),
+            ResponseCurve::SCurve(-100),
+            ResponseCurve::PiecewiseLinear(five_knots()),
+            ResponseCurve::PiecewiseLinear(eleven_knots()),
+            ResponseCurve::CubicSpline(five_knots()),
+            ResponseCurve::CubicSpline(eleven_knots()),
+        ]
+    }
+
+    #[test]
+    fn when_sweeping_the_full_travel_then_every_curve_is_monotonic() {
+        for curve in all_curves() {
+            // Given
+            let mut previous = i16::MIN;
+
+            for value in i16::MIN..=i16::MAX {
+                // When
+                let result = curve.apply(value);
+
+                // Then
+                assert!(
+                    result >= previous,
+                    "{curve:?} decreased at {value}: {previous} -> {result}"
+                );
+                previous = result;
+            }
+        }
+    }
+
+    #[test]
+    fn when_value_is_at_the_end_of_the_travel_then_the_endpoints_are_kept() {
+        for curve in all_curves() {
+            // When
+            let released = curve.apply(i16::MIN);
+            let pressed = curve.apply(i16::MAX);
+
+            // Then
+            assert_eq!(released, i16::MIN, "{curve:?}");
+            assert_eq!(pressed, i16::MAX, "{curve:?}");
+        }
+    }
+
+    #[rstest]
+    #[case(ResponseCurve::Progressive(100), 16_383)]
+    #[case(ResponseCurve::Progressive(-100), 49_150)]
+    #[case(ResponseCurve::Progressive(0), 32_767)]
+    #[case(ResponseCurve::SCurve(100), 32_766)]
+    #[case(ResponseCurve::SCurve(-100), 32_767)]
+    fn when_value_is_in_the_middle_of_the_travel(
+        #[case] curve: ResponseCurve,
+        #[case] expected: u16,
+    ) {
+        // When
+        let result = curve.apply_position(32_767);
+
+        // Then
+        assert_eq!(result, expected);
+    }
+
+    #[rstest]
+    #[case(ResponseCurve::SCurve(100), 16_384, 10_240)]
+    #[case(ResponseCurve::SCurve(-100), 16_384, 22_527)]
+    #[case(ResponseCurve::Progressive(120), 16_384, 4_096)]
+    #[case(ResponseCurve::Progressive(-120), 16_384, 28_671)]
+    fn when_value_is_in_the_first_quarter_of_the_travel(
+        #[case] curve: ResponseCurve,
+        #[case] position: u16,
+        #[case] expected: u16,
+    ) {
+        // When
+        let result = curve.apply_position(position);
+
+        // Then
+        assert_eq!(result, expected);
+    }
+
+    #[rstest]
+    #[case(0, 0)]
+    #[case(16_384, 4_000)]
+    #[case(32_768, 20_000)]
+    #[case(49_152, 52_000)]
+    #[case(u16::MAX, u16::MAX)]
+    fn when_value_is_on_a_knot_then_the_curve_passes_through_it(
+        #[case] position: u16,
+        #[case] expected: u16,
+    ) {
+        // Given
+        let linear = ResponseCurve::PiecewiseLinear(five_knots());
+        let spline = ResponseCurve::CubicSpline(five_knots());
+
+        // When
+        let linear_result = linear.apply_position(position);
+        let spline_result = spline.apply_position(position);
+
+        // Then
+        assert_eq!(linear_result, expected);
+        assert_eq!(spline_result, expected);
+    }
+
+    #[rstest]
+    #[case(0, 10_000)]
+    #[case(9_999, 10_000)]
+    #[case(55_001, 50_000)]
+    #[case(u16::MAX, 50_000)]
+    fn when_value_is_outside_of_the_knots_then_the_edge_value_is_held(
+        #[case] position: u16,
+        #[case] expected: u16,
+    ) {
+        // Given
+        let points = knots(&[(10_000, 10_000), (30_000, 20_000), (55_000, 50_000)]);
+
+        for curve in [
+            ResponseCurve::PiecewiseLinear(points),
+            ResponseCurve::CubicSpline(points),
+        ] {
+            // When
+            let result = curve.apply_position(position);
+
+            // Then
+            assert_eq!(result, expected, "{curve:?}");
+        }
+    }
+
+    #[test]
+    fn when_segment_is_flat_then_the_spline_does_not_overshoot() {
+        // Given
+        let curve = ResponseCurve::CubicSpline(eleven_knots());
+
+        for position in 13_107..=19_660 {
+            // When
+            let result = curve.apply_position(position);
+
+            // Then
+            assert_eq!(result, 2_000);
+        }
+    }
+
+    #[rstest]
+    #[case(&[(0, 0)], CurveError::TooFewPoints)]
+    #[case(&[(0, 0); MAX_CURVE_POINTS + 1], CurveError::TooManyPoints)]
+    #[case(&[(0, 0), (100, 10), (100, 20)], CurveError::NotIncreasing)]
+    #[case(&[(0, 0), (200, 10), (100, 20)], CurveError::NotIncreasing)]
+    #[case(&[(0, 0), (100, 20), (200, 10)], CurveError::NotMonotonic)]
+    fn when_creating_invalid_curve_points(
+        #[case] points: &[(u16, u16)],
+        #[case] expected: CurveError,
+    ) {
+        // Given
+        let points: alloc::vec::Vec<CurvePoint> =
+            points.iter().map(|&(x, y)| CurvePoint::new(x, y)).collect();
+
+        // When
+        let result = CurvePoints::new(&points);
+
+        // Then
+        assert_eq!(result, Err(expected));
+    }
+}
@@ -0,0 +1,159 @@
+//! Deadzones at both ends of the pedal travel.
+//!
+//! The lower and upper deadzones shrink the input range before it is handed to
+//! [`Mapping::map_to_i16`](crate::Mapping::map_to_i16), so a pedal resting slightly above
+//! `range_min` still reads as released and a pedal that can't quite reach `range_max`
+//! still reads as fully pressed. The saturation zone works on the output instead and
+//! snaps everything close to the top of the axis to `i16::MAX`.
+
+/// Percentages are clamped to this value.
+pub const MAX_PERCENT: u8 = 100;
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DeadzoneWidth {
+    /// Width in percent of the travel between `range_min` and `range_max`.
+    Percent(u8),
+    /// Width in the raw units of the sensor.
+    Raw(u32),
+}
+
+impl DeadzoneWidth {
+    fn in_raw_units(&self, travel: i64) -> i64 {
+        match self {
+            DeadzoneWidth::Percent(percent) => travel * (*percent).min(MAX_PERCENT) as i64 / 100,
+            DeadzoneWidth::Raw(width) => *width as i64,
+        }
+    }
+}
+
+impl Default for DeadzoneWidth {
+    fn default() -> Self {
+        DeadzoneWidth::Raw(0)
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Deadzone {
+    /// Readings within this distance from `range_min` are mapped to `i16::MIN`.
+    pub lower: DeadzoneWidth,
+    /// Readings within this distance from `range_max` are mapped to `i16::MAX`.
+    pub upper: DeadzoneWidth,
+    /// Outputs within this percent of the axis from the top snap to `i16::MAX`.
+    pub saturation: u8,
+}
+
+impl Deadzone {
+    pub const NONE: Deadzone = Deadzone {
+        lower: DeadzoneWidth::Raw(0),
+        upper: DeadzoneWidth::Raw(0),
+        saturation: 0,
+    };
+
+    /// Returns the input range with the deadzones removed from both ends.
+    ///
+    /// When the deadzones overlap, the range collapses into a single point in the middle
+    /// of the overlap.
+    pub fn range(&self, min: i64, max: i64) -> (i64, i64) {
+        let travel = max - min;
+        if travel <= 0 {
+            return (min, max);
+        }
+        let lower = min + self.lower.in_raw_units(travel);
+        let upper = max - self.upper.in_raw_units(travel);
+        if lower <= upper {
+            (lower, upper)
+        } else {
+            let middle = upper + (lower - upper) / 2;
+            (middle, middle)
+        }
+    }
+
+    /// Snaps the mapped value to `i16::MAX` when it is inside the saturation zone.
+    pub fn saturate(&self, value: i16) -> i16 {
+        let span = i16::MAX as i32 - i16::MIN as i32;
+        let zone = span * self.saturation.min(MAX_PERCENT) as i32 / 100;
+        if zone > 0 && value as i32 >= i16::MAX as i32 - zone {
+            i16::MAX
+        } else {
+            value
+        }
+    }
+}
+
+#[cfg(test)]
+mod deadzone_testing {
+    use crate::deadzone::{Deadzone, DeadzoneWidth};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(DeadzoneWidth::Percent(0), DeadzoneWidth::Percent(0), 1000, 2000)]
+    #[case(DeadzoneWidth::Percent(5), DeadzoneWidth::Percent(0), 1050, 2000)]
+    #[case(DeadzoneWidth::Percent(0), DeadzoneWidth::Percent(10), 1000, 1900)]
+    #[case(DeadzoneWidth::Raw(20), DeadzoneWidth::Raw(30), 1020, 1970)]
+    #[case(DeadzoneWidth::Percent(2), DeadzoneWidth::Raw(30), 1020, 1970)]
+    #[case(DeadzoneWidth::Percent(60), DeadzoneWidth::Percent(60), 1500, 1500)]
+    #[case(DeadzoneWidth::Percent(200), DeadzoneWidth::Percent(0), 2000, 2000)]
+    #[case(DeadzoneWidth::Raw(800), DeadzoneWidth::Raw(400), 1700, 1700)]
+    fn when_shrinking_the_input_range(
+        #[case] lower: DeadzoneWidth,
+        #[case] upper: DeadzoneWidth,
+        #[case] expected_min: i64,
+        #[case] expected_max: i64,
+    ) {
+        // Given
+        let deadzone = Deadzone {
+            lower,
+            upper,
+            saturation: 0,
+        };
+
+        // When
+        let result = deadzone.range(1000, 2000);
+
+        // Then
+        assert_eq!(result, (expected_min, expected_max));
+    }
+
+    #[test]
+    fn when_range_is_zero_size() {
+        // Given
+        let deadzone = Deadzone {
+            lower: DeadzoneWidth::Raw(10),
+            upper: DeadzoneWidth::Raw(10),
+            saturation: 0,
+        };
+
+        // When
+        let result = deadzone.range(100, 100);
+
+        // Then
+        assert_eq!(result, (100, 100));
+    }
+
+    #[rstest]
+    #[case(0, i16::MAX - 1, i16::MAX - 1)]
+    #[case(5, 29_490, 29_490)]
+    #[case(5, 29_491, i16::MAX)]
+    #[case(5, i16::MAX, i16::MAX)]
+    #[case(100, i16::MIN, i16::MAX)]
+    #[case(250, 0, i16::MAX)]
+    fn when_saturating_the_output(
+        #[case] saturation: u8,
+        #[case] value: i16,
+        #[case] expected: i16,
+    ) {
+        // Given
+        let deadzone = Deadzone {
+            saturation,
+            ..Deadzone::NONE
+        };
+
+        // When
+        let result = deadzone.saturate(value);
+
+        // Then
+        assert_eq!(result, expected);
+    }
+}
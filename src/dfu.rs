@@ -0,0 +1,204 @@
+//! USB device firmware upgrade (DFU 1.1) definitions shared by the firmware and the host.
+//!
+//! The firmware only offers the DFU runtime interface: a `DETACH` request reboots it into
+//! the ROM bootloader of the STM32, which speaks the DfuSe flavour of DFU in DFU mode and
+//! does the actual flashing.
+
+/// Interface class, subclass and protocols of DFU.
+pub const DFU_CLASS: u8 = 0xfe;
+pub const DFU_SUBCLASS: u8 = 0x01;
+pub const DFU_PROTOCOL_RUNTIME: u8 = 0x01;
+pub const DFU_PROTOCOL_DFU_MODE: u8 = 0x02;
+
+/// Type of the DFU functional descriptor.
+pub const DFU_FUNCTIONAL_DESCRIPTOR: u8 = 0x21;
+
+/// USB IDs of the STM32 ROM bootloader in DFU mode.
+pub const BOOTLOADER_VENDOR_ID: u16 = 0x0483;
+pub const BOOTLOADER_PRODUCT_ID: u16 = 0xdf11;
+
+/// Class requests of DFU.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DfuRequest {
+    Detach = 0,
+    Download = 1,
+    Upload = 2,
+    GetStatus = 3,
+    ClearStatus = 4,
+    GetState = 5,
+    Abort = 6,
+}
+
+impl TryFrom<u8> for DfuRequest {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(DfuRequest::Detach),
+            1 => Ok(DfuRequest::Download),
+            2 => Ok(DfuRequest::Upload),
+            3 => Ok(DfuRequest::GetStatus),
+            4 => Ok(DfuRequest::ClearStatus),
+            5 => Ok(DfuRequest::GetState),
+            6 => Ok(DfuRequest::Abort),
+            _ => Err(value),
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DfuState {
+    AppIdle = 0,
+    AppDetach = 1,
+    Idle = 2,
+    DownloadSync = 3,
+    DownloadBusy = 4,
+    DownloadIdle = 5,
+    ManifestSync = 6,
+    Manifest = 7,
+    ManifestWaitReset = 8,
+    UploadIdle = 9,
+    Error = 10,
+}
+
+impl TryFrom<u8> for DfuState {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, u8> {
+        [
+            DfuState::AppIdle,
+            DfuState::AppDetach,
+            DfuState::Idle,
+            DfuState::DownloadSync,
+            DfuState::DownloadBusy,
+            DfuState::DownloadIdle,
+            DfuState::ManifestSync,
+            DfuState::Manifest,
+            DfuState::ManifestWaitReset,
+            DfuState::UploadIdle,
+            DfuState::Error,
+        ]
+        .into_iter()
+        .find(|state| *state as u8 == value)
+        .ok_or(value)
+    }
+}
+
+/// Answer to `GETSTATUS`.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DfuStatus {
+    /// `0` when the last request succeeded, a DFU error code otherwise.
+    pub status: u8,
+    /// Time the host has to wait before the next `GETSTATUS`.
+    pub poll_timeout_ms: u32,
+    pub state: DfuState,
+}
+
+/// Length of the `GETSTATUS` answer.
+pub const DFU_STATUS_LENGTH: usize = 6;
+
+impl DfuStatus {
+    pub fn encode(&self) -> [u8; DFU_STATUS_LENGTH] {
+        let timeout = self.poll_timeout_ms.to_le_bytes();
+        [
+            self.status,
+            timeout[0],
+            timeout[1],
+            timeout[2],
+            self.state as u8,
+            0,
+        ]
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let bytes: &[u8; DFU_STATUS_LENGTH] = bytes.get(..DFU_STATUS_LENGTH)?.try_into().ok()?;
+        Some(Self {
+            status: bytes[0],
+            poll_timeout_ms: u32::from_le_bytes([bytes[1], bytes[2], bytes[3], 0]),
+            state: DfuState::try_from(bytes[4]).ok()?,
+        })
+    }
+}
+
+/// Attributes of the functional descriptor.
+pub const ATTRIBUTE_CAN_DOWNLOAD: u8 = 1 << 0;
+pub const ATTRIBUTE_CAN_UPLOAD: u8 = 1 << 1;
+/// The device detaches by itself after `DETACH`, the host doesn't have to reset it.
+pub const ATTRIBUTE_WILL_DETACH: u8 = 1 << 3;
+
+/// Transfer size of the STM32 ROM bootloader.
+pub const BOOTLOADER_TRANSFER_SIZE: u16 = 2048;
+
+/// Body of the DFU functional descriptor, without its length and type.
+pub const fn functional_descriptor(
+    attributes: u8,
+    detach_timeout_ms: u16,
+    transfer_size: u16,
+) -> [u8; 7] {
+    let timeout = detach_timeout_ms.to_le_bytes();
+    let size = transfer_size.to_le_bytes();
+    // bcdDFUVersion 1.1
+    [
+        attributes, timeout[0], timeout[1], size[0], size[1], 0x10, 0x01,
+    ]
+}
+
+#[cfg(test)]
+mod dfu_testing {
+    use crate::dfu::{functional_descriptor, DfuRequest, DfuState, DfuStatus};
+    use rstest::rstest;
+
+    #[test]
+    fn when_status_is_encoded_then_it_decodes_to_the_same_status() {
+        // Given
+        let status = DfuStatus {
+            status: 0,
+            poll_timeout_ms: 0x01_0203,
+            state: DfuState::DownloadBusy,
+        };
+
+        // When
+        let bytes = status.encode();
+        let result = DfuStatus::decode(&bytes);
+
+        // Then
+        assert_eq!(bytes, [0, 0x03, 0x02, 0x01, 4, 0]);
+        assert_eq!(result, Some(status));
+    }
+
+    #[rstest]
+    #[case(&[0, 0, 0, 0, 11, 0])]
+    #[case(&[0, 0, 0, 0, 2])]
+    fn when_status_is_invalid_then_it_is_not_decoded(#[case] bytes: &[u8]) {
+        // When
+        let result = DfuStatus::decode(bytes);
+
+        // Then
+        assert_eq!(result, None);
+    }
+
+    #[rstest]
+    #[case(0, Ok(DfuRequest::Detach))]
+    #[case(3, Ok(DfuRequest::GetStatus))]
+    #[case(6, Ok(DfuRequest::Abort))]
+    #[case(7, Err(7))]
+    fn when_request_is_parsed(#[case] value: u8, #[case] expected: Result<DfuRequest, u8>) {
+        // When
+        let result = DfuRequest::try_from(value);
+
+        // Then
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn when_functional_descriptor_is_built() {
+        // When
+        let result = functional_descriptor(0x0b, 1_000, 2_048);
+
+        // Then
+        assert_eq!(result, [0x0b, 0xe8, 0x03, 0x00, 0x08, 0x10, 0x01]);
+    }
+}
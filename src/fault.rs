@@ -0,0 +1,300 @@
+//! Detection of broken sensors.
+//!
+//! A broken wire lets the ADC float to one of its rails and a dead HX711 never finishes a
+//! conversion. The [`FaultDetector`] of a monitor looks at every raw reading and every
+//! missing one, and while a fault is active the monitor publishes a safe value instead of
+//! the reading.
+
+use core::ops::{BitOr, BitOrAssign};
+use core::time::Duration;
+
+/// Faults of a sensor, one bit each.
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FaultFlags(pub u8);
+
+impl FaultFlags {
+    pub const NONE: FaultFlags = FaultFlags(0);
+    /// The raw reading is at the low rail, e.g. a broken wiper or a short to ground.
+    pub const RAIL_LOW: FaultFlags = FaultFlags(1 << 0);
+    /// The raw reading is at the high rail, e.g. a broken ground wire.
+    pub const RAIL_HIGH: FaultFlags = FaultFlags(1 << 1);
+    /// The raw reading didn't change at all for too long.
+    pub const STUCK: FaultFlags = FaultFlags(1 << 2);
+    /// No new reading for too long.
+    pub const NOT_READY: FaultFlags = FaultFlags(1 << 3);
+    /// Too many failed reads in a row.
+    pub const READ_ERRORS: FaultFlags = FaultFlags(1 << 4);
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn contains(self, other: FaultFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// The flags of `self` that are not in `other`.
+    pub fn without(self, other: FaultFlags) -> FaultFlags {
+        FaultFlags(self.0 & !other.0)
+    }
+}
+
+impl BitOr for FaultFlags {
+    type Output = FaultFlags;
+
+    fn bitor(self, rhs: FaultFlags) -> FaultFlags {
+        FaultFlags(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for FaultFlags {
+    fn bitor_assign(&mut self, rhs: FaultFlags) {
+        self.0 |= rhs.0;
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FaultConfig {
+    /// Raw readings at or below this are at the low rail. `None` doesn't check it.
+    pub rail_low: Option<i64>,
+    /// Raw readings at or above this are at the high rail. `None` doesn't check it.
+    pub rail_high: Option<i64>,
+    /// Identical raw readings in a row that make the sensor stuck, 0 never does.
+    pub stuck_samples: u32,
+    /// Time without a new reading before the sensor is not ready, zero never.
+    pub not_ready_timeout_ms: u32,
+    /// Failed reads in a row before the sensor is faulty, 0 never.
+    pub max_read_errors: u16,
+    /// Value published while a fault is active.
+    pub safe_value: i16,
+}
+
+impl FaultConfig {
+    pub const DISABLED: FaultConfig = FaultConfig {
+        rail_low: None,
+        rail_high: None,
+        stuck_samples: 0,
+        not_ready_timeout_ms: 0,
+        max_read_errors: 0,
+        safe_value: i16::MIN,
+    };
+}
+
+#[derive(Debug)]
+pub struct FaultDetector {
+    config: FaultConfig,
+    /// Polls without a reading that make the sensor not ready, 0 never.
+    not_ready_polls: u32,
+    faults: FaultFlags,
+    last_raw: Option<i64>,
+    same_count: u32,
+    missed_polls: u32,
+    read_errors: u16,
+}
+
+impl FaultDetector {
+    /// `period` is the time between two polls of the sensor.
+    pub fn new(config: FaultConfig, period: Duration) -> Self {
+        let period_ms = period.as_millis().max(1) as u32;
+        Self {
+            config,
+            not_ready_polls: config.not_ready_timeout_ms.div_ceil(period_ms),
+            faults: FaultFlags::NONE,
+            last_raw: None,
+            same_count: 0,
+            missed_polls: 0,
+            read_errors: 0,
+        }
+    }
+
+    pub fn config(&self) -> FaultConfig {
+        self.config
+    }
+
+    /// Faults that are active now.
+    pub fn faults(&self) -> FaultFlags {
+        self.faults
+    }
+
+    /// Checks a raw reading. It also proves the sensor is ready and readable.
+    pub fn reading(&mut self, raw: i64) -> FaultFlags {
+        self.missed_polls = 0;
+        self.read_errors = 0;
+        if self.last_raw == Some(raw) {
+            self.same_count = self.same_count.saturating_add(1);
+        } else {
+            self.same_count = 1;
+            self.last_raw = Some(raw);
+        }
+
+        let mut faults = FaultFlags::NONE;
+        if self.config.rail_low.is_some_and(|rail| raw <= rail) {
+            faults |= FaultFlags::RAIL_LOW;
+        }
+        if self.config.rail_high.is_some_and(|rail| raw >= rail) {
+            faults |= FaultFlags::RAIL_HIGH;
+        }
+        if self.config.stuck_samples > 0 && self.same_count >= self.config.stuck_samples {
+            faults |= FaultFlags::STUCK;
+        }
+        self.faults = faults;
+        self.faults
+    }
+
+    /// Records a poll that found no new reading.
+    pub fn missing(&mut self) -> FaultFlags {
+        self.missed_polls = self.missed_polls.saturating_add(1);
+        if self.not_ready_polls > 0 && self.missed_polls >= self.not_ready_polls {
+            self.faults |= FaultFlags::NOT_READY;
+        }
+        self.faults
+    }
+
+    /// Records a failed read.
+    pub fn read_error(&mut self) -> FaultFlags {
+        self.read_errors = self.read_errors.saturating_add(1);
+        if self.config.max_read_errors > 0 && self.read_errors >= self.config.max_read_errors {
+            self.faults |= FaultFlags::READ_ERRORS;
+        }
+        self.faults
+    }
+}
+
+#[cfg(test)]
+mod fault_testing {
+    use crate::fault::{FaultConfig, FaultDetector, FaultFlags};
+    use alloc::vec::Vec;
+    use core::time::Duration;
+    use rstest::rstest;
+
+    const CONFIG: FaultConfig = FaultConfig {
+        rail_low: Some(40),
+        rail_high: Some(16_340),
+        stuck_samples: 4,
+        not_ready_timeout_ms: 50,
+        max_read_errors: 3,
+        safe_value: i16::MIN,
+    };
+
+    fn detector() -> FaultDetector {
+        FaultDetector::new(CONFIG, Duration::from_millis(10))
+    }
+
+    #[rstest]
+    #[case(0, FaultFlags::RAIL_LOW)]
+    #[case(40, FaultFlags::RAIL_LOW)]
+    #[case(41, FaultFlags::NONE)]
+    #[case(8_000, FaultFlags::NONE)]
+    #[case(16_339, FaultFlags::NONE)]
+    #[case(16_340, FaultFlags::RAIL_HIGH)]
+    #[case(16_383, FaultFlags::RAIL_HIGH)]
+    fn when_reading_is_checked_against_the_rails(#[case] raw: i64, #[case] expected: FaultFlags) {
+        // Given
+        let mut detector = detector();
+
+        // When
+        let result = detector.reading(raw);
+
+        // Then
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn when_reading_leaves_the_rail_then_the_fault_clears() {
+        // Given
+        let mut detector = detector();
+        detector.reading(16_383);
+
+        // When
+        let result = detector.reading(8_000);
+
+        // Then
+        assert_eq!(result, FaultFlags::NONE);
+    }
+
+    #[test]
+    fn when_reading_does_not_change_then_it_is_stuck() {
+        // Given
+        let mut detector = detector();
+
+        // When
+        let results: Vec<FaultFlags> = [500, 500, 500, 500, 501]
+            .iter()
+            .map(|&raw| detector.reading(raw))
+            .collect();
+
+        // Then
+        assert_eq!(
+            results,
+            [
+                FaultFlags::NONE,
+                FaultFlags::NONE,
+                FaultFlags::NONE,
+                FaultFlags::STUCK,
+                FaultFlags::NONE
+            ]
+        );
+    }
+
+    #[test]
+    fn when_readings_stop_then_the_sensor_is_not_ready_after_the_timeout() {
+        // Given
+        let mut detector = detector();
+        detector.reading(500);
+
+        // When
+        let results: Vec<FaultFlags> = (0..5).map(|_| detector.missing()).collect();
+        let after_reading = detector.reading(501);
+
+        // Then
+        assert_eq!(results[3], FaultFlags::NONE);
+        assert_eq!(results[4], FaultFlags::NOT_READY);
+        assert_eq!(after_reading, FaultFlags::NONE);
+    }
+
+    #[test]
+    fn when_reads_fail_in_a_row_then_the_sensor_is_faulty() {
+        // Given
+        let mut detector = detector();
+
+        // When
+        let first = detector.read_error();
+        detector.read_error();
+        let third = detector.read_error();
+
+        // Then
+        assert_eq!(first, FaultFlags::NONE);
+        assert!(third.contains(FaultFlags::READ_ERRORS));
+    }
+
+    #[test]
+    fn when_detection_is_disabled_then_nothing_is_a_fault() {
+        // Given
+        let mut detector = FaultDetector::new(FaultConfig::DISABLED, Duration::from_millis(1));
+
+        // When
+        let mut faults = FaultFlags::NONE;
+        for _ in 0..1_000 {
+            faults |= detector.reading(0);
+            faults |= detector.missing();
+            faults |= detector.read_error();
+        }
+
+        // Then
+        assert_eq!(faults, FaultFlags::NONE);
+    }
+
+    #[test]
+    fn when_flags_are_combined() {
+        // Given
+        let faults = FaultFlags::RAIL_LOW | FaultFlags::STUCK;
+
+        // Then
+        assert!(faults.contains(FaultFlags::STUCK));
+        assert!(!faults.contains(FaultFlags::RAIL_HIGH));
+        assert_eq!(faults.without(FaultFlags::STUCK), FaultFlags::RAIL_LOW);
+        assert!(!faults.is_empty());
+    }
+}
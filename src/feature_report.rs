@@ -0,0 +1,1114 @@
+//! HID feature reports used by the host to read and write the axis settings.
+//!
+//! Every report starts with its report ID, followed by little endian fields. The ID
+//! encodes both the kind of the report and the axis it belongs to:
+//!
+//! | Report ID     | Content                          | Payload size |
+//! |---------------|----------------------------------|--------------|
+//! | `0x10 + axis` | Calibration range and deadzones  | 19           |
+//! | `0x20 + axis` | Response curve                   | 47           |
+//! | `0x30 + axis` | Filter chain                     | 45           |
+//!
+//! Calibration: `range_min: i32`, `range_max: i32`, lower and upper deadzone as
+//! `unit: u8` (`0` raw, `1` percent) and `width: u32`, `saturation: u8`.
+//!
+//! Curve: `kind: u8` (`0` linear, `1` progressive, `2` S-curve, `3` piecewise linear,
+//! `4` cubic spline), `strength: i8`, `point_count: u8` and [`MAX_CURVE_POINTS`] knots of
+//! `x: u16`, `y: u16`. Unused knots are zero.
+//!
+//! Filter: [`MAX_FILTER_STAGES`] stages of `kind: u8` (`0` none, `1` EMA, `2` median,
+//! `3` one-euro) and 14 bytes of parameters. EMA: `alpha: u16`. Median: `window: u8`.
+//! One-euro: `min_cutoff_mhz: u32`, `beta_uhz: u32`, `derivative_cutoff_mhz: u32`,
+//! `sample_rate_hz: u16`.
+//!
+//! The calibration session report (`0x40`) isn't tied to an axis. Reading it returns
+//! `state: u8` (see [`CalibrationState`]) and for every axis `captured: u8`,
+//! `range_min: i32` and `range_max: i32`. Writing it takes `command: u8` (`0` cancel,
+//! `1` start, `2` commit, `3` commit and save) and ignores the rest of the payload.
+//!
+//! The report mode report (`0x50`) holds `mode: u8` (`0` periodic, `1` on change),
+//! `threshold: u16` and `interval_ms: u16`, the heartbeat in the on-change mode. The
+//! threshold is zero in the periodic mode.
+//!
+//! The report statistics report (`0x51`) is read only and holds `reports: u32`,
+//! `heartbeats: u32`, `last_latency_us: u32` and `max_latency_us: u32`. Writing it resets
+//! the maximum latency.
+//!
+//! The fault status report (`0x60`) is read only and holds the active fault flags of
+//! every axis as `u8`, followed by the latched flags of every axis (see [`FaultFlags`]).
+//! Writing it clears the latched flags that are no longer active.
+//!
+//! The reset cause report (`0x61`) is read only and holds `reason: u8` (see
+//! [`ResetReason`]) and `culprit: u8`, the [`SupervisedTask`] the watchdog reset the chip
+//! for or `0`.
+
+use crate::axis::{Axis, AXIS_COUNT};
+use crate::calibration::{AxisCalibration, CalibrationSession, CalibrationState, CommitTarget};
+use crate::curve::{CurvePoint, CurvePoints, ResponseCurve, MAX_CURVE_POINTS};
+use crate::deadzone::{Deadzone, DeadzoneWidth};
+use crate::fault::FaultFlags;
+use crate::filters::{Filter, FilterChain, OneEuroConfig, MAX_FILTER_STAGES};
+use crate::pedal_state::PedalSnapshot;
+use crate::report_mode::{ReportMode, ReportStatistics};
+use crate::settings::AxisSettings;
+use crate::supervisor::{ResetCause, ResetReason, SupervisedTask};
+
+pub const CALIBRATION_REPORT_BASE: u8 = 0x10;
+pub const CURVE_REPORT_BASE: u8 = 0x20;
+pub const FILTER_REPORT_BASE: u8 = 0x30;
+pub const CALIBRATION_SESSION_REPORT_ID: u8 = 0x40;
+pub const REPORT_MODE_REPORT_ID: u8 = 0x50;
+pub const REPORT_STATISTICS_REPORT_ID: u8 = 0x51;
+pub const FAULT_STATUS_REPORT_ID: u8 = 0x60;
+pub const RESET_CAUSE_REPORT_ID: u8 = 0x61;
+
+pub const CALIBRATION_PAYLOAD_SIZE: usize = 19;
+pub const CURVE_PAYLOAD_SIZE: usize = 3 + MAX_CURVE_POINTS * 4;
+const FILTER_STAGE_SIZE: usize = 15;
+pub const FILTER_PAYLOAD_SIZE: usize = MAX_FILTER_STAGES * FILTER_STAGE_SIZE;
+const CAPTURED_RANGE_SIZE: usize = 9;
+pub const CALIBRATION_SESSION_PAYLOAD_SIZE: usize = 1 + AXIS_COUNT * CAPTURED_RANGE_SIZE;
+pub const REPORT_MODE_PAYLOAD_SIZE: usize = 5;
+pub const REPORT_STATISTICS_PAYLOAD_SIZE: usize = 16;
+pub const FAULT_STATUS_PAYLOAD_SIZE: usize = 2 * AXIS_COUNT;
+pub const RESET_CAUSE_PAYLOAD_SIZE: usize = 2;
+
+/// Size of the largest report including its ID.
+pub const MAX_FEATURE_REPORT_SIZE: usize = 1 + CURVE_PAYLOAD_SIZE;
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReportError {
+    UnknownReport(u8),
+    InvalidLength,
+    /// A field holds a value that doesn't describe valid settings.
+    InvalidValue,
+    BufferTooSmall,
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FeatureKind {
+    Calibration,
+    Curve,
+    Filter,
+}
+
+impl FeatureKind {
+    pub const ALL: [FeatureKind; 3] = [
+        FeatureKind::Calibration,
+        FeatureKind::Curve,
+        FeatureKind::Filter,
+    ];
+
+    const fn base(self) -> u8 {
+        match self {
+            FeatureKind::Calibration => CALIBRATION_REPORT_BASE,
+            FeatureKind::Curve => CURVE_REPORT_BASE,
+            FeatureKind::Filter => FILTER_REPORT_BASE,
+        }
+    }
+
+    pub const fn payload_size(self) -> usize {
+        match self {
+            FeatureKind::Calibration => CALIBRATION_PAYLOAD_SIZE,
+            FeatureKind::Curve => CURVE_PAYLOAD_SIZE,
+            FeatureKind::Filter => FILTER_PAYLOAD_SIZE,
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FeatureReportId {
+    pub kind: FeatureKind,
+    pub axis: Axis,
+}
+
+impl FeatureReportId {
+    pub const fn new(kind: FeatureKind, axis: Axis) -> Self {
+        Self { kind, axis }
+    }
+
+    pub const fn value(self) -> u8 {
+        self.kind.base() + self.axis.index() as u8
+    }
+}
+
+impl TryFrom<u8> for FeatureReportId {
+    type Error = ReportError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        let kind = FeatureKind::ALL
+            .into_iter()
+            .find(|kind| (kind.base()..kind.base() + AXIS_COUNT as u8).contains(&value))
+            .ok_or(ReportError::UnknownReport(value))?;
+        let axis =
+            Axis::try_from(value - kind.base()).map_err(|_| ReportError::UnknownReport(value))?;
+        Ok(Self { kind, axis })
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FeatureReport {
+    Calibration {
+        axis: Axis,
+        calibration: AxisCalibration,
+        deadzone: Deadzone,
+    },
+    Curve {
+        axis: Axis,
+        curve: ResponseCurve,
+    },
+    Filter {
+        axis: Axis,
+        filter: FilterChain,
+    },
+}
+
+impl FeatureReport {
+    /// Builds the report with the given ID from the settings of its axis.
+    pub fn from_settings(id: FeatureReportId, settings: &AxisSettings) -> Self {
+        let axis = id.axis;
+        match id.kind {
+            FeatureKind::Calibration => FeatureReport::Calibration {
+                axis,
+                calibration: settings.calibration,
+                deadzone: settings.deadzone,
+            },
+            FeatureKind::Curve => FeatureReport::Curve {
+                axis,
+                curve: settings.curve,
+            },
+            FeatureKind::Filter => FeatureReport::Filter {
+                axis,
+                filter: settings.filter,
+            },
+        }
+    }
+
+    /// Copies the content of the report into the settings of its axis.
+    pub fn apply(&self, settings: &mut AxisSettings) {
+        match *self {
+            FeatureReport::Calibration {
+                calibration,
+                deadzone,
+                ..
+            } => {
+                settings.calibration = calibration;
+                settings.deadzone = deadzone;
+            }
+            FeatureReport::Curve { curve, .. } => settings.curve = curve,
+            FeatureReport::Filter { filter, .. } => settings.filter = filter,
+        }
+    }
+
+    pub fn id(&self) -> FeatureReportId {
+        match *self {
+            FeatureReport::Calibration { axis, .. } => {
+                FeatureReportId::new(FeatureKind::Calibration, axis)
+            }
+            FeatureReport::Curve { axis, .. } => FeatureReportId::new(FeatureKind::Curve, axis),
+            FeatureReport::Filter { axis, .. } => FeatureReportId::new(FeatureKind::Filter, axis),
+        }
+    }
+
+    /// Writes the report including its ID into `buffer` and returns the written length.
+    pub fn encode(&self, buffer: &mut [u8]) -> Result<usize, ReportError> {
+        let id = self.id();
+        let length = 1 + id.kind.payload_size();
+        if buffer.len() < length {
+            return Err(ReportError::BufferTooSmall);
+        }
+        let buffer = &mut buffer[..length];
+        buffer.fill(0);
+        buffer[0] = id.value();
+        let mut writer = Writer {
+            buffer: &mut buffer[1..],
+            position: 0,
+        };
+
+        match self {
+            FeatureReport::Calibration {
+                calibration,
+                deadzone,
+                ..
+            } => {
+                writer.bytes(&calibration.range_min.to_le_bytes());
+                writer.bytes(&calibration.range_max.to_le_bytes());
+                write_deadzone_width(&mut writer, deadzone.lower);
+                write_deadzone_width(&mut writer, deadzone.upper);
+                writer.bytes(&[deadzone.saturation]);
+            }
+            FeatureReport::Curve { curve, .. } => {
+                let (kind, strength, points) = match curve {
+                    ResponseCurve::Linear => (0, 0, None),
+                    ResponseCurve::Progressive(strength) => (1, *strength, None),
+                    ResponseCurve::SCurve(strength) => (2, *strength, None),
+                    ResponseCurve::PiecewiseLinear(points) => (3, 0, Some(points)),
+                    ResponseCurve::CubicSpline(points) => (4, 0, Some(points)),
+                };
+                let points = points.map(CurvePoints::as_slice).unwrap_or_default();
+                writer.bytes(&[kind, strength as u8, points.len() as u8]);
+                for point in points {
+                    writer.bytes(&point.x.to_le_bytes());
+                    writer.bytes(&point.y.to_le_bytes());
+                }
+            }
+            FeatureReport::Filter { filter, .. } => {
+                for (stage, filter) in filter.filters().enumerate() {
+                    writer.position = stage * FILTER_STAGE_SIZE;
+                    match filter {
+                        Filter::Ema { alpha } => {
+                            writer.bytes(&[1]);
+                            writer.bytes(&alpha.to_le_bytes());
+                        }
+                        Filter::Median { window } => writer.bytes(&[2, *window]),
+                        Filter::OneEuro(config) => {
+                            writer.bytes(&[3]);
+                            writer.bytes(&config.min_cutoff_mhz.to_le_bytes());
+                            writer.bytes(&config.beta_uhz.to_le_bytes());
+                            writer.bytes(&config.derivative_cutoff_mhz.to_le_bytes());
+                            writer.bytes(&config.sample_rate_hz.to_le_bytes());
+                        }
+                    }
+                }
+            }
+        }
+        Ok(length)
+    }
+
+    /// Parses a report that starts with its ID. Trailing bytes are ignored, as some hosts
+    /// pad the reports to a fixed size.
+    pub fn decode(bytes: &[u8]) -> Result<Self, ReportError> {
+        let (&id, payload) = bytes.split_first().ok_or(ReportError::InvalidLength)?;
+        let id = FeatureReportId::try_from(id)?;
+        if payload.len() < id.kind.payload_size() {
+            return Err(ReportError::InvalidLength);
+        }
+        let mut reader = Reader {
+            bytes: payload,
+            position: 0,
+        };
+        let axis = id.axis;
+
+        match id.kind {
+            FeatureKind::Calibration => {
+                let range_min = i32::from_le_bytes(reader.array());
+                let range_max = i32::from_le_bytes(reader.array());
+                if range_min >= range_max {
+                    return Err(ReportError::InvalidValue);
+                }
+                let lower = read_deadzone_width(&mut reader)?;
+                let upper = read_deadzone_width(&mut reader)?;
+                let [saturation] = reader.array();
+                Ok(FeatureReport::Calibration {
+                    axis,
+                    calibration: AxisCalibration::new(range_min, range_max),
+                    deadzone: Deadzone {
+                        lower,
+                        upper,
+                        saturation,
+                    },
+                })
+            }
+            FeatureKind::Curve => {
+                let [kind, strength, count] = reader.array();
+                let count = count as usize;
+                if count > MAX_CURVE_POINTS {
+                    return Err(ReportError::InvalidValue);
+                }
+                let mut points = [CurvePoint::default(); MAX_CURVE_POINTS];
+                for point in points.iter_mut().take(count) {
+                    point.x = u16::from_le_bytes(reader.array());
+                    point.y = u16::from_le_bytes(reader.array());
+                }
+                let points =
+                    || CurvePoints::new(&points[..count]).map_err(|_| ReportError::InvalidValue);
+                let curve = match kind {
+                    0 => ResponseCurve::Linear,
+                    1 => ResponseCurve::Progressive(strength as i8),
+                    2 => ResponseCurve::SCurve(strength as i8),
+                    3 => ResponseCurve::PiecewiseLinear(points()?),
+                    4 => ResponseCurve::CubicSpline(points()?),
+                    _ => return Err(ReportError::InvalidValue),
+                };
+                Ok(FeatureReport::Curve { axis, curve })
+            }
+            FeatureKind::Filter => {
+                let mut filters = [Filter::Ema { alpha: 0 }; MAX_FILTER_STAGES];
+                let mut count = 0;
+                for stage in 0..MAX_FILTER_STAGES {
+                    reader.position = stage * FILTER_STAGE_SIZE;
+                    let [kind] = reader.array();
+                    let filter = match kind {
+                        0 => continue,
+                        1 => Filter::Ema {
+                            alpha: u16::from_le_bytes(reader.array()),
+                        },
+                        2 => {
+                            let [window] = reader.array();
+                            Filter::Median { window }
+                        }
+                        3 => Filter::OneEuro(OneEuroConfig {
+                            min_cutoff_mhz: u32::from_le_bytes(reader.array()),
+                            beta_uhz: u32::from_le_bytes(reader.array()),
+                            derivative_cutoff_mhz: u32::from_le_bytes(reader.array()),
+                            sample_rate_hz: u16::from_le_bytes(reader.array()),
+                        }),
+                        _ => return Err(ReportError::InvalidValue),
+                    };
+                    filters[count] = filter;
+                    count += 1;
+                }
+                let filter =
+                    FilterChain::new(&filters[..count]).map_err(|_| ReportError::InvalidValue)?;
+                Ok(FeatureReport::Filter { axis, filter })
+            }
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SessionCommand {
+    Cancel,
+    Start,
+    Commit(CommitTarget),
+}
+
+impl SessionCommand {
+    /// Executes the command, returns `false` when the session refused it.
+    pub fn execute(&self, session: &CalibrationSession) -> bool {
+        match self {
+            SessionCommand::Cancel => session.cancel(),
+            SessionCommand::Start => session.start(),
+            SessionCommand::Commit(target) => return session.commit(*target),
+        }
+        true
+    }
+
+    pub fn encode(&self, buffer: &mut [u8]) -> Result<usize, ReportError> {
+        let length = 1 + CALIBRATION_SESSION_PAYLOAD_SIZE;
+        if buffer.len() < length {
+            return Err(ReportError::BufferTooSmall);
+        }
+        buffer[..length].fill(0);
+        buffer[0] = CALIBRATION_SESSION_REPORT_ID;
+        buffer[1] = match self {
+            SessionCommand::Cancel => 0,
+            SessionCommand::Start => 1,
+            SessionCommand::Commit(CommitTarget::Ram) => 2,
+            SessionCommand::Commit(CommitTarget::Persistent) => 3,
+        };
+        Ok(length)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ReportError> {
+        let [id, command, ..] = *bytes else {
+            return Err(ReportError::InvalidLength);
+        };
+        if id != CALIBRATION_SESSION_REPORT_ID {
+            return Err(ReportError::UnknownReport(id));
+        }
+        match command {
+            0 => Ok(SessionCommand::Cancel),
+            1 => Ok(SessionCommand::Start),
+            2 => Ok(SessionCommand::Commit(CommitTarget::Ram)),
+            3 => Ok(SessionCommand::Commit(CommitTarget::Persistent)),
+            _ => Err(ReportError::InvalidValue),
+        }
+    }
+}
+
+/// Progress of the calibration session as reported to the host.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SessionStatus {
+    pub state: CalibrationState,
+    pub captured: [Option<AxisCalibration>; AXIS_COUNT],
+}
+
+impl SessionStatus {
+    pub fn from_session(session: &CalibrationSession) -> Self {
+        Self {
+            state: session.state(),
+            captured: Axis::ALL.map(|axis| session.captured(axis)),
+        }
+    }
+
+    pub fn encode(&self, buffer: &mut [u8]) -> Result<usize, ReportError> {
+        let length = 1 + CALIBRATION_SESSION_PAYLOAD_SIZE;
+        if buffer.len() < length {
+            return Err(ReportError::BufferTooSmall);
+        }
+        buffer[..length].fill(0);
+        buffer[0] = CALIBRATION_SESSION_REPORT_ID;
+        let mut writer = Writer {
+            buffer: &mut buffer[1..length],
+            position: 0,
+        };
+        writer.bytes(&[self.state as u8]);
+        for captured in self.captured {
+            let range = captured.unwrap_or(AxisCalibration::new(0, 0));
+            writer.bytes(&[captured.is_some() as u8]);
+            writer.bytes(&range.range_min.to_le_bytes());
+            writer.bytes(&range.range_max.to_le_bytes());
+        }
+        Ok(length)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ReportError> {
+        let (&id, payload) = bytes.split_first().ok_or(ReportError::InvalidLength)?;
+        if id != CALIBRATION_SESSION_REPORT_ID {
+            return Err(ReportError::UnknownReport(id));
+        }
+        if payload.len() < CALIBRATION_SESSION_PAYLOAD_SIZE {
+            return Err(ReportError::InvalidLength);
+        }
+        let mut reader = Reader {
+            bytes: payload,
+            position: 0,
+        };
+        let [state] = reader.array();
+        let state = CalibrationState::try_from(state).map_err(|_| ReportError::InvalidValue)?;
+        let mut captured = [None; AXIS_COUNT];
+        for range in captured.iter_mut() {
+            let [valid] = reader.array();
+            let range_min = i32::from_le_bytes(reader.array());
+            let range_max = i32::from_le_bytes(reader.array());
+            *range = (valid != 0).then_some(AxisCalibration::new(range_min, range_max));
+        }
+        Ok(Self { state, captured })
+    }
+}
+
+impl ReportMode {
+    pub fn encode(&self, buffer: &mut [u8]) -> Result<usize, ReportError> {
+        let length = 1 + REPORT_MODE_PAYLOAD_SIZE;
+        if buffer.len() < length {
+            return Err(ReportError::BufferTooSmall);
+        }
+        buffer[0] = REPORT_MODE_REPORT_ID;
+        let (mode, threshold, interval_ms) = match *self {
+            ReportMode::Periodic { interval_ms } => (0, 0, interval_ms),
+            ReportMode::OnChange {
+                threshold,
+                heartbeat_ms,
+            } => (1, threshold, heartbeat_ms),
+        };
+        let mut writer = Writer {
+            buffer: &mut buffer[1..length],
+            position: 0,
+        };
+        writer.bytes(&[mode]);
+        writer.bytes(&threshold.to_le_bytes());
+        writer.bytes(&interval_ms.to_le_bytes());
+        Ok(length)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ReportError> {
+        let (&id, payload) = bytes.split_first().ok_or(ReportError::InvalidLength)?;
+        if id != REPORT_MODE_REPORT_ID {
+            return Err(ReportError::UnknownReport(id));
+        }
+        if payload.len() < REPORT_MODE_PAYLOAD_SIZE {
+            return Err(ReportError::InvalidLength);
+        }
+        let mut reader = Reader {
+            bytes: payload,
+            position: 0,
+        };
+        let [mode] = reader.array();
+        let threshold = u16::from_le_bytes(reader.array());
+        let interval_ms = u16::from_le_bytes(reader.array());
+        if interval_ms == 0 {
+            return Err(ReportError::InvalidValue);
+        }
+        match mode {
+            0 => Ok(ReportMode::Periodic { interval_ms }),
+            1 => Ok(ReportMode::OnChange {
+                threshold,
+                heartbeat_ms: interval_ms,
+            }),
+            _ => Err(ReportError::InvalidValue),
+        }
+    }
+}
+
+/// Report statistics as reported to the host.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ReportStatus {
+    pub reports: u32,
+    pub heartbeats: u32,
+    pub last_latency_us: u32,
+    pub max_latency_us: u32,
+}
+
+impl ReportStatus {
+    pub fn from_statistics(statistics: &ReportStatistics) -> Self {
+        Self {
+            reports: statistics.reports(),
+            heartbeats: statistics.heartbeats(),
+            last_latency_us: statistics.last_latency_us(),
+            max_latency_us: statistics.max_latency_us(),
+        }
+    }
+
+    pub fn encode(&self, buffer: &mut [u8]) -> Result<usize, ReportError> {
+        let length = 1 + REPORT_STATISTICS_PAYLOAD_SIZE;
+        if buffer.len() < length {
+            return Err(ReportError::BufferTooSmall);
+        }
+        buffer[0] = REPORT_STATISTICS_REPORT_ID;
+        let mut writer = Writer {
+            buffer: &mut buffer[1..length],
+            position: 0,
+        };
+        for counter in [
+            self.reports,
+            self.heartbeats,
+            self.last_latency_us,
+            self.max_latency_us,
+        ] {
+            writer.bytes(&counter.to_le_bytes());
+        }
+        Ok(length)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ReportError> {
+        let (&id, payload) = bytes.split_first().ok_or(ReportError::InvalidLength)?;
+        if id != REPORT_STATISTICS_REPORT_ID {
+            return Err(ReportError::UnknownReport(id));
+        }
+        if payload.len() < REPORT_STATISTICS_PAYLOAD_SIZE {
+            return Err(ReportError::InvalidLength);
+        }
+        let mut reader = Reader {
+            bytes: payload,
+            position: 0,
+        };
+        let [reports, heartbeats, last_latency_us, max_latency_us] =
+            [(); 4].map(|_| u32::from_le_bytes(reader.array()));
+        Ok(Self {
+            reports,
+            heartbeats,
+            last_latency_us,
+            max_latency_us,
+        })
+    }
+}
+
+/// Fault flags of the axes as reported to the host.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FaultStatus {
+    pub faults: [FaultFlags; AXIS_COUNT],
+    pub latched_faults: [FaultFlags; AXIS_COUNT],
+}
+
+impl FaultStatus {
+    pub fn from_snapshot(snapshot: &PedalSnapshot) -> Self {
+        Self {
+            faults: snapshot.axes.map(|axis| axis.faults),
+            latched_faults: snapshot.axes.map(|axis| axis.latched_faults),
+        }
+    }
+
+    pub fn encode(&self, buffer: &mut [u8]) -> Result<usize, ReportError> {
+        let length = 1 + FAULT_STATUS_PAYLOAD_SIZE;
+        if buffer.len() < length {
+            return Err(ReportError::BufferTooSmall);
+        }
+        buffer[0] = FAULT_STATUS_REPORT_ID;
+        for (i, flags) in self.faults.iter().chain(&self.latched_faults).enumerate() {
+            buffer[1 + i] = flags.0;
+        }
+        Ok(length)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ReportError> {
+        let (&id, payload) = bytes.split_first().ok_or(ReportError::InvalidLength)?;
+        if id != FAULT_STATUS_REPORT_ID {
+            return Err(ReportError::UnknownReport(id));
+        }
+        if payload.len() < FAULT_STATUS_PAYLOAD_SIZE {
+            return Err(ReportError::InvalidLength);
+        }
+        Ok(Self {
+            faults: core::array::from_fn(|i| FaultFlags(payload[i])),
+            latched_faults: core::array::from_fn(|i| FaultFlags(payload[AXIS_COUNT + i])),
+        })
+    }
+}
+
+impl ResetCause {
+    pub fn encode(&self, buffer: &mut [u8]) -> Result<usize, ReportError> {
+        let length = 1 + RESET_CAUSE_PAYLOAD_SIZE;
+        if buffer.len() < length {
+            return Err(ReportError::BufferTooSmall);
+        }
+        buffer[0] = RESET_CAUSE_REPORT_ID;
+        buffer[1] = self.reason as u8;
+        buffer[2] = self.culprit.map_or(0, |task| task as u8);
+        Ok(length)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ReportError> {
+        let (&id, payload) = bytes.split_first().ok_or(ReportError::InvalidLength)?;
+        if id != RESET_CAUSE_REPORT_ID {
+            return Err(ReportError::UnknownReport(id));
+        }
+        if payload.len() < RESET_CAUSE_PAYLOAD_SIZE {
+            return Err(ReportError::InvalidLength);
+        }
+        let culprit = match payload[1] {
+            0 => None,
+            task => Some(SupervisedTask::try_from(task).map_err(|_| ReportError::InvalidValue)?),
+        };
+        Ok(Self {
+            reason: ResetReason::from(payload[0]),
+            culprit,
+        })
+    }
+}
+
+fn write_deadzone_width(writer: &mut Writer, width: DeadzoneWidth) {
+    let (unit, value) = match width {
+        DeadzoneWidth::Raw(width) => (0, width),
+        DeadzoneWidth::Percent(percent) => (1, percent as u32),
+    };
+    writer.bytes(&[unit]);
+    writer.bytes(&value.to_le_bytes());
+}
+
+fn read_deadzone_width(reader: &mut Reader) -> Result<DeadzoneWidth, ReportError> {
+    let [unit] = reader.array();
+    let value = u32::from_le_bytes(reader.array());
+    match unit {
+        0 => Ok(DeadzoneWidth::Raw(value)),
+        1 => u8::try_from(value)
+            .map(DeadzoneWidth::Percent)
+            .map_err(|_| ReportError::InvalidValue),
+        _ => Err(ReportError::InvalidValue),
+    }
+}
+
+struct Writer<'a> {
+    buffer: &'a mut [u8],
+    position: usize,
+}
+
+impl Writer<'_> {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.buffer[self.position..self.position + bytes.len()].copy_from_slice(bytes);
+        self.position += bytes.len();
+    }
+}
+
+/// Reads fields of a payload whose length was checked up front.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn array<const N: usize>(&mut self) -> [u8; N] {
+        let mut array = [0u8; N];
+        array.copy_from_slice(&self.bytes[self.position..self.position + N]);
+        self.position += N;
+        array
+    }
+}
+
+#[cfg(test)]
+mod feature_report_testing {
+    use crate::axis::Axis;
+    use crate::calibration::{AxisCalibration, CalibrationSession, CalibrationState, CommitTarget};
+    use crate::curve::{CurvePoint, CurvePoints, ResponseCurve};
+    use crate::deadzone::{Deadzone, DeadzoneWidth};
+    use crate::fault::FaultFlags;
+    use crate::feature_report::{
+        FaultStatus, FeatureKind, FeatureReport, FeatureReportId, ReportError, ReportStatus,
+        SessionCommand, SessionStatus, MAX_FEATURE_REPORT_SIZE,
+    };
+    use crate::filters::{Filter, FilterChain, OneEuroConfig};
+    use crate::report_mode::{ReportMode, ReportReason, ReportStatistics};
+    use crate::settings::AxisSettings;
+    use crate::supervisor::{ResetCause, ResetReason, SupervisedTask};
+    use rstest::rstest;
+
+    fn spline() -> ResponseCurve {
+        ResponseCurve::CubicSpline(
+            CurvePoints::new(&[
+                CurvePoint::new(0, 0),
+                CurvePoint::new(20_000, 10_000),
+                CurvePoint::new(u16::MAX, u16::MAX),
+            ])
+            .unwrap(),
+        )
+    }
+
+    #[rstest]
+    #[case(0x10, FeatureKind::Calibration, Axis::X)]
+    #[case(0x12, FeatureKind::Calibration, Axis::Z)]
+    #[case(0x21, FeatureKind::Curve, Axis::Y)]
+    #[case(0x30, FeatureKind::Filter, Axis::X)]
+    fn when_report_id_is_parsed(#[case] value: u8, #[case] kind: FeatureKind, #[case] axis: Axis) {
+        // When
+        let result = FeatureReportId::try_from(value);
+
+        // Then
+        assert_eq!(result, Ok(FeatureReportId::new(kind, axis)));
+        assert_eq!(result.unwrap().value(), value);
+    }
+
+    #[rstest]
+    #[case(0x01)]
+    #[case(0x13)]
+    #[case(0x2F)]
+    #[case(0x40)]
+    fn when_report_id_is_unknown(#[case] value: u8) {
+        // When
+        let result = FeatureReportId::try_from(value);
+
+        // Then
+        assert_eq!(result, Err(ReportError::UnknownReport(value)));
+    }
+
+    #[rstest]
+    #[case(FeatureReport::Calibration {
+        axis: Axis::Y,
+        calibration: AxisCalibration::new(-1_000, 230_000),
+        deadzone: Deadzone {
+            lower: DeadzoneWidth::Percent(2),
+            upper: DeadzoneWidth::Raw(3_000),
+            saturation: 5,
+        },
+    })]
+    #[case(FeatureReport::Curve { axis: Axis::X, curve: ResponseCurve::Linear })]
+    #[case(FeatureReport::Curve { axis: Axis::X, curve: ResponseCurve::Progressive(-40) })]
+    #[case(FeatureReport::Curve { axis: Axis::Z, curve: ResponseCurve::SCurve(100) })]
+    #[case(FeatureReport::Curve { axis: Axis::Z, curve: spline() })]
+    #[case(FeatureReport::Filter { axis: Axis::X, filter: FilterChain::NONE })]
+    #[case(FeatureReport::Filter {
+        axis: Axis::Y,
+        filter: FilterChain::new(&[
+            Filter::Median { window: 5 },
+            Filter::Ema { alpha: 1_234 },
+            Filter::OneEuro(OneEuroConfig {
+                min_cutoff_mhz: 1_000,
+                beta_uhz: 70_000,
+                derivative_cutoff_mhz: 1_000_000,
+                sample_rate_hz: 200,
+            }),
+        ])
+        .unwrap(),
+    })]
+    fn when_report_is_encoded_then_it_decodes_to_the_same_report(#[case] report: FeatureReport) {
+        // Given
+        let mut buffer = [0xAA; MAX_FEATURE_REPORT_SIZE];
+
+        // When
+        let length = report.encode(&mut buffer).unwrap();
+        let result = FeatureReport::decode(&buffer[..length]);
+
+        // Then
+        assert_eq!(length, 1 + report.id().kind.payload_size());
+        assert_eq!(buffer[0], report.id().value());
+        assert_eq!(result, Ok(report));
+    }
+
+    #[test]
+    fn when_calibration_report_is_encoded_then_the_layout_is_little_endian() {
+        // Given
+        let report = FeatureReport::Calibration {
+            axis: Axis::X,
+            calibration: AxisCalibration::new(1820, 3100),
+            deadzone: Deadzone {
+                lower: DeadzoneWidth::Percent(2),
+                upper: DeadzoneWidth::Raw(0x0102_0304),
+                saturation: 7,
+            },
+        };
+        let mut buffer = [0; MAX_FEATURE_REPORT_SIZE];
+
+        // When
+        let length = report.encode(&mut buffer).unwrap();
+
+        // Then
+        assert_eq!(
+            buffer[..length],
+            [0x10, 0x1C, 0x07, 0, 0, 0x1C, 0x0C, 0, 0, 1, 2, 0, 0, 0, 0, 4, 3, 2, 1, 7]
+        );
+    }
+
+    #[rstest]
+    #[case(&[], ReportError::InvalidLength)]
+    #[case(&[0x10, 0, 0], ReportError::InvalidLength)]
+    #[case(&[0x05], ReportError::UnknownReport(0x05))]
+    fn when_report_is_malformed(#[case] bytes: &[u8], #[case] expected: ReportError) {
+        // When
+        let result = FeatureReport::decode(bytes);
+
+        // Then
+        assert_eq!(result, Err(expected));
+    }
+
+    #[rstest]
+    #[case(FeatureKind::Calibration, 0, 0xFF)]
+    #[case(FeatureKind::Calibration, 8, 2)]
+    #[case(FeatureKind::Curve, 0, 5)]
+    #[case(FeatureKind::Curve, 2, 12)]
+    #[case(FeatureKind::Filter, 0, 4)]
+    #[case(FeatureKind::Filter, 1, 0)]
+    fn when_report_holds_an_invalid_value(
+        #[case] kind: FeatureKind,
+        #[case] offset: usize,
+        #[case] value: u8,
+    ) {
+        // Given
+        let settings = AxisSettings {
+            calibration: AxisCalibration::new(0, 100),
+            curve: spline(),
+            filter: FilterChain::new(&[Filter::Ema { alpha: 100 }]).unwrap(),
+            ..AxisSettings::DEFAULT
+        };
+        let report = FeatureReport::from_settings(FeatureReportId::new(kind, Axis::X), &settings);
+        let mut buffer = [0; MAX_FEATURE_REPORT_SIZE];
+        let length = report.encode(&mut buffer).unwrap();
+        buffer[1 + offset] = value;
+
+        // When
+        let result = FeatureReport::decode(&buffer[..length]);
+
+        // Then
+        assert_eq!(result, Err(ReportError::InvalidValue));
+    }
+
+    #[test]
+    fn when_buffer_is_too_small_then_encoding_fails() {
+        // Given
+        let report = FeatureReport::Curve {
+            axis: Axis::X,
+            curve: ResponseCurve::Linear,
+        };
+        let mut buffer = [0; 8];
+
+        // When
+        let result = report.encode(&mut buffer);
+
+        // Then
+        assert_eq!(result, Err(ReportError::BufferTooSmall));
+    }
+
+    #[test]
+    fn when_report_is_applied_then_only_its_part_of_the_settings_changes() {
+        // Given
+        let mut settings = AxisSettings::DEFAULT;
+        let report = FeatureReport::Curve {
+            axis: Axis::Y,
+            curve: ResponseCurve::SCurve(30),
+        };
+
+        // When
+        report.apply(&mut settings);
+
+        // Then
+        assert_eq!(
+            settings,
+            AxisSettings {
+                curve: ResponseCurve::SCurve(30),
+                ..AxisSettings::DEFAULT
+            }
+        );
+    }
+
+    #[rstest]
+    #[case(SessionCommand::Cancel)]
+    #[case(SessionCommand::Start)]
+    #[case(SessionCommand::Commit(CommitTarget::Ram))]
+    #[case(SessionCommand::Commit(CommitTarget::Persistent))]
+    fn when_session_command_is_encoded_then_it_decodes_to_the_same_command(
+        #[case] command: SessionCommand,
+    ) {
+        // Given
+        let mut buffer = [0; MAX_FEATURE_REPORT_SIZE];
+
+        // When
+        let length = command.encode(&mut buffer).unwrap();
+        let result = SessionCommand::decode(&buffer[..length]);
+
+        // Then
+        assert_eq!(buffer[0], 0x40);
+        assert_eq!(result, Ok(command));
+    }
+
+    #[rstest]
+    #[case(&[0x40], ReportError::InvalidLength)]
+    #[case(&[0x40, 4], ReportError::InvalidValue)]
+    #[case(&[0x10, 1], ReportError::UnknownReport(0x10))]
+    fn when_session_command_is_malformed(#[case] bytes: &[u8], #[case] expected: ReportError) {
+        // When
+        let result = SessionCommand::decode(bytes);
+
+        // Then
+        assert_eq!(result, Err(expected));
+    }
+
+    #[test]
+    fn when_session_commands_are_executed_then_the_session_follows() {
+        // Given
+        let session = CalibrationSession::new();
+
+        // When
+        let commit_while_idle = SessionCommand::Commit(CommitTarget::Ram).execute(&session);
+        SessionCommand::Start.execute(&session);
+        session.observe(Axis::X, 1_830);
+        session.observe(Axis::X, 3_090);
+        let commit = SessionCommand::Commit(CommitTarget::Ram).execute(&session);
+
+        // Then
+        assert!(!commit_while_idle);
+        assert!(commit);
+        assert_eq!(
+            SessionStatus::from_session(&session),
+            SessionStatus {
+                state: CalibrationState::Committed,
+                captured: [Some(AxisCalibration::new(1_830, 3_090)), None, None],
+            }
+        );
+    }
+
+    #[test]
+    fn when_session_status_is_encoded_then_it_decodes_to_the_same_status() {
+        // Given
+        let status = SessionStatus {
+            state: CalibrationState::Capturing,
+            captured: [
+                Some(AxisCalibration::new(1_830, 3_090)),
+                None,
+                Some(AxisCalibration::new(-5, 65_000)),
+            ],
+        };
+        let mut buffer = [0; MAX_FEATURE_REPORT_SIZE];
+
+        // When
+        let length = status.encode(&mut buffer).unwrap();
+        let result = SessionStatus::decode(&buffer[..length]);
+
+        // Then
+        assert_eq!(length, 29);
+        assert_eq!(result, Ok(status));
+    }
+
+    #[rstest]
+    #[case(ReportMode::Periodic { interval_ms: 10 })]
+    #[case(ReportMode::OnChange { threshold: 16, heartbeat_ms: 100 })]
+    fn when_report_mode_is_encoded_then_it_decodes_to_the_same_mode(#[case] mode: ReportMode) {
+        // Given
+        let mut buffer = [0; MAX_FEATURE_REPORT_SIZE];
+
+        // When
+        let length = mode.encode(&mut buffer).unwrap();
+        let result = ReportMode::decode(&buffer[..length]);
+
+        // Then
+        assert_eq!(length, 6);
+        assert_eq!(result, Ok(mode));
+    }
+
+    #[rstest]
+    #[case(&[0x50, 1, 16, 0], ReportError::InvalidLength)]
+    #[case(&[0x50, 2, 16, 0, 100, 0], ReportError::InvalidValue)]
+    #[case(&[0x50, 0, 0, 0, 0, 0], ReportError::InvalidValue)]
+    #[case(&[0x51, 0, 0, 0, 10, 0], ReportError::UnknownReport(0x51))]
+    fn when_report_mode_is_malformed(#[case] bytes: &[u8], #[case] expected: ReportError) {
+        // When
+        let result = ReportMode::decode(bytes);
+
+        // Then
+        assert_eq!(result, Err(expected));
+    }
+
+    #[test]
+    fn when_report_status_is_encoded_then_it_decodes_to_the_same_status() {
+        // Given
+        let statistics = ReportStatistics::new();
+        statistics.record(ReportReason::Change, 1_250);
+        statistics.record(ReportReason::Timeout, 0);
+        statistics.record(ReportReason::Change, 830);
+        let status = ReportStatus::from_statistics(&statistics);
+        let mut buffer = [0; MAX_FEATURE_REPORT_SIZE];
+
+        // When
+        let length = status.encode(&mut buffer).unwrap();
+        let result = ReportStatus::decode(&buffer[..length]);
+
+        // Then
+        assert_eq!(length, 17);
+        assert_eq!(
+            result,
+            Ok(ReportStatus {
+                reports: 3,
+                heartbeats: 1,
+                last_latency_us: 830,
+                max_latency_us: 1_250,
+            })
+        );
+    }
+
+    #[test]
+    fn when_fault_status_is_encoded_then_it_decodes_to_the_same_status() {
+        // Given
+        let status = FaultStatus {
+            faults: [FaultFlags::RAIL_HIGH, FaultFlags::NONE, FaultFlags::NONE],
+            latched_faults: [
+                FaultFlags::RAIL_HIGH | FaultFlags::STUCK,
+                FaultFlags::NOT_READY,
+                FaultFlags::NONE,
+            ],
+        };
+        let mut buffer = [0; MAX_FEATURE_REPORT_SIZE];
+
+        // When
+        let length = status.encode(&mut buffer).unwrap();
+        let result = FaultStatus::decode(&buffer[..length]);
+
+        // Then
+        assert_eq!(
+            &buffer[..length],
+            &[0x60, 0x02, 0x00, 0x00, 0x06, 0x08, 0x00]
+        );
+        assert_eq!(result, Ok(status));
+    }
+
+    #[test]
+    fn when_reset_cause_is_encoded_then_it_decodes_to_the_same_cause() {
+        // Given
+        let cause = ResetCause {
+            reason: ResetReason::IndependentWatchdog,
+            culprit: Some(SupervisedTask::LoadCell),
+        };
+        let mut buffer = [0; MAX_FEATURE_REPORT_SIZE];
+
+        // When
+        let length = cause.encode(&mut buffer).unwrap();
+        let result = ResetCause::decode(&buffer[..length]);
+
+        // Then
+        assert_eq!(&buffer[..length], &[0x61, 0x05, 0x03]);
+        assert_eq!(result, Ok(cause));
+    }
+
+    #[test]
+    fn when_reset_cause_names_an_unknown_task_then_it_is_rejected() {
+        // When
+        let result = ResetCause::decode(&[0x61, 0x05, 0x42]);
+
+        // Then
+        assert_eq!(result, Err(ReportError::InvalidValue));
+    }
+}
@@ -0,0 +1,91 @@
+use crate::filters::{from_fixed, to_fixed};
+
+/// Exponential moving average: `y[n] = y[n-1] + alpha * (x[n] - y[n-1])`.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub struct ExponentialMovingAverage {
+    alpha: u16,
+    state: Option<i64>,
+}
+
+impl ExponentialMovingAverage {
+    /// `alpha` is the weight of the new sample, where `u16::MAX` stands for `1.0`.
+    pub fn new(alpha: u16) -> Self {
+        Self { alpha, state: None }
+    }
+
+    pub fn apply(&mut self, value: i64) -> i64 {
+        let value = to_fixed(value);
+        let state = match self.state {
+            Some(state) => state + (value - state) * self.alpha as i64 / u16::MAX as i64,
+            None => value,
+        };
+        self.state = Some(state);
+        from_fixed(state)
+    }
+
+    pub fn reset(&mut self) {
+        self.state = None;
+    }
+}
+
+#[cfg(test)]
+mod ema_testing {
+    use crate::filters::ema::ExponentialMovingAverage;
+    use rstest::rstest;
+
+    #[test]
+    fn when_first_sample_arrives_then_it_is_passed_through() {
+        // Given
+        let mut filter = ExponentialMovingAverage::new(1000);
+
+        // When
+        let result = filter.apply(1234);
+
+        // Then
+        assert_eq!(result, 1234);
+    }
+
+    #[rstest]
+    #[case(u16::MAX, 1000, 1000)]
+    #[case(u16::MAX / 2, 1000, 500)]
+    #[case(u16::MAX / 4, -1000, -250)]
+    #[case(0, 1000, 0)]
+    fn when_step_is_applied(#[case] alpha: u16, #[case] step: i64, #[case] expected: i64) {
+        // Given
+        let mut filter = ExponentialMovingAverage::new(alpha);
+        filter.apply(0);
+
+        // When
+        let result = filter.apply(step);
+
+        // Then
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn when_input_is_constant_then_output_converges_to_it() {
+        // Given
+        let mut filter = ExponentialMovingAverage::new(u16::MAX / 16);
+        filter.apply(0);
+
+        // When
+        let result = (0..200).map(|_| filter.apply(4095)).last().unwrap();
+
+        // Then
+        assert_eq!(result, 4095);
+    }
+
+    #[test]
+    fn when_reset_then_next_sample_is_passed_through() {
+        // Given
+        let mut filter = ExponentialMovingAverage::new(100);
+        filter.apply(0);
+
+        // When
+        filter.reset();
+        let result = filter.apply(500);
+
+        // Then
+        assert_eq!(result, 500);
+    }
+}
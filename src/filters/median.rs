@@ -0,0 +1,93 @@
+/// Largest supported median window.
+pub const MAX_MEDIAN_WINDOW: usize = 9;
+
+/// Median of the last `window` samples.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub struct Median {
+    window: usize,
+    samples: [i64; MAX_MEDIAN_WINDOW],
+    len: usize,
+    next: usize,
+}
+
+impl Median {
+    /// The window is clamped to `1..=MAX_MEDIAN_WINDOW`.
+    pub fn new(window: u8) -> Self {
+        Self {
+            window: (window as usize).clamp(1, MAX_MEDIAN_WINDOW),
+            samples: [0; MAX_MEDIAN_WINDOW],
+            len: 0,
+            next: 0,
+        }
+    }
+
+    pub fn apply(&mut self, value: i64) -> i64 {
+        self.samples[self.next] = value;
+        self.next = (self.next + 1) % self.window;
+        self.len = (self.len + 1).min(self.window);
+
+        let mut sorted = self.samples;
+        let sorted = &mut sorted[..self.len];
+        sorted.sort_unstable();
+        sorted[self.len / 2]
+    }
+
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.next = 0;
+    }
+}
+
+#[cfg(test)]
+mod median_testing {
+    use crate::filters::median::{Median, MAX_MEDIAN_WINDOW};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(3, &[10, 10, 900, 10, 10], 10)]
+    #[case(3, &[10, 900, 900, 10], 900)]
+    #[case(5, &[10, 900, 900, 10, 10], 10)]
+    #[case(5, &[1, 2, 3, 4, 5, 6, 7], 5)]
+    #[case(1, &[1, 2, 3, 4, 5, 6, 7], 7)]
+    fn when_sequence_is_filtered(
+        #[case] window: u8,
+        #[case] sequence: &[i64],
+        #[case] expected: i64,
+    ) {
+        // Given
+        let mut filter = Median::new(window);
+
+        // When
+        let result = sequence.iter().map(|&v| filter.apply(v)).last().unwrap();
+
+        // Then
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn when_window_is_not_full_yet_then_median_of_available_samples_is_used() {
+        // Given
+        let mut filter = Median::new(5);
+
+        // When
+        let first = filter.apply(100);
+        let second = filter.apply(-100);
+        let third = filter.apply(50);
+
+        // Then
+        assert_eq!(first, 100);
+        assert_eq!(second, 100);
+        assert_eq!(third, 50);
+    }
+
+    #[rstest]
+    #[case(0, 1)]
+    #[case(200, MAX_MEDIAN_WINDOW)]
+    fn when_window_is_out_of_range_then_it_is_clamped(#[case] window: u8, #[case] expected: usize) {
+        // When
+        let result = Median::new(window);
+
+        // Then
+        assert_eq!(result.window, expected);
+    }
+}
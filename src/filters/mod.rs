@@ -0,0 +1,250 @@
+//! Digital filters for the raw sensor readings.
+//!
+//! A [`FilterChain`] describes up to [`MAX_FILTER_STAGES`] filters that are applied one
+//! after the other, and a [`FilterPipeline`] holds the state needed to run it. All state
+//! lives in fixed size arrays, nothing is allocated.
+
+mod ema;
+mod median;
+mod one_euro;
+#[cfg(test)]
+pub(crate) mod test_signals;
+
+pub use ema::ExponentialMovingAverage;
+pub use median::{Median, MAX_MEDIAN_WINDOW};
+pub use one_euro::{OneEuro, OneEuroConfig};
+
+/// Maximum number of filters in a chain.
+pub const MAX_FILTER_STAGES: usize = 3;
+
+/// Fractional bits kept by the averaging filters, so slow changes aren't lost to rounding.
+const FRACTION_BITS: u32 = 8;
+
+fn to_fixed(value: i64) -> i64 {
+    value << FRACTION_BITS
+}
+
+fn from_fixed(value: i64) -> i64 {
+    (value + (1 << (FRACTION_BITS - 1))) >> FRACTION_BITS
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FilterError {
+    TooManyStages,
+    InvalidParameter,
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Filter {
+    /// Exponential moving average, `alpha` is the weight of the new sample where
+    /// `u16::MAX` stands for `1.0`.
+    Ema { alpha: u16 },
+    /// Median of the last `window` samples, up to [`MAX_MEDIAN_WINDOW`].
+    Median { window: u8 },
+    /// Speed adaptive low pass filter.
+    OneEuro(OneEuroConfig),
+}
+
+impl Filter {
+    fn validate(&self) -> Result<(), FilterError> {
+        let valid = match self {
+            Filter::Ema { alpha } => *alpha > 0,
+            Filter::Median { window } => (1..=MAX_MEDIAN_WINDOW).contains(&(*window as usize)),
+            Filter::OneEuro(config) => config.sample_rate_hz > 0,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(FilterError::InvalidParameter)
+        }
+    }
+}
+
+/// Filters applied to the raw readings, in order.
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FilterChain {
+    filters: [Option<Filter>; MAX_FILTER_STAGES],
+}
+
+impl FilterChain {
+    pub const NONE: FilterChain = FilterChain {
+        filters: [None; MAX_FILTER_STAGES],
+    };
+
+    pub fn new(filters: &[Filter]) -> Result<Self, FilterError> {
+        if filters.len() > MAX_FILTER_STAGES {
+            return Err(FilterError::TooManyStages);
+        }
+        let mut chain = Self::NONE;
+        for (slot, filter) in chain.filters.iter_mut().zip(filters) {
+            filter.validate()?;
+            *slot = Some(*filter);
+        }
+        Ok(chain)
+    }
+
+    pub fn filters(&self) -> impl Iterator<Item = &Filter> {
+        self.filters.iter().flatten()
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+enum FilterStage {
+    Ema(ExponentialMovingAverage),
+    Median(Median),
+    OneEuro(OneEuro),
+}
+
+impl FilterStage {
+    fn new(filter: &Filter) -> Self {
+        match filter {
+            Filter::Ema { alpha } => FilterStage::Ema(ExponentialMovingAverage::new(*alpha)),
+            Filter::Median { window } => FilterStage::Median(Median::new(*window)),
+            Filter::OneEuro(config) => FilterStage::OneEuro(OneEuro::new(*config)),
+        }
+    }
+
+    fn apply(&mut self, value: i64) -> i64 {
+        match self {
+            FilterStage::Ema(filter) => filter.apply(value),
+            FilterStage::Median(filter) => filter.apply(value),
+            FilterStage::OneEuro(filter) => filter.apply(value),
+        }
+    }
+
+    fn reset(&mut self) {
+        match self {
+            FilterStage::Ema(filter) => filter.reset(),
+            FilterStage::Median(filter) => filter.reset(),
+            FilterStage::OneEuro(filter) => filter.reset(),
+        }
+    }
+}
+
+/// Runs the filters of a [`FilterChain`] and keeps their state between samples.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub struct FilterPipeline {
+    chain: FilterChain,
+    stages: [Option<FilterStage>; MAX_FILTER_STAGES],
+}
+
+impl FilterPipeline {
+    pub fn new(chain: FilterChain) -> Self {
+        Self {
+            chain,
+            stages: chain
+                .filters
+                .map(|filter| filter.as_ref().map(FilterStage::new)),
+        }
+    }
+
+    pub fn chain(&self) -> FilterChain {
+        self.chain
+    }
+
+    pub fn apply(&mut self, value: i64) -> i64 {
+        self.stages
+            .iter_mut()
+            .flatten()
+            .fold(value, |value, stage| stage.apply(value))
+    }
+
+    pub fn reset(&mut self) {
+        self.stages
+            .iter_mut()
+            .flatten()
+            .for_each(FilterStage::reset);
+    }
+}
+
+impl From<FilterChain> for FilterPipeline {
+    fn from(chain: FilterChain) -> Self {
+        Self::new(chain)
+    }
+}
+
+#[cfg(test)]
+mod filters_testing {
+    use crate::filters::{Filter, FilterChain, FilterError, FilterPipeline, OneEuroConfig};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(&[], Ok(0))]
+    #[case(&[Filter::Median { window: 3 }], Ok(1))]
+    #[case(&[Filter::Median { window: 3 }, Filter::Ema { alpha: 100 }, Filter::Ema { alpha: 1 }], Ok(3))]
+    #[case(&[Filter::Ema { alpha: 1 }; 4], Err(FilterError::TooManyStages))]
+    #[case(&[Filter::Ema { alpha: 0 }], Err(FilterError::InvalidParameter))]
+    #[case(&[Filter::Median { window: 0 }], Err(FilterError::InvalidParameter))]
+    #[case(&[Filter::Median { window: 10 }], Err(FilterError::InvalidParameter))]
+    #[case(
+        &[Filter::OneEuro(OneEuroConfig {
+            min_cutoff_mhz: 1000,
+            beta_uhz: 0,
+            derivative_cutoff_mhz: 1000,
+            sample_rate_hz: 0,
+        })],
+        Err(FilterError::InvalidParameter)
+    )]
+    fn when_creating_a_chain(
+        #[case] filters: &[Filter],
+        #[case] expected: Result<usize, FilterError>,
+    ) {
+        // When
+        let result = FilterChain::new(filters);
+
+        // Then
+        assert_eq!(result.map(|chain| chain.filters().count()), expected);
+    }
+
+    #[test]
+    fn when_chain_is_empty_then_values_are_passed_through() {
+        // Given
+        let mut pipeline = FilterPipeline::new(FilterChain::NONE);
+
+        // When
+        let result = pipeline.apply(-12345);
+
+        // Then
+        assert_eq!(result, -12345);
+    }
+
+    #[test]
+    fn when_stages_are_chained_then_they_are_applied_in_order() {
+        // Given
+        let chain = FilterChain::new(&[
+            Filter::Median { window: 3 },
+            Filter::Ema {
+                alpha: u16::MAX / 2,
+            },
+        ])
+        .unwrap();
+        let mut pipeline = FilterPipeline::new(chain);
+
+        // When
+        let results: alloc::vec::Vec<i64> = [0, 0, 1000, 0, 0]
+            .iter()
+            .map(|&v| pipeline.apply(v))
+            .collect();
+
+        // Then
+        assert_eq!(results, [0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn when_reset_then_state_is_dropped() {
+        // Given
+        let chain = FilterChain::new(&[Filter::Ema { alpha: 1 }]).unwrap();
+        let mut pipeline = FilterPipeline::new(chain);
+        pipeline.apply(0);
+
+        // When
+        pipeline.reset();
+        let result = pipeline.apply(1000);
+
+        // Then
+        assert_eq!(result, 1000);
+    }
+}
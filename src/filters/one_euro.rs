@@ -0,0 +1,144 @@
+use crate::filters::{from_fixed, to_fixed};
+
+/// 2 * PI approximated as 710 / 113.
+const TWO_PI_NUMERATOR: u128 = 710;
+const TWO_PI_DENOMINATOR: u128 = 113;
+const ALPHA_ONE: u128 = 1 << 16;
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct OneEuroConfig {
+    /// Cutoff frequency used while the pedal is at rest, in mHz.
+    pub min_cutoff_mhz: u32,
+    /// Increase of the cutoff frequency in uHz per raw unit/s of pedal speed.
+    pub beta_uhz: u32,
+    /// Cutoff frequency of the speed estimation, in mHz.
+    pub derivative_cutoff_mhz: u32,
+    /// Rate at which the samples arrive.
+    pub sample_rate_hz: u16,
+}
+
+/// Adaptive low pass filter from Casiez et al., "1 Euro Filter: A Simple Speed-based
+/// Low-pass Filter for Noisy Input in Interactive Systems". Slow movements are smoothed
+/// heavily to remove jitter, fast movements raise the cutoff to keep the lag low.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub struct OneEuro {
+    config: OneEuroConfig,
+    value: Option<i64>,
+    speed: i64,
+}
+
+impl OneEuro {
+    pub fn new(config: OneEuroConfig) -> Self {
+        Self {
+            config,
+            value: None,
+            speed: 0,
+        }
+    }
+
+    pub fn apply(&mut self, value: i64) -> i64 {
+        let value = to_fixed(value);
+        let Some(previous) = self.value else {
+            self.value = Some(value);
+            return from_fixed(value);
+        };
+
+        let rate = self.config.sample_rate_hz.max(1) as i64;
+        let raw_speed = (value - previous) * rate;
+        self.speed = low_pass(
+            self.speed,
+            raw_speed,
+            self.alpha(self.config.derivative_cutoff_mhz as u128),
+        );
+
+        let speed = from_fixed(self.speed).unsigned_abs() as u128;
+        let cutoff =
+            self.config.min_cutoff_mhz as u128 + self.config.beta_uhz as u128 * speed / 1000;
+        let filtered = low_pass(previous, value, self.alpha(cutoff));
+        self.value = Some(filtered);
+        from_fixed(filtered)
+    }
+
+    pub fn reset(&mut self) {
+        self.value = None;
+        self.speed = 0;
+    }
+
+    /// Smoothing factor `1 / (1 + tau / Te)` in Q16, with `tau = 1 / (2 * PI * cutoff)`.
+    fn alpha(&self, cutoff_mhz: u128) -> u32 {
+        let rate = self.config.sample_rate_hz.max(1) as u128;
+        let scaled_cutoff = TWO_PI_NUMERATOR * cutoff_mhz;
+        let alpha = ALPHA_ONE * scaled_cutoff / (scaled_cutoff + TWO_PI_DENOMINATOR * 1000 * rate);
+        alpha as u32
+    }
+}
+
+fn low_pass(previous: i64, value: i64, alpha: u32) -> i64 {
+    previous + (value - previous) * alpha as i64 / ALPHA_ONE as i64
+}
+
+#[cfg(test)]
+mod one_euro_testing {
+    use crate::filters::one_euro::{OneEuro, OneEuroConfig};
+    use rstest::rstest;
+
+    const CONFIG: OneEuroConfig = OneEuroConfig {
+        min_cutoff_mhz: 1_000,
+        beta_uhz: 50_000,
+        derivative_cutoff_mhz: 1_000,
+        sample_rate_hz: 200,
+    };
+
+    #[rstest]
+    #[case(0, 0)]
+    #[case(1_000, 1_996)]
+    #[case(10_000, 15_666)]
+    #[case(u32::MAX as u128, 65_535)]
+    fn when_calculating_the_smoothing_factor(#[case] cutoff_mhz: u128, #[case] expected: u32) {
+        // Given
+        let filter = OneEuro::new(CONFIG);
+
+        // When
+        let result = filter.alpha(cutoff_mhz);
+
+        // Then
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn when_pedal_is_at_rest_then_jitter_is_suppressed() {
+        // Given
+        let mut filter = OneEuro::new(CONFIG);
+        filter.apply(2000);
+
+        // When
+        let results: alloc::vec::Vec<i64> = (0..400)
+            .map(|i| filter.apply(if i % 2 == 0 { 2010 } else { 1990 }))
+            .collect();
+
+        // Then
+        assert!(results.iter().all(|&v| (1995..=2005).contains(&v)));
+    }
+
+    #[test]
+    fn when_pedal_moves_fast_then_output_follows_with_little_lag() {
+        // Given
+        let mut slow = OneEuro::new(OneEuroConfig {
+            beta_uhz: 0,
+            ..CONFIG
+        });
+        let mut fast = OneEuro::new(CONFIG);
+        slow.apply(0);
+        fast.apply(0);
+
+        // When
+        let slow_result = (1..=20).map(|i| slow.apply(i * 200)).last().unwrap();
+        let fast_result = (1..=20).map(|i| fast.apply(i * 200)).last().unwrap();
+
+        // Then
+        assert!(slow_result < 1500);
+        assert!(fast_result > 3500);
+        assert!(fast_result <= 4000);
+    }
+}
@@ -0,0 +1,26 @@
+//! Deterministic noisy signals for the tests of the filters and the monitors.
+
+use alloc::vec::Vec;
+
+/// Noise in `-amplitude..=amplitude` from a linear congruential generator, the same on
+/// every run.
+pub(crate) fn noise(length: usize, amplitude: i32) -> Vec<i32> {
+    let mut state: u32 = 0x1234_5678;
+    (0..length)
+        .map(|_| {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (state >> 8) as i32 % (2 * amplitude + 1) - amplitude
+        })
+        .collect()
+}
+
+/// [`noise`] with `spike` in place of every 17th sample.
+pub(crate) fn noise_with_spikes(length: usize, amplitude: i32, spike: i32) -> Vec<i32> {
+    let mut values = noise(length, amplitude);
+    values
+        .iter_mut()
+        .skip(16)
+        .step_by(17)
+        .for_each(|value| *value = spike);
+    values
+}
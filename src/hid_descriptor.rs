@@ -0,0 +1,542 @@
+//! Typed builder of HID report descriptors, evaluated at compile time.
+//!
+//! Every item is checked as it is added and a mistake is a const panic, so it fails the
+//! build. The builder also sums up the length of every report, which lets a static
+//! assertion check a report struct against the descriptor, see the code `build.rs` generates
+//! for `input_report.rs`.
+
+/// Longest descriptor the builder holds.
+pub const MAX_DESCRIPTOR_SIZE: usize = 512;
+/// Most reports, counting input, output and feature reports apart, a descriptor declares.
+pub(crate) const MAX_REPORTS: usize = 32;
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReportKind {
+    Input,
+    Output,
+    Feature,
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct UsagePage(pub u16);
+
+impl UsagePage {
+    pub const GENERIC_DESKTOP: UsagePage = UsagePage(0x01);
+    pub const SIMULATION: UsagePage = UsagePage(0x02);
+    pub const GAME: UsagePage = UsagePage(0x05);
+    pub const GENERIC_DEVICE: UsagePage = UsagePage(0x06);
+    pub const BUTTON: UsagePage = UsagePage(0x09);
+    /// First of the vendor defined pages.
+    pub const VENDOR_DEFINED: UsagePage = UsagePage(0xFF00);
+}
+
+/// Usages of the generic desktop page.
+pub mod usage {
+    pub const POINTER: u32 = 0x01;
+    pub const MOUSE: u32 = 0x02;
+    pub const JOYSTICK: u32 = 0x04;
+    pub const GAMEPAD: u32 = 0x05;
+    pub const MULTI_AXIS_CONTROLLER: u32 = 0x08;
+    pub const X: u32 = 0x30;
+    pub const Y: u32 = 0x31;
+    pub const Z: u32 = 0x32;
+    pub const RX: u32 = 0x33;
+    pub const RY: u32 = 0x34;
+    pub const RZ: u32 = 0x35;
+    pub const SLIDER: u32 = 0x36;
+    pub const DIAL: u32 = 0x37;
+    pub const WHEEL: u32 = 0x38;
+}
+
+/// Flags of the input, output and feature items.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MainFlags(pub u32);
+
+impl MainFlags {
+    /// Data, array, absolute, the flags every other one is added to.
+    pub const DATA: MainFlags = MainFlags(0);
+
+    pub const fn constant(self) -> Self {
+        Self(self.0 | 1 << 0)
+    }
+
+    pub const fn variable(self) -> Self {
+        Self(self.0 | 1 << 1)
+    }
+
+    pub const fn relative(self) -> Self {
+        Self(self.0 | 1 << 2)
+    }
+
+    pub const fn wrap(self) -> Self {
+        Self(self.0 | 1 << 3)
+    }
+
+    pub const fn non_linear(self) -> Self {
+        Self(self.0 | 1 << 4)
+    }
+
+    pub const fn no_preferred(self) -> Self {
+        Self(self.0 | 1 << 5)
+    }
+
+    pub const fn null_state(self) -> Self {
+        Self(self.0 | 1 << 6)
+    }
+
+    /// Only for output and feature items.
+    pub const fn volatile(self) -> Self {
+        Self(self.0 | 1 << 7)
+    }
+
+    pub const fn buffered_bytes(self) -> Self {
+        Self(self.0 | 1 << 8)
+    }
+
+    pub const fn is_constant(self) -> bool {
+        self.0 & 1 != 0
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Collection {
+    Physical = 0,
+    Application = 1,
+    Logical = 2,
+    Report = 3,
+    NamedArray = 4,
+    UsageSwitch = 5,
+    UsageModifier = 6,
+}
+
+#[derive(Copy, Clone)]
+struct ReportBits {
+    kind: ReportKind,
+    id: u8,
+    bits: u32,
+}
+
+/// A report descriptor being built. Meant to live in a `const`, the descriptor then goes
+/// to the firmware through [`ReportDescriptor::to_array`].
+#[derive(Copy, Clone)]
+pub struct ReportDescriptor {
+    bytes: [u8; MAX_DESCRIPTOR_SIZE],
+    length: usize,
+    report_size: u32,
+    report_count: u32,
+    report_id: u8,
+    logical_minimum: i32,
+    logical_maximum: i32,
+    depth: usize,
+    reports: [ReportBits; MAX_REPORTS],
+    report_total: usize,
+}
+
+impl ReportDescriptor {
+    pub const fn new() -> Self {
+        Self {
+            bytes: [0; MAX_DESCRIPTOR_SIZE],
+            length: 0,
+            report_size: 0,
+            report_count: 0,
+            report_id: 0,
+            logical_minimum: 0,
+            logical_maximum: 0,
+            depth: 0,
+            reports: [ReportBits {
+                kind: ReportKind::Input,
+                id: 0,
+                bits: 0,
+            }; MAX_REPORTS],
+            report_total: 0,
+        }
+    }
+
+    pub const fn usage_page(self, page: UsagePage) -> Self {
+        self.unsigned(0x04, page.0 as u32)
+    }
+
+    /// A usage of the current page, or an extended usage with its page in the upper 16
+    /// bits.
+    pub const fn usage(self, usage: u32) -> Self {
+        self.unsigned(0x08, usage)
+    }
+
+    pub const fn usage_minimum(self, usage: u32) -> Self {
+        self.unsigned(0x18, usage)
+    }
+
+    pub const fn usage_maximum(self, usage: u32) -> Self {
+        self.unsigned(0x28, usage)
+    }
+
+    pub const fn logical_minimum(mut self, value: i32) -> Self {
+        self.logical_minimum = value;
+        self.signed(0x14, value)
+    }
+
+    pub const fn logical_maximum(mut self, value: i32) -> Self {
+        self.logical_maximum = value;
+        self.signed(0x24, value)
+    }
+
+    pub const fn physical_minimum(self, value: i32) -> Self {
+        self.signed(0x34, value)
+    }
+
+    pub const fn physical_maximum(self, value: i32) -> Self {
+        self.signed(0x44, value)
+    }
+
+    /// Size of every value of the next main items, in bits.
+    pub const fn report_size(mut self, bits: u32) -> Self {
+        assert!(bits >= 1 && bits <= 32, "Report sizes go from 1 to 32 bits");
+        self.report_size = bits;
+        self.unsigned(0x74, bits)
+    }
+
+    pub const fn report_id(mut self, id: u8) -> Self {
+        assert!(id != 0, "Report ID 0 is reserved");
+        assert!(
+            self.find_report(ReportKind::Input, 0).is_none()
+                && self.find_report(ReportKind::Output, 0).is_none()
+                && self.find_report(ReportKind::Feature, 0).is_none(),
+            "Report IDs have to come before the first main item"
+        );
+        self.report_id = id;
+        self.unsigned(0x84, id as u32)
+    }
+
+    pub const fn report_count(mut self, count: u32) -> Self {
+        assert!(count >= 1, "The report count must be at least 1");
+        self.report_count = count;
+        self.unsigned(0x94, count)
+    }
+
+    pub const fn input(self, flags: MainFlags) -> Self {
+        assert!(flags.0 & 1 << 7 == 0, "Input items can't be volatile");
+        self.main_data(ReportKind::Input, 0x80, flags)
+    }
+
+    pub const fn output(self, flags: MainFlags) -> Self {
+        self.main_data(ReportKind::Output, 0x90, flags)
+    }
+
+    pub const fn feature(self, flags: MainFlags) -> Self {
+        self.main_data(ReportKind::Feature, 0xB0, flags)
+    }
+
+    pub const fn collection(mut self, collection: Collection) -> Self {
+        self.depth += 1;
+        self.item(0xA0, collection as u32, 1)
+    }
+
+    pub const fn end_collection(mut self) -> Self {
+        assert!(self.depth > 0, "End collection without a collection");
+        self.depth -= 1;
+        self.item(0xC0, 0, 0)
+    }
+
+    /// Checks the descriptor is complete: every collection is closed and every report is
+    /// made of whole bytes.
+    pub const fn finish(self) -> Self {
+        assert!(self.depth == 0, "A collection isn't closed");
+        let mut index = 0;
+        while index < self.report_total {
+            assert!(
+                self.reports[index].bits.is_multiple_of(8),
+                "A report isn't made of whole bytes"
+            );
+            index += 1;
+        }
+        self
+    }
+
+    pub const fn len(&self) -> usize {
+        self.length
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// The descriptor, `N` being [`ReportDescriptor::len`].
+    pub const fn to_array<const N: usize>(&self) -> [u8; N] {
+        assert!(
+            N == self.length,
+            "The array must be as long as the descriptor"
+        );
+        let mut array = [0; N];
+        let mut index = 0;
+        while index < N {
+            array[index] = self.bytes[index];
+            index += 1;
+        }
+        array
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.length]
+    }
+
+    /// Length of a report in bytes including its report ID, 0 when the descriptor doesn't
+    /// declare it. `id` is 0 for a descriptor without report IDs.
+    pub const fn report_length(&self, kind: ReportKind, id: u8) -> usize {
+        match self.find_report(kind, id) {
+            Some(index) => {
+                self.reports[index].bits.div_ceil(8) as usize + if id == 0 { 0 } else { 1 }
+            }
+            None => 0,
+        }
+    }
+
+    const fn find_report(&self, kind: ReportKind, id: u8) -> Option<usize> {
+        let mut index = 0;
+        while index < self.report_total {
+            let report = self.reports[index];
+            if report.kind as u8 == kind as u8 && report.id == id {
+                return Some(index);
+            }
+            index += 1;
+        }
+        None
+    }
+
+    const fn main_data(mut self, kind: ReportKind, prefix: u8, flags: MainFlags) -> Self {
+        assert!(
+            self.report_size != 0 && self.report_count != 0,
+            "Main items need a report size and a report count"
+        );
+        if !flags.is_constant() {
+            assert!(
+                self.logical_minimum <= self.logical_maximum,
+                "The logical minimum is above the maximum"
+            );
+            let size = self.report_size;
+            let (low, high) = if self.logical_minimum < 0 {
+                (-(1_i64 << (size - 1)), (1_i64 << (size - 1)) - 1)
+            } else {
+                (0, (1_i64 << size) - 1)
+            };
+            assert!(
+                self.logical_minimum as i64 >= low && self.logical_maximum as i64 <= high,
+                "The logical range doesn't fit in the report size"
+            );
+        }
+
+        let bits = self.report_size * self.report_count;
+        match self.find_report(kind, self.report_id) {
+            Some(index) => self.reports[index].bits += bits,
+            None => {
+                assert!(self.report_total < MAX_REPORTS, "Too many reports");
+                self.reports[self.report_total] = ReportBits {
+                    kind,
+                    id: self.report_id,
+                    bits,
+                };
+                self.report_total += 1;
+            }
+        }
+        let size = if flags.0 <= 0xFF { 1 } else { 2 };
+        self.item(prefix, flags.0, size)
+    }
+
+    const fn unsigned(self, prefix: u8, value: u32) -> Self {
+        let size = if value == 0 {
+            0
+        } else if value <= 0xFF {
+            1
+        } else if value <= 0xFFFF {
+            2
+        } else {
+            4
+        };
+        self.item(prefix, value, size)
+    }
+
+    const fn signed(self, prefix: u8, value: i32) -> Self {
+        let size = if value == 0 {
+            0
+        } else if value >= i8::MIN as i32 && value <= i8::MAX as i32 {
+            1
+        } else if value >= i16::MIN as i32 && value <= i16::MAX as i32 {
+            2
+        } else {
+            4
+        };
+        self.item(prefix, value as u32, size)
+    }
+
+    /// Appends a short item, `prefix` holding its tag and type.
+    const fn item(mut self, prefix: u8, value: u32, size: usize) -> Self {
+        assert!(
+            self.length + 1 + size <= MAX_DESCRIPTOR_SIZE,
+            "The descriptor is longer than MAX_DESCRIPTOR_SIZE"
+        );
+        let size_code = if size == 4 { 3 } else { size as u8 };
+        self.bytes[self.length] = prefix | size_code;
+        let value = value.to_le_bytes();
+        let mut index = 0;
+        while index < size {
+            self.bytes[self.length + 1 + index] = value[index];
+            index += 1;
+        }
+        self.length += 1 + size;
+        self
+    }
+}
+
+impl Default for ReportDescriptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod hid_descriptor_testing {
+    use crate::hid_descriptor::{
+        usage, Collection, MainFlags, ReportDescriptor, ReportKind, UsagePage,
+    };
+    use rstest::rstest;
+
+    fn axes() -> ReportDescriptor {
+        ReportDescriptor::new()
+            .usage_page(UsagePage::GENERIC_DESKTOP)
+            .usage(usage::JOYSTICK)
+            .collection(Collection::Application)
+            .report_id(1)
+            .usage(usage::X)
+            .usage(usage::Y)
+            .logical_minimum(-32_768)
+            .logical_maximum(32_767)
+            .report_size(16)
+            .report_count(2)
+            .input(MainFlags::DATA.variable())
+    }
+
+    #[test]
+    fn when_descriptor_is_built_then_items_use_the_shortest_encoding() {
+        // When
+        let result = axes().end_collection().finish();
+
+        // Then
+        assert_eq!(
+            result.as_bytes(),
+            [
+                0x05, 0x01, 0x09, 0x04, 0xA1, 0x01, 0x85, 0x01, 0x09, 0x30, 0x09, 0x31, 0x16, 0x00,
+                0x80, 0x26, 0xFF, 0x7F, 0x75, 0x10, 0x95, 0x02, 0x81, 0x02, 0xC0,
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case(0, &[0x14])]
+    #[case(-1, &[0x15, 0xFF])]
+    #[case(255, &[0x16, 0xFF, 0x00])]
+    #[case(-70_000, &[0x17, 0x90, 0xEE, 0xFE, 0xFF])]
+    fn when_signed_value_is_added_then_it_keeps_its_sign(
+        #[case] value: i32,
+        #[case] expected: &[u8],
+    ) {
+        // When
+        let result = ReportDescriptor::new().logical_minimum(value);
+
+        // Then
+        assert_eq!(result.as_bytes(), expected);
+    }
+
+    #[test]
+    fn when_items_are_added_then_the_report_lengths_are_summed_per_id() {
+        // Given
+        let descriptor = axes()
+            .usage_page(UsagePage::BUTTON)
+            .usage_minimum(1)
+            .usage_maximum(4)
+            .logical_minimum(0)
+            .logical_maximum(1)
+            .report_size(1)
+            .report_count(4)
+            .input(MainFlags::DATA.variable())
+            .report_count(4)
+            .input(MainFlags::DATA.constant())
+            .report_id(2)
+            .usage_page(UsagePage::VENDOR_DEFINED)
+            .usage(0x10)
+            .logical_maximum(255)
+            .report_size(8)
+            .report_count(3)
+            .feature(MainFlags::DATA.variable())
+            .end_collection()
+            .finish();
+
+        // When
+        let input = descriptor.report_length(ReportKind::Input, 1);
+        let feature = descriptor.report_length(ReportKind::Feature, 2);
+        let missing = descriptor.report_length(ReportKind::Output, 1);
+
+        // Then
+        assert_eq!(input, 1 + 4 + 1);
+        assert_eq!(feature, 1 + 3);
+        assert_eq!(missing, 0);
+    }
+
+    #[test]
+    fn when_descriptor_is_copied_into_an_array_then_it_is_the_same() {
+        // Given
+        const DESCRIPTOR: ReportDescriptor = ReportDescriptor::new()
+            .usage_page(UsagePage::SIMULATION)
+            .collection(Collection::Physical)
+            .end_collection()
+            .finish();
+
+        // When
+        let result: [u8; DESCRIPTOR.len()] = DESCRIPTOR.to_array();
+
+        // Then
+        assert_eq!(result, [0x05, 0x02, 0xA1, 0x00, 0xC0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "isn't closed")]
+    fn when_collection_is_left_open_then_the_descriptor_is_refused() {
+        // When
+        axes().finish();
+    }
+
+    #[test]
+    #[should_panic(expected = "whole bytes")]
+    fn when_report_is_not_whole_bytes_then_the_descriptor_is_refused() {
+        // When
+        axes()
+            .logical_minimum(0)
+            .logical_maximum(1)
+            .report_size(1)
+            .report_count(3)
+            .input(MainFlags::DATA.variable())
+            .end_collection()
+            .finish();
+    }
+
+    #[test]
+    #[should_panic(expected = "doesn't fit")]
+    fn when_logical_range_exceeds_the_report_size_then_the_item_is_refused() {
+        // When
+        axes().report_size(8).input(MainFlags::DATA.variable());
+    }
+
+    #[test]
+    #[should_panic(expected = "before the first main item")]
+    fn when_report_id_comes_after_a_main_item_without_one_then_it_is_refused() {
+        // When
+        ReportDescriptor::new()
+            .logical_maximum(1)
+            .report_size(8)
+            .report_count(1)
+            .input(MainFlags::DATA.variable())
+            .report_id(1);
+    }
+}
@@ -0,0 +1,745 @@
+//! Parser of HID report descriptors.
+//!
+//! [`fields`] walks the items of a descriptor and yields the layout of every input, output
+//! and feature item: where its values sit in the report, their size, usages and logical
+//! range. [`validate`] also checks the descriptor as a whole and sums up the length of every
+//! report, which the tests use to check the descriptors the firmware ships.
+
+use crate::hid_descriptor::{MainFlags, ReportKind, MAX_REPORTS};
+
+/// Most usages a main item can have.
+const MAX_USAGES: usize = 16;
+/// Deepest stack of pushed global items.
+const MAX_PUSHES: usize = 4;
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ParseError {
+    /// An item runs past the end of the descriptor.
+    Truncated,
+    /// Long items aren't supported.
+    LongItem,
+    /// An item with the reserved type or an unknown tag, holding its prefix.
+    UnknownItem(u8),
+    /// An end collection without a collection, or a collection left open.
+    UnbalancedCollection,
+    /// A main item before any report size or report count.
+    MissingReportSize,
+    /// A report ID of 0, which is reserved.
+    InvalidReportId,
+    /// Main items before the first report ID of a descriptor using report IDs.
+    MixedReportIds,
+    /// A report whose length isn't whole bytes.
+    NotWholeBytes {
+        kind: ReportKind,
+        id: u8,
+    },
+    TooManyUsages,
+    TooManyReports,
+    /// A pop without a push, or too many pushes.
+    UnbalancedPush,
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ItemType {
+    Main,
+    Global,
+    Local,
+}
+
+/// A short item of a descriptor.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Item {
+    pub item_type: ItemType,
+    pub tag: u8,
+    /// Data bytes of the item, 0, 1, 2 or 4.
+    pub size: u8,
+    pub data: u32,
+}
+
+impl Item {
+    /// The data read as a signed value of its size.
+    pub fn signed(&self) -> i32 {
+        match self.size {
+            1 => self.data as u8 as i8 as i32,
+            2 => self.data as u16 as i16 as i32,
+            _ => self.data as i32,
+        }
+    }
+}
+
+/// The items of a descriptor, in order.
+pub struct Items<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Items<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+}
+
+impl Iterator for Items<'_> {
+    type Item = Result<Item, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&prefix, rest) = self.bytes.split_first()?;
+        if prefix == 0xFE {
+            self.bytes = &[];
+            return Some(Err(ParseError::LongItem));
+        }
+        let size = [0, 1, 2, 4][(prefix & 0b11) as usize];
+        let item_type = match (prefix >> 2) & 0b11 {
+            0 => ItemType::Main,
+            1 => ItemType::Global,
+            2 => ItemType::Local,
+            _ => {
+                self.bytes = &[];
+                return Some(Err(ParseError::UnknownItem(prefix)));
+            }
+        };
+        let Some(data) = rest.get(..size) else {
+            self.bytes = &[];
+            return Some(Err(ParseError::Truncated));
+        };
+        self.bytes = &rest[size..];
+        let mut bytes = [0; 4];
+        bytes[..size].copy_from_slice(data);
+        Some(Ok(Item {
+            item_type,
+            tag: prefix >> 4,
+            size: size as u8,
+            data: u32::from_le_bytes(bytes),
+        }))
+    }
+}
+
+/// Usages of a main item, each with its usage page in the upper 16 bits.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Usages {
+    list: [u32; MAX_USAGES],
+    length: usize,
+    minimum: Option<u32>,
+    maximum: Option<u32>,
+}
+
+impl Usages {
+    const EMPTY: Usages = Usages {
+        list: [0; MAX_USAGES],
+        length: 0,
+        minimum: None,
+        maximum: None,
+    };
+
+    /// Usage of the value at `index`: the listed usages come first, then the range, and the
+    /// last usage covers the remaining values.
+    pub fn get(&self, index: usize) -> Option<u32> {
+        if index < self.length {
+            return Some(self.list[index]);
+        }
+        match (self.minimum, self.maximum) {
+            (Some(minimum), Some(maximum)) => {
+                Some((minimum + (index - self.length) as u32).min(maximum))
+            }
+            _ => self.length.checked_sub(1).map(|last| self.list[last]),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0 && self.minimum.is_none()
+    }
+}
+
+/// Layout of an input, output or feature item.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Field {
+    pub kind: ReportKind,
+    /// 0 in a descriptor without report IDs.
+    pub report_id: u8,
+    /// Offset of the first value in the report data, after the report ID.
+    pub bit_offset: u32,
+    /// Size of every value in bits.
+    pub size: u32,
+    pub count: u32,
+    pub flags: MainFlags,
+    pub logical_minimum: i32,
+    pub logical_maximum: i32,
+    pub usages: Usages,
+}
+
+impl Field {
+    /// Reads the value at `index` from a whole report, including its report ID. `None` when
+    /// the report is too short.
+    pub fn read(&self, report: &[u8], index: u32) -> Option<i32> {
+        let start = self.bit_position(index)?;
+        let mut value: u32 = 0;
+        for bit in 0..self.size {
+            let position = start + bit as usize;
+            let byte = report.get(position / 8)?;
+            value |= ((*byte >> (position % 8)) as u32 & 1) << bit;
+        }
+        if self.logical_minimum < 0 && self.size < 32 && value & 1 << (self.size - 1) != 0 {
+            value |= u32::MAX << self.size;
+        }
+        Some(value as i32)
+    }
+
+    /// Writes `value` at `index` into a whole report, including its report ID. `None` when
+    /// the report is too short.
+    pub fn write(&self, report: &mut [u8], index: u32, value: i32) -> Option<()> {
+        let start = self.bit_position(index)?;
+        if report.len() * 8 < start + self.size as usize {
+            return None;
+        }
+        for bit in 0..self.size {
+            let position = start + bit as usize;
+            let mask = 1 << (position % 8);
+            if (value as u32 >> bit) & 1 != 0 {
+                report[position / 8] |= mask;
+            } else {
+                report[position / 8] &= !mask;
+            }
+        }
+        Some(())
+    }
+
+    fn bit_position(&self, index: u32) -> Option<usize> {
+        if index >= self.count {
+            return None;
+        }
+        let id_bits = if self.report_id == 0 { 0 } else { 8 };
+        Some((id_bits + self.bit_offset + index * self.size) as usize)
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+struct Globals {
+    usage_page: u16,
+    logical_minimum: i32,
+    logical_maximum: i32,
+    report_size: u32,
+    report_count: u32,
+    report_id: u8,
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+struct ReportBits {
+    kind: ReportKind,
+    id: u8,
+    bits: u32,
+}
+
+/// Length of every report of a descriptor.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub struct ReportLengths {
+    reports: [ReportBits; MAX_REPORTS],
+    count: usize,
+}
+
+impl ReportLengths {
+    /// Length in bytes including the report ID, `None` when the descriptor doesn't declare
+    /// the report.
+    pub fn get(&self, kind: ReportKind, id: u8) -> Option<usize> {
+        self.reports[..self.count]
+            .iter()
+            .find(|report| report.kind == kind && report.id == id)
+            .map(|report| report.bits as usize / 8 + if id == 0 { 0 } else { 1 })
+    }
+
+    /// Every report as its kind, ID and length including the ID.
+    pub fn iter(&self) -> impl Iterator<Item = (ReportKind, u8, usize)> + '_ {
+        self.reports[..self.count].iter().map(|report| {
+            (
+                report.kind,
+                report.id,
+                self.get(report.kind, report.id).unwrap(),
+            )
+        })
+    }
+}
+
+/// Layout of every main item of a descriptor, in order.
+pub struct Fields<'a> {
+    items: Items<'a>,
+    globals: Globals,
+    pushed: [Globals; MAX_PUSHES],
+    push_depth: usize,
+    usages: Usages,
+    depth: usize,
+    lengths: ReportLengths,
+    done: bool,
+}
+
+pub fn fields(descriptor: &[u8]) -> Fields<'_> {
+    let globals = Globals {
+        usage_page: 0,
+        logical_minimum: 0,
+        logical_maximum: 0,
+        report_size: 0,
+        report_count: 0,
+        report_id: 0,
+    };
+    Fields {
+        items: Items::new(descriptor),
+        globals,
+        pushed: [globals; MAX_PUSHES],
+        push_depth: 0,
+        usages: Usages::EMPTY,
+        depth: 0,
+        lengths: ReportLengths {
+            reports: [ReportBits {
+                kind: ReportKind::Input,
+                id: 0,
+                bits: 0,
+            }; MAX_REPORTS],
+            count: 0,
+        },
+        done: false,
+    }
+}
+
+/// Parses the whole descriptor and checks the collections are balanced, report IDs are used
+/// by every report or none, and every report is whole bytes.
+pub fn validate(descriptor: &[u8]) -> Result<ReportLengths, ParseError> {
+    let mut fields = fields(descriptor);
+    for field in fields.by_ref() {
+        field?;
+    }
+    let lengths = fields.lengths;
+    let reports = &lengths.reports[..lengths.count];
+    if reports.iter().any(|r| r.id == 0) && reports.iter().any(|r| r.id != 0) {
+        return Err(ParseError::MixedReportIds);
+    }
+    if let Some(report) = reports.iter().find(|r| !r.bits.is_multiple_of(8)) {
+        return Err(ParseError::NotWholeBytes {
+            kind: report.kind,
+            id: report.id,
+        });
+    }
+    Ok(lengths)
+}
+
+impl Fields<'_> {
+    fn item(&mut self, item: Item) -> Result<Option<Field>, ParseError> {
+        match (item.item_type, item.tag) {
+            (ItemType::Main, 0x8) => return self.main_data(ReportKind::Input, item).map(Some),
+            (ItemType::Main, 0x9) => return self.main_data(ReportKind::Output, item).map(Some),
+            (ItemType::Main, 0xB) => return self.main_data(ReportKind::Feature, item).map(Some),
+            (ItemType::Main, 0xA) => {
+                self.depth += 1;
+                self.usages = Usages::EMPTY;
+            }
+            (ItemType::Main, 0xC) => {
+                self.depth = self
+                    .depth
+                    .checked_sub(1)
+                    .ok_or(ParseError::UnbalancedCollection)?;
+                self.usages = Usages::EMPTY;
+            }
+            (ItemType::Global, 0x0) => self.globals.usage_page = item.data as u16,
+            (ItemType::Global, 0x1) => self.globals.logical_minimum = item.signed(),
+            (ItemType::Global, 0x2) => self.globals.logical_maximum = item.signed(),
+            (ItemType::Global, 0x3..=0x6) => {}
+            (ItemType::Global, 0x7) => self.globals.report_size = item.data,
+            (ItemType::Global, 0x8) => {
+                self.globals.report_id =
+                    u8::try_from(item.data).map_err(|_| ParseError::InvalidReportId)?;
+                if self.globals.report_id == 0 {
+                    return Err(ParseError::InvalidReportId);
+                }
+            }
+            (ItemType::Global, 0x9) => self.globals.report_count = item.data,
+            (ItemType::Global, 0xA) => {
+                *self
+                    .pushed
+                    .get_mut(self.push_depth)
+                    .ok_or(ParseError::UnbalancedPush)? = self.globals;
+                self.push_depth += 1;
+            }
+            (ItemType::Global, 0xB) => {
+                self.push_depth = self
+                    .push_depth
+                    .checked_sub(1)
+                    .ok_or(ParseError::UnbalancedPush)?;
+                self.globals = self.pushed[self.push_depth];
+            }
+            (ItemType::Local, 0x0) => {
+                let usage = self.extended_usage(item);
+                let slot = self
+                    .usages
+                    .list
+                    .get_mut(self.usages.length)
+                    .ok_or(ParseError::TooManyUsages)?;
+                *slot = usage;
+                self.usages.length += 1;
+            }
+            (ItemType::Local, 0x1) => self.usages.minimum = Some(self.extended_usage(item)),
+            (ItemType::Local, 0x2) => self.usages.maximum = Some(self.extended_usage(item)),
+            (ItemType::Local, 0x3..=0xA) => {}
+            _ => {
+                let prefix = item.tag << 4 | (item.item_type as u8) << 2;
+                return Err(ParseError::UnknownItem(prefix));
+            }
+        }
+        Ok(None)
+    }
+
+    fn extended_usage(&self, item: Item) -> u32 {
+        if item.size == 4 {
+            item.data
+        } else {
+            (self.globals.usage_page as u32) << 16 | item.data
+        }
+    }
+
+    fn main_data(&mut self, kind: ReportKind, item: Item) -> Result<Field, ParseError> {
+        let globals = self.globals;
+        if globals.report_size == 0 || globals.report_count == 0 {
+            return Err(ParseError::MissingReportSize);
+        }
+        let bits = globals.report_size * globals.report_count;
+        let lengths = &mut self.lengths;
+        let bit_offset = match lengths.reports[..lengths.count]
+            .iter_mut()
+            .find(|report| report.kind == kind && report.id == globals.report_id)
+        {
+            Some(report) => {
+                report.bits += bits;
+                report.bits - bits
+            }
+            None => {
+                let report = lengths
+                    .reports
+                    .get_mut(lengths.count)
+                    .ok_or(ParseError::TooManyReports)?;
+                *report = ReportBits {
+                    kind,
+                    id: globals.report_id,
+                    bits,
+                };
+                lengths.count += 1;
+                0
+            }
+        };
+        let field = Field {
+            kind,
+            report_id: globals.report_id,
+            bit_offset,
+            size: globals.report_size,
+            count: globals.report_count,
+            flags: MainFlags(item.data),
+            logical_minimum: globals.logical_minimum,
+            logical_maximum: globals.logical_maximum,
+            usages: self.usages,
+        };
+        self.usages = Usages::EMPTY;
+        Ok(field)
+    }
+}
+
+impl Iterator for Fields<'_> {
+    type Item = Result<Field, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        loop {
+            let Some(item) = self.items.next() else {
+                self.done = true;
+                return (self.depth != 0).then_some(Err(ParseError::UnbalancedCollection));
+            };
+            match item.and_then(|item| self.item(item)) {
+                Ok(None) => continue,
+                Ok(Some(field)) => return Some(Ok(field)),
+                Err(error) => {
+                    self.done = true;
+                    return Some(Err(error));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod hid_parser_testing {
+    use crate::feature_report::{
+        FeatureKind, FeatureReportId, CALIBRATION_SESSION_PAYLOAD_SIZE,
+        CALIBRATION_SESSION_REPORT_ID, FAULT_STATUS_PAYLOAD_SIZE, FAULT_STATUS_REPORT_ID,
+        REPORT_MODE_PAYLOAD_SIZE, REPORT_MODE_REPORT_ID, REPORT_STATISTICS_PAYLOAD_SIZE,
+        REPORT_STATISTICS_REPORT_ID, RESET_CAUSE_PAYLOAD_SIZE, RESET_CAUSE_REPORT_ID,
+    };
+    use crate::hid_descriptor::{usage, ReportKind, UsagePage};
+    use crate::hid_parser::{fields, validate, Field, Item, ItemType, Items, ParseError};
+    use crate::input_report::{
+        DiagnosticsReport, InputReport, PedalboxReport, StatusReport, DIAGNOSTICS_REPORT_ID,
+        PEDALBOX_REPORT_DESCRIPTOR, PEDALBOX_REPORT_ID, STATUS_REPORT_ID,
+    };
+    use crate::pedal_state::PedalSnapshot;
+    use crate::prelude::Axis;
+    use alloc::vec::Vec;
+    use rstest::rstest;
+
+    fn inputs(id: u8) -> Vec<Field> {
+        fields(PEDALBOX_REPORT_DESCRIPTOR)
+            .map(Result::unwrap)
+            .filter(|field| field.kind == ReportKind::Input && field.report_id == id)
+            .collect()
+    }
+
+    /// Reads every value of `fields` from `report` and writes them into a report of zeros.
+    fn read_and_write(fields: &[Field], report: &[u8]) -> (Vec<i32>, Vec<u8>) {
+        let mut values = Vec::new();
+        let mut written = alloc::vec![0; report.len()];
+        written[0] = report[0];
+        for field in fields {
+            for index in 0..field.count {
+                let value = field.read(report, index).unwrap();
+                field.write(&mut written, index, value).unwrap();
+                values.push(value);
+            }
+        }
+        (values, written)
+    }
+
+    #[rstest]
+    #[case(&[0x14], 0, 0)]
+    #[case(&[0x15, 0xFF], 0xFF, -1)]
+    #[case(&[0x16, 0x00, 0x80], 0x8000, -32_768)]
+    #[case(&[0x17, 0x90, 0xEE, 0xFE, 0xFF], 0xFFFE_EE90, -70_000)]
+    fn when_item_is_decoded_then_its_data_is_read_by_its_size(
+        #[case] bytes: &[u8],
+        #[case] data: u32,
+        #[case] signed: i32,
+    ) {
+        // When
+        let result = Items::new(bytes).next().unwrap().unwrap();
+
+        // Then
+        assert_eq!(result.item_type, ItemType::Global);
+        assert_eq!(result.tag, 0x1);
+        assert_eq!(result.data, data);
+        assert_eq!(result.signed(), signed);
+    }
+
+    #[rstest]
+    #[case(&[0x26, 0xFF], ParseError::Truncated)]
+    #[case(&[0xFE, 0x00, 0x00], ParseError::LongItem)]
+    #[case(&[0x0D, 0x00], ParseError::UnknownItem(0x0D))]
+    fn when_item_is_invalid_then_decoding_stops(
+        #[case] bytes: &[u8],
+        #[case] expected: ParseError,
+    ) {
+        // When
+        let result: Vec<Result<Item, ParseError>> = Items::new(bytes).collect();
+
+        // Then
+        assert_eq!(result, [Err(expected)]);
+    }
+
+    #[test]
+    fn when_shipped_descriptor_is_validated_then_it_is_well_formed() {
+        // When
+        let result = validate(PEDALBOX_REPORT_DESCRIPTOR);
+
+        // Then
+        assert!(result.is_ok(), "{result:?}");
+    }
+
+    #[test]
+    fn when_shipped_descriptor_is_parsed_then_the_input_reports_match_their_structs() {
+        // When
+        let lengths = validate(PEDALBOX_REPORT_DESCRIPTOR).unwrap();
+
+        // Then
+        let inputs: Vec<(u8, usize)> = lengths
+            .iter()
+            .filter(|(kind, _, _)| *kind == ReportKind::Input)
+            .map(|(_, id, length)| (id, length))
+            .collect();
+        assert_eq!(
+            inputs,
+            [
+                (PEDALBOX_REPORT_ID, PedalboxReport::SIZE),
+                (STATUS_REPORT_ID, StatusReport::SIZE),
+                (DIAGNOSTICS_REPORT_ID, DiagnosticsReport::SIZE)
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case(STATUS_REPORT_ID)]
+    #[case(DIAGNOSTICS_REPORT_ID)]
+    fn when_shipped_descriptor_is_parsed_then_status_and_diagnostics_are_vendor_defined(
+        #[case] id: u8,
+    ) {
+        // When
+        let inputs = inputs(id);
+
+        // Then
+        let vendor = (UsagePage::VENDOR_DEFINED.0 as u32) << 16;
+        assert!(!inputs.is_empty());
+        for field in inputs {
+            for index in 0..field.count as usize {
+                assert_eq!(
+                    field.usages.get(index).map(|usage| usage >> 16),
+                    Some(vendor >> 16)
+                );
+            }
+        }
+    }
+
+    #[rstest]
+    #[case(FeatureReportId::new(FeatureKind::Calibration, Axis::X).value(), FeatureKind::Calibration.payload_size())]
+    #[case(FeatureReportId::new(FeatureKind::Calibration, Axis::Z).value(), FeatureKind::Calibration.payload_size())]
+    #[case(FeatureReportId::new(FeatureKind::Curve, Axis::Y).value(), FeatureKind::Curve.payload_size())]
+    #[case(FeatureReportId::new(FeatureKind::Filter, Axis::Z).value(), FeatureKind::Filter.payload_size())]
+    #[case(CALIBRATION_SESSION_REPORT_ID, CALIBRATION_SESSION_PAYLOAD_SIZE)]
+    #[case(REPORT_MODE_REPORT_ID, REPORT_MODE_PAYLOAD_SIZE)]
+    #[case(REPORT_STATISTICS_REPORT_ID, REPORT_STATISTICS_PAYLOAD_SIZE)]
+    #[case(FAULT_STATUS_REPORT_ID, FAULT_STATUS_PAYLOAD_SIZE)]
+    #[case(RESET_CAUSE_REPORT_ID, RESET_CAUSE_PAYLOAD_SIZE)]
+    fn when_shipped_descriptor_is_parsed_then_feature_reports_match_their_payloads(
+        #[case] id: u8,
+        #[case] payload_size: usize,
+    ) {
+        // When
+        let lengths = validate(PEDALBOX_REPORT_DESCRIPTOR).unwrap();
+
+        // Then
+        assert_eq!(lengths.get(ReportKind::Feature, id), Some(1 + payload_size));
+    }
+
+    #[test]
+    fn when_shipped_descriptor_is_parsed_then_the_axes_and_buttons_have_their_usages() {
+        // When
+        let inputs = inputs(PEDALBOX_REPORT_ID);
+
+        // Then
+        let desktop = (UsagePage::GENERIC_DESKTOP.0 as u32) << 16;
+        let button = (UsagePage::BUTTON.0 as u32) << 16;
+        assert_eq!(inputs.len(), 2);
+        assert_eq!(
+            (0..3).map(|i| inputs[0].usages.get(i)).collect::<Vec<_>>(),
+            [
+                Some(desktop | usage::X),
+                Some(desktop | usage::Y),
+                Some(desktop | usage::Z)
+            ]
+        );
+        assert_eq!(
+            (inputs[0].logical_minimum, inputs[0].logical_maximum),
+            (-32_768, 32_767)
+        );
+        assert_eq!(inputs[1].usages.get(0), Some(button | 1));
+        assert_eq!(inputs[1].usages.get(7), Some(button | 8));
+        assert_eq!((inputs[1].bit_offset, inputs[1].size), (48, 1));
+    }
+
+    #[test]
+    fn when_pedalbox_report_is_read_through_the_layout_then_it_round_trips() {
+        // Given
+        let report = PedalboxReport::new(-2, 0x0102, i16::MAX, 0b1000_0101);
+        let bytes = bytemuck::bytes_of(&report);
+
+        // When
+        let (values, written) = read_and_write(&inputs(PEDALBOX_REPORT_ID), bytes);
+
+        // Then
+        assert_eq!(
+            values,
+            [-2, 0x0102, i16::MAX as i32, 1, 0, 1, 0, 0, 0, 0, 1]
+        );
+        assert_eq!(written, bytes);
+    }
+
+    #[test]
+    fn when_status_report_is_read_through_the_layout_then_it_round_trips() {
+        // Given
+        let report = StatusReport::new(&PedalSnapshot::UNKNOWN, -123, [1, 20, 255], 86_400);
+        let bytes = bytemuck::bytes_of(&report);
+
+        // When
+        let (values, written) = read_and_write(&inputs(STATUS_REPORT_ID), bytes);
+
+        // Then
+        assert_eq!(values, [0, 0, 0, -123, 1, 20, 255, 86_400]);
+        assert_eq!(written, bytes);
+    }
+
+    #[test]
+    fn when_diagnostics_report_is_read_through_the_layout_then_it_round_trips() {
+        // Given
+        let report = DiagnosticsReport::new([16_383, -8_388_608, i32::MAX]);
+        let bytes = bytemuck::bytes_of(&report);
+
+        // When
+        let (values, written) = read_and_write(&inputs(DIAGNOSTICS_REPORT_ID), bytes);
+
+        // Then
+        assert_eq!(values, [16_383, -8_388_608, i32::MAX]);
+        assert_eq!(written, bytes);
+    }
+
+    #[test]
+    fn when_report_is_too_short_then_values_past_its_end_are_not_read() {
+        // Given
+        let inputs = inputs(PEDALBOX_REPORT_ID);
+
+        // When
+        let result = inputs[1].read(&[PEDALBOX_REPORT_ID, 0, 0, 0, 0, 0, 0], 0);
+
+        // Then
+        assert_eq!(result, None);
+    }
+
+    #[rstest]
+    // Collection (Application) without End Collection
+    #[case(&[0xA1, 0x01], ParseError::UnbalancedCollection)]
+    // End Collection without Collection
+    #[case(&[0xC0], ParseError::UnbalancedCollection)]
+    // Input before any Report Size
+    #[case(&[0x95, 0x01, 0x81, 0x02], ParseError::MissingReportSize)]
+    // Report Size (1), Report Count (3), Input
+    #[case(&[0x75, 0x01, 0x95, 0x03, 0x81, 0x02], ParseError::NotWholeBytes { kind: ReportKind::Input, id: 0 })]
+    // Input without a report ID, then Report ID (1) and another Input
+    #[case(&[0x75, 0x08, 0x95, 0x01, 0x81, 0x02, 0x85, 0x01, 0x81, 0x02], ParseError::MixedReportIds)]
+    // Report ID (0)
+    #[case(&[0x84], ParseError::InvalidReportId)]
+    // Pop without Push
+    #[case(&[0xB4], ParseError::UnbalancedPush)]
+    fn when_descriptor_is_malformed_then_it_is_refused(
+        #[case] bytes: &[u8],
+        #[case] expected: ParseError,
+    ) {
+        // When
+        let result = validate(bytes);
+
+        // Then
+        assert_eq!(result, Err(expected));
+    }
+
+    #[test]
+    fn when_globals_are_pushed_then_pop_restores_them() {
+        // Given
+        let bytes = [
+            0x75, 0x08, 0x95, 0x01, // Report Size (8), Report Count (1)
+            0xA4, // Push
+            0x75, 0x10, 0x81, 0x02, // Report Size (16), Input
+            0xB4, // Pop
+            0x81, 0x02, // Input
+        ];
+
+        // When
+        let result: Vec<(u32, u32)> = fields(&bytes)
+            .map(|field| field.map(|f| (f.bit_offset, f.size)).unwrap())
+            .collect();
+
+        // Then
+        assert_eq!(result, [(0, 16), (16, 8)]);
+    }
+}
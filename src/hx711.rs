@@ -0,0 +1,459 @@
+//! Driver of the HX711 load cell amplifier.
+//!
+//! The HX711 pulls DOUT low when a conversion is ready. The driver waits for that edge
+//! asynchronously, then clocks the 24 bits out MSB first on SCK. The extra pulses after
+//! the data select the channel and gain of the next conversion. SCK must not stay high for
+//! 60 µs or the chip powers down, so the bits are clocked in a critical section.
+//!
+//! A task waiting on [`Hx711::read`] hands the conversions to the load cell monitor through
+//! [`LatestConversion`].
+
+use crate::LoadCell;
+use core::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use embedded_hal::blocking::delay::DelayUs;
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use embedded_hal_async::digital::Wait;
+
+/// Number of data bits of a conversion.
+const DATA_BITS: u8 = 24;
+/// SCK high and low time. The chip needs 0.2 µs at least.
+const CLOCK_DELAY_US: u32 = 1;
+
+/// Input channel and gain of the conversions.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Gain {
+    /// Channel A with gain 128, the default after power up.
+    ChannelA128,
+    ChannelA64,
+    ChannelB32,
+}
+
+impl Gain {
+    /// SCK pulses after the data bits that select this gain for the next conversion.
+    const fn extra_pulses(self) -> u8 {
+        match self {
+            Gain::ChannelA128 => 1,
+            Gain::ChannelB32 => 2,
+            Gain::ChannelA64 => 3,
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Hx711Error {
+    /// DOUT couldn't be read or waited on.
+    Input,
+    /// SCK couldn't be driven.
+    Output,
+}
+
+pub struct Hx711<Dout, Sck, D> {
+    dout: Dout,
+    sck: Sck,
+    delay: D,
+    gain: Gain,
+}
+
+impl<Dout, Sck, D> Hx711<Dout, Sck, D>
+where
+    Dout: InputPin,
+    Sck: OutputPin,
+    D: DelayUs<u32>,
+{
+    /// `delay` has to wait about a microsecond precisely, a timer ticking slower than that
+    /// would keep SCK high long enough to power the chip down.
+    pub fn new(dout: Dout, mut sck: Sck, delay: D, gain: Gain) -> Result<Self, Hx711Error> {
+        sck.set_low().map_err(|_| Hx711Error::Output)?;
+        Ok(Self {
+            dout,
+            sck,
+            delay,
+            gain,
+        })
+    }
+
+    pub fn gain(&self) -> Gain {
+        self.gain
+    }
+
+    /// Selects the gain. It takes effect from the conversion after the next read, which is
+    /// still made with the previous gain.
+    pub fn set_gain(&mut self, gain: Gain) {
+        self.gain = gain;
+    }
+
+    /// Whether a conversion is waiting to be read.
+    pub fn is_ready(&self) -> Result<bool, Hx711Error> {
+        self.dout.is_low().map_err(|_| Hx711Error::Input)
+    }
+
+    /// Clocks out the conversion, DOUT has to be low already.
+    fn read_conversion(&mut self) -> Result<i32, Hx711Error> {
+        critical_section::with(|_| {
+            let mut raw: u32 = 0;
+            for _ in 0..DATA_BITS {
+                self.pulse()?;
+                let bit = self.dout.is_high().map_err(|_| Hx711Error::Input)?;
+                raw = (raw << 1) | bit as u32;
+            }
+            for _ in 0..self.gain.extra_pulses() {
+                self.pulse()?;
+            }
+            Ok(sign_extend(raw))
+        })
+    }
+
+    fn pulse(&mut self) -> Result<(), Hx711Error> {
+        self.sck.set_high().map_err(|_| Hx711Error::Output)?;
+        self.delay.delay_us(CLOCK_DELAY_US);
+        self.sck.set_low().map_err(|_| Hx711Error::Output)?;
+        self.delay.delay_us(CLOCK_DELAY_US);
+        Ok(())
+    }
+}
+
+impl<Dout, Sck, D> Hx711<Dout, Sck, D>
+where
+    Dout: InputPin + Wait,
+    Sck: OutputPin,
+    D: DelayUs<u32>,
+{
+    /// Waits for the next conversion and reads it.
+    pub async fn read(&mut self) -> Result<i32, Hx711Error> {
+        self.dout
+            .wait_for_low()
+            .await
+            .map_err(|_| Hx711Error::Input)?;
+        self.read_conversion()
+    }
+}
+
+/// Polls the chip instead of waiting for it.
+impl<Dout, Sck, D> LoadCell for Hx711<Dout, Sck, D>
+where
+    Dout: InputPin,
+    Sck: OutputPin,
+    D: DelayUs<u32>,
+{
+    type ReturnType = i32;
+    type Error = Hx711Error;
+
+    fn read(&mut self) -> nb::Result<i32, Hx711Error> {
+        if !self.is_ready()? {
+            return Err(nb::Error::WouldBlock);
+        }
+        Ok(self.read_conversion()?)
+    }
+}
+
+/// Turns the 24 bit two's complement conversion into an `i32`.
+fn sign_extend(raw: u32) -> i32 {
+    ((raw << 8) as i32) >> 8
+}
+
+/// The newest conversion, handed over from the task reading the chip to the monitor, or
+/// the failure of the last read.
+#[derive(Debug, Default)]
+pub struct LatestConversion {
+    value: AtomicI32,
+    fresh: AtomicBool,
+    failed: AtomicBool,
+}
+
+impl LatestConversion {
+    pub const fn new() -> Self {
+        Self {
+            value: AtomicI32::new(0),
+            fresh: AtomicBool::new(false),
+            failed: AtomicBool::new(false),
+        }
+    }
+
+    pub fn publish(&self, value: i32) {
+        self.value.store(value, Ordering::Relaxed);
+        self.fresh.store(true, Ordering::Release);
+    }
+
+    /// Records that the chip couldn't be read, so the monitor counts a read error.
+    pub fn publish_error(&self) {
+        self.failed.store(true, Ordering::Release);
+    }
+
+    /// The newest conversion without consuming it, e.g. for diagnostics.
+    pub fn peek(&self) -> i32 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+/// Every conversion and every error is read once, `WouldBlock` until the next one is
+/// published.
+impl LoadCell for &LatestConversion {
+    type ReturnType = i32;
+    type Error = ();
+
+    fn read(&mut self) -> nb::Result<i32, ()> {
+        if self.failed.swap(false, Ordering::Acquire) {
+            return Err(nb::Error::Other(()));
+        }
+        if !self.fresh.swap(false, Ordering::Acquire) {
+            return Err(nb::Error::WouldBlock);
+        }
+        Ok(self.value.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod hx711_testing {
+    use crate::hx711::{Gain, Hx711, Hx711Error, LatestConversion};
+    use crate::LoadCell;
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
+    use core::convert::Infallible;
+    use embedded_hal::blocking::delay::DelayUs;
+    use embedded_hal::digital::v2::{InputPin, OutputPin};
+    use embedded_hal_1::digital::ErrorType;
+    use embedded_hal_async::digital::Wait;
+    use rstest::rstest;
+
+    /// Shifts `conversion` out MSB first on the rising edges of SCK, like the chip.
+    #[derive(Default)]
+    struct SimulatedChip {
+        conversion: Option<u32>,
+        sck_high: bool,
+        pulses: u8,
+        /// Pulses counted when the last conversion was finished by SCK staying low.
+        finished_pulses: Option<u8>,
+        waits: u32,
+    }
+
+    impl SimulatedChip {
+        fn dout(&self) -> bool {
+            match self.conversion {
+                None => true,
+                Some(_) if self.pulses == 0 => false,
+                Some(conversion) if self.pulses <= 24 => conversion >> (24 - self.pulses) & 1 == 1,
+                Some(_) => true,
+            }
+        }
+    }
+
+    type Chip = Rc<RefCell<SimulatedChip>>;
+
+    struct SimulatedDout(Chip);
+    struct SimulatedSck(Chip);
+    struct NoDelay;
+
+    impl InputPin for SimulatedDout {
+        type Error = Infallible;
+
+        fn is_high(&self) -> Result<bool, Infallible> {
+            Ok(self.0.borrow().dout())
+        }
+
+        fn is_low(&self) -> Result<bool, Infallible> {
+            Ok(!self.0.borrow().dout())
+        }
+    }
+
+    impl ErrorType for SimulatedDout {
+        type Error = Infallible;
+    }
+
+    /// The conversion is always ready by the time the wait for low is over. DOUT only
+    /// changes with SCK, so a wait for any other level or edge never ends.
+    impl Wait for SimulatedDout {
+        async fn wait_for_high(&mut self) -> Result<(), Infallible> {
+            if self.0.borrow().dout() {
+                return Ok(());
+            }
+            core::future::pending().await
+        }
+
+        async fn wait_for_low(&mut self) -> Result<(), Infallible> {
+            let mut chip = self.0.borrow_mut();
+            chip.waits += 1;
+            chip.conversion.get_or_insert(0x12_3456);
+            Ok(())
+        }
+
+        async fn wait_for_rising_edge(&mut self) -> Result<(), Infallible> {
+            core::future::pending().await
+        }
+
+        async fn wait_for_falling_edge(&mut self) -> Result<(), Infallible> {
+            core::future::pending().await
+        }
+
+        async fn wait_for_any_edge(&mut self) -> Result<(), Infallible> {
+            core::future::pending().await
+        }
+    }
+
+    impl OutputPin for SimulatedSck {
+        type Error = Infallible;
+
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            let mut chip = self.0.borrow_mut();
+            if !chip.sck_high {
+                chip.pulses += 1;
+            }
+            chip.sck_high = true;
+            Ok(())
+        }
+
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            self.0.borrow_mut().sck_high = false;
+            Ok(())
+        }
+    }
+
+    impl DelayUs<u32> for NoDelay {
+        fn delay_us(&mut self, _us: u32) {}
+    }
+
+    fn hx711(
+        conversion: Option<u32>,
+        gain: Gain,
+    ) -> (Hx711<SimulatedDout, SimulatedSck, NoDelay>, Chip) {
+        let chip = Rc::new(RefCell::new(SimulatedChip {
+            conversion,
+            ..SimulatedChip::default()
+        }));
+        let driver = Hx711::new(
+            SimulatedDout(chip.clone()),
+            SimulatedSck(chip.clone()),
+            NoDelay,
+            gain,
+        )
+        .unwrap();
+        (driver, chip)
+    }
+
+    /// Ends the conversion once the driver is done with it.
+    fn finish(chip: &Chip) {
+        let mut chip = chip.borrow_mut();
+        chip.finished_pulses = Some(chip.pulses);
+        chip.conversion = None;
+        chip.pulses = 0;
+    }
+
+    #[rstest]
+    #[case(0x00_0000, 0)]
+    #[case(0x00_0001, 1)]
+    #[case(0x12_3456, 0x12_3456)]
+    #[case(0x7F_FFFF, 8_388_607)]
+    #[case(0x80_0000, -8_388_608)]
+    #[case(0xFF_FFFF, -1)]
+    fn when_conversion_is_ready_then_it_is_decoded(#[case] conversion: u32, #[case] expected: i32) {
+        // Given
+        let (mut driver, _) = hx711(Some(conversion), Gain::ChannelA128);
+
+        // When
+        let result = LoadCell::read(&mut driver);
+
+        // Then
+        assert_eq!(result, Ok(expected));
+    }
+
+    #[rstest]
+    #[case(Gain::ChannelA128, 25)]
+    #[case(Gain::ChannelB32, 26)]
+    #[case(Gain::ChannelA64, 27)]
+    fn when_gain_is_selected_then_the_extra_pulses_are_clocked(
+        #[case] gain: Gain,
+        #[case] expected: u8,
+    ) {
+        // Given
+        let (mut driver, chip) = hx711(Some(0x00_0100), Gain::ChannelA128);
+        driver.set_gain(gain);
+
+        // When
+        let result = LoadCell::read(&mut driver);
+        finish(&chip);
+
+        // Then
+        assert_eq!(result, Ok(0x100));
+        assert_eq!(chip.borrow().finished_pulses, Some(expected));
+        assert!(!chip.borrow().sck_high);
+    }
+
+    #[test]
+    fn when_conversion_is_not_ready_then_polling_would_block() {
+        // Given
+        let (mut driver, chip) = hx711(None, Gain::ChannelA128);
+
+        // When
+        let result = LoadCell::read(&mut driver);
+
+        // Then
+        assert_eq!(result, Err(nb::Error::WouldBlock));
+        assert_eq!(chip.borrow().pulses, 0);
+    }
+
+    #[test]
+    fn when_read_is_awaited_then_it_waits_for_dout_to_go_low() {
+        // Given
+        let (mut driver, chip) = hx711(None, Gain::ChannelA64);
+
+        // When
+        let result = embassy_futures::block_on(driver.read());
+        finish(&chip);
+
+        // Then
+        assert_eq!(result, Ok::<_, Hx711Error>(0x12_3456));
+        assert_eq!(chip.borrow().waits, 1);
+        assert_eq!(chip.borrow().finished_pulses, Some(27));
+    }
+
+    #[test]
+    fn when_conversion_is_published_then_it_is_read_once() {
+        // Given
+        let latest: &LatestConversion = alloc::boxed::Box::leak(Default::default());
+        let mut load_cell = latest;
+        let before = load_cell.read();
+
+        // When
+        latest.publish(-42);
+        let first = load_cell.read();
+        let second = load_cell.read();
+
+        // Then
+        assert_eq!(before, Err(nb::Error::WouldBlock));
+        assert_eq!(first, Ok(-42));
+        assert_eq!(second, Err(nb::Error::WouldBlock));
+    }
+
+    #[test]
+    fn when_error_is_published_then_it_is_read_once() {
+        // Given
+        let latest: &LatestConversion = alloc::boxed::Box::leak(Default::default());
+        let mut load_cell = latest;
+
+        // When
+        latest.publish_error();
+        let first = load_cell.read();
+        let second = load_cell.read();
+
+        // Then
+        assert_eq!(first, Err(nb::Error::Other(())));
+        assert_eq!(second, Err(nb::Error::WouldBlock));
+    }
+
+    #[test]
+    fn when_conversion_is_peeked_then_it_is_still_read() {
+        // Given
+        let latest: &LatestConversion = alloc::boxed::Box::leak(Default::default());
+        let mut load_cell = latest;
+        latest.publish(1_234);
+
+        // When
+        let peeked = latest.peek();
+        let read = load_cell.read();
+
+        // Then
+        assert_eq!(peeked, 1_234);
+        assert_eq!(read, Ok(1_234));
+    }
+}
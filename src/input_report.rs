@@ -0,0 +1,231 @@
+//! Input reports sent to the host.
+//!
+//! The gameplay report with the state of the pedals goes out at the rate of the report mode.
+//! The status report, with the faults, the temperature of the chip, the firmware version and
+//! the uptime, and the diagnostics report with the unmapped readings of the sensors go out
+//! at fixed, slower rates. They sit in a vendor defined collection that games ignore.
+
+use crate::axis::{Axis, AXIS_COUNT};
+use crate::fault::FaultFlags;
+use crate::pedal_state::PedalSnapshot;
+
+// `PEDALBOX_REPORT_DESCRIPTOR`, the report IDs and the structs of the input reports,
+// generated by build.rs from `pedalbox_hid.xml`. The buttons are debounced, button 1 being
+// bit 0.
+include!(concat!(env!("OUT_DIR"), "/pedalbox_hid.rs"));
+
+/// Length of the longest input report, the size of the HID endpoint.
+pub const MAX_INPUT_REPORT_SIZE: usize = max(
+    PedalboxReport::SIZE,
+    max(StatusReport::SIZE, DiagnosticsReport::SIZE),
+);
+
+const fn max(a: usize, b: usize) -> usize {
+    if a > b {
+        a
+    } else {
+        b
+    }
+}
+
+/// An input report, identified by its report ID.
+pub trait InputReport: bytemuck::Pod {
+    const ID: u8;
+    const SIZE: usize = core::mem::size_of::<Self>();
+
+    /// Parses a report read from the host side, `None` when it's another report.
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.get(..Self::SIZE)?;
+        (bytes[0] == Self::ID).then(|| bytemuck::pod_read_unaligned(bytes))
+    }
+}
+
+impl InputReport for PedalboxReport {
+    const ID: u8 = PEDALBOX_REPORT_ID;
+}
+
+impl InputReport for StatusReport {
+    const ID: u8 = STATUS_REPORT_ID;
+}
+
+impl InputReport for DiagnosticsReport {
+    const ID: u8 = DIAGNOSTICS_REPORT_ID;
+}
+
+impl PedalboxReport {
+    pub fn new(x: i16, y: i16, z: i16, buttons: u8) -> Self {
+        Self {
+            id: PEDALBOX_REPORT_ID,
+            x,
+            y,
+            z,
+            buttons,
+        }
+    }
+}
+
+impl StatusReport {
+    /// `temperature` in tenths of °C.
+    pub fn new(
+        snapshot: &PedalSnapshot,
+        temperature: i16,
+        version: [u8; 3],
+        uptime_s: u32,
+    ) -> Self {
+        let faults = |axis| snapshot.axis(axis).faults.0;
+        Self {
+            id: STATUS_REPORT_ID,
+            faults_x: faults(Axis::X),
+            faults_y: faults(Axis::Y),
+            faults_z: faults(Axis::Z),
+            temperature,
+            version_major: version[0],
+            version_minor: version[1],
+            version_patch: version[2],
+            uptime_s,
+        }
+    }
+
+    /// Active faults of `axis`.
+    pub fn faults(&self, axis: Axis) -> FaultFlags {
+        FaultFlags([self.faults_x, self.faults_y, self.faults_z][axis.index()])
+    }
+
+    pub fn version(&self) -> [u8; 3] {
+        [self.version_major, self.version_minor, self.version_patch]
+    }
+}
+
+impl DiagnosticsReport {
+    /// `raw` holds the unmapped reading of every axis, in ADC or HX711 counts.
+    pub fn new(raw: [i32; AXIS_COUNT]) -> Self {
+        Self {
+            id: DIAGNOSTICS_REPORT_ID,
+            raw_x: raw[0],
+            raw_y: raw[1],
+            raw_z: raw[2],
+        }
+    }
+
+    pub fn raw(&self, axis: Axis) -> i32 {
+        [self.raw_x, self.raw_y, self.raw_z][axis.index()]
+    }
+}
+
+#[cfg(test)]
+mod input_report_testing {
+    use crate::axis::Axis;
+    use crate::fault::FaultFlags;
+    use crate::input_report::{
+        DiagnosticsReport, InputReport, PedalboxReport, StatusReport, MAX_INPUT_REPORT_SIZE,
+        PEDALBOX_REPORT_DESCRIPTOR,
+    };
+    use crate::pedal_state::PedalSnapshot;
+    use rstest::rstest;
+
+    #[test]
+    fn when_report_is_serialized_then_it_is_packed_little_endian() {
+        // Given
+        let report = PedalboxReport::new(-2, 0x0102, i16::MAX, 0b101);
+
+        // When
+        let result = bytemuck::bytes_of(&report);
+
+        // Then
+        assert_eq!(result, [0x01, 0xFE, 0xFF, 0x02, 0x01, 0xFF, 0x7F, 0b101]);
+    }
+
+    #[test]
+    fn when_descriptor_is_generated_then_the_report_holds_the_id_the_axes_and_the_buttons() {
+        // When
+        let size = PedalboxReport::SIZE;
+
+        // Then
+        assert_eq!(size, 1 + 3 * 2 + 1);
+        assert_eq!(PEDALBOX_REPORT_DESCRIPTOR.last(), Some(&0xC0));
+    }
+
+    #[rstest]
+    #[case(&[0x01, 0xFE, 0xFF, 0x02, 0x01, 0xFF, 0x7F, 0x05], Some((-2, 0x0102, i16::MAX, 5)))]
+    #[case(&[0x01, 0xFE, 0xFF, 0x02, 0x01, 0xFF, 0x7F, 0x05, 0x00], Some((-2, 0x0102, i16::MAX, 5)))]
+    #[case(&[0x01, 0xFE, 0xFF, 0x02, 0x01, 0xFF, 0x7F], None)]
+    #[case(&[0x10, 0xFE, 0xFF, 0x02, 0x01, 0xFF, 0x7F, 0x05], None)]
+    fn when_report_is_parsed(#[case] bytes: &[u8], #[case] expected: Option<(i16, i16, i16, u8)>) {
+        // When
+        let result = PedalboxReport::from_bytes(bytes);
+
+        // Then
+        assert_eq!(
+            result.map(|report| (report.x, report.y, report.z, report.buttons)),
+            expected
+        );
+    }
+
+    #[test]
+    fn when_status_report_is_serialized_then_it_holds_faults_temperature_version_and_uptime() {
+        // Given
+        let mut snapshot = PedalSnapshot::UNKNOWN;
+        snapshot.axes[Axis::Y.index()].faults = FaultFlags::NOT_READY | FaultFlags::STUCK;
+        let report = StatusReport::new(&snapshot, -15, [1, 2, 3], 0x0102_0304);
+
+        // When
+        let result = bytemuck::bytes_of(&report);
+
+        // Then
+        assert_eq!(
+            result,
+            [0x02, 0x00, 0x0C, 0x00, 0xF1, 0xFF, 1, 2, 3, 0x04, 0x03, 0x02, 0x01]
+        );
+        assert_eq!(
+            report.faults(Axis::Y),
+            FaultFlags::NOT_READY | FaultFlags::STUCK
+        );
+        assert_eq!(report.version(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn when_diagnostics_report_is_serialized_then_it_holds_the_raw_readings() {
+        // Given
+        let report = DiagnosticsReport::new([12_400, -8_388_608, 0x0102]);
+
+        // When
+        let result = bytemuck::bytes_of(&report);
+
+        // Then
+        assert_eq!(
+            result,
+            [0x03, 0x70, 0x30, 0, 0, 0x00, 0x00, 0x80, 0xFF, 0x02, 0x01, 0, 0]
+        );
+        assert_eq!(report.raw(Axis::Y), -8_388_608);
+    }
+
+    #[test]
+    fn when_reports_of_every_kind_arrive_then_each_is_parsed_by_its_id() {
+        // Given
+        let pedals = PedalboxReport::new(1, 2, 3, 4);
+        let status = StatusReport::new(&PedalSnapshot::UNKNOWN, 250, [0, 1, 0], 60);
+        let diagnostics = DiagnosticsReport::new([1, 2, 3]);
+
+        // When
+        let result = (
+            StatusReport::from_bytes(bytemuck::bytes_of(&pedals)),
+            PedalboxReport::from_bytes(bytemuck::bytes_of(&status)),
+            DiagnosticsReport::from_bytes(bytemuck::bytes_of(&diagnostics)),
+        );
+
+        // Then
+        assert!(result.0.is_none());
+        assert!(result.1.is_none());
+        assert_eq!(result.2.map(|report| report.raw(Axis::Z)), Some(3));
+    }
+
+    #[test]
+    fn when_endpoint_is_sized_then_every_report_fits() {
+        // When
+        let size = MAX_INPUT_REPORT_SIZE;
+
+        // Then
+        assert_eq!(size, 13);
+        assert!(PedalboxReport::SIZE <= size);
+    }
+}
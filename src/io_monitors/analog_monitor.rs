@@ -1,8 +1,16 @@
+use crate::curve::ResponseCurve;
+use crate::deadzone::Deadzone;
+use crate::fault::{FaultConfig, FaultDetector};
+use crate::filters::FilterChain;
 use crate::fmt::debug;
 #[cfg(target_arch = "x86_64")]
 use crate::fmt::defmt::Format;
+use crate::io_monitors::axis_output::AxisOutput;
+use crate::io_monitors::{AxisMonitor, Monitor};
+use crate::pedal_state::AxisChannel;
+use crate::settings::AxisSettings;
 use crate::{AnalogRead, Mapping};
-use core::sync::atomic::{AtomicI16, Ordering};
+use core::time::Duration;
 #[cfg(target_arch = "arm")]
 use defmt::Format;
 
@@ -13,9 +21,14 @@ where
 {
     pub range_min: T,
     pub range_max: T,
+    pub curve: ResponseCurve,
+    pub deadzone: Deadzone,
+    pub filter: FilterChain,
+    pub faults: FaultConfig,
+    pub period: Duration,
     pub adc: Adc,
     pub pin: Pin,
-    pub output_channel: &'static AtomicI16,
+    pub output_channel: AxisChannel,
 }
 
 pub struct AnalogMonitor<Adc, Pin, T>
@@ -23,12 +36,10 @@ where
     Adc: AnalogRead<Pin, ReturnType = T>,
     T: Mapping,
 {
-    name: &'static str,
-    range_min: T,
-    range_max: T,
+    output: AxisOutput<T>,
+    period: Duration,
     adc: Adc,
     pin: Pin,
-    output_channel: &'static AtomicI16,
 }
 
 impl<Adc, Pin, T> AnalogMonitor<Adc, Pin, T>
@@ -41,32 +52,85 @@ where
         config: AnalogMonitorConfig<Adc, Pin, T>,
     ) -> AnalogMonitor<Adc, Pin, T> {
         Self {
-            name,
+            output: AxisOutput::new(
+                name,
+                (config.range_min, config.range_max),
+                config.curve,
+                config.deadzone,
+                config.filter,
+                FaultDetector::new(config.faults, config.period),
+                config.output_channel,
+            ),
+            period: config.period,
             adc: config.adc,
             pin: config.pin,
-            range_min: config.range_min,
-            range_max: config.range_max,
-            output_channel: config.output_channel,
         }
     }
+}
 
-    pub fn run(&mut self) {
+impl<Adc, Pin, T> Monitor for AnalogMonitor<Adc, Pin, T>
+where
+    Adc: AnalogRead<Pin, ReturnType = T>,
+    T: Mapping + Format,
+{
+    type Sample = T;
+    type Output = i16;
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn sample(&mut self) -> Option<T> {
         let raw_reading = self.adc.read(&mut self.pin);
-        let mapped_reading = raw_reading.map_to_i16(self.range_min, self.range_max);
-        self.output_channel.store(mapped_reading, Ordering::Relaxed);
+        self.output.check_reading(raw_reading.into());
+        Some(raw_reading)
+    }
+
+    fn process(&mut self, raw_reading: T) -> i16 {
+        let mapped_reading = self.output.process(raw_reading.into());
         debug!(
             "Analog Monitor[{}]: Raw -> {}\tMapped -> {}",
-            self.name, raw_reading, mapped_reading
+            self.output.name, raw_reading, mapped_reading
         );
+        mapped_reading
+    }
+
+    fn publish(&mut self, mapped_reading: i16) {
+        self.output.publish(mapped_reading, true);
+    }
+}
+
+impl<Adc, Pin, T> AxisMonitor for AnalogMonitor<Adc, Pin, T>
+where
+    Adc: AnalogRead<Pin, ReturnType = T>,
+    T: Mapping + Format + TryFrom<i32>,
+{
+    fn apply_settings(&mut self, settings: &AxisSettings) {
+        self.output.apply_settings(settings);
+    }
+
+    fn reading(&self) -> Option<i64> {
+        self.output.reading()
     }
 }
 
 #[cfg(test)]
 mod analog_monitor_testing {
+    use crate::axis::Axis;
+    use crate::calibration::AxisCalibration;
+    use crate::curve::ResponseCurve;
+    use crate::deadzone::{Deadzone, DeadzoneWidth};
+    use crate::fault::{FaultConfig, FaultFlags};
+    use crate::filters::test_signals::noise;
+    use crate::filters::{Filter, FilterChain, OneEuroConfig};
     use crate::io_monitors::analog_monitor::{AnalogMonitor, AnalogMonitorConfig};
+    use crate::io_monitors::{AxisMonitor, Monitor, Runnable};
+    use crate::pedal_state::{AxisChannel, PedalState};
+    use crate::settings::AxisSettings;
     use crate::AnalogRead;
     use alloc::boxed::Box;
-    use core::sync::atomic::{AtomicI16, Ordering};
+    use alloc::vec::Vec;
+    use core::time::Duration;
     use rstest::rstest;
 
     #[derive(Eq, PartialEq, Debug, Copy, Clone)]
@@ -85,6 +149,27 @@ mod analog_monitor_testing {
         }
     }
 
+    #[derive(Eq, PartialEq, Debug, Clone)]
+    struct MockNoisyPin {
+        pub values: Vec<u16>,
+        pub index: usize,
+    }
+
+    impl AnalogRead<MockNoisyPin> for MockAdc {
+        type ReturnType = u16;
+
+        fn read(&mut self, pin: &mut MockNoisyPin) -> Self::ReturnType {
+            let value = pin.values[pin.index % pin.values.len()];
+            pin.index += 1;
+            value
+        }
+    }
+
+    fn channel() -> AxisChannel {
+        let state = Box::leak(Box::new(PedalState::new(|| 0)));
+        state.axis_channel(Axis::X)
+    }
+
     #[test]
     fn when_creating_new_monitor() {
         // Given
@@ -97,20 +182,28 @@ mod analog_monitor_testing {
         let config = AnalogMonitorConfig {
             range_min,
             range_max,
+            curve: ResponseCurve::Linear,
+            deadzone: Deadzone::NONE,
+            filter: FilterChain::NONE,
+            faults: FaultConfig::DISABLED,
             adc: adc.clone(),
             pin: pin.clone(),
-            output_channel: Box::leak(Box::new(AtomicI16::default())),
+            period: Duration::from_millis(5),
+            output_channel: channel(),
         };
 
         // When
         let result = AnalogMonitor::new(name, config);
 
         // Then
-        assert_eq!(result.name, name);
+        assert_eq!(result.output.name, name);
         assert_eq!(result.adc, adc);
         assert_eq!(result.pin, pin);
-        assert_eq!(result.range_min, range_min);
-        assert_eq!(result.range_max, range_max);
+        assert_eq!(result.output.range_min, range_min);
+        assert_eq!(result.output.range_max, range_max);
+        assert_eq!(result.output.curve, ResponseCurve::Linear);
+        assert_eq!(result.output.filter.chain(), FilterChain::NONE);
+        assert_eq!(Monitor::period(&result), Duration::from_millis(5));
     }
 
     #[rstest]
@@ -129,14 +222,19 @@ mod analog_monitor_testing {
         // Given
         let adc = MockAdc {};
         let pin = MockPin { value };
-        let output = Box::leak(Box::new(AtomicI16::default()));
+        let output = channel();
         let mut monitor = AnalogMonitor::new(
             "test",
             AnalogMonitorConfig {
                 range_min: minimum,
                 range_max: maximum,
+                curve: ResponseCurve::Linear,
+                deadzone: Deadzone::NONE,
+                filter: FilterChain::NONE,
+                faults: FaultConfig::DISABLED,
                 adc,
                 pin,
+                period: Duration::from_millis(5),
                 output_channel: output,
             },
         );
@@ -145,7 +243,266 @@ mod analog_monitor_testing {
         monitor.run();
 
         // Then
-        let result = output.load(Ordering::Relaxed);
+        let result = output.value();
         assert_eq!(result, expected);
     }
+
+    #[rstest]
+    #[case(ResponseCurve::Linear, -1)]
+    #[case(ResponseCurve::Progressive(100), -16385)]
+    #[case(ResponseCurve::Progressive(-100), 16382)]
+    #[case(ResponseCurve::SCurve(100), -2)]
+    fn when_response_curve_is_configured(#[case] curve: ResponseCurve, #[case] expected: i16) {
+        // Given
+        let output = channel();
+        let mut monitor = AnalogMonitor::new(
+            "test",
+            AnalogMonitorConfig {
+                range_min: 0,
+                range_max: 100,
+                curve,
+                deadzone: Deadzone::NONE,
+                filter: FilterChain::NONE,
+                faults: FaultConfig::DISABLED,
+                adc: MockAdc {},
+                pin: MockPin { value: 50 },
+                period: Duration::from_millis(5),
+                output_channel: output,
+            },
+        );
+
+        // When
+        monitor.run();
+
+        // Then
+        let result = output.value();
+        assert_eq!(result, expected);
+    }
+
+    #[rstest]
+    #[case(1800, i16::MIN)]
+    #[case(1830, i16::MIN)]
+    #[case(1840, i16::MIN)]
+    #[case(2460, -1)]
+    #[case(3080, i16::MAX)]
+    #[case(3090, i16::MAX)]
+    #[case(3100, i16::MAX)]
+    fn when_deadzones_are_configured(#[case] value: u16, #[case] expected: i16) {
+        // Given
+        let output = channel();
+        let mut monitor = AnalogMonitor::new(
+            "test",
+            AnalogMonitorConfig {
+                range_min: 1820,
+                range_max: 3100,
+                curve: ResponseCurve::Linear,
+                deadzone: Deadzone {
+                    lower: DeadzoneWidth::Raw(20),
+                    upper: DeadzoneWidth::Raw(20),
+                    saturation: 0,
+                },
+                filter: FilterChain::NONE,
+                faults: FaultConfig::DISABLED,
+                adc: MockAdc {},
+                pin: MockPin { value },
+                period: Duration::from_millis(5),
+                output_channel: output,
+            },
+        );
+
+        // When
+        monitor.run();
+
+        // Then
+        let result = output.value();
+        assert_eq!(result, expected);
+    }
+
+    #[rstest]
+    #[case(0, 2_400, 6_553)]
+    #[case(5, 2_400, 6_553)]
+    #[case(5, 3_810, i16::MAX)]
+    #[case(10, 3_700, i16::MAX)]
+    fn when_saturation_zone_is_configured(
+        #[case] saturation: u8,
+        #[case] value: u16,
+        #[case] expected: i16,
+    ) {
+        // Given
+        let output = channel();
+        let mut monitor = AnalogMonitor::new(
+            "test",
+            AnalogMonitorConfig {
+                range_min: 0,
+                range_max: 4000,
+                curve: ResponseCurve::Linear,
+                deadzone: Deadzone {
+                    lower: DeadzoneWidth::Percent(0),
+                    upper: DeadzoneWidth::Percent(0),
+                    saturation,
+                },
+                filter: FilterChain::NONE,
+                faults: FaultConfig::DISABLED,
+                adc: MockAdc {},
+                pin: MockPin { value },
+                period: Duration::from_millis(5),
+                output_channel: output,
+            },
+        );
+
+        // When
+        monitor.run();
+
+        // Then
+        let result = output.value();
+        assert_eq!(result, expected);
+    }
+
+    fn run_noisy_sequence(filter: FilterChain) -> Vec<i16> {
+        let values = noise(400, 40)
+            .iter()
+            .map(|noise| (2460 + noise) as u16)
+            .collect();
+        let output = channel();
+        let mut monitor = AnalogMonitor::new(
+            "test",
+            AnalogMonitorConfig {
+                range_min: 1820,
+                range_max: 3100,
+                curve: ResponseCurve::Linear,
+                deadzone: Deadzone::NONE,
+                filter,
+                faults: FaultConfig::DISABLED,
+                adc: MockAdc {},
+                pin: MockNoisyPin { values, index: 0 },
+                period: Duration::from_millis(5),
+                output_channel: output,
+            },
+        );
+
+        (0..400)
+            .map(|_| {
+                monitor.run();
+                output.value()
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case(FilterChain::new(&[Filter::Ema { alpha: u16::MAX / 16 }]).unwrap())]
+    #[case(FilterChain::new(&[Filter::Median { window: 9 }, Filter::Ema { alpha: u16::MAX / 8 }]).unwrap())]
+    #[case(FilterChain::new(&[Filter::OneEuro(OneEuroConfig {
+        min_cutoff_mhz: 1_000,
+        beta_uhz: 1_000,
+        derivative_cutoff_mhz: 1_000,
+        sample_rate_hz: 200,
+    })]).unwrap())]
+    fn when_noisy_sequence_is_filtered_then_output_is_bounded(#[case] filter: FilterChain) {
+        // Given
+        let bound = -1 - 1_300..=-1 + 1_300;
+        let unfiltered = run_noisy_sequence(FilterChain::NONE);
+
+        // When
+        let result = run_noisy_sequence(filter);
+
+        // Then
+        assert!(unfiltered.iter().any(|value| !bound.contains(value)));
+        assert!(result.iter().skip(50).all(|value| bound.contains(value)));
+    }
+
+    #[rstest]
+    #[case(AxisCalibration::new(0, 200), (0, 200), -1)]
+    #[case(AxisCalibration::new(0, 400), (0, 400), -16_385)]
+    #[case(AxisCalibration::new(200, 0), (0, 200), -1)]
+    #[case(AxisCalibration::new(-1, 200), (0, 200), -1)]
+    fn when_settings_are_applied(
+        #[case] calibration: AxisCalibration,
+        #[case] expected_range: (u16, u16),
+        #[case] expected: i16,
+    ) {
+        // Given
+        let output = channel();
+        let mut monitor = AnalogMonitor::new(
+            "test",
+            AnalogMonitorConfig {
+                range_min: 0,
+                range_max: 200,
+                curve: ResponseCurve::Linear,
+                deadzone: Deadzone::NONE,
+                filter: FilterChain::NONE,
+                faults: FaultConfig::DISABLED,
+                adc: MockAdc {},
+                pin: MockPin { value: 100 },
+                period: Duration::from_millis(5),
+                output_channel: output,
+            },
+        );
+        let filter = FilterChain::new(&[Filter::Median { window: 3 }]).unwrap();
+
+        // When
+        monitor.apply_settings(&AxisSettings {
+            calibration,
+            filter,
+            ..AxisSettings::DEFAULT
+        });
+        monitor.run();
+
+        // Then
+        assert_eq!(
+            (monitor.output.range_min, monitor.output.range_max),
+            expected_range
+        );
+        assert_eq!(monitor.output.filter.chain(), filter);
+        assert_eq!(output.value(), expected);
+    }
+
+    #[rstest]
+    #[case(0, FaultFlags::RAIL_LOW)]
+    #[case(4_095, FaultFlags::RAIL_HIGH)]
+    fn when_wire_breaks_then_the_safe_value_is_published(
+        #[case] broken_value: u16,
+        #[case] expected_faults: FaultFlags,
+    ) {
+        // Given
+        let output = channel();
+        let mut monitor = AnalogMonitor::new(
+            "test",
+            AnalogMonitorConfig {
+                range_min: 1820,
+                range_max: 3100,
+                curve: ResponseCurve::Linear,
+                deadzone: Deadzone::NONE,
+                filter: FilterChain::NONE,
+                faults: FaultConfig {
+                    rail_low: Some(10),
+                    rail_high: Some(4_085),
+                    safe_value: i16::MIN,
+                    ..FaultConfig::DISABLED
+                },
+                adc: MockAdc {},
+                pin: MockPin { value: 3_100 },
+                period: Duration::from_millis(5),
+                output_channel: output,
+            },
+        );
+        monitor.run();
+        let before_break = output.state();
+
+        // When
+        monitor.pin.value = broken_value;
+        monitor.run();
+        let broken = output.state();
+        monitor.pin.value = 3_100;
+        monitor.run();
+
+        // Then
+        assert_eq!((before_break.value, before_break.healthy), (i16::MAX, true));
+        assert_eq!((broken.value, broken.healthy), (i16::MIN, false));
+        assert_eq!(broken.faults, expected_faults);
+        assert_eq!(broken.raw, broken_value as i64);
+        assert_eq!(monitor.reading(), Some(3_100));
+        assert_eq!(output.state().value, i16::MAX);
+        assert_eq!(output.state().faults, FaultFlags::NONE);
+        assert_eq!(output.state().latched_faults, expected_faults);
+    }
 }
@@ -0,0 +1,147 @@
+use crate::curve::ResponseCurve;
+use crate::deadzone::Deadzone;
+use crate::fault::{FaultDetector, FaultFlags};
+use crate::filters::{FilterChain, FilterPipeline};
+use crate::fmt::{info, warn};
+use crate::pedal_state::AxisChannel;
+use crate::settings::AxisSettings;
+use crate::Mapping;
+
+/// The part shared by the axis monitors: turns samples into the value of the axis through
+/// the filter, range, deadzone and curve, and publishes it, or the safe value while the
+/// sensor is faulty.
+pub(crate) struct AxisOutput<T>
+where
+    T: Mapping,
+{
+    pub(crate) name: &'static str,
+    pub(crate) range_min: T,
+    pub(crate) range_max: T,
+    pub(crate) curve: ResponseCurve,
+    pub(crate) deadzone: Deadzone,
+    pub(crate) filter: FilterPipeline,
+    pub(crate) reading: Option<i64>,
+    /// Last sample of the sensor, before any processing.
+    raw: Option<i64>,
+    faults: FaultDetector,
+    output_channel: AxisChannel,
+}
+
+impl<T> AxisOutput<T>
+where
+    T: Mapping,
+{
+    pub(crate) fn new(
+        name: &'static str,
+        (range_min, range_max): (T, T),
+        curve: ResponseCurve,
+        deadzone: Deadzone,
+        filter: FilterChain,
+        faults: FaultDetector,
+        output_channel: AxisChannel,
+    ) -> Self {
+        Self {
+            name,
+            range_min,
+            range_max,
+            curve,
+            deadzone,
+            filter: FilterPipeline::new(filter),
+            reading: None,
+            raw: None,
+            faults,
+            output_channel,
+        }
+    }
+
+    /// Filters the sample and maps it to the axis.
+    pub(crate) fn process(&mut self, sample: i64) -> i16 {
+        let filtered_reading = self.filter.apply(sample);
+        self.reading = Some(filtered_reading);
+        let (min, max) = self
+            .deadzone
+            .range(self.range_min.into(), self.range_max.into());
+        self.deadzone
+            .saturate(self.curve.apply(filtered_reading.map_to_i16(min, max)))
+    }
+
+    pub(crate) fn publish(&self, value: i16, healthy: bool) {
+        let raw = self.raw.unwrap_or_default();
+        if self.faults.faults().is_empty() {
+            self.output_channel.publish(value, raw, healthy);
+        } else {
+            self.output_channel
+                .publish(self.faults.config().safe_value, raw, false);
+        }
+    }
+
+    /// Last filtered reading, `None` while the sensor is faulty.
+    pub(crate) fn reading(&self) -> Option<i64> {
+        self.reading.filter(|_| self.faults.faults().is_empty())
+    }
+
+    /// Checks a raw reading of the sensor for faults, it is published along with the value.
+    pub(crate) fn check_reading(&mut self, raw: i64) {
+        self.raw = Some(raw);
+        let before = self.faults.faults();
+        let faults = self.faults.reading(raw);
+        self.update_faults(before, faults);
+    }
+
+    /// Records that the sensor had no new reading.
+    pub(crate) fn check_missing(&mut self) {
+        let before = self.faults.faults();
+        let faults = self.faults.missing();
+        self.update_faults(before, faults);
+    }
+
+    /// Records that the sensor couldn't be read.
+    pub(crate) fn check_read_error(&mut self) {
+        let before = self.faults.faults();
+        let faults = self.faults.read_error();
+        self.update_faults(before, faults);
+    }
+
+    /// Publishes the safe value as soon as a fault is raised, the sensor may not deliver
+    /// another sample to publish it with.
+    fn update_faults(&mut self, before: FaultFlags, faults: FaultFlags) {
+        if faults == before {
+            return;
+        }
+        let raised = faults.without(before);
+        let cleared = before.without(faults);
+        if !raised.is_empty() {
+            warn!("Monitor[{}]: Fault {} raised", self.name, raised.0);
+        }
+        if !cleared.is_empty() {
+            info!("Monitor[{}]: Fault {} cleared", self.name, cleared.0);
+        }
+        self.output_channel.set_faults(faults);
+        if !raised.is_empty() {
+            self.publish(self.faults.config().safe_value, false);
+        }
+    }
+
+    /// Takes over new settings. The filter state is only dropped when the filter chain
+    /// changes, and an invalid range keeps the previous one.
+    pub(crate) fn apply_settings(&mut self, settings: &AxisSettings)
+    where
+        T: TryFrom<i32>,
+    {
+        match settings.calibration.range() {
+            Some((min, max)) => {
+                self.range_min = min;
+                self.range_max = max;
+            }
+            None => warn!(
+                "Monitor[{}]: Ignored invalid range {}..{}",
+                self.name, settings.calibration.range_min, settings.calibration.range_max
+            ),
+        }
+        self.curve = settings.curve;
+        self.deadzone = settings.deadzone;
+        if self.filter.chain() != settings.filter {
+            self.filter = FilterPipeline::new(settings.filter);
+        }
+    }
+}
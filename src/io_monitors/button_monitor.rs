@@ -0,0 +1,347 @@
+use crate::fmt::{debug, warn};
+use crate::io_monitors::Monitor;
+use crate::pedal_state::ButtonChannel;
+use core::time::Duration;
+use embedded_hal::digital::v2::InputPin;
+
+/// Number of buttons the input report has room for.
+pub const MAX_BUTTONS: usize = 8;
+
+/// How a bouncing contact is turned into a clean press and release. Both count in
+/// samples, so the time they take depends on how often the monitor runs.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Debounce {
+    /// A counter moves towards `samples` while the contact reads pressed and towards zero
+    /// while it reads released. The button changes state when the counter hits an end.
+    Integration { samples: u8 },
+    /// The button takes the level of the contact once it read the same for `samples` in a
+    /// row. Every bounce restarts the wait.
+    Timer { samples: u8 },
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+struct Debouncer {
+    pressed: bool,
+    count: u8,
+}
+
+impl Debouncer {
+    fn update(&mut self, contact: bool, debounce: Debounce) -> bool {
+        match debounce {
+            Debounce::Integration { samples } => {
+                self.count = match contact {
+                    true => (self.count + 1).min(samples),
+                    false => self.count.saturating_sub(1),
+                };
+                if self.count >= samples {
+                    self.pressed = true;
+                } else if self.count == 0 {
+                    self.pressed = false;
+                }
+            }
+            Debounce::Timer { samples } => {
+                if contact == self.pressed {
+                    self.count = 0;
+                } else {
+                    self.count += 1;
+                    if self.count >= samples {
+                        self.pressed = contact;
+                        self.count = 0;
+                    }
+                }
+            }
+        }
+        self.pressed
+    }
+}
+
+pub struct ButtonMonitorConfig<P, const N: usize>
+where
+    P: InputPin,
+{
+    pub pins: [P; N],
+    /// The contacts pull the pins low when pressed.
+    pub active_low: bool,
+    pub debounce: Debounce,
+    pub period: Duration,
+    pub output_channel: ButtonChannel,
+}
+
+/// Debounces up to [`MAX_BUTTONS`] contacts and publishes them as a bitmask, the first pin
+/// being bit 0.
+pub struct ButtonMonitor<P, const N: usize>
+where
+    P: InputPin,
+{
+    name: &'static str,
+    pins: [P; N],
+    active_low: bool,
+    debounce: Debounce,
+    debouncers: [Debouncer; N],
+    period: Duration,
+    output_channel: ButtonChannel,
+}
+
+impl<P, const N: usize> ButtonMonitor<P, N>
+where
+    P: InputPin,
+{
+    pub fn new(name: &'static str, config: ButtonMonitorConfig<P, N>) -> Self {
+        const { assert!(N <= MAX_BUTTONS, "Too many buttons for the input report") };
+        Self {
+            name,
+            pins: config.pins,
+            active_low: config.active_low,
+            debounce: config.debounce,
+            debouncers: [Debouncer::default(); N],
+            period: config.period,
+            output_channel: config.output_channel,
+        }
+    }
+
+    /// Debounced state of the buttons.
+    pub fn buttons(&self) -> u8 {
+        self.debouncers
+            .iter()
+            .enumerate()
+            .filter(|(_, debouncer)| debouncer.pressed)
+            .fold(0, |mask, (index, _)| mask | 1 << index)
+    }
+}
+
+impl<P, const N: usize> Monitor for ButtonMonitor<P, N>
+where
+    P: InputPin,
+{
+    /// Contacts that read closed, a contact that couldn't be read keeps its button state.
+    type Sample = u8;
+    type Output = u8;
+
+    fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn sample(&mut self) -> Option<u8> {
+        let mut contacts = 0;
+        for (index, (pin, debouncer)) in self.pins.iter().zip(&self.debouncers).enumerate() {
+            let closed = match pin.is_high() {
+                Ok(high) => high != self.active_low,
+                Err(_) => {
+                    warn!(
+                        "Button Monitor[{}]: Failed to read button {}",
+                        self.name, index
+                    );
+                    debouncer.pressed
+                }
+            };
+            contacts |= (closed as u8) << index;
+        }
+        Some(contacts)
+    }
+
+    fn process(&mut self, contacts: u8) -> u8 {
+        for (index, debouncer) in self.debouncers.iter_mut().enumerate() {
+            debouncer.update(contacts & 1 << index != 0, self.debounce);
+        }
+        self.buttons()
+    }
+
+    fn publish(&mut self, buttons: u8) {
+        if self.output_channel.buttons() != buttons {
+            debug!("Button Monitor[{}]: Buttons -> {:08b}", self.name, buttons);
+        }
+        self.output_channel.publish(buttons);
+    }
+}
+
+#[cfg(test)]
+mod button_monitor_testing {
+    use crate::io_monitors::button_monitor::{
+        ButtonMonitor, ButtonMonitorConfig, Debounce, Debouncer,
+    };
+    use crate::io_monitors::Runnable;
+    use crate::pedal_state::{ButtonChannel, PedalState};
+    use alloc::boxed::Box;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    use core::cell::Cell;
+    use core::time::Duration;
+    use embedded_hal::digital::v2::InputPin;
+    use rstest::rstest;
+
+    /// Plays back a scripted sequence of levels, one per read, and keeps the last one.
+    struct MockPin {
+        levels: Vec<Option<bool>>,
+        position: Cell<usize>,
+    }
+
+    impl MockPin {
+        fn new(script: &str) -> Self {
+            let levels = script
+                .chars()
+                .map(|level| match level {
+                    '1' => Some(true),
+                    '0' => Some(false),
+                    _ => None,
+                })
+                .collect();
+            Self {
+                levels,
+                position: Cell::new(0),
+            }
+        }
+    }
+
+    impl InputPin for MockPin {
+        type Error = ();
+
+        fn is_high(&self) -> Result<bool, Self::Error> {
+            let position = self.position.get();
+            self.position.set(position + 1);
+            let level = self.levels[position.min(self.levels.len() - 1)];
+            level.ok_or(())
+        }
+
+        fn is_low(&self) -> Result<bool, Self::Error> {
+            self.is_high().map(|high| !high)
+        }
+    }
+
+    fn channel() -> ButtonChannel {
+        let state = Box::leak(Box::new(PedalState::new(|| 0)));
+        state.button_channel()
+    }
+
+    fn debounce(script: &str, debounce: Debounce) -> String {
+        let mut debouncer = Debouncer::default();
+        script
+            .chars()
+            .map(|level| match debouncer.update(level == '1', debounce) {
+                true => '1',
+                false => '0',
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case("0000000000", "0000000000")]
+    #[case("1111111111", "0011111111")]
+    #[case("1010111100", "0000001111")]
+    #[case("1111011010", "0011111111")]
+    #[case("1111100000", "0011111000")]
+    fn when_contact_is_integrated(#[case] contact: &str, #[case] expected: &str) {
+        // When
+        let result = debounce(contact, Debounce::Integration { samples: 3 });
+
+        // Then
+        assert_eq!(result, expected);
+    }
+
+    #[rstest]
+    #[case("0000000000", "0000000000")]
+    #[case("1111111111", "0011111111")]
+    #[case("1010111100", "0000001111")]
+    #[case("1101101110", "0000000011")]
+    #[case("1110100000", "0011111000")]
+    fn when_contact_is_timed(#[case] contact: &str, #[case] expected: &str) {
+        // When
+        let result = debounce(contact, Debounce::Timer { samples: 3 });
+
+        // Then
+        assert_eq!(result, expected);
+    }
+
+    #[rstest]
+    #[case(Debounce::Integration { samples: 1 })]
+    #[case(Debounce::Timer { samples: 1 })]
+    fn when_single_sample_is_enough(#[case] debounce_config: Debounce) {
+        // When
+        let result = debounce("0110100", debounce_config);
+
+        // Then
+        assert_eq!(result, "0110100");
+    }
+
+    #[test]
+    fn when_buttons_bounce_then_bitmask_is_published_once_settled() {
+        // Given
+        let output_channel = channel();
+        let mut monitor = ButtonMonitor::new(
+            "BUTTONS",
+            ButtonMonitorConfig {
+                pins: [
+                    MockPin::new("0101011111"),
+                    MockPin::new("0000000000"),
+                    MockPin::new("1111111111"),
+                ],
+                active_low: false,
+                debounce: Debounce::Integration { samples: 4 },
+                period: Duration::from_millis(1),
+                output_channel,
+            },
+        );
+
+        // When
+        let published: Vec<u8> = (0..10)
+            .map(|_| {
+                monitor.run();
+                output_channel.buttons()
+            })
+            .collect();
+
+        // Then
+        assert_eq!(published, [0, 0, 0, 4, 4, 4, 4, 4, 5, 5]);
+        assert_eq!(monitor.buttons(), 0b101);
+    }
+
+    #[test]
+    fn when_contact_is_active_low_then_low_level_is_pressed() {
+        // Given
+        let output_channel = channel();
+        let mut monitor = ButtonMonitor::new(
+            "BUTTONS",
+            ButtonMonitorConfig {
+                pins: [MockPin::new("1"), MockPin::new("0")],
+                active_low: true,
+                debounce: Debounce::Timer { samples: 2 },
+                period: Duration::from_millis(1),
+                output_channel,
+            },
+        );
+
+        // When
+        monitor.run();
+        monitor.run();
+
+        // Then
+        assert_eq!(output_channel.buttons(), 0b10);
+    }
+
+    #[test]
+    fn when_pin_cannot_be_read_then_button_keeps_its_state() {
+        // Given
+        let output_channel = channel();
+        let mut monitor = ButtonMonitor::new(
+            "BUTTONS",
+            ButtonMonitorConfig {
+                pins: [MockPin::new("11xxx0")],
+                active_low: false,
+                debounce: Debounce::Timer { samples: 2 },
+                period: Duration::from_millis(1),
+                output_channel,
+            },
+        );
+
+        // When
+        let published: Vec<u8> = (0..6)
+            .map(|_| {
+                monitor.run();
+                output_channel.buttons()
+            })
+            .collect();
+
+        // Then
+        assert_eq!(published, [0, 1, 1, 1, 1, 1]);
+    }
+}
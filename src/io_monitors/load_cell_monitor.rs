@@ -1,8 +1,19 @@
+use crate::curve::ResponseCurve;
+use crate::deadzone::Deadzone;
+use crate::fault::{FaultConfig, FaultDetector};
+use crate::filters::FilterChain;
 #[cfg(target_arch = "x86_64")]
 use crate::fmt::defmt::Format;
-use crate::fmt::{debug, warn};
+use crate::fmt::{debug, info, warn};
+use crate::io_monitors::axis_output::AxisOutput;
+use crate::io_monitors::{AxisMonitor, Monitor};
+use crate::pedal_state::AxisChannel;
+use crate::settings::AxisSettings;
+use crate::spike::{RejectAction, SpikeRejection, SpikeRejector, Verdict};
+use crate::tare::{Tare, TareConfig, TareRequest, TareStatus};
 use crate::{LoadCell, Mapping};
-use core::sync::atomic::{AtomicI16, Ordering};
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::time::Duration;
 #[cfg(target_arch = "arm")]
 use defmt::Format;
 
@@ -13,8 +24,40 @@ where
 {
     pub range_min: T,
     pub range_max: T,
+    pub curve: ResponseCurve,
+    pub deadzone: Deadzone,
+    pub filter: FilterChain,
+    pub spike_rejection: SpikeRejection,
+    pub tare: TareConfig,
+    pub tare_request: &'static TareRequest,
+    pub faults: FaultConfig,
+    pub period: Duration,
     pub load_cell: L,
-    pub output_channel: &'static AtomicI16,
+    pub output_channel: AxisChannel,
+    pub statistics: &'static LoadCellStatistics,
+}
+
+/// Counters shared with the rest of the firmware to tell how healthy the load cell is.
+#[derive(Debug, Default)]
+pub struct LoadCellStatistics {
+    rejected_samples: AtomicU32,
+}
+
+impl LoadCellStatistics {
+    pub const fn new() -> Self {
+        Self {
+            rejected_samples: AtomicU32::new(0),
+        }
+    }
+
+    /// Number of samples dropped by the spike rejection since boot.
+    pub fn rejected_samples(&self) -> u32 {
+        self.rejected_samples.load(Ordering::Relaxed)
+    }
+
+    fn record_rejection(&self) -> u32 {
+        self.rejected_samples.fetch_add(1, Ordering::Relaxed) + 1
+    }
 }
 
 pub struct LoadCellMonitor<L, T>
@@ -22,11 +65,15 @@ where
     L: LoadCell<ReturnType = T>,
     T: Mapping,
 {
-    name: &'static str,
-    range_min: T,
-    range_max: T,
+    output: AxisOutput<T>,
+    period: Duration,
+    spike_rejector: SpikeRejector,
+    tare: Tare,
+    tare_request: &'static TareRequest,
+    offset: i64,
+    taring: bool,
     load_cell: L,
-    output_channel: &'static AtomicI16,
+    statistics: &'static LoadCellStatistics,
 }
 
 impl<L, T> LoadCellMonitor<L, T>
@@ -36,37 +83,163 @@ where
 {
     pub fn new(name: &'static str, config: LoadCellMonitorConfig<L, T>) -> LoadCellMonitor<L, T> {
         Self {
-            name,
-            range_min: config.range_min,
-            range_max: config.range_max,
+            output: AxisOutput::new(
+                name,
+                (config.range_min, config.range_max),
+                config.curve,
+                config.deadzone,
+                config.filter,
+                FaultDetector::new(config.faults, config.period),
+                config.output_channel,
+            ),
+            period: config.period,
+            spike_rejector: SpikeRejector::new(config.spike_rejection),
+            tare: Tare::new(config.tare),
+            tare_request: config.tare_request,
+            offset: 0,
+            taring: false,
             load_cell: config.load_cell,
-            output_channel: config.output_channel,
+            statistics: config.statistics,
+        }
+    }
+
+    /// Offset subtracted from the readings, measured by the last successful tare.
+    pub fn offset(&self) -> i64 {
+        self.offset
+    }
+
+    /// Returns the sample to use for this cycle, or `None` when there is nothing to publish.
+    fn reject_spikes(&mut self, raw_reading: T) -> Option<i64> {
+        match self.spike_rejector.check(raw_reading.into()) {
+            Verdict::Accepted(value) => Some(value),
+            Verdict::Rejected { last_good } => {
+                let rejected = self.statistics.record_rejection();
+                warn!(
+                    "Load Cell Monitor[{}]: Rejected implausible reading {} ({} so far)",
+                    self.output.name, raw_reading, rejected
+                );
+                match self.spike_rejector.config().action {
+                    RejectAction::Discard => None,
+                    RejectAction::HoldLastGood => Some(last_good),
+                }
+            }
+        }
+    }
+
+    /// Feeds the sample to the tare while one is in progress. Returns whether the sample
+    /// was used up by the tare.
+    fn collect_tare(&mut self, sample: i64) -> bool {
+        if self.tare_request.take() {
+            self.tare.start();
         }
+        match self.tare.add_sample(sample) {
+            TareStatus::Idle => false,
+            TareStatus::Collecting => true,
+            TareStatus::Done(offset) => {
+                self.offset = offset;
+                self.output.filter.reset();
+                info!(
+                    "Load Cell Monitor[{}]: Tared, offset -> {}",
+                    self.output.name, offset
+                );
+                true
+            }
+            TareStatus::Rejected { deviation } => {
+                warn!(
+                    "Load Cell Monitor[{}]: Tare rejected, deviation {} is too high",
+                    self.output.name, deviation
+                );
+                true
+            }
+        }
+    }
+}
+
+impl<L, T> Monitor for LoadCellMonitor<L, T>
+where
+    L: LoadCell<ReturnType = T>,
+    T: Mapping + Format,
+{
+    type Sample = i64;
+    type Output = i16;
+
+    fn period(&self) -> Duration {
+        self.period
     }
 
-    pub fn run(&mut self) {
+    /// The load cell converts slower than it is polled, so most polls find no new
+    /// conversion and publish nothing.
+    fn sample(&mut self) -> Option<i64> {
         match self.load_cell.read() {
             Ok(raw_reading) => {
-                let mapped_reading = raw_reading.map_to_i16(self.range_min, self.range_max);
-                self.output_channel.store(mapped_reading, Ordering::Relaxed);
-                debug!(
-                    "Analog Monitor[{}]: Raw -> {}\tMapped -> {}",
-                    self.name, raw_reading, mapped_reading
-                );
+                self.output.check_reading(raw_reading.into());
+                self.reject_spikes(raw_reading)
             }
-            Err(_) => {
-                warn!("Couldn't retrieve data")
+            Err(nb::Error::WouldBlock) => {
+                self.output.check_missing();
+                None
             }
+            Err(nb::Error::Other(_)) => {
+                warn!("Couldn't retrieve data");
+                self.output.check_read_error();
+                None
+            }
+        }
+    }
+
+    /// Publishes the bottom of the axis while a tare is in progress.
+    fn process(&mut self, sample: i64) -> i16 {
+        self.taring = self.collect_tare(sample);
+        if self.taring {
+            return i16::MIN;
         }
+        let mapped_reading = self.output.process(sample - self.offset);
+        debug!(
+            "Load Cell Monitor[{}]: Raw -> {}\tMapped -> {}",
+            self.output.name, sample, mapped_reading
+        );
+        mapped_reading
+    }
+
+    fn publish(&mut self, mapped_reading: i16) {
+        self.output.publish(mapped_reading, !self.taring);
+    }
+}
+
+impl<L, T> AxisMonitor for LoadCellMonitor<L, T>
+where
+    L: LoadCell<ReturnType = T>,
+    T: Mapping + Format + TryFrom<i32>,
+{
+    fn apply_settings(&mut self, settings: &AxisSettings) {
+        self.output.apply_settings(settings);
+    }
+
+    fn reading(&self) -> Option<i64> {
+        self.output.reading()
     }
 }
 
 #[cfg(test)]
 mod load_cell_monitor_testing {
-    use crate::io_monitors::load_cell_monitor::{LoadCellMonitor, LoadCellMonitorConfig};
+    use crate::axis::Axis;
+    use crate::curve::{CurvePoint, CurvePoints, ResponseCurve};
+    use crate::deadzone::Deadzone;
+    use crate::fault::{FaultConfig, FaultFlags};
+    use crate::filters::test_signals::noise_with_spikes;
+    use crate::filters::{Filter, FilterChain, OneEuroConfig};
+    use crate::hx711::LatestConversion;
+    use crate::io_monitors::load_cell_monitor::{
+        LoadCellMonitor, LoadCellMonitorConfig, LoadCellStatistics,
+    };
+    use crate::io_monitors::{AxisMonitor, Monitor, Runnable};
+    use crate::pedal_state::{AxisChannel, PedalState};
+    use crate::spike::{RejectAction, SpikeRejection};
+    use crate::tare::{TareConfig, TareRequest};
     use crate::LoadCell;
     use alloc::boxed::Box;
-    use core::sync::atomic::{AtomicI16, Ordering};
+    use alloc::vec::Vec;
+    use core::time::Duration;
     use rstest::rstest;
 
     #[derive(Eq, PartialEq, Debug, Copy, Clone)]
@@ -78,11 +251,54 @@ mod load_cell_monitor_testing {
         type ReturnType = i32;
         type Error = ();
 
-        fn read(&mut self) -> Result<Self::ReturnType, Self::Error> {
+        fn read(&mut self) -> nb::Result<Self::ReturnType, Self::Error> {
+            Ok(self.value)
+        }
+    }
+
+    #[derive(Eq, PartialEq, Debug, Clone)]
+    struct MockNoisyLoadCell {
+        values: Vec<i32>,
+        index: usize,
+    }
+
+    impl LoadCell for MockNoisyLoadCell {
+        type ReturnType = i32;
+        type Error = ();
+
+        fn read(&mut self) -> nb::Result<Self::ReturnType, Self::Error> {
+            let value = self.values[self.index % self.values.len()];
+            self.index += 1;
+            Ok(value)
+        }
+    }
+
+    /// Finishes a conversion every `polls_per_conversion` reads, like an HX711 polled
+    /// faster than its output data rate.
+    struct MockSlowLoadCell {
+        value: i32,
+        polls_per_conversion: u32,
+        polls: u32,
+    }
+
+    impl LoadCell for MockSlowLoadCell {
+        type ReturnType = i32;
+        type Error = ();
+
+        fn read(&mut self) -> nb::Result<Self::ReturnType, Self::Error> {
+            self.polls += 1;
+            if !self.polls.is_multiple_of(self.polls_per_conversion) {
+                return Err(nb::Error::WouldBlock);
+            }
             Ok(self.value)
         }
     }
 
+    fn channel() -> AxisChannel {
+        let state = Box::leak(Box::new(PedalState::new(|| 0)));
+        state.axis_channel(Axis::X)
+    }
+
     #[test]
     fn when_creating_new_monitor() {
         // Given
@@ -93,18 +309,33 @@ mod load_cell_monitor_testing {
         let config = LoadCellMonitorConfig {
             range_min,
             range_max,
+            curve: ResponseCurve::Linear,
+            deadzone: Deadzone::NONE,
+            filter: FilterChain::NONE,
+            spike_rejection: SpikeRejection::DISABLED,
+            tare: TareConfig::DISABLED,
+            tare_request: Box::leak(Box::new(TareRequest::new())),
+            faults: FaultConfig::DISABLED,
+            period: Duration::from_millis(10),
             load_cell,
-            output_channel: Box::leak(Box::new(AtomicI16::default())),
+            output_channel: channel(),
+            statistics: Box::leak(Box::new(LoadCellStatistics::new())),
         };
 
         // When
         let result = LoadCellMonitor::new(name, config);
 
         // Then
-        assert_eq!(result.name, name);
-        assert_eq!(result.range_min, range_min);
-        assert_eq!(result.range_max, range_max);
+        assert_eq!(result.output.name, name);
+        assert_eq!(result.output.range_min, range_min);
+        assert_eq!(result.output.range_max, range_max);
         assert_eq!(result.load_cell, load_cell);
+        assert_eq!(result.output.curve, ResponseCurve::Linear);
+        assert_eq!(result.output.filter.chain(), FilterChain::NONE);
+        assert_eq!(Monitor::period(&result), Duration::from_millis(10));
+        assert_eq!(result.spike_rejector.config(), SpikeRejection::DISABLED);
+        assert_eq!(result.tare.config(), TareConfig::DISABLED);
+        assert_eq!(result.offset(), 0);
     }
 
     #[rstest]
@@ -121,14 +352,64 @@ mod load_cell_monitor_testing {
     ) {
         // Given
         let load_cell = MockLoadCell { value };
-        let output = Box::leak(Box::new(AtomicI16::default()));
+        let output = channel();
         let mut monitor = LoadCellMonitor::new(
             "test",
             LoadCellMonitorConfig {
                 range_min: minimum,
                 range_max: maximum,
+                curve: ResponseCurve::Linear,
+                deadzone: Deadzone::NONE,
+                filter: FilterChain::NONE,
+                spike_rejection: SpikeRejection::DISABLED,
+                tare: TareConfig::DISABLED,
+                tare_request: Box::leak(Box::new(TareRequest::new())),
+                faults: FaultConfig::DISABLED,
                 load_cell,
+                period: Duration::from_millis(10),
+                output_channel: output,
+                statistics: Box::leak(Box::new(LoadCellStatistics::new())),
+            },
+        );
+
+        // When
+        monitor.run();
+
+        // Then
+        let result = output.value();
+        assert_eq!(result, expected);
+    }
+
+    #[rstest]
+    #[case(0, i16::MIN)]
+    #[case(25, -2)]
+    #[case(50, 10_921)]
+    #[case(100, i16::MAX)]
+    fn when_custom_curve_is_configured(#[case] value: i32, #[case] expected: i16) {
+        // Given
+        let points = CurvePoints::new(&[
+            CurvePoint::new(0, 0),
+            CurvePoint::new(16_384, 32_768),
+            CurvePoint::new(u16::MAX, u16::MAX),
+        ])
+        .unwrap();
+        let output = channel();
+        let mut monitor = LoadCellMonitor::new(
+            "test",
+            LoadCellMonitorConfig {
+                range_min: 0,
+                range_max: 100,
+                curve: ResponseCurve::PiecewiseLinear(points),
+                deadzone: Deadzone::NONE,
+                filter: FilterChain::NONE,
+                spike_rejection: SpikeRejection::DISABLED,
+                tare: TareConfig::DISABLED,
+                tare_request: Box::leak(Box::new(TareRequest::new())),
+                faults: FaultConfig::DISABLED,
+                load_cell: MockLoadCell { value },
+                period: Duration::from_millis(10),
                 output_channel: output,
+                statistics: Box::leak(Box::new(LoadCellStatistics::new())),
             },
         );
 
@@ -136,7 +417,384 @@ mod load_cell_monitor_testing {
         monitor.run();
 
         // Then
-        let result = output.load(Ordering::Relaxed);
+        let result = output.value();
         assert_eq!(result, expected);
     }
+
+    fn run_noisy_sequence(filter: FilterChain) -> Vec<i16> {
+        let values = noise_with_spikes(400, 2_000, 8_000_000)
+            .iter()
+            .map(|noise| 115_000 + noise)
+            .collect();
+        let output = channel();
+        let mut monitor = LoadCellMonitor::new(
+            "test",
+            LoadCellMonitorConfig {
+                range_min: 0,
+                range_max: 230_000,
+                curve: ResponseCurve::Linear,
+                deadzone: Deadzone::NONE,
+                filter,
+                spike_rejection: SpikeRejection::DISABLED,
+                tare: TareConfig::DISABLED,
+                tare_request: Box::leak(Box::new(TareRequest::new())),
+                faults: FaultConfig::DISABLED,
+                load_cell: MockNoisyLoadCell { values, index: 0 },
+                period: Duration::from_millis(10),
+                output_channel: output,
+                statistics: Box::leak(Box::new(LoadCellStatistics::new())),
+            },
+        );
+
+        (0..400)
+            .map(|_| {
+                monitor.run();
+                output.value()
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case(FilterChain::new(&[Filter::Median { window: 3 }]).unwrap())]
+    #[case(FilterChain::new(&[Filter::Median { window: 5 }, Filter::Ema { alpha: u16::MAX / 8 }]).unwrap())]
+    #[case(FilterChain::new(&[Filter::Median { window: 3 }, Filter::OneEuro(OneEuroConfig {
+        min_cutoff_mhz: 1_000,
+        beta_uhz: 10,
+        derivative_cutoff_mhz: 1_000,
+        sample_rate_hz: 80,
+    })]).unwrap())]
+    fn when_noisy_sequence_is_filtered_then_output_is_bounded(#[case] filter: FilterChain) {
+        // Given
+        let bound = -1_200..=1_200;
+        let unfiltered = run_noisy_sequence(FilterChain::NONE);
+
+        // When
+        let result = run_noisy_sequence(filter);
+
+        // Then
+        assert!(unfiltered.contains(&i16::MAX));
+        assert!(result.iter().skip(20).all(|value| bound.contains(value)));
+    }
+
+    #[rstest]
+    #[case(RejectAction::HoldLastGood)]
+    #[case(RejectAction::Discard)]
+    fn when_load_cell_returns_glitches_then_they_are_rejected_and_counted(
+        #[case] action: RejectAction,
+    ) {
+        // Given
+        let output = channel();
+        let statistics = Box::leak(Box::new(LoadCellStatistics::new()));
+        let values = [115_000, 115_000, 8_388_607, 115_500, -8_388_608, 115_000];
+        let mut monitor = LoadCellMonitor::new(
+            "test",
+            LoadCellMonitorConfig {
+                range_min: 0,
+                range_max: 230_000,
+                curve: ResponseCurve::Linear,
+                deadzone: Deadzone::NONE,
+                filter: FilterChain::NONE,
+                spike_rejection: SpikeRejection {
+                    max_jump: 20_000,
+                    max_slew: 20_000,
+                    history: 5,
+                    max_consecutive_rejections: 3,
+                    action,
+                },
+                tare: TareConfig::DISABLED,
+                tare_request: Box::leak(Box::new(TareRequest::new())),
+                faults: FaultConfig::DISABLED,
+                load_cell: MockNoisyLoadCell {
+                    values: values.to_vec(),
+                    index: 0,
+                },
+                period: Duration::from_millis(10),
+                output_channel: output,
+                statistics,
+            },
+        );
+
+        // When
+        let results: Vec<i16> = values
+            .iter()
+            .map(|_| {
+                monitor.run();
+                output.value()
+            })
+            .collect();
+
+        // Then
+        assert_eq!(results, [-1, -1, -1, 141, 141, -1]);
+        assert_eq!(statistics.rejected_samples(), 2);
+    }
+
+    #[test]
+    fn when_load_cell_is_polled_faster_than_it_converts_then_only_conversions_are_published() {
+        // Given
+        let output = channel();
+        let statistics = Box::leak(Box::new(LoadCellStatistics::new()));
+        let mut monitor = LoadCellMonitor::new(
+            "test",
+            LoadCellMonitorConfig {
+                range_min: 0,
+                range_max: 230_000,
+                curve: ResponseCurve::Linear,
+                deadzone: Deadzone::NONE,
+                filter: FilterChain::NONE,
+                spike_rejection: SpikeRejection::DISABLED,
+                tare: TareConfig::DISABLED,
+                tare_request: Box::leak(Box::new(TareRequest::new())),
+                faults: FaultConfig::DISABLED,
+                load_cell: MockSlowLoadCell {
+                    value: 115_000,
+                    polls_per_conversion: 12,
+                    polls: 0,
+                },
+                period: Duration::from_millis(1),
+                output_channel: output,
+                statistics,
+            },
+        );
+
+        // When
+        (0..30).for_each(|_| monitor.run());
+
+        // Then
+        let state = output.state();
+        assert_eq!((state.value, state.sequence), (-1, 2));
+        assert_eq!(statistics.rejected_samples(), 0);
+    }
+
+    #[test]
+    fn when_load_cell_stops_converting_then_the_safe_value_is_published() {
+        // Given
+        let output = channel();
+        let mut monitor = LoadCellMonitor::new(
+            "test",
+            LoadCellMonitorConfig {
+                range_min: 0,
+                range_max: 230_000,
+                curve: ResponseCurve::Linear,
+                deadzone: Deadzone::NONE,
+                filter: FilterChain::NONE,
+                spike_rejection: SpikeRejection::DISABLED,
+                tare: TareConfig::DISABLED,
+                tare_request: Box::leak(Box::new(TareRequest::new())),
+                faults: FaultConfig {
+                    not_ready_timeout_ms: 100,
+                    max_read_errors: 3,
+                    safe_value: i16::MIN,
+                    ..FaultConfig::DISABLED
+                },
+                load_cell: MockSlowLoadCell {
+                    value: 230_000,
+                    polls_per_conversion: 1,
+                    polls: 0,
+                },
+                period: Duration::from_millis(10),
+                output_channel: output,
+                statistics: Box::leak(Box::new(LoadCellStatistics::new())),
+            },
+        );
+        monitor.run();
+        let before_stop = output.state();
+
+        // When
+        monitor.load_cell.polls_per_conversion = u32::MAX;
+        let values: Vec<i16> = (0..10)
+            .map(|_| {
+                monitor.run();
+                output.value()
+            })
+            .collect();
+
+        // Then
+        assert_eq!(before_stop.value, i16::MAX);
+        assert_eq!(values[8], i16::MAX);
+        assert_eq!(values[9], i16::MIN);
+        assert_eq!(output.state().faults, FaultFlags::NOT_READY);
+        assert!(!output.state().healthy);
+    }
+
+    #[test]
+    fn when_chip_fails_to_be_read_repeatedly_then_a_read_error_is_raised() {
+        // Given
+        let output = channel();
+        let latest: &'static LatestConversion = Box::leak(Box::new(LatestConversion::new()));
+        let mut monitor = LoadCellMonitor::new(
+            "test",
+            LoadCellMonitorConfig {
+                range_min: 0,
+                range_max: 230_000,
+                curve: ResponseCurve::Linear,
+                deadzone: Deadzone::NONE,
+                filter: FilterChain::NONE,
+                spike_rejection: SpikeRejection::DISABLED,
+                tare: TareConfig::DISABLED,
+                tare_request: Box::leak(Box::new(TareRequest::new())),
+                faults: FaultConfig {
+                    max_read_errors: 3,
+                    safe_value: i16::MIN,
+                    ..FaultConfig::DISABLED
+                },
+                load_cell: latest,
+                period: Duration::from_millis(10),
+                output_channel: output,
+                statistics: Box::leak(Box::new(LoadCellStatistics::new())),
+            },
+        );
+        latest.publish(230_000);
+        monitor.run();
+
+        // When
+        let faults: Vec<FaultFlags> = (0..3)
+            .map(|_| {
+                latest.publish_error();
+                monitor.run();
+                monitor.run();
+                output.state().faults
+            })
+            .collect();
+
+        // Then
+        assert_eq!(faults[1], FaultFlags::NONE);
+        assert_eq!(faults[2], FaultFlags::READ_ERRORS);
+        assert_eq!(output.value(), i16::MIN);
+    }
+
+    fn taring_monitor(
+        value: i32,
+        tare_request: &'static TareRequest,
+        output: AxisChannel,
+    ) -> LoadCellMonitor<MockLoadCell, i32> {
+        LoadCellMonitor::new(
+            "test",
+            LoadCellMonitorConfig {
+                range_min: 0,
+                range_max: 230_000,
+                curve: ResponseCurve::Linear,
+                deadzone: Deadzone::NONE,
+                filter: FilterChain::NONE,
+                spike_rejection: SpikeRejection::DISABLED,
+                tare: TareConfig {
+                    samples: 4,
+                    max_deviation: 500,
+                    at_startup: true,
+                },
+                tare_request,
+                faults: FaultConfig::DISABLED,
+                load_cell: MockLoadCell { value },
+                period: Duration::from_millis(10),
+                output_channel: output,
+                statistics: Box::leak(Box::new(LoadCellStatistics::new())),
+            },
+        )
+    }
+
+    #[test]
+    fn when_started_then_the_offset_is_measured_before_publishing() {
+        // Given
+        let output = channel();
+        let mut monitor = taring_monitor(40_000, Box::leak(Box::new(TareRequest::new())), output);
+
+        // When
+        let results: Vec<i16> = (0..4)
+            .map(|_| {
+                monitor.run();
+                output.value()
+            })
+            .collect();
+        monitor.load_cell.value = 40_000 + 115_000;
+        monitor.run();
+
+        // Then
+        assert_eq!(results, [i16::MIN; 4]);
+        assert_eq!(monitor.offset(), 40_000);
+        assert_eq!(output.value(), -1);
+    }
+
+    #[test]
+    fn when_taring_then_the_axis_is_unhealthy() {
+        // Given
+        let output = channel();
+        let mut monitor = taring_monitor(40_000, Box::leak(Box::new(TareRequest::new())), output);
+
+        // When
+        monitor.run();
+        let while_taring = output.state().healthy;
+        (0..4).for_each(|_| monitor.run());
+
+        // Then
+        assert!(!while_taring);
+        assert!(output.state().healthy);
+    }
+
+    #[test]
+    fn when_tare_is_requested_then_the_offset_is_measured_again() {
+        // Given
+        let output = channel();
+        let request = Box::leak(Box::new(TareRequest::new()));
+        let mut monitor = taring_monitor(40_000, request, output);
+        (0..4).for_each(|_| monitor.run());
+
+        // When
+        monitor.load_cell.value = 45_000;
+        request.request();
+        (0..4).for_each(|_| monitor.run());
+        monitor.load_cell.value = 45_000 + 230_000;
+        monitor.run();
+
+        // Then
+        assert_eq!(monitor.offset(), 45_000);
+        assert_eq!(output.value(), i16::MAX);
+    }
+
+    #[test]
+    fn when_pedal_moves_during_tare_then_the_offset_is_kept() {
+        // Given
+        let output = channel();
+        let mut monitor = taring_monitor(0, Box::leak(Box::new(TareRequest::new())), output);
+
+        // When
+        [0, 0, 10_000, 10_000].iter().for_each(|&value| {
+            monitor.load_cell.value = value;
+            monitor.run();
+        });
+
+        // Then
+        assert_eq!(monitor.offset(), 0);
+        assert!(monitor.tare.is_active());
+    }
+
+    #[test]
+    fn when_tared_then_the_reading_is_relative_to_the_offset() {
+        // Given
+        let output = channel();
+        let mut monitor = taring_monitor(40_000, Box::leak(Box::new(TareRequest::new())), output);
+        (0..4).for_each(|_| monitor.run());
+        let while_taring = monitor.reading();
+
+        // When
+        monitor.load_cell.value = 40_000 + 115_000;
+        monitor.run();
+
+        // Then
+        assert_eq!(while_taring, None);
+        assert_eq!(monitor.reading(), Some(115_000));
+    }
+
+    #[test]
+    fn when_tared_then_the_conversion_is_published_as_read() {
+        // Given
+        let output = channel();
+        let mut monitor = taring_monitor(40_000, Box::leak(Box::new(TareRequest::new())), output);
+        (0..4).for_each(|_| monitor.run());
+
+        // When
+        monitor.load_cell.value = 40_000 + 115_000;
+        monitor.run();
+
+        // Then
+        assert_eq!(output.state().raw, 155_000);
+    }
 }
@@ -1,5 +1,12 @@
 mod analog_monitor;
+mod axis_output;
+mod button_monitor;
 mod load_cell_monitor;
+mod monitor;
+mod runner;
 
 pub use analog_monitor::{AnalogMonitor, AnalogMonitorConfig};
-pub use load_cell_monitor::{LoadCellMonitor, LoadCellMonitorConfig};
+pub use button_monitor::{ButtonMonitor, ButtonMonitorConfig, Debounce, MAX_BUTTONS};
+pub use load_cell_monitor::{LoadCellMonitor, LoadCellMonitorConfig, LoadCellStatistics};
+pub use monitor::{AxisBinding, AxisMonitor, Monitor};
+pub use runner::{MonitorRunner, Runnable};
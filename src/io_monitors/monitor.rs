@@ -0,0 +1,255 @@
+use crate::axis::Axis;
+use crate::calibration::CalibrationSession;
+use crate::settings::{AxisSettings, SharedSettings};
+use core::time::Duration;
+
+/// A sensor that is polled periodically. Every run takes a sample, processes it and
+/// publishes the result, see [`Runnable`](crate::io_monitors::Runnable).
+pub trait Monitor {
+    /// What one read of the sensor yields.
+    type Sample;
+    /// What the monitor publishes.
+    type Output;
+
+    /// Time between two runs.
+    fn period(&self) -> Duration;
+
+    /// Reads the sensor. `None` when there is nothing to publish this time.
+    fn sample(&mut self) -> Option<Self::Sample>;
+
+    fn process(&mut self, sample: Self::Sample) -> Self::Output;
+
+    fn publish(&mut self, output: Self::Output);
+}
+
+/// A monitor that drives one axis of the pedalbox.
+pub trait AxisMonitor: Monitor<Output = i16> {
+    /// Takes over new settings. The filter state is only dropped when the filter chain
+    /// changes, and an invalid range keeps the previous one.
+    fn apply_settings(&mut self, settings: &AxisSettings);
+
+    /// Last filtered reading in sensor units, before it was mapped to the axis.
+    fn reading(&self) -> Option<i64>;
+}
+
+/// Ties an axis monitor to the rest of the firmware: it follows the shared settings of its
+/// axis and feeds its readings to the calibration session.
+pub struct AxisBinding<M>
+where
+    M: AxisMonitor,
+{
+    axis: Axis,
+    monitor: M,
+    settings: &'static SharedSettings,
+    session: &'static CalibrationSession,
+    generation: u32,
+}
+
+impl<M> AxisBinding<M>
+where
+    M: AxisMonitor,
+{
+    pub fn new(
+        axis: Axis,
+        monitor: M,
+        settings: &'static SharedSettings,
+        session: &'static CalibrationSession,
+    ) -> Self {
+        Self {
+            axis,
+            monitor,
+            settings,
+            session,
+            generation: settings.generation(),
+        }
+    }
+
+    pub fn monitor(&self) -> &M {
+        &self.monitor
+    }
+}
+
+impl<M> Monitor for AxisBinding<M>
+where
+    M: AxisMonitor,
+{
+    type Sample = M::Sample;
+    type Output = i16;
+
+    fn period(&self) -> Duration {
+        self.monitor.period()
+    }
+
+    fn sample(&mut self) -> Option<Self::Sample> {
+        let generation = self.settings.generation();
+        if generation != self.generation {
+            self.generation = generation;
+            self.monitor.apply_settings(&self.settings.get(self.axis));
+        }
+        self.monitor.sample()
+    }
+
+    fn process(&mut self, sample: Self::Sample) -> i16 {
+        self.monitor.process(sample)
+    }
+
+    fn publish(&mut self, output: i16) {
+        self.monitor.publish(output);
+        if let Some(reading) = self.monitor.reading() {
+            self.session.observe(self.axis, reading);
+        }
+    }
+}
+
+#[cfg(test)]
+mod monitor_testing {
+    use crate::axis::Axis;
+    use crate::calibration::{AxisCalibration, CalibrationSession};
+    use crate::io_monitors::monitor::{AxisBinding, AxisMonitor, Monitor};
+    use crate::io_monitors::Runnable;
+    use crate::settings::{AxisSettings, SharedSettings};
+    use alloc::boxed::Box;
+    use core::time::Duration;
+
+    #[derive(Default)]
+    struct MockAxisMonitor {
+        value: Option<i64>,
+        range_max: i32,
+        published: Option<i16>,
+    }
+
+    impl Monitor for MockAxisMonitor {
+        type Sample = i64;
+        type Output = i16;
+
+        fn period(&self) -> Duration {
+            Duration::from_millis(5)
+        }
+
+        fn sample(&mut self) -> Option<i64> {
+            self.value
+        }
+
+        fn process(&mut self, sample: i64) -> i16 {
+            (sample * i16::MAX as i64 / self.range_max as i64) as i16
+        }
+
+        fn publish(&mut self, output: i16) {
+            self.published = Some(output);
+        }
+    }
+
+    impl AxisMonitor for MockAxisMonitor {
+        fn apply_settings(&mut self, settings: &AxisSettings) {
+            self.range_max = settings.calibration.range_max;
+        }
+
+        fn reading(&self) -> Option<i64> {
+            self.value
+        }
+    }
+
+    fn shared() -> (&'static SharedSettings, &'static CalibrationSession) {
+        (
+            Box::leak(Box::new(SharedSettings::new())),
+            Box::leak(Box::new(CalibrationSession::new())),
+        )
+    }
+
+    fn settings(range_max: i32) -> AxisSettings {
+        AxisSettings {
+            calibration: AxisCalibration::new(0, range_max),
+            ..AxisSettings::DEFAULT
+        }
+    }
+
+    #[test]
+    fn when_sample_is_taken_then_it_is_processed_and_published() {
+        // Given
+        let (shared_settings, session) = shared();
+        let monitor = MockAxisMonitor {
+            value: Some(50),
+            range_max: 100,
+            published: None,
+        };
+        let mut binding = AxisBinding::new(Axis::Y, monitor, shared_settings, session);
+
+        // When
+        binding.run();
+
+        // Then
+        assert_eq!(binding.monitor().published, Some(i16::MAX / 2));
+    }
+
+    #[test]
+    fn when_there_is_no_sample_then_nothing_is_published() {
+        // Given
+        let (shared_settings, session) = shared();
+        let mut binding = AxisBinding::new(
+            Axis::Y,
+            MockAxisMonitor::default(),
+            shared_settings,
+            session,
+        );
+
+        // When
+        binding.run();
+
+        // Then
+        assert_eq!(binding.monitor().published, None);
+    }
+
+    #[test]
+    fn when_settings_change_then_they_are_applied_to_the_monitor() {
+        // Given
+        let (shared_settings, session) = shared();
+        shared_settings.set(Axis::X, settings(100));
+        shared_settings.set(Axis::Z, settings(300));
+        let monitor = MockAxisMonitor {
+            value: Some(50),
+            range_max: 100,
+            published: None,
+        };
+        let mut binding = AxisBinding::new(Axis::Z, monitor, shared_settings, session);
+        binding.run();
+        let before_change = binding.monitor().range_max;
+
+        // When
+        shared_settings.set(Axis::Z, settings(200));
+        binding.run();
+
+        // Then
+        assert_eq!(before_change, 100);
+        assert_eq!(binding.monitor().range_max, 200);
+        assert_eq!(binding.monitor().published, Some(i16::MAX / 4));
+    }
+
+    #[test]
+    fn when_calibration_is_capturing_then_the_readings_are_observed() {
+        // Given
+        let (shared_settings, session) = shared();
+        let mut binding = AxisBinding::new(
+            Axis::Z,
+            MockAxisMonitor {
+                range_max: 100,
+                ..MockAxisMonitor::default()
+            },
+            shared_settings,
+            session,
+        );
+        session.start();
+
+        // When
+        for value in [40, 10, 90, 60] {
+            binding.monitor.value = Some(value);
+            binding.run();
+        }
+
+        // Then
+        assert_eq!(
+            session.captured(Axis::Z),
+            Some(AxisCalibration::new(10, 90))
+        );
+        assert_eq!(session.captured(Axis::X), None);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::io_monitors::Monitor;
+use core::time::Duration;
+
+/// What the runner needs from a monitor. Every [`Monitor`] is runnable, this trait only
+/// hides the sample and output types so monitors of any kind can share a runner.
+pub trait Runnable {
+    fn period(&self) -> Duration;
+
+    /// Samples, processes and publishes once.
+    fn run(&mut self);
+}
+
+impl<M> Runnable for M
+where
+    M: Monitor,
+{
+    fn period(&self) -> Duration {
+        Monitor::period(self)
+    }
+
+    fn run(&mut self) {
+        if let Some(sample) = self.sample() {
+            let output = self.process(sample);
+            self.publish(output);
+        }
+    }
+}
+
+/// Runs a set of monitors from a single task, each one at its own period.
+pub struct MonitorRunner<'a, const N: usize> {
+    monitors: [&'a mut dyn Runnable; N],
+    due: [Duration; N],
+}
+
+impl<'a, const N: usize> MonitorRunner<'a, N> {
+    pub fn new(monitors: [&'a mut dyn Runnable; N]) -> Self {
+        Self {
+            monitors,
+            due: [Duration::ZERO; N],
+        }
+    }
+
+    /// Runs every monitor that is due at `now` and returns when the next one is due. A
+    /// monitor that fell behind skips the runs it missed instead of catching up.
+    pub fn poll(&mut self, now: Duration) -> Duration {
+        for (monitor, due) in self.monitors.iter_mut().zip(&mut self.due) {
+            if *due > now {
+                continue;
+            }
+            monitor.run();
+            *due += monitor.period();
+            if *due <= now {
+                *due = now + monitor.period();
+            }
+        }
+        self.due.iter().copied().min().unwrap_or(now)
+    }
+}
+
+#[cfg(test)]
+mod runner_testing {
+    use crate::io_monitors::runner::{MonitorRunner, Runnable};
+    use crate::io_monitors::Monitor;
+    use alloc::vec::Vec;
+    use core::time::Duration;
+
+    struct MockMonitor {
+        period: Duration,
+        samples: Option<u32>,
+        published: Vec<u32>,
+    }
+
+    impl MockMonitor {
+        fn new(period_ms: u64) -> Self {
+            Self {
+                period: Duration::from_millis(period_ms),
+                samples: Some(0),
+                published: Vec::new(),
+            }
+        }
+    }
+
+    impl Monitor for MockMonitor {
+        type Sample = u32;
+        type Output = u32;
+
+        fn period(&self) -> Duration {
+            self.period
+        }
+
+        fn sample(&mut self) -> Option<u32> {
+            let sample = self.samples?;
+            self.samples = Some(sample + 1);
+            Some(sample)
+        }
+
+        fn process(&mut self, sample: u32) -> u32 {
+            sample * 10
+        }
+
+        fn publish(&mut self, output: u32) {
+            self.published.push(output);
+        }
+    }
+
+    fn ms(value: u64) -> Duration {
+        Duration::from_millis(value)
+    }
+
+    #[test]
+    fn when_monitor_runs_then_sample_is_processed_and_published() {
+        // Given
+        let mut monitor = MockMonitor::new(1);
+
+        // When
+        (0..3).for_each(|_| monitor.run());
+
+        // Then
+        assert_eq!(monitor.published, [0, 10, 20]);
+    }
+
+    #[test]
+    fn when_monitor_has_no_sample_then_nothing_is_published() {
+        // Given
+        let mut monitor = MockMonitor::new(1);
+        monitor.samples = None;
+
+        // When
+        monitor.run();
+
+        // Then
+        assert!(monitor.published.is_empty());
+    }
+
+    #[test]
+    fn when_polled_then_every_monitor_runs_at_its_own_period() {
+        // Given
+        let mut fast = MockMonitor::new(5);
+        let mut slow = MockMonitor::new(10);
+        let mut runner = MonitorRunner::new([&mut fast, &mut slow]);
+
+        // When
+        let mut wakeups = Vec::new();
+        let mut now = ms(0);
+        while now < ms(30) {
+            now = runner.poll(now);
+            wakeups.push(now);
+        }
+
+        // Then
+        assert_eq!(wakeups, [ms(5), ms(10), ms(15), ms(20), ms(25), ms(30)]);
+        assert_eq!(fast.published.len(), 6);
+        assert_eq!(slow.published.len(), 3);
+    }
+
+    #[test]
+    fn when_runner_falls_behind_then_missed_runs_are_skipped() {
+        // Given
+        let mut monitor = MockMonitor::new(5);
+        let mut runner = MonitorRunner::new([&mut monitor]);
+        runner.poll(ms(0));
+
+        // When
+        let next = runner.poll(ms(23));
+        let early = runner.poll(ms(24));
+
+        // Then
+        assert_eq!(next, ms(28));
+        assert_eq!(early, ms(28));
+        assert_eq!(monitor.published, [0, 10]);
+    }
+}
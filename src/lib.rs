@@ -2,22 +2,70 @@
 
 #[cfg(test)]
 extern crate alloc;
-#[cfg(target_arch = "arm")]
-use embassy_stm32::adc::Adc;
-#[cfg(target_arch = "arm")]
-use embedded_hal::blocking::delay::DelayUs;
-#[cfg(target_arch = "arm")]
-use embedded_hal::digital::v2::InputPin;
-#[cfg(target_arch = "arm")]
-use embedded_hal::digital::v2::OutputPin;
-#[cfg(target_arch = "arm")]
-use hx711::Hx711;
 
+pub mod axis;
+pub mod calibration;
+pub mod console;
+pub mod crc;
+pub mod curve;
+pub mod deadzone;
+pub mod dfu;
+pub mod fault;
+pub mod feature_report;
+pub mod filters;
 pub mod fmt;
+pub mod hid_descriptor;
+pub mod hid_parser;
+pub mod hx711;
+pub mod input_report;
 pub mod io_monitors;
+pub mod oversampling;
+pub mod pedal_state;
+pub mod polling;
+pub mod report_mode;
+pub mod serial_number;
+pub mod settings;
+pub mod spike;
+pub mod supervisor;
+pub mod tare;
+pub mod temperature;
+
+/// USB vendor ID of the pedalbox.
+pub const USB_VENDOR_ID: u16 = 0xcafe;
+/// USB product ID of the pedalbox.
+pub const USB_PRODUCT_ID: u16 = 0x2025;
+/// Major, minor and patch version of the firmware, from its Cargo.toml.
+pub const FIRMWARE_VERSION: [u8; 3] = [
+    parse_version(env!("CARGO_PKG_VERSION_MAJOR")),
+    parse_version(env!("CARGO_PKG_VERSION_MINOR")),
+    parse_version(env!("CARGO_PKG_VERSION_PATCH")),
+];
+
+const fn parse_version(part: &str) -> u8 {
+    let digits = part.as_bytes();
+    let mut value: u32 = 0;
+    let mut index = 0;
+    while index < digits.len() {
+        value = value * 10 + (digits[index] - b'0') as u32;
+        index += 1;
+    }
+    core::assert!(
+        value <= u8::MAX as u32,
+        "Version numbers must fit in a byte"
+    );
+    value as u8
+}
 
 pub mod prelude {
+    pub use super::axis::Axis;
+    pub use super::calibration::{AxisCalibration, Calibration, CalibrationStore};
+    pub use super::curve::{CurvePoint, CurvePoints, ResponseCurve};
+    pub use super::deadzone::{Deadzone, DeadzoneWidth};
+    pub use super::filters::{Filter, FilterChain, OneEuroConfig};
     pub use super::fmt::*;
+    pub use super::settings::{AxisSettings, SharedSettings};
+    pub use super::spike::{RejectAction, SpikeRejection};
+    pub use super::tare::{TareConfig, TareRequest};
     pub use super::{AnalogRead, Mapping};
     pub use crate::io_monitors::*;
 }
@@ -25,25 +73,9 @@ pub mod prelude {
 pub trait LoadCell {
     type ReturnType;
     type Error;
-    fn read(&mut self) -> Result<Self::ReturnType, Self::Error>;
-}
 
-#[cfg(target_arch = "arm")]
-impl<D, IN, OUT, EIN, EOUT> LoadCell for Hx711<D, IN, OUT>
-where
-    D: DelayUs<u32>,
-    IN: InputPin<Error = EIN>,
-    OUT: OutputPin<Error = EOUT>,
-{
-    type ReturnType = i32;
-    type Error = ();
-
-    fn read(&mut self) -> Result<Self::ReturnType, Self::Error> {
-        match self.retrieve() {
-            Ok(v) => Ok(v),
-            Err(_) => Err(()),
-        }
-    }
+    /// Reads the latest conversion. `WouldBlock` when the next one isn't finished yet.
+    fn read(&mut self) -> nb::Result<Self::ReturnType, Self::Error>;
 }
 
 pub trait Mapping
@@ -74,19 +106,6 @@ pub trait AnalogRead<Pin> {
     fn read(&mut self, pin: &mut Pin) -> Self::ReturnType;
 }
 
-#[cfg(target_arch = "arm")]
-impl<Pin, T> AnalogRead<Pin> for Adc<'_, T>
-where
-    Pin: embassy_stm32::adc::AdcChannel<T>,
-    T: embassy_stm32::adc::Instance,
-{
-    type ReturnType = u16;
-
-    fn read(&mut self, pin: &mut Pin) -> Self::ReturnType {
-        self.blocking_read(pin)
-    }
-}
-
 #[cfg(test)]
 mod test_mapping {
     use crate::Mapping;
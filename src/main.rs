@@ -2,37 +2,235 @@
 #![no_main]
 
 mod board;
+mod bootloader;
 mod usb;
+mod watchdog;
 
-use core::sync::atomic::Ordering;
 #[cfg(not(feature = "defmt"))]
 use panic_halt as _;
 #[cfg(feature = "defmt")]
 use {defmt_rtt as _, panic_probe as _};
 
-use crate::board::Board;
+use crate::board::{temperature_calibration, Board, CycleDelay};
 use crate::usb::{
-    PedalboxConfiguration, PedalboxReport, UsbConfiguration, AXIS_X, AXIS_Y, AXIS_Z, BOS_DESC,
-    CONFIG_DESC, CONTROL_BUF, EP_OUT_BUFFER, HID_STATE, MSOS_DESC,
+    DfuRuntimeHandler, FeatureReportHandler, PedalboxConfiguration, UsbConfiguration, BOS_DESC,
+    BRAKE_STATISTICS, BRAKE_TARE_REQUEST, CALIBRATION_SESSION, CDC_STATE, CONFIG_DESC, CONTROL_BUF,
+    DFU_DETACH, EP_OUT_BUFFER, FEATURE_HANDLER, HID_STATE, MSOS_DESC, PEDAL_STATE, REPORT_MODE,
+    REPORT_STATISTICS, SETTINGS,
 };
 use embassy_executor::Spawner;
-use embassy_stm32::adc::Adc;
+use embassy_futures::select::{select, Either};
+use embassy_stm32::adc::{Adc, RingBufferedAdc, SampleTime, Sequence};
+use embassy_stm32::exti::ExtiInput;
+use embassy_stm32::flash::{Blocking, Flash};
 use embassy_stm32::gpio::{Input, Output};
-use embassy_stm32::peripherals::{ADC1, ADC2, PA5, PA7, USB_OTG_FS};
-use embassy_stm32::{Config, Peri};
-use embassy_time::{Delay, Duration, Timer};
+use embassy_stm32::peripherals::{ADC1, IWDG, USB_OTG_FS};
+use embassy_stm32::wdg::IndependentWatchdog;
+use embassy_stm32::Config;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{with_timeout, Duration, Instant, Timer};
+use embassy_usb::class::cdc_acm::{self, CdcAcmClass};
 use embassy_usb::class::hid;
 use embassy_usb::class::hid::HidWriter;
 use embassy_usb::Builder;
-use hx711::Hx711;
-use rusty_pedalbox::fmt::warn;
+use rusty_pedalbox::axis::Axis;
+use rusty_pedalbox::calibration::{
+    AxisCalibration, Calibration, CalibrationState, CalibrationStore, CommitTarget,
+};
+use rusty_pedalbox::console::{echo, Console, ConsoleAction, LineBuffer, ResponseBuffer};
+use rusty_pedalbox::curve::ResponseCurve;
+use rusty_pedalbox::deadzone::{Deadzone, DeadzoneWidth};
+use rusty_pedalbox::fault::FaultConfig;
+use rusty_pedalbox::filters::{Filter, FilterChain};
+use rusty_pedalbox::fmt::{info, warn};
+use rusty_pedalbox::hx711::{Gain, Hx711, LatestConversion};
+use rusty_pedalbox::input_report::{
+    DiagnosticsReport, PedalboxReport, StatusReport, MAX_INPUT_REPORT_SIZE,
+};
 use rusty_pedalbox::io_monitors::{
-    AnalogMonitor, AnalogMonitorConfig, LoadCellMonitor, LoadCellMonitorConfig,
+    AnalogMonitor, AnalogMonitorConfig, AxisBinding, ButtonMonitor, ButtonMonitorConfig, Debounce,
+    LoadCellMonitor, LoadCellMonitorConfig, MonitorRunner,
 };
+use rusty_pedalbox::oversampling::{OversampledReadings, Oversampler, ScanChannel};
+use rusty_pedalbox::polling::PollingProfile;
+use rusty_pedalbox::report_mode::{newest_sample_us, PeriodicReport, ReportScheduler};
+use rusty_pedalbox::serial_number::{serial_number, DeviceName, SERIAL_NUMBER_SIZE};
+use rusty_pedalbox::settings::AxisSettings;
+use rusty_pedalbox::spike::{RejectAction, SpikeRejection};
+use rusty_pedalbox::supervisor::{SupervisedTask, Supervisor, TaskWatch};
+use rusty_pedalbox::tare::TareConfig;
+use rusty_pedalbox::temperature::TemperatureCalibration;
+use rusty_pedalbox::FIRMWARE_VERSION;
+use static_cell::StaticCell;
+
+/// Flash offsets of the last two 128K sectors, see `memory.x`.
+const CALIBRATION_SLOTS: [u32; 2] = [0xC_0000, 0xE_0000];
+
+/// Gas, clutch and the temperature sensor of the chip, in the order of the ADC scan.
+const ANALOG_CHANNELS: usize = 3;
+const GAS_CHANNEL: ScanChannel = ScanChannel(0);
+const CLUTCH_CHANNEL: ScanChannel = ScanChannel(1);
+const TEMPERATURE_CHANNEL: ScanChannel = ScanChannel(2);
+/// 16 conversions per value give 14 bit readings.
+const OVERSAMPLING_RATIO: u16 = 16;
+/// Room for two oversampled values of every channel. DMA wakes the ADC task at every half,
+/// i.e. for every new value.
+const ADC_DMA_BUFFER_LEN: usize = 2 * ANALOG_CHANNELS * OVERSAMPLING_RATIO as usize;
+
+/// ADC clock, PCLK2 of 84 MHz divided by 4.
+const ADC_CLOCK_HZ: u64 = 21_000_000;
+/// Sampling time of the potentiometers, the low impedance divider needs little.
+const POTENTIOMETER_SAMPLE_TIME: SampleTime = SampleTime::CYCLES144;
+/// The temperature sensor needs 10 us of sampling, 480 cycles at 21 MHz are 23 us.
+const TEMPERATURE_SAMPLE_TIME: SampleTime = SampleTime::CYCLES480;
+/// Cycles of one scan: the sampling times of the channels plus 12 cycles of conversion each.
+const ADC_SCAN_CYCLES: u64 = 2 * (144 + 12) + (480 + 12);
+/// Time until every channel has a new oversampled value, about 0.61 ms.
+const ADC_VALUE_PERIOD_US: u64 =
+    ADC_SCAN_CYCLES * OVERSAMPLING_RATIO as u64 * 1_000_000 / ADC_CLOCK_HZ;
+
+static ADC_DMA_BUFFER: StaticCell<[u16; ADC_DMA_BUFFER_LEN]> = StaticCell::new();
+static ANALOG_READINGS: OversampledReadings<ANALOG_CHANNELS> = OversampledReadings::new();
+static BRAKE_CONVERSIONS: LatestConversion = LatestConversion::new();
+
+type PotentiometerMonitor =
+    AxisBinding<AnalogMonitor<&'static OversampledReadings<ANALOG_CHANNELS>, ScanChannel, u16>>;
+type BrakeMonitor = AxisBinding<LoadCellMonitor<&'static LatestConversion, i32>>;
+type BrakeLoadCell = Hx711<ExtiInput<'static>, Output<'static>, CycleDelay>;
+type Buttons = ButtonMonitor<Input<'static>, 8>;
+
+static GAS_MONITOR: StaticCell<PotentiometerMonitor> = StaticCell::new();
+static BRAKE_MONITOR: StaticCell<BrakeMonitor> = StaticCell::new();
+static CLUTCH_MONITOR: StaticCell<PotentiometerMonitor> = StaticCell::new();
+static BUTTON_MONITOR: StaticCell<Buttons> = StaticCell::new();
+/// RATE pin of the HX711, kept for good since dropping it would let the input float.
+static BRAKE_RATE: StaticCell<Output<'static>> = StaticCell::new();
+
+/// Rates the pedals are sampled and reported at.
+#[cfg(not(feature = "polling-1khz"))]
+pub const POLLING: PollingProfile = PollingProfile::HZ_100;
+#[cfg(feature = "polling-1khz")]
+pub const POLLING: PollingProfile = PollingProfile::HZ_1000;
+const _: () = assert!(POLLING.keeps_up(), "A pedal can't keep up with the polling");
+const _: () = assert!(
+    ADC_VALUE_PERIOD_US <= POLLING.analog_period.as_micros() as u64,
+    "The ADC scan can't keep up with the potentiometer polling"
+);
+
+/// Gas, brake, clutch and the buttons.
+const MONITOR_COUNT: usize = 4;
+
+/// Periods of the status and diagnostics input reports, the pedals have their report mode.
+const STATUS_PERIOD: core::time::Duration = core::time::Duration::from_secs(1);
+const DIAGNOSTICS_PERIOD: core::time::Duration = core::time::Duration::from_millis(100);
+
+/// Packet size of the bulk endpoints of the serial console.
+const CONSOLE_PACKET_SIZE: usize = 64;
+/// Room for the longest answer of the console, the help.
+const CONSOLE_RESPONSE_SIZE: usize = 768;
+/// How often `watch on` shows the values.
+const CONSOLE_WATCH_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Asks the calibration task to save the calibration in use.
+static SAVE_CALIBRATION: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+/// Asks the calibration task to save the name of the pedalbox, `None` to remove it.
+static SAVE_NAME: Signal<CriticalSectionRawMutex, Option<DeviceName>> = Signal::new();
+
+static SERIAL_NUMBER: StaticCell<[u8; SERIAL_NUMBER_SIZE]> = StaticCell::new();
+
+/// The watchdog resets the chip when the supervisor doesn't feed it for this long. Erasing
+/// a flash sector blocks every task for up to 2 s.
+const WATCHDOG_TIMEOUT_US: u32 = 4_000_000;
+/// Monitors, ADC and load cell.
+const SUPERVISED_TASK_COUNT: usize = 3;
+const SUPERVISOR_PERIOD: Duration = Duration::from_millis(100);
+/// A supervisor that didn't run for two periods was held up with every other task, e.g. by
+/// a flash erase. Each deadline leaves room for such a gap plus the check-in interval of
+/// its task.
+const SUPERVISOR_MAX_GAP: core::time::Duration = core::time::Duration::from_millis(200);
+/// Longest wait for a conversion of the HX711, two conversions at its slowest rate. The
+/// fault detector of the brake reports a load cell that stays silent.
+const LOAD_CELL_READ_TIMEOUT: Duration = Duration::from_millis(200);
+
+static MONITOR_WATCH: TaskWatch = TaskWatch::new(
+    SupervisedTask::Monitors,
+    core::time::Duration::from_millis(300),
+);
+static ADC_WATCH: TaskWatch =
+    TaskWatch::new(SupervisedTask::Adc, core::time::Duration::from_millis(300));
+static LOAD_CELL_WATCH: TaskWatch = TaskWatch::new(
+    SupervisedTask::LoadCell,
+    core::time::Duration::from_millis(500),
+);
+
+/// A potentiometer within 64 counts of a rail lost a wire. Identical oversampled readings
+/// for seconds mean the ADC scan stopped.
+const POTENTIOMETER_FAULTS: FaultConfig = FaultConfig {
+    rail_low: Some(64),
+    rail_high: Some(16_316),
+    stuck_samples: 5_000,
+    not_ready_timeout_ms: 0,
+    max_read_errors: 0,
+    safe_value: i16::MIN,
+};
+
+/// A saturated HX711 lost its bridge, and its noise never repeats a 24 bit conversion 100
+/// times in a row.
+const LOAD_CELL_FAULTS: FaultConfig = FaultConfig {
+    rail_low: Some(-8_388_608),
+    rail_high: Some(8_388_607),
+    stuck_samples: 100,
+    not_ready_timeout_ms: 500,
+    max_read_errors: 3,
+    safe_value: i16::MIN,
+};
+
+/// Full scale of an oversampled reading, 12 bits of the ADC plus the extra bits of the
+/// oversampling.
+const OVERSAMPLED_MAX: i32 = (1 << (12 + OVERSAMPLING_RATIO.trailing_zeros() / 2)) - 1;
+
+const DEFAULT_CALIBRATION: Calibration = Calibration {
+    axes: [
+        AxisCalibration::new(7280, 12_400),
+        AxisCalibration::new(0, 230_000),
+        AxisCalibration::new(0, OVERSAMPLED_MAX),
+    ],
+};
+
 #[embassy_executor::main]
 async fn main(spawner: Spawner) {
+    bootloader::start_if_requested();
     let p = embassy_stm32::init(Config::usb_configuration());
-    let board = Board::new(p);
+    let mut board = Board::new(p);
+
+    let reset_cause = watchdog::take_reset_cause();
+    info!("Reset by {:?}", reset_cause.reason);
+    if let Some(culprit) = reset_cause.culprit {
+        warn!(
+            "The watchdog reset the chip, the {} task hung",
+            culprit.name()
+        );
+    }
+
+    let mut calibration_store: CalibrationStore<Flash<'static, Blocking>> =
+        CalibrationStore::new(Flash::new_blocking(board.flash), CALIBRATION_SLOTS);
+    let (calibration, name) = match calibration_store.load() {
+        Some(stored) => {
+            info!("Calibration #{} loaded from flash", stored.sequence);
+            (stored.calibration, stored.name)
+        }
+        None => {
+            warn!("No valid calibration in flash, using defaults");
+            (DEFAULT_CALIBRATION, None)
+        }
+    };
+    let serial_number = serial_number(
+        embassy_stm32::uid::uid(),
+        name.as_ref(),
+        SERIAL_NUMBER.init([0; SERIAL_NUMBER_SIZE]),
+    );
+    info!("USB serial number {}", serial_number);
 
     let ep_out_buffer = EP_OUT_BUFFER.init([0; 256]);
     let config_desc = CONFIG_DESC.init([0; 256]);
@@ -40,6 +238,14 @@ async fn main(spawner: Spawner) {
     let msos_desc = MSOS_DESC.init([0; 128]);
     let control_buf = CONTROL_BUF.init([0; 64]);
     let hid_state = HID_STATE.init(hid::State::new());
+    let feature_handler = FEATURE_HANDLER.init(FeatureReportHandler::new(
+        &SETTINGS,
+        &CALIBRATION_SESSION,
+        &REPORT_MODE,
+        &REPORT_STATISTICS,
+        &PEDAL_STATE,
+        reset_cause,
+    ));
 
     let driver = embassy_stm32::usb::Driver::new_fs(
         board.usb_peripheral,
@@ -50,70 +256,402 @@ async fn main(spawner: Spawner) {
         embassy_stm32::usb::Config::default(),
     );
 
+    let mut usb_config = embassy_usb::Config::pedalbox_configuration();
+    usb_config.serial_number = Some(serial_number);
     let mut builder = Builder::new(
         driver,
-        embassy_usb::Config::pedalbox_configuration(),
+        usb_config,
         config_desc,
         bos_desc,
         msos_desc,
         control_buf,
     );
 
-    let hid_writer = HidWriter::<_, 8>::new(
+    let mut hid_config = hid::Config::pedalbox_configuration();
+    hid_config.request_handler = Some(feature_handler);
+    let hid_writer =
+        HidWriter::<_, MAX_INPUT_REPORT_SIZE>::new(&mut builder, hid_state, hid_config);
+    spawner
+        .spawn(hid_task(hid_writer, temperature_calibration()))
+        .expect("Failed to spawn hid task");
+
+    let console = CdcAcmClass::new(
         &mut builder,
-        hid_state,
-        hid::Config::pedalbox_configuration(),
+        CDC_STATE.init(cdc_acm::State::new()),
+        CONSOLE_PACKET_SIZE as u16,
     );
     spawner
-        .spawn(hid_task(hid_writer))
-        .expect("Failed to spawn hid task");
+        .spawn(console_task(console))
+        .expect("Failed to spawn console task");
+
+    DfuRuntimeHandler::add(&mut builder);
+    spawner
+        .spawn(dfu_detach_task())
+        .expect("Failed to spawn DFU detach task");
 
     let usb = builder.build();
     spawner
         .spawn(usb_task(usb))
         .expect("Failed to spawn usb task");
 
+    let adc = Adc::new(board.analog_adc);
+    let mut temperature = adc.enable_temperature();
+    let mut adc = adc.into_ring_buffered(
+        board.analog_dma,
+        ADC_DMA_BUFFER.init([0; ADC_DMA_BUFFER_LEN]),
+    );
+    adc.set_sample_sequence(
+        Sequence::One,
+        &mut board.gas_potentiometer,
+        POTENTIOMETER_SAMPLE_TIME,
+    );
+    adc.set_sample_sequence(
+        Sequence::Two,
+        &mut board.clutch_potentiometer,
+        POTENTIOMETER_SAMPLE_TIME,
+    );
+    adc.set_sample_sequence(Sequence::Three, &mut temperature, TEMPERATURE_SAMPLE_TIME);
+    spawner
+        .spawn(adc_task(adc))
+        .expect("Failed to spawn adc task");
+
+    let gas_settings = AxisSettings {
+        calibration: calibration.axis(Axis::X),
+        deadzone: Deadzone {
+            lower: DeadzoneWidth::Percent(2),
+            upper: DeadzoneWidth::Percent(2),
+            saturation: 0,
+        },
+        curve: ResponseCurve::Linear,
+        filter: FilterChain::new(&[Filter::Ema {
+            alpha: POLLING.analog_ema_alpha,
+        }])
+        .expect("Invalid analog filter chain"),
+    };
+    SETTINGS.set(Axis::X, gas_settings);
+    let (gas_min, gas_max) = axis_range(&calibration, Axis::X);
     let gas_pedal = AnalogMonitor::new(
         "GAS_PEDAL",
         AnalogMonitorConfig {
-            range_min: 1820,
-            range_max: 3100,
-            adc: Adc::new(board.gas_adc),
-            pin: board.gas_potentiometer,
-            output_channel: &AXIS_X,
+            range_min: gas_min,
+            range_max: gas_max,
+            curve: gas_settings.curve,
+            deadzone: gas_settings.deadzone,
+            filter: gas_settings.filter,
+            faults: POTENTIOMETER_FAULTS,
+            period: POLLING.analog_period,
+            adc: &ANALOG_READINGS,
+            pin: GAS_CHANNEL,
+            output_channel: PEDAL_STATE.axis_channel(Axis::X),
         },
     );
-    spawner
-        .spawn(input_monitor_x(gas_pedal))
-        .expect("Failed to spawn input monitor X");
 
+    let brake_settings = AxisSettings {
+        calibration: calibration.axis(Axis::Y),
+        deadzone: Deadzone::NONE,
+        curve: ResponseCurve::Linear,
+        filter: FilterChain::new(&[Filter::Median { window: 3 }])
+            .expect("Invalid load cell filter chain"),
+    };
+    SETTINGS.set(Axis::Y, brake_settings);
+    let (brake_min, brake_max) = axis_range(&calibration, Axis::Y);
+    let brake_rate = BRAKE_RATE.init(board.brake_rate);
+    brake_rate.set_level(POLLING.load_cell_rate.rate_pin_high().into());
+    let load_cell = Hx711::new(
+        board.brake_data,
+        board.brake_clock,
+        CycleDelay,
+        Gain::ChannelA128,
+    )
+    .expect("Failed to create HX711 driver");
+    spawner
+        .spawn(load_cell_task(load_cell))
+        .expect("Failed to spawn load cell task");
     let brake_pedal = LoadCellMonitor::new(
         "BRAKE_PEDAL",
         LoadCellMonitorConfig {
-            range_min: 0,
-            range_max: 230_000,
-            load_cell: Hx711::new(Delay, board.brake_data, board.brake_clock)
-                .expect("Failed to create HX711 driver"),
-            output_channel: &AXIS_Y,
+            range_min: brake_min,
+            range_max: brake_max,
+            curve: brake_settings.curve,
+            deadzone: brake_settings.deadzone,
+            filter: brake_settings.filter,
+            spike_rejection: SpikeRejection {
+                max_jump: 150_000,
+                max_slew: 120_000,
+                history: 5,
+                max_consecutive_rejections: 3,
+                action: RejectAction::HoldLastGood,
+            },
+            tare: TareConfig {
+                samples: 16,
+                max_deviation: 2_000,
+                at_startup: true,
+            },
+            tare_request: &BRAKE_TARE_REQUEST,
+            faults: LOAD_CELL_FAULTS,
+            period: POLLING.load_cell_period,
+            load_cell: &BRAKE_CONVERSIONS,
+            output_channel: PEDAL_STATE.axis_channel(Axis::Y),
+            statistics: &BRAKE_STATISTICS,
         },
     );
-    spawner
-        .spawn(input_monitor_y(brake_pedal))
-        .expect("Failed to spawn input monitor Y");
 
+    let clutch_settings = AxisSettings {
+        calibration: calibration.axis(Axis::Z),
+        ..gas_settings
+    };
+    SETTINGS.set(Axis::Z, clutch_settings);
+    let (clutch_min, clutch_max) = axis_range(&calibration, Axis::Z);
     let clutch_pedal = AnalogMonitor::new(
         "CLUTCH_PEDAL",
         AnalogMonitorConfig {
-            range_min: u16::MIN,
-            range_max: u16::MAX,
-            adc: Adc::new(board.clutch_adc),
-            pin: board.clutch_potentiometer,
-            output_channel: &AXIS_Z,
+            range_min: clutch_min,
+            range_max: clutch_max,
+            curve: clutch_settings.curve,
+            deadzone: clutch_settings.deadzone,
+            filter: clutch_settings.filter,
+            faults: POTENTIOMETER_FAULTS,
+            period: POLLING.analog_period,
+            adc: &ANALOG_READINGS,
+            pin: CLUTCH_CHANNEL,
+            output_channel: PEDAL_STATE.axis_channel(Axis::Z),
+        },
+    );
+
+    let buttons = ButtonMonitor::new(
+        "BUTTONS",
+        ButtonMonitorConfig {
+            pins: board.buttons,
+            active_low: true,
+            debounce: Debounce::Integration { samples: 5 },
+            period: core::time::Duration::from_millis(1),
+            output_channel: PEDAL_STATE.button_channel(),
         },
     );
+    let runner = MonitorRunner::new([
+        GAS_MONITOR.init(AxisBinding::new(
+            Axis::X,
+            gas_pedal,
+            &SETTINGS,
+            &CALIBRATION_SESSION,
+        )),
+        BRAKE_MONITOR.init(AxisBinding::new(
+            Axis::Y,
+            brake_pedal,
+            &SETTINGS,
+            &CALIBRATION_SESSION,
+        )),
+        CLUTCH_MONITOR.init(AxisBinding::new(
+            Axis::Z,
+            clutch_pedal,
+            &SETTINGS,
+            &CALIBRATION_SESSION,
+        )),
+        BUTTON_MONITOR.init(buttons),
+    ]);
+    spawner
+        .spawn(monitor_task(runner))
+        .expect("Failed to spawn monitor task");
+
+    spawner
+        .spawn(calibration_task(calibration_store, board.user_button))
+        .expect("Failed to spawn calibration task");
+
+    #[cfg(feature = "debug")]
+    watchdog::freeze_on_halt();
+    let mut iwdg = IndependentWatchdog::new(board.watchdog, WATCHDOG_TIMEOUT_US);
+    iwdg.unleash();
+    let supervisor = Supervisor::new(
+        [&MONITOR_WATCH, &ADC_WATCH, &LOAD_CELL_WATCH],
+        SUPERVISOR_MAX_GAP,
+        uptime_ms(),
+    );
     spawner
-        .spawn(input_monitor_z(clutch_pedal))
-        .expect("Failed to spawn input monitor Z");
+        .spawn(supervisor_task(iwdg, supervisor))
+        .expect("Failed to spawn supervisor task");
+}
+
+/// Feeds the watchdog while every supervised task keeps checking in. Once one is overdue
+/// it is recorded and the watchdog is left to reset the chip.
+#[embassy_executor::task]
+async fn supervisor_task(
+    mut iwdg: IndependentWatchdog<'static, IWDG>,
+    mut supervisor: Supervisor<'static, SUPERVISED_TASK_COUNT>,
+) {
+    loop {
+        if let Some(culprit) = supervisor.overdue(uptime_ms()) {
+            warn!("The {} task hung, resetting", culprit.name());
+            watchdog::record_culprit(culprit);
+            return;
+        }
+        iwdg.pet();
+        Timer::after(SUPERVISOR_PERIOD).await;
+    }
+}
+
+/// Uptime in milliseconds for the task watches, wrapping after 49 days.
+fn uptime_ms() -> u32 {
+    Instant::now().as_millis() as u32
+}
+
+/// Applies the ranges learned by the calibration session. The user button starts a new
+/// capture, or commits the running one and saves it. The console can save the calibration
+/// in use as well.
+#[embassy_executor::task]
+async fn calibration_task(
+    mut store: CalibrationStore<Flash<'static, Blocking>>,
+    mut button: ExtiInput<'static>,
+) {
+    loop {
+        let pressed = select(
+            button.wait_for_rising_edge(),
+            Timer::after(Duration::from_millis(100)),
+        )
+        .await;
+        if let Either::First(()) = pressed {
+            if CALIBRATION_SESSION.state() == CalibrationState::Capturing {
+                CALIBRATION_SESSION.commit(CommitTarget::Persistent);
+            } else {
+                info!("Calibration started, sweep every pedal");
+                CALIBRATION_SESSION.start();
+            }
+            // Debounce
+            Timer::after(Duration::from_millis(50)).await;
+        }
+
+        let target = CALIBRATION_SESSION.take_commit();
+        if target.is_some() {
+            for axis in Axis::ALL {
+                if let Some(range) = CALIBRATION_SESSION.captured(axis) {
+                    info!(
+                        "Axis {} calibrated to {}..{}",
+                        axis.index(),
+                        range.range_min,
+                        range.range_max
+                    );
+                    SETTINGS.update(axis, |settings| settings.calibration = range);
+                }
+            }
+        }
+        if target == Some(CommitTarget::Persistent) || SAVE_CALIBRATION.try_take().is_some() {
+            match store.save(&SETTINGS.calibration()) {
+                Ok(sequence) => info!("Calibration #{} saved", sequence),
+                Err(e) => warn!("Failed to save calibration: {:?}", e),
+            }
+        }
+        if let Some(name) = SAVE_NAME.try_take() {
+            match store.save_name(name, &SETTINGS.calibration()) {
+                Ok(sequence) => info!("Name saved in record #{}", sequence),
+                Err(e) => warn!("Failed to save the name: {:?}", e),
+            }
+        }
+    }
+}
+
+/// Serves the console on the USB serial port, echoing what is typed and answering every
+/// line.
+#[embassy_executor::task]
+async fn console_task(
+    mut class: CdcAcmClass<'static, embassy_stm32::usb::Driver<'static, USB_OTG_FS>>,
+) {
+    let mut console = Console::new(&SETTINGS, &PEDAL_STATE, &REPORT_MODE, &BRAKE_TARE_REQUEST);
+    let mut line = LineBuffer::new();
+    let mut response = ResponseBuffer::<CONSOLE_RESPONSE_SIZE>::new();
+    let mut packet = [0; CONSOLE_PACKET_SIZE];
+    loop {
+        class.wait_connection().await;
+        info!("Console connected");
+        loop {
+            response.clear();
+            let received = if console.is_watching() {
+                match select(
+                    class.read_packet(&mut packet),
+                    Timer::after(CONSOLE_WATCH_INTERVAL),
+                )
+                .await
+                {
+                    Either::First(received) => received,
+                    Either::Second(()) => {
+                        let _ = console.write_values(&mut response);
+                        Ok(0)
+                    }
+                }
+            } else {
+                class.read_packet(&mut packet).await
+            };
+            let Ok(length) = received else {
+                break;
+            };
+
+            let mut action = ConsoleAction::None;
+            for &byte in &packet[..length] {
+                let _ = echo(byte, &mut response);
+                if let Some(text) = line.push(byte) {
+                    match console.run_line(text, &mut response) {
+                        ConsoleAction::None => {}
+                        requested => action = requested,
+                    }
+                }
+            }
+            if write_response(&mut class, response.as_bytes())
+                .await
+                .is_err()
+            {
+                break;
+            }
+            match action {
+                ConsoleAction::None => {}
+                ConsoleAction::SaveCalibration => SAVE_CALIBRATION.signal(()),
+                ConsoleAction::SaveName(name) => SAVE_NAME.signal(name),
+                ConsoleAction::Bootloader => {
+                    // Lets the host fetch the answer before the device disappears
+                    Timer::after(Duration::from_millis(50)).await;
+                    bootloader::reboot_into_bootloader();
+                }
+            }
+        }
+        info!("Console disconnected");
+    }
+}
+
+/// Reboots into the ROM bootloader once the host asks for DFU mode.
+#[embassy_executor::task]
+async fn dfu_detach_task() {
+    DFU_DETACH.wait().await;
+    // Lets the control transfer of the request finish
+    Timer::after(Duration::from_millis(10)).await;
+    bootloader::reboot_into_bootloader();
+}
+
+/// Writes an answer in packets, ending it with a short one so the host doesn't wait for
+/// more.
+async fn write_response(
+    class: &mut CdcAcmClass<'static, embassy_stm32::usb::Driver<'static, USB_OTG_FS>>,
+    bytes: &[u8],
+) -> Result<(), embassy_usb::driver::EndpointError> {
+    if bytes.is_empty() {
+        return Ok(());
+    }
+    for chunk in bytes.chunks(CONSOLE_PACKET_SIZE) {
+        class.write_packet(chunk).await?;
+    }
+    if bytes.len().is_multiple_of(CONSOLE_PACKET_SIZE) {
+        class.write_packet(&[]).await?;
+    }
+    Ok(())
+}
+
+/// Range of an axis in the sample type of its monitor. Falls back to the default when the
+/// stored range doesn't fit the sensor.
+fn axis_range<T: TryFrom<i32>>(calibration: &Calibration, axis: Axis) -> (T, T) {
+    calibration
+        .axis(axis)
+        .range()
+        .or_else(|| {
+            warn!("Stored calibration of axis {} is invalid", axis.index());
+            DEFAULT_CALIBRATION.axis(axis).range()
+        })
+        .expect("Invalid default calibration")
 }
 
 #[embassy_executor::task]
@@ -123,49 +661,121 @@ async fn usb_task(
     device.run().await;
 }
 
+/// Sends the pedal reports as the report mode asks for, and the status and diagnostics
+/// reports at their periods. A write waits for the host to poll, so the changes in between
+/// are merged into the next report.
 #[embassy_executor::task]
 async fn hid_task(
-    mut writer: HidWriter<'static, embassy_stm32::usb::Driver<'static, USB_OTG_FS>, 8>,
+    mut writer: HidWriter<
+        'static,
+        embassy_stm32::usb::Driver<'static, USB_OTG_FS>,
+        MAX_INPUT_REPORT_SIZE,
+    >,
+    temperature: TemperatureCalibration,
 ) {
+    let mut pedals = PEDAL_STATE
+        .subscribe()
+        .expect("Too many pedal state subscribers");
+    let mut scheduler = ReportScheduler::new();
+    let mut status = PeriodicReport::new(STATUS_PERIOD);
+    let mut diagnostics = PeriodicReport::new(DIAGNOSTICS_PERIOD);
+    let extra_bits = Oversampler::<ANALOG_CHANNELS>::new(OVERSAMPLING_RATIO).extra_bits();
     loop {
-        let report = PedalboxReport {
-            x: AXIS_X.load(Ordering::Relaxed),
-            y: AXIS_Y.load(Ordering::Relaxed),
-            z: AXIS_Z.load(Ordering::Relaxed),
-            buttons: 0,
-        };
-
-        let bytes = bytemuck::bytes_of(&report);
-        if let Err(e) = writer.write(bytes).await {
-            warn!("HID write failed: {:?}", e);
+        let mode = REPORT_MODE.get();
+        let deadline = scheduler
+            .deadline_us(mode)
+            .min(status.deadline_us())
+            .min(diagnostics.deadline_us());
+        let snapshot =
+            match select(pedals.changed(), Timer::at(Instant::from_micros(deadline))).await {
+                Either::First(snapshot) => snapshot,
+                Either::Second(()) => PEDAL_STATE.snapshot(),
+            };
+        let now = Instant::now().as_micros();
+
+        if let Some(reason) = scheduler.check(mode, &snapshot, now) {
+            let report = PedalboxReport::new(
+                snapshot.axis(Axis::X).value,
+                snapshot.axis(Axis::Y).value,
+                snapshot.axis(Axis::Z).value,
+                snapshot.buttons,
+            );
+            match writer.write(bytemuck::bytes_of(&report)).await {
+                Ok(()) => {
+                    let latency = Instant::now().as_micros() - newest_sample_us(&snapshot);
+                    REPORT_STATISTICS.record(reason, latency.try_into().unwrap_or(u32::MAX));
+                }
+                Err(e) => warn!("HID write failed: {:?}", e),
+            }
+            scheduler.reported(&snapshot, now);
         }
 
-        Timer::after(Duration::from_millis(10)).await;
+        if status.check(now) {
+            let report = StatusReport::new(
+                &snapshot,
+                temperature.decicelsius(ANALOG_READINGS.get(TEMPERATURE_CHANNEL), extra_bits),
+                FIRMWARE_VERSION,
+                Instant::now().as_secs() as u32,
+            );
+            if let Err(e) = writer.write(bytemuck::bytes_of(&report)).await {
+                warn!("HID write of the status failed: {:?}", e);
+            }
+        }
+
+        if diagnostics.check(now) {
+            let report = DiagnosticsReport::new([
+                ANALOG_READINGS.get(GAS_CHANNEL) as i32,
+                BRAKE_CONVERSIONS.peek(),
+                ANALOG_READINGS.get(CLUTCH_CHANNEL) as i32,
+            ]);
+            if let Err(e) = writer.write(bytemuck::bytes_of(&report)).await {
+                warn!("HID write of the diagnostics failed: {:?}", e);
+            }
+        }
     }
 }
 
+/// Converts the potentiometers continuously and keeps their latest oversampled values.
 #[embassy_executor::task]
-async fn input_monitor_x(mut monitor: AnalogMonitor<Adc<'static, ADC1>, Peri<'static, PA7>, u16>) {
+async fn adc_task(mut adc: RingBufferedAdc<'static, ADC1>) {
+    let mut oversampler = Oversampler::<ANALOG_CHANNELS>::new(OVERSAMPLING_RATIO);
+    let mut conversions = [0; ADC_DMA_BUFFER_LEN / 2];
     loop {
-        monitor.run();
-        Timer::after(Duration::from_millis(5)).await;
+        match adc.read(&mut conversions).await {
+            Ok(_) => oversampler.feed(&conversions, |values| ANALOG_READINGS.store(values)),
+            Err(e) => {
+                warn!("ADC overrun, restarting the scan: {:?}", e);
+                oversampler.reset();
+            }
+        }
+        ADC_WATCH.check_in(uptime_ms());
     }
 }
 
+/// Reads every conversion of the HX711 as soon as it is ready. A missing conversion only
+/// times out the read, so the task keeps checking in.
 #[embassy_executor::task]
-async fn input_monitor_z(mut monitor: AnalogMonitor<Adc<'static, ADC2>, Peri<'static, PA5>, u16>) {
+async fn load_cell_task(mut load_cell: BrakeLoadCell) {
     loop {
-        monitor.run();
-        Timer::after(Duration::from_millis(5)).await;
+        match with_timeout(LOAD_CELL_READ_TIMEOUT, load_cell.read()).await {
+            Ok(Ok(conversion)) => BRAKE_CONVERSIONS.publish(conversion),
+            Ok(Err(e)) => {
+                warn!("Failed to read the load cell: {:?}", e);
+                BRAKE_CONVERSIONS.publish_error();
+            }
+            Err(_) => {}
+        }
+        LOAD_CELL_WATCH.check_in(uptime_ms());
     }
 }
 
+/// Runs every monitor, each one at its own period.
 #[embassy_executor::task]
-async fn input_monitor_y(
-    mut monitor: LoadCellMonitor<Hx711<Delay, Input<'static>, Output<'static>>, i32>,
-) {
+async fn monitor_task(mut runner: MonitorRunner<'static, MONITOR_COUNT>) {
     loop {
-        monitor.run();
-        Timer::after(Duration::from_millis(10)).await;
+        let now = core::time::Duration::from_micros(Instant::now().as_micros());
+        let next = runner.poll(now);
+        MONITOR_WATCH.check_in(uptime_ms());
+        Timer::at(Instant::from_micros(next.as_micros() as u64)).await;
     }
 }
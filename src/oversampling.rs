@@ -0,0 +1,227 @@
+//! Oversampling of the ADC scan.
+//!
+//! The ADC converts every analog pedal over and over in scan mode and DMA writes the
+//! conversions interleaved into a ring buffer, e.g. `[gas, clutch, gas, clutch, ..]`. The
+//! [`Oversampler`] sums `ratio` conversions of each channel and shifts the sum, so a ratio
+//! of 4^n gives n extra bits on top of the 12 bits of the ADC. The results land in
+//! [`OversampledReadings`], which the monitors read through [`AnalogRead`].
+
+use crate::AnalogRead;
+use core::sync::atomic::{AtomicU16, Ordering};
+
+/// Largest supported ratio, the sum of 256 conversions of 12 bits fits in a `u32` and the
+/// result in 16 bits.
+pub const MAX_OVERSAMPLING_RATIO: u16 = 256;
+
+/// Position of a pedal in the scan sequence of the ADC.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub struct ScanChannel(pub usize);
+
+/// Latest oversampled value of every channel of the scan.
+#[derive(Debug)]
+pub struct OversampledReadings<const N: usize> {
+    values: [AtomicU16; N],
+}
+
+impl<const N: usize> OversampledReadings<N> {
+    pub const fn new() -> Self {
+        Self {
+            values: [const { AtomicU16::new(0) }; N],
+        }
+    }
+
+    pub fn store(&self, values: [u16; N]) {
+        for (slot, value) in self.values.iter().zip(values) {
+            slot.store(value, Ordering::Relaxed);
+        }
+    }
+
+    pub fn get(&self, channel: ScanChannel) -> u16 {
+        self.values[channel.0].load(Ordering::Relaxed)
+    }
+}
+
+impl<const N: usize> Default for OversampledReadings<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> AnalogRead<ScanChannel> for &OversampledReadings<N> {
+    type ReturnType = u16;
+
+    fn read(&mut self, pin: &mut ScanChannel) -> Self::ReturnType {
+        self.get(*pin)
+    }
+}
+
+/// Averages the interleaved conversions of `N` channels.
+#[derive(Debug)]
+pub struct Oversampler<const N: usize> {
+    ratio: u16,
+    extra_bits: u32,
+    sums: [u32; N],
+    /// Channel of the next conversion.
+    channel: usize,
+    /// Full scans summed so far.
+    count: u16,
+}
+
+impl<const N: usize> Oversampler<N> {
+    /// `ratio` has to be a power of four up to [`MAX_OVERSAMPLING_RATIO`].
+    pub const fn new(ratio: u16) -> Self {
+        assert!(
+            ratio.is_power_of_two()
+                && ratio.trailing_zeros().is_multiple_of(2)
+                && ratio <= MAX_OVERSAMPLING_RATIO,
+            "Oversampling ratio must be a power of four up to 256"
+        );
+        Self {
+            ratio,
+            extra_bits: ratio.trailing_zeros() / 2,
+            sums: [0; N],
+            channel: 0,
+            count: 0,
+        }
+    }
+
+    /// Bits added to the resolution of the ADC.
+    pub fn extra_bits(&self) -> u32 {
+        self.extra_bits
+    }
+
+    /// Feeds interleaved conversions, calling `output` every time each channel collected
+    /// `ratio` of them. The scan may be split anywhere between two calls.
+    pub fn feed(&mut self, conversions: &[u16], mut output: impl FnMut([u16; N])) {
+        for &conversion in conversions {
+            self.sums[self.channel] += conversion as u32;
+            self.channel += 1;
+            if self.channel < N {
+                continue;
+            }
+            self.channel = 0;
+            self.count += 1;
+            if self.count == self.ratio {
+                let extra_bits = self.extra_bits;
+                output(self.sums.map(|sum| (sum >> extra_bits) as u16));
+                self.sums = [0; N];
+                self.count = 0;
+            }
+        }
+    }
+
+    /// Drops the partial sums, to be called when the scan restarts, e.g. after an overrun.
+    pub fn reset(&mut self) {
+        self.sums = [0; N];
+        self.channel = 0;
+        self.count = 0;
+    }
+}
+
+#[cfg(test)]
+mod oversampling_testing {
+    use crate::oversampling::{OversampledReadings, Oversampler, ScanChannel};
+    use crate::AnalogRead;
+    use alloc::vec::Vec;
+    use rstest::rstest;
+
+    fn run<const N: usize>(oversampler: &mut Oversampler<N>, conversions: &[u16]) -> Vec<[u16; N]> {
+        let mut outputs = Vec::new();
+        oversampler.feed(conversions, |values| outputs.push(values));
+        outputs
+    }
+
+    #[rstest]
+    #[case(1, 0)]
+    #[case(4, 1)]
+    #[case(16, 2)]
+    #[case(256, 4)]
+    fn when_ratio_is_a_power_of_four_then_it_adds_bits(#[case] ratio: u16, #[case] bits: u32) {
+        // When
+        let result = Oversampler::<2>::new(ratio);
+
+        // Then
+        assert_eq!(result.extra_bits(), bits);
+    }
+
+    #[rstest]
+    #[case(8)]
+    #[case(12)]
+    #[case(1024)]
+    #[should_panic(expected = "power of four")]
+    fn when_ratio_is_not_a_power_of_four_then_it_is_refused(#[case] ratio: u16) {
+        // When
+        Oversampler::<2>::new(ratio);
+    }
+
+    #[test]
+    fn when_16_scans_are_fed_then_each_channel_gains_two_bits() {
+        // Given
+        let mut oversampler = Oversampler::<2>::new(16);
+        let conversions: Vec<u16> = (0..16).flat_map(|_| [1_000, 4_095]).collect();
+
+        // When
+        let result = run(&mut oversampler, &conversions);
+
+        // Then
+        assert_eq!(result, [[4_000, 16_380]]);
+    }
+
+    #[test]
+    fn when_conversions_are_noisy_then_the_average_resolves_between_codes() {
+        // Given
+        let mut oversampler = Oversampler::<1>::new(16);
+        let conversions: Vec<u16> = (0..16)
+            .map(|i| if i % 4 == 0 { 1_001 } else { 1_000 })
+            .collect();
+
+        // When
+        let result = run(&mut oversampler, &conversions);
+
+        // Then
+        assert_eq!(result, [[4_001]]);
+    }
+
+    #[test]
+    fn when_scan_is_split_between_reads_then_channels_stay_aligned() {
+        // Given
+        let mut oversampler = Oversampler::<3>::new(4);
+        let conversions: Vec<u16> = (0..8).flat_map(|_| [100, 200, 300]).collect();
+
+        // When
+        let mut result = run(&mut oversampler, &conversions[..7]);
+        result.extend(run(&mut oversampler, &conversions[7..17]));
+        result.extend(run(&mut oversampler, &conversions[17..]));
+
+        // Then
+        assert_eq!(result, [[200, 400, 600], [200, 400, 600]]);
+    }
+
+    #[test]
+    fn when_reset_then_partial_sums_are_dropped() {
+        // Given
+        let mut oversampler = Oversampler::<2>::new(4);
+        run(&mut oversampler, &[4_095, 4_095, 4_095]);
+
+        // When
+        oversampler.reset();
+        let result = run(&mut oversampler, &[10, 20, 10, 20, 10, 20, 10, 20]);
+
+        // Then
+        assert_eq!(result, [[20, 40]]);
+    }
+
+    #[test]
+    fn when_readings_are_stored_then_each_channel_reads_its_own() {
+        // Given
+        let readings: &OversampledReadings<2> = alloc::boxed::Box::leak(Default::default());
+        readings.store([4_000, 12_000]);
+        let mut adc = readings;
+
+        // When
+        let result = [adc.read(&mut ScanChannel(0)), adc.read(&mut ScanChannel(1))];
+
+        // Then
+        assert_eq!(result, [4_000, 12_000]);
+    }
+}
@@ -0,0 +1,327 @@
+//! Latest state of the pedals and buttons.
+//!
+//! The monitors publish into a [`PedalState`] and the tasks reporting the pedals subscribe
+//! to it. Every change is made to one snapshot under a lock, so a consumer never sees the
+//! axes of different moments mixed together.
+
+use crate::axis::{Axis, AXIS_COUNT};
+use crate::fault::FaultFlags;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::watch::{Receiver, Watch};
+
+/// Number of tasks that can subscribe to the pedal state.
+pub const SUBSCRIBER_COUNT: usize = 4;
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AxisState {
+    /// Value reported to the host.
+    pub value: i16,
+    /// Last sample of the sensor as read, in ADC or HX711 counts, before the filter, the
+    /// tare and the spike rejection.
+    pub raw: i64,
+    /// Microseconds since boot when the value was published.
+    pub timestamp_us: u64,
+    /// Number of values published on the axis, wraps around.
+    pub sequence: u32,
+    /// Whether the value can be trusted. It is false before the first value and while the
+    /// sensor can't be measured, e.g. during a tare or a fault.
+    pub healthy: bool,
+    /// Faults of the sensor that are active now.
+    pub faults: FaultFlags,
+    /// Every fault since the host cleared them last.
+    pub latched_faults: FaultFlags,
+}
+
+impl AxisState {
+    pub const UNKNOWN: AxisState = AxisState {
+        value: i16::MIN,
+        raw: 0,
+        timestamp_us: 0,
+        sequence: 0,
+        healthy: false,
+        faults: FaultFlags::NONE,
+        latched_faults: FaultFlags::NONE,
+    };
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PedalSnapshot {
+    pub axes: [AxisState; AXIS_COUNT],
+    /// Debounced buttons, button 1 being bit 0.
+    pub buttons: u8,
+}
+
+impl PedalSnapshot {
+    pub const UNKNOWN: PedalSnapshot = PedalSnapshot {
+        axes: [AxisState::UNKNOWN; AXIS_COUNT],
+        buttons: 0,
+    };
+
+    pub fn axis(&self, axis: Axis) -> AxisState {
+        self.axes[axis.index()]
+    }
+}
+
+pub type PedalReceiver<'a> = Receiver<'a, CriticalSectionRawMutex, PedalSnapshot, SUBSCRIBER_COUNT>;
+
+pub struct PedalState {
+    watch: Watch<CriticalSectionRawMutex, PedalSnapshot, SUBSCRIBER_COUNT>,
+    clock: fn() -> u64,
+}
+
+impl PedalState {
+    /// `clock` tells the microseconds since boot to timestamp the values with.
+    pub const fn new(clock: fn() -> u64) -> Self {
+        Self {
+            watch: Watch::new_with(PedalSnapshot::UNKNOWN),
+            clock,
+        }
+    }
+
+    pub fn snapshot(&self) -> PedalSnapshot {
+        self.watch.try_get().unwrap_or(PedalSnapshot::UNKNOWN)
+    }
+
+    /// Subscribes to the changes, `None` when there are [`SUBSCRIBER_COUNT`] subscribers
+    /// already.
+    pub fn subscribe(&self) -> Option<PedalReceiver<'_>> {
+        self.watch.receiver()
+    }
+
+    /// Where the monitor of an axis publishes.
+    pub fn axis_channel(&'static self, axis: Axis) -> AxisChannel {
+        AxisChannel { state: self, axis }
+    }
+
+    /// Where the button monitor publishes.
+    pub fn button_channel(&'static self) -> ButtonChannel {
+        ButtonChannel { state: self }
+    }
+
+    /// Forgets the faults that are no longer active.
+    pub fn clear_latched_faults(&self) {
+        self.watch.sender().send_modify(|snapshot| {
+            let snapshot = snapshot.get_or_insert(PedalSnapshot::UNKNOWN);
+            for axis in &mut snapshot.axes {
+                axis.latched_faults = axis.faults;
+            }
+        });
+    }
+
+    fn update_axis(&self, axis: Axis, update: impl Fn(&mut AxisState)) {
+        self.watch.sender().send_modify(|snapshot| {
+            let snapshot = snapshot.get_or_insert(PedalSnapshot::UNKNOWN);
+            update(&mut snapshot.axes[axis.index()]);
+        });
+    }
+}
+
+/// Publishes the values of one axis into a [`PedalState`].
+#[derive(Copy, Clone)]
+pub struct AxisChannel {
+    state: &'static PedalState,
+    axis: Axis,
+}
+
+impl AxisChannel {
+    pub fn publish(&self, value: i16, raw: i64, healthy: bool) {
+        let timestamp_us = (self.state.clock)();
+        self.state.update_axis(self.axis, |state| {
+            *state = AxisState {
+                value,
+                raw,
+                timestamp_us,
+                sequence: state.sequence.wrapping_add(1),
+                healthy,
+                ..*state
+            }
+        });
+    }
+
+    /// Marks the value as untrustworthy until the next one is published.
+    pub fn invalidate(&self) {
+        self.state
+            .update_axis(self.axis, |state| state.healthy = false);
+    }
+
+    /// Sets the active faults, they stay latched until the host clears them.
+    pub fn set_faults(&self, faults: FaultFlags) {
+        self.state.update_axis(self.axis, |state| {
+            state.faults = faults;
+            state.latched_faults |= faults;
+        });
+    }
+
+    pub fn state(&self) -> AxisState {
+        self.state.snapshot().axis(self.axis)
+    }
+
+    /// Last value published on the axis.
+    pub fn value(&self) -> i16 {
+        self.state().value
+    }
+}
+
+/// Publishes the debounced buttons into a [`PedalState`].
+#[derive(Copy, Clone)]
+pub struct ButtonChannel {
+    state: &'static PedalState,
+}
+
+impl ButtonChannel {
+    /// Publishes the buttons, the subscribers are only notified when they changed.
+    pub fn publish(&self, buttons: u8) {
+        self.state.watch.sender().send_if_modified(|snapshot| {
+            let snapshot = snapshot.get_or_insert(PedalSnapshot::UNKNOWN);
+            let changed = snapshot.buttons != buttons;
+            snapshot.buttons = buttons;
+            changed
+        });
+    }
+
+    pub fn buttons(&self) -> u8 {
+        self.state.snapshot().buttons
+    }
+}
+
+#[cfg(test)]
+mod pedal_state_testing {
+    use crate::axis::Axis;
+    use crate::fault::FaultFlags;
+    use crate::pedal_state::{AxisState, PedalSnapshot, PedalState, SUBSCRIBER_COUNT};
+    use alloc::boxed::Box;
+
+    fn clock() -> u64 {
+        1_500
+    }
+
+    fn pedal_state() -> &'static PedalState {
+        Box::leak(Box::new(PedalState::new(clock)))
+    }
+
+    #[test]
+    fn when_nothing_is_published_then_state_is_unknown() {
+        // Given
+        let state = pedal_state();
+
+        // When
+        let snapshot = state.snapshot();
+
+        // Then
+        assert_eq!(snapshot, PedalSnapshot::UNKNOWN);
+        assert!(!snapshot.axis(Axis::X).healthy);
+    }
+
+    #[test]
+    fn when_axis_is_published_then_it_is_stamped_and_counted() {
+        // Given
+        let state = pedal_state();
+        let channel = state.axis_channel(Axis::Y);
+
+        // When
+        channel.publish(-1_000, 115_000, false);
+        channel.publish(2_000, 140_000, true);
+
+        // Then
+        assert_eq!(
+            state.snapshot().axis(Axis::Y),
+            AxisState {
+                value: 2_000,
+                raw: 140_000,
+                timestamp_us: 1_500,
+                sequence: 2,
+                healthy: true,
+                ..AxisState::UNKNOWN
+            }
+        );
+        assert_eq!(state.snapshot().axis(Axis::X), AxisState::UNKNOWN);
+    }
+
+    #[test]
+    fn when_axis_is_invalidated_then_value_is_kept_but_unhealthy() {
+        // Given
+        let state = pedal_state();
+        let channel = state.axis_channel(Axis::Z);
+        channel.publish(300, 2_000, true);
+
+        // When
+        channel.invalidate();
+
+        // Then
+        let axis = channel.state();
+        assert_eq!((axis.value, axis.sequence, axis.healthy), (300, 1, false));
+    }
+
+    #[test]
+    fn when_faults_are_set_then_they_stay_latched_until_cleared() {
+        // Given
+        let state = pedal_state();
+        let channel = state.axis_channel(Axis::X);
+        channel.set_faults(FaultFlags::RAIL_HIGH);
+        channel.publish(i16::MIN, 16_383, false);
+        channel.set_faults(FaultFlags::STUCK);
+        let before_clear = channel.state();
+
+        // When
+        state.clear_latched_faults();
+
+        // Then
+        assert_eq!(before_clear.faults, FaultFlags::STUCK);
+        assert_eq!(
+            before_clear.latched_faults,
+            FaultFlags::RAIL_HIGH | FaultFlags::STUCK
+        );
+        assert_eq!(channel.state().latched_faults, FaultFlags::STUCK);
+    }
+
+    #[test]
+    fn when_axis_changes_then_subscribers_are_notified() {
+        // Given
+        let state = pedal_state();
+        let mut receiver = state.subscribe().unwrap();
+        receiver.try_changed();
+
+        // When
+        state.axis_channel(Axis::X).publish(10, 2_000, true);
+
+        // Then
+        let snapshot = receiver.try_changed().unwrap();
+        assert_eq!(snapshot.axis(Axis::X).value, 10);
+        assert_eq!(receiver.try_changed(), None);
+    }
+
+    #[test]
+    fn when_buttons_stay_the_same_then_subscribers_are_not_notified() {
+        // Given
+        let state = pedal_state();
+        let channel = state.button_channel();
+        let mut receiver = state.subscribe().unwrap();
+        channel.publish(0b101);
+        receiver.try_changed();
+
+        // When
+        channel.publish(0b101);
+
+        // Then
+        assert_eq!(receiver.try_changed(), None);
+        assert_eq!(channel.buttons(), 0b101);
+    }
+
+    #[test]
+    fn when_there_are_too_many_subscribers_then_they_are_refused() {
+        // Given
+        let state = pedal_state();
+        let receivers: alloc::vec::Vec<_> = (0..SUBSCRIBER_COUNT)
+            .map(|_| state.subscribe().unwrap())
+            .collect();
+
+        // When
+        let result = state.subscribe();
+
+        // Then
+        assert_eq!(receivers.len(), SUBSCRIBER_COUNT);
+        assert!(result.is_none());
+    }
+}
@@ -0,0 +1,153 @@
+//! Rates the pedals are sampled and reported at.
+//!
+//! The HID poll interval, the sample periods of the monitors and the report mode only make
+//! sense together: a pedal sampled slower than the host polls repeats its last value, so a
+//! [`PollingProfile`] picks them as a set.
+
+use crate::report_mode::ReportMode;
+use core::time::Duration;
+
+/// Output data rate of the HX711, selected by its RATE pin.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Hx711Rate {
+    /// RATE pin low, 10 samples per second with the least noise.
+    Sps10,
+    /// RATE pin high, 80 samples per second.
+    Sps80,
+}
+
+impl Hx711Rate {
+    /// Time between two conversions.
+    pub const fn conversion_period(self) -> Duration {
+        match self {
+            Hx711Rate::Sps10 => Duration::from_millis(100),
+            Hx711Rate::Sps80 => Duration::from_micros(12_500),
+        }
+    }
+
+    pub const fn rate_pin_high(self) -> bool {
+        matches!(self, Hx711Rate::Sps80)
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub struct PollingProfile {
+    /// Interval the host polls the HID endpoint at, in milliseconds.
+    pub poll_ms: u8,
+    /// Sample period of the potentiometers.
+    pub analog_period: Duration,
+    /// Smoothing of the potentiometers. More samples need a smaller alpha for the same
+    /// time constant.
+    pub analog_ema_alpha: u16,
+    /// How often the load cell is asked for a new conversion.
+    pub load_cell_period: Duration,
+    pub load_cell_rate: Hx711Rate,
+    /// Report mode after boot.
+    pub report_mode: ReportMode,
+}
+
+impl PollingProfile {
+    /// 100 Hz, the load cell converting at its quietest rate.
+    pub const HZ_100: PollingProfile = PollingProfile {
+        poll_ms: 10,
+        analog_period: Duration::from_millis(5),
+        analog_ema_alpha: u16::MAX / 4,
+        load_cell_period: Duration::from_millis(10),
+        load_cell_rate: Hx711Rate::Sps10,
+        report_mode: ReportMode::DEFAULT,
+    };
+
+    /// 1 kHz. The potentiometers are sampled every millisecond with the same ~17 ms
+    /// smoothing as at 200 Hz, and the load cell is polled every millisecond so a new
+    /// conversion is reported at most 1 ms after it finished.
+    pub const HZ_1000: PollingProfile = PollingProfile {
+        poll_ms: 1,
+        analog_period: Duration::from_millis(1),
+        analog_ema_alpha: 3_664,
+        load_cell_period: Duration::from_millis(1),
+        load_cell_rate: Hx711Rate::Sps80,
+        report_mode: ReportMode::OnChange {
+            threshold: 16,
+            heartbeat_ms: 10,
+        },
+    };
+
+    pub const fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_ms as u64)
+    }
+
+    /// Whether every pedal is sampled at least as often as the host polls and the load
+    /// cell at least as often as it converts.
+    pub const fn keeps_up(&self) -> bool {
+        let poll_us = self.poll_interval().as_micros();
+        self.poll_ms > 0
+            && self.analog_period.as_micros() <= poll_us
+            && self.load_cell_period.as_micros()
+                <= self.load_cell_rate.conversion_period().as_micros()
+    }
+}
+
+#[cfg(test)]
+mod polling_testing {
+    use crate::polling::{Hx711Rate, PollingProfile};
+    use core::time::Duration;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(PollingProfile::HZ_100)]
+    #[case(PollingProfile::HZ_1000)]
+    fn when_profile_is_provided_then_it_keeps_up(#[case] profile: PollingProfile) {
+        // When
+        let result = profile.keeps_up();
+
+        // Then
+        assert!(result);
+    }
+
+    #[rstest]
+    #[case(PollingProfile { poll_ms: 0, ..PollingProfile::HZ_1000 })]
+    #[case(PollingProfile { analog_period: Duration::from_millis(5), ..PollingProfile::HZ_1000 })]
+    #[case(PollingProfile { load_cell_period: Duration::from_millis(20), ..PollingProfile::HZ_1000 })]
+    fn when_a_path_is_slower_than_the_profile_then_it_does_not_keep_up(
+        #[case] profile: PollingProfile,
+    ) {
+        // When
+        let result = profile.keeps_up();
+
+        // Then
+        assert!(!result);
+    }
+
+    #[test]
+    fn when_1khz_profile_is_used_then_the_smoothing_matches_100hz() {
+        // Given
+        let slow = PollingProfile::HZ_100;
+        let fast = PollingProfile::HZ_1000;
+        let slow_steps = 5;
+        let fast_steps = slow_steps * slow.analog_period.as_millis() as i32;
+
+        // When
+        let remaining = |alpha: u16, steps: i32| (1.0 - alpha as f64 / 65_536.0).powi(steps);
+
+        // Then
+        let difference = remaining(slow.analog_ema_alpha, slow_steps)
+            - remaining(fast.analog_ema_alpha, fast_steps);
+        assert!(difference.abs() < 0.01, "difference {}", difference);
+    }
+
+    #[rstest]
+    #[case(Hx711Rate::Sps10, Duration::from_millis(100), false)]
+    #[case(Hx711Rate::Sps80, Duration::from_micros(12_500), true)]
+    fn when_hx711_rate_is_selected(
+        #[case] rate: Hx711Rate,
+        #[case] expected_period: Duration,
+        #[case] expected_pin: bool,
+    ) {
+        // When
+        let result = (rate.conversion_period(), rate.rate_pin_high());
+
+        // Then
+        assert_eq!(result, (expected_period, expected_pin));
+    }
+}
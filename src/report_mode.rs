@@ -0,0 +1,436 @@
+//! Decides when the input report is sent to the host.
+//!
+//! In the periodic mode a report goes out at a fixed interval. In the on-change mode a
+//! report goes out as soon as the pedals moved enough, and a heartbeat repeats the last
+//! state when they didn't. Either way the host's polling interval is the upper bound of
+//! the report rate. The status and diagnostics reports go out on their own
+//! [`PeriodicReport`] schedule.
+
+use crate::axis::{Axis, AXIS_COUNT};
+use crate::pedal_state::PedalSnapshot;
+use core::cell::Cell;
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::time::Duration;
+use critical_section::Mutex;
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReportMode {
+    /// A report every `interval_ms`, whether the pedals moved or not.
+    Periodic { interval_ms: u16 },
+    /// A report when an axis moved more than `threshold` since the last report or a button
+    /// changed, and one every `heartbeat_ms` otherwise.
+    OnChange { threshold: u16, heartbeat_ms: u16 },
+}
+
+impl ReportMode {
+    pub const DEFAULT: ReportMode = ReportMode::OnChange {
+        threshold: 16,
+        heartbeat_ms: 100,
+    };
+
+    /// Longest time without a report.
+    fn max_gap_us(self) -> u64 {
+        match self {
+            ReportMode::Periodic { interval_ms } => interval_ms as u64 * 1_000,
+            ReportMode::OnChange { heartbeat_ms, .. } => heartbeat_ms as u64 * 1_000,
+        }
+    }
+}
+
+/// Report mode shared between the HID task and the host facing handlers.
+pub struct SharedReportMode {
+    mode: Mutex<Cell<ReportMode>>,
+}
+
+impl SharedReportMode {
+    pub const fn new(mode: ReportMode) -> Self {
+        Self {
+            mode: Mutex::new(Cell::new(mode)),
+        }
+    }
+
+    pub fn get(&self) -> ReportMode {
+        critical_section::with(|cs| self.mode.borrow(cs).get())
+    }
+
+    pub fn set(&self, mode: ReportMode) {
+        critical_section::with(|cs| self.mode.borrow(cs).set(mode));
+    }
+}
+
+/// Why a report is sent.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReportReason {
+    /// The pedals or buttons changed.
+    Change,
+    /// Nothing changed, but it is time to report anyway.
+    Timeout,
+}
+
+/// Remembers what was reported last to decide whether the next state is worth a report.
+#[derive(Debug)]
+pub struct ReportScheduler {
+    last_values: [i16; AXIS_COUNT],
+    last_buttons: u8,
+    /// Microseconds since boot of the last report, `None` before the first one.
+    last_report_us: Option<u64>,
+}
+
+impl ReportScheduler {
+    pub const fn new() -> Self {
+        Self {
+            last_values: [0; AXIS_COUNT],
+            last_buttons: 0,
+            last_report_us: None,
+        }
+    }
+
+    /// Whether `snapshot` has to be reported at `now_us`, and why.
+    pub fn check(
+        &self,
+        mode: ReportMode,
+        snapshot: &PedalSnapshot,
+        now_us: u64,
+    ) -> Option<ReportReason> {
+        let Some(last_report_us) = self.last_report_us else {
+            return Some(ReportReason::Change);
+        };
+        if let ReportMode::OnChange { threshold, .. } = mode {
+            let moved = Axis::ALL.iter().any(|&axis| {
+                snapshot
+                    .axis(axis)
+                    .value
+                    .abs_diff(self.last_values[axis.index()])
+                    > threshold
+            });
+            if moved || snapshot.buttons != self.last_buttons {
+                return Some(ReportReason::Change);
+            }
+        }
+        (now_us >= last_report_us + mode.max_gap_us()).then_some(ReportReason::Timeout)
+    }
+
+    /// Records that `snapshot` was reported at `now_us`.
+    pub fn reported(&mut self, snapshot: &PedalSnapshot, now_us: u64) {
+        self.last_values = Axis::ALL.map(|axis| snapshot.axis(axis).value);
+        self.last_buttons = snapshot.buttons;
+        self.last_report_us = Some(now_us);
+    }
+
+    /// Time since boot in microseconds when a report is due even if nothing changes.
+    pub fn deadline_us(&self, mode: ReportMode) -> u64 {
+        self.last_report_us
+            .map_or(0, |last_report_us| last_report_us + mode.max_gap_us())
+    }
+}
+
+impl Default for ReportScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Schedule of a report sent at a fixed period, whatever the pedals do.
+#[derive(Debug)]
+pub struct PeriodicReport {
+    period_us: u64,
+    /// Microseconds since boot when the next report is due.
+    next_us: u64,
+}
+
+impl PeriodicReport {
+    /// The first report is due right away.
+    pub const fn new(period: Duration) -> Self {
+        Self {
+            period_us: period.as_micros() as u64,
+            next_us: 0,
+        }
+    }
+
+    /// Whether the report is due at `now_us`, scheduling the next one when it is. A late
+    /// report doesn't make up for the ones it missed.
+    pub fn check(&mut self, now_us: u64) -> bool {
+        if now_us < self.next_us {
+            return false;
+        }
+        self.next_us += self.period_us;
+        if self.next_us <= now_us {
+            self.next_us = now_us + self.period_us;
+        }
+        true
+    }
+
+    /// Time since boot in microseconds when the next report is due.
+    pub fn deadline_us(&self) -> u64 {
+        self.next_us
+    }
+}
+
+/// Counters on the reports sent, kept for diagnostics.
+#[derive(Debug, Default)]
+pub struct ReportStatistics {
+    reports: AtomicU32,
+    heartbeats: AtomicU32,
+    last_latency_us: AtomicU32,
+    max_latency_us: AtomicU32,
+}
+
+impl ReportStatistics {
+    pub const fn new() -> Self {
+        Self {
+            reports: AtomicU32::new(0),
+            heartbeats: AtomicU32::new(0),
+            last_latency_us: AtomicU32::new(0),
+            max_latency_us: AtomicU32::new(0),
+        }
+    }
+
+    /// Counts a report. The latency is the time from the newest sample in the report to the
+    /// moment the host took it, only meaningful for reports sent because of a change.
+    pub fn record(&self, reason: ReportReason, latency_us: u32) {
+        self.reports.fetch_add(1, Ordering::Relaxed);
+        match reason {
+            ReportReason::Change => {
+                self.last_latency_us.store(latency_us, Ordering::Relaxed);
+                self.max_latency_us.fetch_max(latency_us, Ordering::Relaxed);
+            }
+            ReportReason::Timeout => {
+                self.heartbeats.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Reports sent since boot.
+    pub fn reports(&self) -> u32 {
+        self.reports.load(Ordering::Relaxed)
+    }
+
+    /// Reports sent because of a timeout rather than a change.
+    pub fn heartbeats(&self) -> u32 {
+        self.heartbeats.load(Ordering::Relaxed)
+    }
+
+    pub fn last_latency_us(&self) -> u32 {
+        self.last_latency_us.load(Ordering::Relaxed)
+    }
+
+    pub fn max_latency_us(&self) -> u32 {
+        self.max_latency_us.load(Ordering::Relaxed)
+    }
+
+    /// Starts measuring the maximum latency again.
+    pub fn reset_max_latency(&self) {
+        self.max_latency_us.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Newest sample time of the axes in the snapshot, in microseconds since boot.
+pub fn newest_sample_us(snapshot: &PedalSnapshot) -> u64 {
+    snapshot
+        .axes
+        .iter()
+        .map(|axis| axis.timestamp_us)
+        .max()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod report_mode_testing {
+    use crate::axis::Axis;
+    use crate::pedal_state::{AxisState, PedalSnapshot};
+    use crate::report_mode::{
+        newest_sample_us, PeriodicReport, ReportMode, ReportReason, ReportScheduler,
+        ReportStatistics,
+    };
+    use core::time::Duration;
+    use rstest::rstest;
+
+    const ON_CHANGE: ReportMode = ReportMode::OnChange {
+        threshold: 10,
+        heartbeat_ms: 100,
+    };
+    const PERIODIC: ReportMode = ReportMode::Periodic { interval_ms: 10 };
+
+    fn snapshot(values: [i16; 3], buttons: u8) -> PedalSnapshot {
+        let mut snapshot = PedalSnapshot::UNKNOWN;
+        for axis in Axis::ALL {
+            snapshot.axes[axis.index()] = AxisState {
+                value: values[axis.index()],
+                timestamp_us: 1_000 * axis.index() as u64,
+                ..AxisState::UNKNOWN
+            };
+        }
+        snapshot.buttons = buttons;
+        snapshot
+    }
+
+    fn reported_scheduler() -> ReportScheduler {
+        let mut scheduler = ReportScheduler::new();
+        scheduler.reported(&snapshot([0, 100, -100], 0b1), 50_000);
+        scheduler
+    }
+
+    #[rstest]
+    #[case(ON_CHANGE)]
+    #[case(PERIODIC)]
+    fn when_nothing_was_reported_then_report_is_due(#[case] mode: ReportMode) {
+        // Given
+        let scheduler = ReportScheduler::new();
+
+        // When
+        let result = scheduler.check(mode, &PedalSnapshot::UNKNOWN, 0);
+
+        // Then
+        assert_eq!(result, Some(ReportReason::Change));
+    }
+
+    #[rstest]
+    #[case([10, 100, -100], 0b1, None)]
+    #[case([0, 89, -100], 0b1, Some(ReportReason::Change))]
+    #[case([-11, 100, -100], 0b1, Some(ReportReason::Change))]
+    #[case([0, 100, -100], 0b11, Some(ReportReason::Change))]
+    fn when_pedals_move_in_on_change_mode(
+        #[case] values: [i16; 3],
+        #[case] buttons: u8,
+        #[case] expected: Option<ReportReason>,
+    ) {
+        // Given
+        let scheduler = reported_scheduler();
+
+        // When
+        let result = scheduler.check(ON_CHANGE, &snapshot(values, buttons), 51_000);
+
+        // Then
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn when_small_moves_add_up_then_they_are_reported() {
+        // Given
+        let mut scheduler = reported_scheduler();
+        let first_move = snapshot([6, 100, -100], 0b1);
+        let second_move = snapshot([12, 100, -100], 0b1);
+
+        // When
+        let first = scheduler.check(ON_CHANGE, &first_move, 51_000);
+        let second = scheduler.check(ON_CHANGE, &second_move, 52_000);
+        scheduler.reported(&second_move, 52_000);
+        let after_report = scheduler.check(ON_CHANGE, &second_move, 53_000);
+
+        // Then
+        assert_eq!(first, None);
+        assert_eq!(second, Some(ReportReason::Change));
+        assert_eq!(after_report, None);
+    }
+
+    #[rstest]
+    #[case(ON_CHANGE, 149_999, None)]
+    #[case(ON_CHANGE, 150_000, Some(ReportReason::Timeout))]
+    #[case(PERIODIC, 59_999, None)]
+    #[case(PERIODIC, 60_000, Some(ReportReason::Timeout))]
+    fn when_nothing_changes(
+        #[case] mode: ReportMode,
+        #[case] now_us: u64,
+        #[case] expected: Option<ReportReason>,
+    ) {
+        // Given
+        let scheduler = reported_scheduler();
+
+        // When
+        let result = scheduler.check(mode, &snapshot([0, 100, -100], 0b1), now_us);
+
+        // Then
+        assert_eq!(result, expected);
+        assert_eq!(
+            scheduler.deadline_us(mode),
+            50_000 + mode.max_gap_us(),
+            "deadline of {:?}",
+            mode
+        );
+    }
+
+    #[test]
+    fn when_pedals_move_in_periodic_mode_then_interval_is_kept() {
+        // Given
+        let scheduler = reported_scheduler();
+        let moved = snapshot([30_000, 100, -100], 0);
+
+        // When
+        let early = scheduler.check(PERIODIC, &moved, 55_000);
+        let on_time = scheduler.check(PERIODIC, &moved, 60_000);
+
+        // Then
+        assert_eq!(early, None);
+        assert_eq!(on_time, Some(ReportReason::Timeout));
+    }
+
+    #[test]
+    fn when_reports_are_recorded_then_latency_is_tracked_for_changes() {
+        // Given
+        let statistics = ReportStatistics::new();
+
+        // When
+        statistics.record(ReportReason::Change, 900);
+        statistics.record(ReportReason::Change, 400);
+        statistics.record(ReportReason::Timeout, 80_000);
+
+        // Then
+        assert_eq!(statistics.reports(), 3);
+        assert_eq!(statistics.heartbeats(), 1);
+        assert_eq!(statistics.last_latency_us(), 400);
+        assert_eq!(statistics.max_latency_us(), 900);
+    }
+
+    #[test]
+    fn when_max_latency_is_reset_then_it_starts_over() {
+        // Given
+        let statistics = ReportStatistics::new();
+        statistics.record(ReportReason::Change, 900);
+
+        // When
+        statistics.reset_max_latency();
+        statistics.record(ReportReason::Change, 400);
+
+        // Then
+        assert_eq!(statistics.max_latency_us(), 400);
+    }
+
+    #[test]
+    fn when_snapshot_is_reported_then_newest_sample_counts() {
+        // When
+        let result = newest_sample_us(&snapshot([0, 0, 0], 0));
+
+        // Then
+        assert_eq!(result, 2_000);
+    }
+
+    #[test]
+    fn when_periodic_report_is_checked_then_it_is_due_once_per_period() {
+        // Given
+        let mut status = PeriodicReport::new(Duration::from_millis(100));
+
+        // When
+        let result = [0, 50_000, 100_000, 150_000, 199_999, 200_000, 200_001]
+            .map(|now_us| status.check(now_us));
+
+        // Then
+        assert_eq!(result, [true, false, true, false, false, true, false]);
+        assert_eq!(status.deadline_us(), 300_000);
+    }
+
+    #[test]
+    fn when_periodic_report_is_late_then_the_missed_ones_are_skipped() {
+        // Given
+        let mut status = PeriodicReport::new(Duration::from_millis(100));
+        status.check(0);
+
+        // When
+        let result = [350_000, 360_000, 450_000].map(|now_us| status.check(now_us));
+
+        // Then
+        assert_eq!(result, [true, false, true]);
+        assert_eq!(status.deadline_us(), 550_000);
+    }
+}
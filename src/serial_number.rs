@@ -0,0 +1,174 @@
+//! USB serial number of the pedalbox.
+//!
+//! Every STM32 has a 96-bit unique ID, so its hex digits tell two pedalboxes on one PC
+//! apart. A name the user saved with the calibration replaces it, e.g. to keep the serial
+//! number of a replaced board.
+
+use core::fmt;
+
+/// Size of the unique ID of the chip.
+pub const UID_SIZE: usize = 12;
+/// Longest name the user can give the pedalbox.
+pub const MAX_NAME_LENGTH: usize = 16;
+/// Room for the serial number, the hex digits of the unique ID or the name.
+pub const SERIAL_NUMBER_SIZE: usize = 2 * UID_SIZE;
+
+const _: () = assert!(MAX_NAME_LENGTH <= SERIAL_NUMBER_SIZE);
+
+/// Name given to the pedalbox by the user, of ASCII letters, digits, `-`, `_` and `.`.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DeviceName {
+    bytes: [u8; MAX_NAME_LENGTH],
+    length: usize,
+}
+
+impl DeviceName {
+    /// `None` when the name is empty, longer than [`MAX_NAME_LENGTH`] or has other
+    /// characters.
+    pub fn new(name: &str) -> Option<Self> {
+        let allowed = |byte: &u8| byte.is_ascii_alphanumeric() || b"-_.".contains(byte);
+        if name.is_empty() || name.len() > MAX_NAME_LENGTH || !name.bytes().all(|b| allowed(&b)) {
+            return None;
+        }
+        let mut bytes = [0; MAX_NAME_LENGTH];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Some(Self {
+            bytes,
+            length: name.len(),
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only ASCII gets in through `new`
+        core::str::from_utf8(&self.bytes[..self.length]).unwrap_or_default()
+    }
+}
+
+impl fmt::Display for DeviceName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Writes the unique ID as upper case hex digits, in the order of its bytes in memory.
+pub fn encode_uid<'a>(uid: &[u8; UID_SIZE], buffer: &'a mut [u8; SERIAL_NUMBER_SIZE]) -> &'a str {
+    const DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+    for (pair, byte) in buffer.chunks_exact_mut(2).zip(uid) {
+        pair[0] = DIGITS[(byte >> 4) as usize];
+        pair[1] = DIGITS[(byte & 0x0F) as usize];
+    }
+    // Only hex digits were written
+    core::str::from_utf8(buffer).unwrap_or_default()
+}
+
+/// The serial number: the name when the user gave one, the unique ID otherwise.
+pub fn serial_number<'a>(
+    uid: &[u8; UID_SIZE],
+    name: Option<&DeviceName>,
+    buffer: &'a mut [u8; SERIAL_NUMBER_SIZE],
+) -> &'a str {
+    match name {
+        Some(name) => {
+            let length = name.length;
+            buffer[..length].copy_from_slice(&name.bytes[..length]);
+            core::str::from_utf8(&buffer[..length]).unwrap_or_default()
+        }
+        None => encode_uid(uid, buffer),
+    }
+}
+
+#[cfg(test)]
+mod serial_number_testing {
+    use crate::serial_number::{
+        encode_uid, serial_number, DeviceName, MAX_NAME_LENGTH, SERIAL_NUMBER_SIZE,
+    };
+    use rstest::rstest;
+
+    const UID: [u8; 12] = [
+        0x2B, 0x00, 0x3C, 0x00, 0x0F, 0x51, 0x34, 0x37, 0x31, 0x38, 0x39, 0xA7,
+    ];
+
+    #[test]
+    fn when_uid_is_encoded_then_every_byte_gives_two_upper_case_hex_digits() {
+        // Given
+        let mut buffer = [0; SERIAL_NUMBER_SIZE];
+
+        // When
+        let result = encode_uid(&UID, &mut buffer);
+
+        // Then
+        assert_eq!(result, "2B003C000F513437313839A7");
+    }
+
+    #[rstest]
+    #[case([0x00; 12], "000000000000000000000000")]
+    #[case([0xFF; 12], "FFFFFFFFFFFFFFFFFFFFFFFF")]
+    #[case([0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF, 0x10, 0x32, 0x54, 0x76], "0123456789ABCDEF10325476")]
+    fn when_uid_is_encoded(#[case] uid: [u8; 12], #[case] expected: &str) {
+        // Given
+        let mut buffer = [0; SERIAL_NUMBER_SIZE];
+
+        // When
+        let result = encode_uid(&uid, &mut buffer);
+
+        // Then
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn when_uids_differ_in_one_bit_then_the_serial_numbers_differ() {
+        // Given
+        let mut other = UID;
+        other[11] ^= 0x01;
+        let (mut first, mut second) = ([0; SERIAL_NUMBER_SIZE], [0; SERIAL_NUMBER_SIZE]);
+
+        // When
+        let result = (
+            serial_number(&UID, None, &mut first).to_owned(),
+            serial_number(&other, None, &mut second).to_owned(),
+        );
+
+        // Then
+        assert_ne!(result.0, result.1);
+    }
+
+    #[test]
+    fn when_user_named_the_pedalbox_then_the_name_is_the_serial_number() {
+        // Given
+        let name = DeviceName::new("rig-2.left_seat").unwrap();
+        let mut buffer = [0; SERIAL_NUMBER_SIZE];
+
+        // When
+        let result = serial_number(&UID, Some(&name), &mut buffer);
+
+        // Then
+        assert_eq!(result, "rig-2.left_seat");
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("with space")]
+    #[case("comma,separated")]
+    #[case("pédale")]
+    #[case("seventeen-chars-x")]
+    fn when_name_is_invalid_then_it_is_refused(#[case] name: &str) {
+        // When
+        let result = DeviceName::new(name);
+
+        // Then
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn when_name_is_as_long_as_allowed_then_it_is_kept_whole() {
+        // Given
+        let text = "A".repeat(MAX_NAME_LENGTH);
+
+        // When
+        let result = DeviceName::new(&text).map(|name| name.to_string());
+
+        // Then
+        assert_eq!(result.as_deref(), Some(text.as_str()));
+    }
+}
@@ -0,0 +1,123 @@
+//! Runtime settings of the pedal axes.
+//!
+//! The monitors start with the settings found here and pick up later changes, e.g. the
+//! ones written by the host through the HID feature reports. Every change bumps a
+//! generation counter, so a monitor only has to compare a number to know whether it
+//! needs to reload its settings.
+
+use crate::axis::{Axis, AXIS_COUNT};
+use crate::calibration::{AxisCalibration, Calibration};
+use crate::curve::ResponseCurve;
+use crate::deadzone::Deadzone;
+use crate::filters::FilterChain;
+use core::cell::Cell;
+use core::sync::atomic::{AtomicU32, Ordering};
+use critical_section::Mutex;
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AxisSettings {
+    pub calibration: AxisCalibration,
+    pub deadzone: Deadzone,
+    pub curve: ResponseCurve,
+    pub filter: FilterChain,
+}
+
+impl AxisSettings {
+    pub const DEFAULT: AxisSettings = AxisSettings {
+        calibration: AxisCalibration::new(0, 0),
+        deadzone: Deadzone::NONE,
+        curve: ResponseCurve::Linear,
+        filter: FilterChain::NONE,
+    };
+}
+
+/// Settings of all axes shared between the tasks.
+pub struct SharedSettings {
+    axes: Mutex<Cell<[AxisSettings; AXIS_COUNT]>>,
+    generation: AtomicU32,
+}
+
+impl SharedSettings {
+    pub const fn new() -> Self {
+        Self {
+            axes: Mutex::new(Cell::new([AxisSettings::DEFAULT; AXIS_COUNT])),
+            generation: AtomicU32::new(0),
+        }
+    }
+
+    pub fn get(&self, axis: Axis) -> AxisSettings {
+        critical_section::with(|cs| self.axes.borrow(cs).get()[axis.index()])
+    }
+
+    pub fn set(&self, axis: Axis, settings: AxisSettings) {
+        self.update(axis, |current| *current = settings);
+    }
+
+    pub fn update(&self, axis: Axis, change: impl FnOnce(&mut AxisSettings)) {
+        critical_section::with(|cs| {
+            let cell = self.axes.borrow(cs);
+            let mut axes = cell.get();
+            change(&mut axes[axis.index()]);
+            cell.set(axes);
+        });
+        self.generation.fetch_add(1, Ordering::Release);
+    }
+
+    /// Changes whenever any of the settings change.
+    pub fn generation(&self) -> u32 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    pub fn calibration(&self) -> Calibration {
+        Calibration {
+            axes: Axis::ALL.map(|axis| self.get(axis).calibration),
+        }
+    }
+}
+
+impl Default for SharedSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod settings_testing {
+    use crate::axis::Axis;
+    use crate::calibration::AxisCalibration;
+    use crate::curve::ResponseCurve;
+    use crate::settings::{AxisSettings, SharedSettings};
+
+    #[test]
+    fn when_settings_are_updated_then_only_the_given_axis_changes() {
+        // Given
+        let settings = SharedSettings::new();
+        let generation = settings.generation();
+
+        // When
+        settings.update(Axis::Y, |axis| axis.curve = ResponseCurve::Progressive(50));
+
+        // Then
+        assert_ne!(settings.generation(), generation);
+        assert_eq!(settings.get(Axis::Y).curve, ResponseCurve::Progressive(50));
+        assert_eq!(settings.get(Axis::X), AxisSettings::DEFAULT);
+        assert_eq!(settings.get(Axis::Z), AxisSettings::DEFAULT);
+    }
+
+    #[test]
+    fn when_calibration_is_collected_then_it_holds_every_axis() {
+        // Given
+        let settings = SharedSettings::new();
+        settings.update(Axis::X, |axis| {
+            axis.calibration = AxisCalibration::new(1820, 3100)
+        });
+
+        // When
+        let result = settings.calibration();
+
+        // Then
+        assert_eq!(result.axis(Axis::X), AxisCalibration::new(1820, 3100));
+        assert_eq!(result.axis(Axis::Z), AxisCalibration::new(0, 0));
+    }
+}
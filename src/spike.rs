@@ -0,0 +1,248 @@
+//! Spike and glitch rejection for raw sensor readings.
+//!
+//! The HX711 sometimes returns garbage after a bit-slip on the serial line. Such samples
+//! are far away from the recent history and change faster than a foot can press a pedal,
+//! so they are detected by comparing each sample against the median of the last accepted
+//! samples and against the last accepted sample.
+
+/// Largest supported history window.
+pub const MAX_SPIKE_HISTORY: usize = 8;
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RejectAction {
+    /// Rejected samples are dropped and the output isn't updated.
+    Discard,
+    /// The last good sample is used again in place of the rejected one.
+    HoldLastGood,
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SpikeRejection {
+    /// Largest accepted distance from the median of the recent samples, in raw units.
+    pub max_jump: u32,
+    /// Largest accepted change between two consecutive samples, in raw units.
+    pub max_slew: u32,
+    /// Number of accepted samples the median is taken from, up to [`MAX_SPIKE_HISTORY`].
+    pub history: u8,
+    /// After this many rejections in a row the reading is accepted as the new level,
+    /// so a real step change can't lock the output forever. `0` never gives up.
+    pub max_consecutive_rejections: u8,
+    pub action: RejectAction,
+}
+
+impl SpikeRejection {
+    pub const DISABLED: SpikeRejection = SpikeRejection {
+        max_jump: u32::MAX,
+        max_slew: u32::MAX,
+        history: 1,
+        max_consecutive_rejections: 0,
+        action: RejectAction::Discard,
+    };
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum Verdict {
+    Accepted(i64),
+    /// The sample was rejected, `last_good` is the last accepted sample.
+    Rejected {
+        last_good: i64,
+    },
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub struct SpikeRejector {
+    config: SpikeRejection,
+    history: [i64; MAX_SPIKE_HISTORY],
+    len: usize,
+    next: usize,
+    last_good: Option<i64>,
+    consecutive_rejections: u8,
+}
+
+impl SpikeRejector {
+    pub fn new(config: SpikeRejection) -> Self {
+        Self {
+            config,
+            history: [0; MAX_SPIKE_HISTORY],
+            len: 0,
+            next: 0,
+            last_good: None,
+            consecutive_rejections: 0,
+        }
+    }
+
+    pub fn config(&self) -> SpikeRejection {
+        self.config
+    }
+
+    pub fn check(&mut self, value: i64) -> Verdict {
+        let Some(last_good) = self.last_good else {
+            self.accept(value);
+            return Verdict::Accepted(value);
+        };
+
+        let jump = (value - self.median()).unsigned_abs();
+        let slew = (value - last_good).unsigned_abs();
+        let plausible = jump <= self.config.max_jump as u64 && slew <= self.config.max_slew as u64;
+        let give_up = self.config.max_consecutive_rejections != 0
+            && self.consecutive_rejections >= self.config.max_consecutive_rejections;
+
+        if plausible {
+            self.accept(value);
+            Verdict::Accepted(value)
+        } else if give_up {
+            self.reset();
+            self.accept(value);
+            Verdict::Accepted(value)
+        } else {
+            self.consecutive_rejections = self.consecutive_rejections.saturating_add(1);
+            Verdict::Rejected { last_good }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.next = 0;
+        self.last_good = None;
+        self.consecutive_rejections = 0;
+    }
+
+    fn window(&self) -> usize {
+        (self.config.history as usize).clamp(1, MAX_SPIKE_HISTORY)
+    }
+
+    fn accept(&mut self, value: i64) {
+        let window = self.window();
+        self.history[self.next] = value;
+        self.next = (self.next + 1) % window;
+        self.len = (self.len + 1).min(window);
+        self.last_good = Some(value);
+        self.consecutive_rejections = 0;
+    }
+
+    fn median(&self) -> i64 {
+        let mut sorted = self.history;
+        let sorted = &mut sorted[..self.len];
+        sorted.sort_unstable();
+        sorted[self.len / 2]
+    }
+}
+
+#[cfg(test)]
+mod spike_testing {
+    use crate::spike::{RejectAction, SpikeRejection, SpikeRejector, Verdict};
+    use rstest::rstest;
+
+    const CONFIG: SpikeRejection = SpikeRejection {
+        max_jump: 1_000,
+        max_slew: 500,
+        history: 5,
+        max_consecutive_rejections: 3,
+        action: RejectAction::HoldLastGood,
+    };
+
+    #[test]
+    fn when_first_sample_arrives_then_it_is_accepted() {
+        // Given
+        let mut rejector = SpikeRejector::new(CONFIG);
+
+        // When
+        let result = rejector.check(8_000_000);
+
+        // Then
+        assert_eq!(result, Verdict::Accepted(8_000_000));
+    }
+
+    #[rstest]
+    #[case(10_400, Verdict::Accepted(10_400))]
+    #[case(9_600, Verdict::Accepted(9_600))]
+    #[case(10_501, Verdict::Rejected { last_good: 10_000 })]
+    #[case(-8_388_608, Verdict::Rejected { last_good: 10_000 })]
+    #[case(8_388_607, Verdict::Rejected { last_good: 10_000 })]
+    fn when_sample_follows_a_steady_level(#[case] value: i64, #[case] expected: Verdict) {
+        // Given
+        let mut rejector = SpikeRejector::new(CONFIG);
+        (0..5).for_each(|_| {
+            rejector.check(10_000);
+        });
+
+        // When
+        let result = rejector.check(value);
+
+        // Then
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn when_pedal_ramps_slowly_then_the_median_check_rejects_a_far_sample() {
+        // Given
+        let mut rejector = SpikeRejector::new(SpikeRejection {
+            max_slew: u32::MAX,
+            ..CONFIG
+        });
+        [0, 200, 400, 600, 800].iter().for_each(|&v| {
+            rejector.check(v);
+        });
+
+        // When
+        let ramp = rejector.check(1_000);
+        let spike = rejector.check(3_000);
+
+        // Then
+        assert_eq!(ramp, Verdict::Accepted(1_000));
+        assert_eq!(spike, Verdict::Rejected { last_good: 1_000 });
+    }
+
+    #[test]
+    fn when_level_changes_for_good_then_it_is_accepted_after_the_limit() {
+        // Given
+        let mut rejector = SpikeRejector::new(CONFIG);
+        rejector.check(0);
+
+        // When
+        let results: alloc::vec::Vec<Verdict> = (0..5).map(|_| rejector.check(50_000)).collect();
+
+        // Then
+        assert_eq!(
+            results,
+            [
+                Verdict::Rejected { last_good: 0 },
+                Verdict::Rejected { last_good: 0 },
+                Verdict::Rejected { last_good: 0 },
+                Verdict::Accepted(50_000),
+                Verdict::Accepted(50_000),
+            ]
+        );
+    }
+
+    #[test]
+    fn when_limit_is_zero_then_rejection_never_gives_up() {
+        // Given
+        let mut rejector = SpikeRejector::new(SpikeRejection {
+            max_consecutive_rejections: 0,
+            ..CONFIG
+        });
+        rejector.check(0);
+
+        // When
+        let result = (0..300).map(|_| rejector.check(50_000)).last().unwrap();
+
+        // Then
+        assert_eq!(result, Verdict::Rejected { last_good: 0 });
+    }
+
+    #[test]
+    fn when_disabled_then_every_sample_is_accepted() {
+        // Given
+        let mut rejector = SpikeRejector::new(SpikeRejection::DISABLED);
+        rejector.check(0);
+
+        // When
+        let result = rejector.check(i32::MIN as i64);
+
+        // Then
+        assert_eq!(result, Verdict::Accepted(i32::MIN as i64));
+    }
+}
@@ -0,0 +1,379 @@
+//! Supervision of the firmware tasks.
+//!
+//! A task that hangs freezes its pedal without any fault, because nothing publishes a new
+//! value. Every supervised task checks in to its [`TaskWatch`] whenever it makes progress
+//! and the [`Supervisor`] only feeds the independent watchdog while every task checked in
+//! within its deadline. The overdue task is written to a backup register before the
+//! watchdog resets the chip, so the next boot can tell why it happened through
+//! [`ResetCause`].
+
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::time::Duration;
+
+/// Tasks watched by the supervisor.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SupervisedTask {
+    /// Runs the monitors of the pedals and the buttons.
+    Monitors = 1,
+    /// Collects the oversampled potentiometer readings.
+    Adc = 2,
+    /// Reads the conversions of the HX711.
+    LoadCell = 3,
+}
+
+impl SupervisedTask {
+    pub const ALL: [SupervisedTask; 3] = [
+        SupervisedTask::Monitors,
+        SupervisedTask::Adc,
+        SupervisedTask::LoadCell,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SupervisedTask::Monitors => "monitors",
+            SupervisedTask::Adc => "adc",
+            SupervisedTask::LoadCell => "load cell",
+        }
+    }
+}
+
+impl TryFrom<u8> for SupervisedTask {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        SupervisedTask::ALL
+            .into_iter()
+            .find(|task| *task as u8 == value)
+            .ok_or(value)
+    }
+}
+
+/// Check-ins of a supervised task, shared between the task and the supervisor.
+#[derive(Debug)]
+pub struct TaskWatch {
+    task: SupervisedTask,
+    deadline_ms: u32,
+    last_check_in_ms: AtomicU32,
+}
+
+impl TaskWatch {
+    /// The task has to check in at least once per `deadline`.
+    pub const fn new(task: SupervisedTask, deadline: Duration) -> Self {
+        Self {
+            task,
+            deadline_ms: deadline.as_millis() as u32,
+            last_check_in_ms: AtomicU32::new(0),
+        }
+    }
+
+    pub fn task(&self) -> SupervisedTask {
+        self.task
+    }
+
+    /// `now_ms` is the uptime in milliseconds, it may wrap around.
+    pub fn check_in(&self, now_ms: u32) {
+        self.last_check_in_ms.store(now_ms, Ordering::Relaxed);
+    }
+
+    pub fn is_overdue(&self, now_ms: u32) -> bool {
+        let elapsed = now_ms.wrapping_sub(self.last_check_in_ms.load(Ordering::Relaxed));
+        elapsed > self.deadline_ms
+    }
+}
+
+/// Decides whether the watchdog may be fed.
+pub struct Supervisor<'a, const N: usize> {
+    watches: [&'a TaskWatch; N],
+    /// Longest gap between two checks before the supervisor counts as stalled.
+    max_gap_ms: u32,
+    last_check_ms: u32,
+}
+
+impl<'a, const N: usize> Supervisor<'a, N> {
+    /// Starts the deadlines of every task at `now_ms`. A gap longer than `max_gap` between
+    /// two checks means the supervisor was held up, so it has to leave room for the jitter
+    /// of its period, and every deadline has to be longer than `max_gap` plus the time
+    /// between two check-ins of its task.
+    pub fn new(watches: [&'a TaskWatch; N], max_gap: Duration, now_ms: u32) -> Self {
+        let mut supervisor = Self {
+            watches,
+            max_gap_ms: max_gap.as_millis() as u32,
+            last_check_ms: now_ms,
+        };
+        supervisor.restart(now_ms);
+        supervisor
+    }
+
+    /// The first task that missed its deadline. When the supervisor itself was held up for
+    /// longer than its maximum gap, e.g. by a flash erase, every task was blocked
+    /// with it and the deadlines start over instead.
+    pub fn overdue(&mut self, now_ms: u32) -> Option<SupervisedTask> {
+        let gap = now_ms.wrapping_sub(self.last_check_ms);
+        self.last_check_ms = now_ms;
+        if gap > self.max_gap_ms {
+            self.restart(now_ms);
+            return None;
+        }
+        self.watches
+            .iter()
+            .find(|watch| watch.is_overdue(now_ms))
+            .map(|watch| watch.task())
+    }
+
+    fn restart(&mut self, now_ms: u32) {
+        for watch in self.watches {
+            watch.check_in(now_ms);
+        }
+    }
+}
+
+/// Marks a backup register that holds the task the supervisor reset the chip for.
+const CULPRIT_MAGIC: u32 = 0x5afe_d000;
+const CULPRIT_MASK: u32 = 0xff;
+
+/// Value of the backup register recording `task` as the culprit of a watchdog reset.
+pub fn culprit_record(task: SupervisedTask) -> u32 {
+    CULPRIT_MAGIC | task as u32
+}
+
+/// Reset flags of the RCC control and status register.
+const BORRSTF: u32 = 1 << 25;
+const PINRSTF: u32 = 1 << 26;
+const PORRSTF: u32 = 1 << 27;
+const SFTRSTF: u32 = 1 << 28;
+const IWDGRSTF: u32 = 1 << 29;
+const WWDGRSTF: u32 = 1 << 30;
+const LPWRRSTF: u32 = 1 << 31;
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ResetReason {
+    /// No reset flag was set.
+    Unknown = 0,
+    PowerOn = 1,
+    /// The reset pin, e.g. the reset button or a debugger.
+    Pin = 2,
+    Brownout = 3,
+    Software = 4,
+    IndependentWatchdog = 5,
+    WindowWatchdog = 6,
+    LowPower = 7,
+}
+
+impl ResetReason {
+    /// Reason of the last reset from the RCC control and status register. A power-on
+    /// reset also sets the brownout and pin flags and every reset sets the pin flag, so the
+    /// most specific flag wins.
+    pub fn from_csr(csr: u32) -> Self {
+        [
+            (LPWRRSTF, ResetReason::LowPower),
+            (WWDGRSTF, ResetReason::WindowWatchdog),
+            (IWDGRSTF, ResetReason::IndependentWatchdog),
+            (SFTRSTF, ResetReason::Software),
+            (PORRSTF, ResetReason::PowerOn),
+            (BORRSTF, ResetReason::Brownout),
+            (PINRSTF, ResetReason::Pin),
+        ]
+        .into_iter()
+        .find(|(flag, _)| csr & flag != 0)
+        .map_or(ResetReason::Unknown, |(_, reason)| reason)
+    }
+}
+
+impl From<u8> for ResetReason {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => ResetReason::PowerOn,
+            2 => ResetReason::Pin,
+            3 => ResetReason::Brownout,
+            4 => ResetReason::Software,
+            5 => ResetReason::IndependentWatchdog,
+            6 => ResetReason::WindowWatchdog,
+            7 => ResetReason::LowPower,
+            _ => ResetReason::Unknown,
+        }
+    }
+}
+
+/// Why the firmware started.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ResetCause {
+    pub reason: ResetReason,
+    /// The task the supervisor reset the chip for.
+    pub culprit: Option<SupervisedTask>,
+}
+
+impl ResetCause {
+    /// `csr` is the RCC control and status register and `backup` the backup register of
+    /// [`culprit_record`]. A culprit only counts when the watchdog did the reset, any
+    /// other reset came before the watchdog could fire.
+    pub fn from_registers(csr: u32, backup: u32) -> Self {
+        let reason = ResetReason::from_csr(csr);
+        let culprit = (reason == ResetReason::IndependentWatchdog
+            && backup & !CULPRIT_MASK == CULPRIT_MAGIC)
+            .then(|| SupervisedTask::try_from((backup & CULPRIT_MASK) as u8).ok())
+            .flatten();
+        Self { reason, culprit }
+    }
+}
+
+#[cfg(test)]
+mod supervisor_testing {
+    use crate::supervisor::{
+        culprit_record, ResetCause, ResetReason, SupervisedTask, Supervisor, TaskWatch,
+    };
+    use core::time::Duration;
+    use rstest::rstest;
+
+    const MAX_GAP: Duration = Duration::from_millis(200);
+
+    fn watch(task: SupervisedTask, deadline_ms: u64) -> &'static TaskWatch {
+        alloc::boxed::Box::leak(alloc::boxed::Box::new(TaskWatch::new(
+            task,
+            Duration::from_millis(deadline_ms),
+        )))
+    }
+
+    #[test]
+    fn when_every_task_checks_in_then_none_is_overdue() {
+        // Given
+        let monitors = watch(SupervisedTask::Monitors, 100);
+        let adc = watch(SupervisedTask::Adc, 50);
+        let mut supervisor = Supervisor::new([monitors, adc], MAX_GAP, 1_000);
+
+        // When
+        monitors.check_in(1_030);
+        adc.check_in(1_040);
+        supervisor.overdue(1_040);
+        monitors.check_in(1_070);
+        adc.check_in(1_080);
+        let result = supervisor.overdue(1_080);
+
+        // Then
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn when_a_task_misses_its_deadline_then_it_is_the_culprit() {
+        // Given
+        let monitors = watch(SupervisedTask::Monitors, 100);
+        let load_cell = watch(SupervisedTask::LoadCell, 500);
+        let mut supervisor = Supervisor::new([monitors, load_cell], MAX_GAP, 0);
+
+        // When
+        for now in (50..=450).step_by(50) {
+            monitors.check_in(now);
+            supervisor.overdue(now);
+        }
+        let before = supervisor.overdue(500);
+        let after = supervisor.overdue(501);
+
+        // Then
+        assert_eq!(before, None);
+        assert_eq!(after, Some(SupervisedTask::LoadCell));
+    }
+
+    #[test]
+    fn when_a_task_never_checks_in_then_its_deadline_starts_with_the_supervisor() {
+        // Given
+        let adc = watch(SupervisedTask::Adc, 50);
+
+        // When
+        let mut supervisor = Supervisor::new([adc], MAX_GAP, 10_000);
+
+        // Then
+        assert_eq!(supervisor.overdue(10_030), None);
+        assert_eq!(supervisor.overdue(10_050), None);
+        assert_eq!(supervisor.overdue(10_051), Some(SupervisedTask::Adc));
+    }
+
+    #[test]
+    fn when_uptime_wraps_around_then_the_deadline_still_holds() {
+        // Given
+        let adc = watch(SupervisedTask::Adc, 50);
+        let mut supervisor = Supervisor::new([adc], MAX_GAP, u32::MAX - 10);
+
+        // When
+        let result = supervisor.overdue(20);
+
+        // Then
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn when_supervisor_was_held_up_then_the_deadlines_start_over() {
+        // Given
+        let monitors = watch(SupervisedTask::Monitors, 100);
+        let load_cell = watch(SupervisedTask::LoadCell, 500);
+        let mut supervisor = Supervisor::new([monitors, load_cell], MAX_GAP, 0);
+
+        // When
+        let after_stall = supervisor.overdue(2_000);
+        let next = supervisor.overdue(2_100);
+        let late = supervisor.overdue(2_150);
+
+        // Then
+        assert_eq!(after_stall, None);
+        assert_eq!(next, None);
+        assert_eq!(late, Some(SupervisedTask::Monitors));
+    }
+
+    #[test]
+    fn when_supervisor_runs_a_bit_late_then_a_silent_task_is_still_reported() {
+        // Given
+        let monitors = watch(SupervisedTask::Monitors, 300);
+        let load_cell = watch(SupervisedTask::LoadCell, 500);
+        let mut supervisor = Supervisor::new([monitors, load_cell], MAX_GAP, 0);
+
+        // When
+        let result = (1..=10).map(|period| period * 101).find_map(|now| {
+            monitors.check_in(now);
+            supervisor.overdue(now)
+        });
+
+        // Then
+        assert_eq!(result, Some(SupervisedTask::LoadCell));
+    }
+
+    #[rstest]
+    #[case(0, ResetReason::Unknown)]
+    #[case(0x0e00_0000, ResetReason::PowerOn)]
+    #[case(0x0600_0000, ResetReason::Brownout)]
+    #[case(0x0400_0000, ResetReason::Pin)]
+    #[case(0x1400_0000, ResetReason::Software)]
+    #[case(0x2400_0000, ResetReason::IndependentWatchdog)]
+    #[case(0x4400_0000, ResetReason::WindowWatchdog)]
+    #[case(0x8400_0000, ResetReason::LowPower)]
+    fn when_reset_flags_are_read(#[case] csr: u32, #[case] expected: ResetReason) {
+        // When
+        let result = ResetReason::from_csr(csr);
+
+        // Then
+        assert_eq!(result, expected);
+        assert_eq!(ResetReason::from(result as u8), result);
+    }
+
+    #[rstest]
+    #[case(
+        0x2400_0000,
+        culprit_record(SupervisedTask::LoadCell),
+        Some(SupervisedTask::LoadCell)
+    )]
+    #[case(0x2400_0000, 0, None)]
+    #[case(0x2400_0000, 0x1234_5603, None)]
+    #[case(0x2400_0000, culprit_record(SupervisedTask::Adc) + 7, None)]
+    #[case(0x0400_0000, culprit_record(SupervisedTask::Adc), None)]
+    fn when_reset_cause_is_read(
+        #[case] csr: u32,
+        #[case] backup: u32,
+        #[case] expected: Option<SupervisedTask>,
+    ) {
+        // When
+        let result = ResetCause::from_registers(csr, backup);
+
+        // Then
+        assert_eq!(result.culprit, expected);
+    }
+}
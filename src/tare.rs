@@ -0,0 +1,242 @@
+//! Taring (zeroing) of sensors whose offset drifts, like the HX711 load cell.
+//!
+//! The offset is the average of a number of samples taken while the pedal is at rest. If
+//! the samples spread too much the pedal was most likely touched, so the collection
+//! starts over. The offset is kept by the monitor and subtracted from every reading,
+//! which keeps the [`LoadCell`](crate::LoadCell) implementations unaware of it.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TareConfig {
+    /// Number of samples averaged into the offset. `0` disables taring.
+    pub samples: u8,
+    /// Largest accepted standard deviation of the samples, in raw units.
+    pub max_deviation: u32,
+    /// Tare as soon as the monitor starts.
+    pub at_startup: bool,
+}
+
+impl TareConfig {
+    pub const DISABLED: TareConfig = TareConfig {
+        samples: 0,
+        max_deviation: u32::MAX,
+        at_startup: false,
+    };
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TareStatus {
+    Idle,
+    Collecting,
+    /// A new offset was measured.
+    Done(i64),
+    /// The samples spread too much, the collection starts over.
+    Rejected {
+        deviation: u64,
+    },
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub struct Tare {
+    config: TareConfig,
+    active: bool,
+    count: u8,
+    sum: i128,
+    sum_of_squares: i128,
+}
+
+impl Tare {
+    pub fn new(config: TareConfig) -> Self {
+        Self {
+            config,
+            active: config.at_startup && config.samples > 0,
+            count: 0,
+            sum: 0,
+            sum_of_squares: 0,
+        }
+    }
+
+    pub fn config(&self) -> TareConfig {
+        self.config
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Starts a new collection. Does nothing when taring is disabled.
+    pub fn start(&mut self) {
+        self.clear();
+        self.active = self.config.samples > 0;
+    }
+
+    pub fn add_sample(&mut self, value: i64) -> TareStatus {
+        if !self.active {
+            return TareStatus::Idle;
+        }
+
+        self.count += 1;
+        self.sum += value as i128;
+        self.sum_of_squares += value as i128 * value as i128;
+        if self.count < self.config.samples {
+            return TareStatus::Collecting;
+        }
+
+        let count = self.count as i128;
+        let variance = (count * self.sum_of_squares - self.sum * self.sum) / (count * count);
+        let max_variance = self.config.max_deviation as i128 * self.config.max_deviation as i128;
+        if variance > max_variance {
+            let deviation = isqrt(variance as u128);
+            self.clear();
+            return TareStatus::Rejected { deviation };
+        }
+
+        let offset = rounded_div(self.sum, count) as i64;
+        self.clear();
+        self.active = false;
+        TareStatus::Done(offset)
+    }
+
+    fn clear(&mut self) {
+        self.count = 0;
+        self.sum = 0;
+        self.sum_of_squares = 0;
+    }
+}
+
+/// Flag used by other tasks to ask a monitor for a new tare.
+#[derive(Debug, Default)]
+pub struct TareRequest(AtomicBool);
+
+impl TareRequest {
+    pub const fn new() -> Self {
+        Self(AtomicBool::new(false))
+    }
+
+    pub fn request(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns whether a tare was requested and clears the request.
+    pub fn take(&self) -> bool {
+        self.0.swap(false, Ordering::Relaxed)
+    }
+}
+
+fn rounded_div(numerator: i128, denominator: i128) -> i128 {
+    if numerator >= 0 {
+        (numerator + denominator / 2) / denominator
+    } else {
+        (numerator - denominator / 2) / denominator
+    }
+}
+
+fn isqrt(value: u128) -> u64 {
+    let mut low: u128 = 0;
+    let mut high: u128 = u64::MAX as u128;
+    while low < high {
+        let middle = (low + high).div_ceil(2);
+        if middle * middle <= value {
+            low = middle;
+        } else {
+            high = middle - 1;
+        }
+    }
+    low as u64
+}
+
+#[cfg(test)]
+mod tare_testing {
+    use crate::tare::{Tare, TareConfig, TareRequest, TareStatus};
+    use rstest::rstest;
+
+    const CONFIG: TareConfig = TareConfig {
+        samples: 4,
+        max_deviation: 100,
+        at_startup: true,
+    };
+
+    #[rstest]
+    #[case(&[1000, 1000, 1000, 1000], TareStatus::Done(1000))]
+    #[case(&[1000, 1100, 1000, 1100], TareStatus::Done(1050))]
+    #[case(&[-1000, -1100, -1000, -1101], TareStatus::Done(-1050))]
+    #[case(&[1000, 1000, 1000], TareStatus::Collecting)]
+    #[case(&[0, 0, 1000, 1000], TareStatus::Rejected { deviation: 500 })]
+    #[case(&[1000, 1000, 1000, 1000, 1000], TareStatus::Idle)]
+    fn when_samples_are_collected(#[case] samples: &[i64], #[case] expected: TareStatus) {
+        // Given
+        let mut tare = Tare::new(CONFIG);
+
+        // When
+        let result = samples.iter().map(|&v| tare.add_sample(v)).last().unwrap();
+
+        // Then
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn when_collection_is_rejected_then_it_starts_over() {
+        // Given
+        let mut tare = Tare::new(CONFIG);
+        [0, 0, 1000, 1000].iter().for_each(|&v| {
+            tare.add_sample(v);
+        });
+
+        // When
+        let result = [20, 20, 20, 20]
+            .iter()
+            .map(|&v| tare.add_sample(v))
+            .last()
+            .unwrap();
+
+        // Then
+        assert_eq!(result, TareStatus::Done(20));
+    }
+
+    #[rstest]
+    #[case(CONFIG, true)]
+    #[case(TareConfig { at_startup: false, ..CONFIG }, false)]
+    #[case(TareConfig::DISABLED, false)]
+    fn when_creating_new_tare(#[case] config: TareConfig, #[case] expected: bool) {
+        // When
+        let result = Tare::new(config);
+
+        // Then
+        assert_eq!(result.is_active(), expected);
+    }
+
+    #[rstest]
+    #[case(CONFIG, true)]
+    #[case(TareConfig::DISABLED, false)]
+    fn when_tare_is_started_on_demand(#[case] config: TareConfig, #[case] expected: bool) {
+        // Given
+        let mut tare = Tare::new(TareConfig {
+            at_startup: false,
+            ..config
+        });
+
+        // When
+        tare.start();
+
+        // Then
+        assert_eq!(tare.is_active(), expected);
+    }
+
+    #[test]
+    fn when_tare_is_requested_then_the_request_is_taken_once() {
+        // Given
+        let request = TareRequest::new();
+        request.request();
+
+        // When
+        let first = request.take();
+        let second = request.take();
+
+        // Then
+        assert!(first);
+        assert!(!second);
+    }
+}
@@ -0,0 +1,109 @@
+//! Temperature of the chip, measured by its internal sensor on the ADC scan.
+
+/// Readings of the temperature sensor at 30 °C and 110 °C, converted with 12 bits at
+/// VDDA = 3.3 V. The factory stores them in the system memory of every chip.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TemperatureCalibration {
+    pub at_30: u16,
+    pub at_110: u16,
+}
+
+impl TemperatureCalibration {
+    /// Typical sensor of the datasheet, 0.76 V at 25 °C and 2.5 mV/°C.
+    pub const TYPICAL: TemperatureCalibration = TemperatureCalibration {
+        at_30: 959,
+        at_110: 1207,
+    };
+
+    /// Whether the values look like a calibration, an erased or missing one doesn't.
+    pub fn is_valid(&self) -> bool {
+        self.at_30 < self.at_110 && self.at_110 < 0x0FFF
+    }
+
+    /// Temperature in tenths of °C of a reading with `extra_bits` of oversampling on top of
+    /// the 12 bits of the ADC.
+    pub fn decicelsius(&self, reading: u16, extra_bits: u32) -> i16 {
+        let at_30 = (self.at_30 as i32) << extra_bits;
+        let span = ((self.at_110 as i32) << extra_bits) - at_30;
+        if span <= 0 {
+            return i16::MIN;
+        }
+        let tenths = 300 + (reading as i32 - at_30) * 800 / span;
+        tenths.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+    }
+}
+
+#[cfg(test)]
+mod temperature_testing {
+    use crate::temperature::TemperatureCalibration;
+    use rstest::rstest;
+
+    const CALIBRATION: TemperatureCalibration = TemperatureCalibration {
+        at_30: 1000,
+        at_110: 1200,
+    };
+
+    #[rstest]
+    #[case(1000, 0, 300)]
+    #[case(1200, 0, 1100)]
+    #[case(1100, 0, 700)]
+    #[case(1050, 0, 500)]
+    #[case(4400, 2, 700)]
+    #[case(4000, 2, 300)]
+    #[case(900, 0, -100)]
+    fn when_reading_is_converted_then_it_is_interpolated_between_the_calibration_points(
+        #[case] reading: u16,
+        #[case] extra_bits: u32,
+        #[case] expected: i16,
+    ) {
+        // When
+        let result = CALIBRATION.decicelsius(reading, extra_bits);
+
+        // Then
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn when_calibration_is_typical_then_25_degrees_read_as_25_degrees() {
+        // Given
+        let reading = (0.76 / 3.3 * 4095.0_f32).round() as u16;
+
+        // When
+        let result = TemperatureCalibration::TYPICAL.decicelsius(reading, 0);
+
+        // Then
+        assert!((245..=255).contains(&result), "{result}");
+    }
+
+    #[rstest]
+    #[case(CALIBRATION, true)]
+    #[case(TemperatureCalibration::TYPICAL, true)]
+    #[case(TemperatureCalibration { at_30: 0xFFFF, at_110: 0xFFFF }, false)]
+    #[case(TemperatureCalibration { at_30: 1200, at_110: 1000 }, false)]
+    fn when_calibration_is_checked(
+        #[case] calibration: TemperatureCalibration,
+        #[case] expected: bool,
+    ) {
+        // When
+        let result = calibration.is_valid();
+
+        // Then
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn when_calibration_is_invalid_then_the_temperature_is_unknown() {
+        // Given
+        let calibration = TemperatureCalibration {
+            at_30: 1200,
+            at_110: 1200,
+        };
+
+        // When
+        let result = calibration.decicelsius(1200, 0);
+
+        // Then
+        assert_eq!(result, i16::MIN);
+    }
+}
@@ -1,53 +1,54 @@
-use bytemuck::{Pod, Zeroable};
-use core::sync::atomic::AtomicI16;
+use crate::POLLING;
+use embassy_stm32::peripherals::USB_OTG_FS;
 use embassy_stm32::rcc::{
     mux, AHBPrescaler, APBPrescaler, Hse, HseMode, Pll, PllMul, PllPDiv, PllPreDiv, PllQDiv,
     PllSource, Sysclk,
 };
 use embassy_stm32::time::Hertz;
 use embassy_stm32::Config;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_usb::class::cdc_acm;
 use embassy_usb::class::hid;
+use embassy_usb::class::hid::{ReportId, RequestHandler};
+use embassy_usb::control::{InResponse, OutResponse, Recipient, Request, RequestType};
+use embassy_usb::types::InterfaceNumber;
+use embassy_usb::{Builder, Handler};
+use rusty_pedalbox::calibration::CalibrationSession;
+use rusty_pedalbox::dfu::{
+    functional_descriptor, DfuRequest, DfuState, DfuStatus, ATTRIBUTE_CAN_DOWNLOAD,
+    ATTRIBUTE_CAN_UPLOAD, ATTRIBUTE_WILL_DETACH, BOOTLOADER_TRANSFER_SIZE, DFU_CLASS,
+    DFU_FUNCTIONAL_DESCRIPTOR, DFU_PROTOCOL_RUNTIME, DFU_STATUS_LENGTH, DFU_SUBCLASS,
+};
+use rusty_pedalbox::feature_report::{
+    FaultStatus, FeatureReport, FeatureReportId, ReportStatus, SessionCommand, SessionStatus,
+    CALIBRATION_SESSION_REPORT_ID, FAULT_STATUS_REPORT_ID, REPORT_MODE_REPORT_ID,
+    REPORT_STATISTICS_REPORT_ID, RESET_CAUSE_REPORT_ID,
+};
+use rusty_pedalbox::fmt::{info, warn};
+use rusty_pedalbox::input_report::{MAX_INPUT_REPORT_SIZE, PEDALBOX_REPORT_DESCRIPTOR};
+use rusty_pedalbox::io_monitors::LoadCellStatistics;
+use rusty_pedalbox::pedal_state::PedalState;
+use rusty_pedalbox::report_mode::{ReportMode, ReportStatistics, SharedReportMode};
+use rusty_pedalbox::settings::SharedSettings;
+use rusty_pedalbox::supervisor::ResetCause;
+use rusty_pedalbox::tare::TareRequest;
+use rusty_pedalbox::{USB_PRODUCT_ID, USB_VENDOR_ID};
 use static_cell::StaticCell;
 
-#[repr(C, packed)]
-#[derive(Clone, Copy, Pod, Zeroable)]
-pub struct PedalboxReport {
-    pub x: i16,
-    pub y: i16,
-    pub z: i16,
-    pub buttons: u8,
-}
+pub static PEDAL_STATE: PedalState = PedalState::new(uptime_us);
 
-pub static AXIS_X: AtomicI16 = AtomicI16::new(0);
-pub static AXIS_Y: AtomicI16 = AtomicI16::new(0);
-pub static AXIS_Z: AtomicI16 = AtomicI16::new(0);
-
-const PEDALBOX_REPORT_DESCRIPTOR: &[u8] = &[
-    0x05, 0x01, /*  Usage Page (Desktop),           */
-    0x09, 0x04, /*  Usage (Joystick),               */
-    0xA1, 0x01, /*  Collection (Application),       */
-    0x05, 0x01, /*      Usage Page (Desktop),       */
-    0x09, 0x30, /*      Usage (X),                  */
-    0x09, 0x31, /*      Usage (Y),                  */
-    0x09, 0x32, /*      Usage (Z),                  */
-    0x16, 0x00, 0x80, /*      Logical Minimum (-32768),   */
-    0x26, 0xFF, 0x7F, /*      Logical Maximum (32767),    */
-    0x75, 0x10, /*      Report Size (16),           */
-    0x95, 0x03, /*      Report Count (3),           */
-    0x81, 0x02, /*      Input (Variable),           */
-    0x05, 0x09, /*      Usage Page (Button),        */
-    0x19, 0x01, /*      Usage Minimum (01h),        */
-    0x29, 0x01, /*      Usage Maximum (01h),        */
-    0x14, /*      Logical Minimum (0),        */
-    0x25, 0x01, /*      Logical Maximum (1),        */
-    0x75, 0x01, /*      Report Size (1),            */
-    0x95, 0x01, /*      Report Count (1),           */
-    0x81, 0x02, /*      Input (Variable),           */
-    0x75, 0x07, /*      Report Size (7),            */
-    0x95, 0x01, /*      Report Count (1),           */
-    0x81, 0x01, /*      Input (Constant),           */
-    0xC0, /*  End Collection                  */
-];
+pub static BRAKE_STATISTICS: LoadCellStatistics = LoadCellStatistics::new();
+pub static BRAKE_TARE_REQUEST: TareRequest = TareRequest::new();
+
+pub static SETTINGS: SharedSettings = SharedSettings::new();
+pub static CALIBRATION_SESSION: CalibrationSession = CalibrationSession::new();
+pub static REPORT_MODE: SharedReportMode = SharedReportMode::new(POLLING.report_mode);
+pub static REPORT_STATISTICS: ReportStatistics = ReportStatistics::new();
+
+fn uptime_us() -> u64 {
+    embassy_time::Instant::now().as_micros()
+}
 
 pub static EP_OUT_BUFFER: StaticCell<[u8; 256]> = StaticCell::new();
 pub static CONFIG_DESC: StaticCell<[u8; 256]> = StaticCell::new();
@@ -55,6 +56,209 @@ pub static BOS_DESC: StaticCell<[u8; 256]> = StaticCell::new();
 pub static MSOS_DESC: StaticCell<[u8; 128]> = StaticCell::new();
 pub static CONTROL_BUF: StaticCell<[u8; 64]> = StaticCell::new();
 pub static HID_STATE: StaticCell<hid::State<'static>> = StaticCell::new();
+pub static CDC_STATE: StaticCell<cdc_acm::State<'static>> = StaticCell::new();
+pub static FEATURE_HANDLER: StaticCell<FeatureReportHandler> = StaticCell::new();
+pub static DFU_HANDLER: StaticCell<DfuRuntimeHandler> = StaticCell::new();
+
+/// Raised by a DFU `DETACH`, the firmware then reboots into the ROM bootloader.
+pub static DFU_DETACH: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+/// Longest time the firmware takes to leave after a `DETACH`.
+const DFU_DETACH_TIMEOUT_MS: u16 = 100;
+
+/// Serves the feature reports holding the axis settings, the calibration session, the
+/// report mode, the sensor faults and the cause of the last reset.
+pub struct FeatureReportHandler {
+    settings: &'static SharedSettings,
+    session: &'static CalibrationSession,
+    report_mode: &'static SharedReportMode,
+    report_statistics: &'static ReportStatistics,
+    pedal_state: &'static PedalState,
+    reset_cause: ResetCause,
+}
+
+impl FeatureReportHandler {
+    pub fn new(
+        settings: &'static SharedSettings,
+        session: &'static CalibrationSession,
+        report_mode: &'static SharedReportMode,
+        report_statistics: &'static ReportStatistics,
+        pedal_state: &'static PedalState,
+        reset_cause: ResetCause,
+    ) -> Self {
+        Self {
+            settings,
+            session,
+            report_mode,
+            report_statistics,
+            pedal_state,
+            reset_cause,
+        }
+    }
+
+    fn execute_session_command(&mut self, data: &[u8]) -> OutResponse {
+        match SessionCommand::decode(data) {
+            Ok(command) if command.execute(self.session) => {
+                info!("Calibration command {:?} executed", command);
+                OutResponse::Accepted
+            }
+            Ok(command) => {
+                warn!("Calibration command {:?} refused", command);
+                OutResponse::Rejected
+            }
+            Err(e) => {
+                warn!("Calibration command rejected: {:?}", e);
+                OutResponse::Rejected
+            }
+        }
+    }
+
+    fn apply_settings_report(&mut self, id: u8, data: &[u8]) -> OutResponse {
+        match FeatureReport::decode(data) {
+            Ok(report) if report.id().value() == id => {
+                self.settings
+                    .update(report.id().axis, |settings| report.apply(settings));
+                info!("Feature report {} applied", id);
+                OutResponse::Accepted
+            }
+            Ok(_) => {
+                warn!("Feature report {} carries a different report ID", id);
+                OutResponse::Rejected
+            }
+            Err(e) => {
+                warn!("Feature report {} rejected: {:?}", id, e);
+                OutResponse::Rejected
+            }
+        }
+    }
+
+    fn set_report_mode(&mut self, data: &[u8]) -> OutResponse {
+        match ReportMode::decode(data) {
+            Ok(mode) => {
+                info!("Report mode {:?} selected", mode);
+                self.report_mode.set(mode);
+                OutResponse::Accepted
+            }
+            Err(e) => {
+                warn!("Report mode rejected: {:?}", e);
+                OutResponse::Rejected
+            }
+        }
+    }
+}
+
+impl RequestHandler for FeatureReportHandler {
+    fn get_report(&mut self, id: ReportId, buf: &mut [u8]) -> Option<usize> {
+        let ReportId::Feature(id) = id else {
+            return None;
+        };
+        match id {
+            CALIBRATION_SESSION_REPORT_ID => SessionStatus::from_session(self.session).encode(buf),
+            REPORT_MODE_REPORT_ID => self.report_mode.get().encode(buf),
+            REPORT_STATISTICS_REPORT_ID => {
+                ReportStatus::from_statistics(self.report_statistics).encode(buf)
+            }
+            FAULT_STATUS_REPORT_ID => {
+                FaultStatus::from_snapshot(&self.pedal_state.snapshot()).encode(buf)
+            }
+            RESET_CAUSE_REPORT_ID => self.reset_cause.encode(buf),
+            _ => {
+                let id = FeatureReportId::try_from(id).ok()?;
+                FeatureReport::from_settings(id, &self.settings.get(id.axis)).encode(buf)
+            }
+        }
+        .ok()
+    }
+
+    fn set_report(&mut self, id: ReportId, data: &[u8]) -> OutResponse {
+        let ReportId::Feature(id) = id else {
+            return OutResponse::Rejected;
+        };
+        match id {
+            CALIBRATION_SESSION_REPORT_ID => self.execute_session_command(data),
+            REPORT_MODE_REPORT_ID => self.set_report_mode(data),
+            REPORT_STATISTICS_REPORT_ID => {
+                self.report_statistics.reset_max_latency();
+                OutResponse::Accepted
+            }
+            FAULT_STATUS_REPORT_ID => {
+                info!("Latched faults cleared");
+                self.pedal_state.clear_latched_faults();
+                OutResponse::Accepted
+            }
+            _ => self.apply_settings_report(id, data),
+        }
+    }
+}
+
+/// DFU runtime interface. It only announces that the device can be updated and detaches
+/// into the ROM bootloader, which does the update in DFU mode.
+pub struct DfuRuntimeHandler {
+    interface: InterfaceNumber,
+}
+
+impl DfuRuntimeHandler {
+    /// Adds the DFU runtime interface to the device.
+    pub fn add(builder: &mut Builder<'static, embassy_stm32::usb::Driver<'static, USB_OTG_FS>>) {
+        let mut function = builder.function(DFU_CLASS, DFU_SUBCLASS, DFU_PROTOCOL_RUNTIME);
+        let mut interface = function.interface();
+        let number = interface.interface_number();
+        let mut alt = interface.alt_setting(DFU_CLASS, DFU_SUBCLASS, DFU_PROTOCOL_RUNTIME, None);
+        alt.descriptor(
+            DFU_FUNCTIONAL_DESCRIPTOR,
+            &functional_descriptor(
+                ATTRIBUTE_CAN_DOWNLOAD | ATTRIBUTE_CAN_UPLOAD | ATTRIBUTE_WILL_DETACH,
+                DFU_DETACH_TIMEOUT_MS,
+                BOOTLOADER_TRANSFER_SIZE,
+            ),
+        );
+        drop(function);
+        builder.handler(DFU_HANDLER.init(Self { interface: number }));
+    }
+
+    fn is_addressed(&self, request: &Request) -> bool {
+        request.request_type == RequestType::Class
+            && request.recipient == Recipient::Interface
+            && request.index == u8::from(self.interface) as u16
+    }
+}
+
+impl Handler for DfuRuntimeHandler {
+    fn control_out(&mut self, request: Request, _data: &[u8]) -> Option<OutResponse> {
+        if !self.is_addressed(&request) {
+            return None;
+        }
+        match DfuRequest::try_from(request.request) {
+            Ok(DfuRequest::Detach) => {
+                info!("DFU detach requested");
+                DFU_DETACH.signal(());
+                Some(OutResponse::Accepted)
+            }
+            _ => Some(OutResponse::Rejected),
+        }
+    }
+
+    fn control_in<'a>(&'a mut self, request: Request, buf: &'a mut [u8]) -> Option<InResponse<'a>> {
+        if !self.is_addressed(&request) {
+            return None;
+        }
+        match DfuRequest::try_from(request.request) {
+            Ok(DfuRequest::GetStatus) if buf.len() >= DFU_STATUS_LENGTH => {
+                let status = DfuStatus {
+                    status: 0,
+                    poll_timeout_ms: 0,
+                    state: DfuState::AppIdle,
+                };
+                buf[..DFU_STATUS_LENGTH].copy_from_slice(&status.encode());
+                Some(InResponse::Accepted(&buf[..DFU_STATUS_LENGTH]))
+            }
+            Ok(DfuRequest::GetState) if !buf.is_empty() => {
+                buf[0] = DfuState::AppIdle as u8;
+                Some(InResponse::Accepted(&buf[..1]))
+            }
+            _ => Some(InResponse::Rejected),
+        }
+    }
+}
 
 pub trait UsbConfiguration {
     fn usb_configuration() -> Config;
@@ -93,18 +297,18 @@ impl PedalboxConfiguration for hid::Config<'_> {
         Self {
             report_descriptor: PEDALBOX_REPORT_DESCRIPTOR,
             request_handler: None,
-            poll_ms: 10,
-            max_packet_size: 8,
+            poll_ms: POLLING.poll_ms,
+            max_packet_size: MAX_INPUT_REPORT_SIZE as u16,
         }
     }
 }
 
 impl PedalboxConfiguration for embassy_usb::Config<'_> {
     fn pedalbox_configuration() -> Self {
-        let mut config = embassy_usb::Config::new(0xcafe, 0x2025);
+        let mut config = embassy_usb::Config::new(USB_VENDOR_ID, USB_PRODUCT_ID);
         config.manufacturer = Some("8 BitHunters");
         config.product = Some("Rusty Pedalbox");
-        config.serial_number = Some("0001");
+        // The serial number comes from the unique ID of the chip, see `serial_number.rs`
         config
     }
 }
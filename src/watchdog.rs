@@ -0,0 +1,29 @@
+use embassy_stm32::pac;
+use rusty_pedalbox::supervisor::{culprit_record, ResetCause, SupervisedTask};
+
+/// RTC backup register holding the culprit of a watchdog reset. The backup domain keeps it
+/// across every reset but a power loss.
+const CULPRIT_REGISTER: usize = 0;
+
+/// Reads why the chip was reset and clears the flags, so the next boot only sees its own
+/// reset. Needs the backup domain set up by `embassy_stm32::init`.
+pub fn take_reset_cause() -> ResetCause {
+    let csr = pac::RCC.csr().read().0;
+    let backup = pac::RTC.bkpr(CULPRIT_REGISTER).read().bkp();
+    pac::RCC.csr().modify(|w| w.set_rmvf(true));
+    pac::RTC.bkpr(CULPRIT_REGISTER).write(|w| w.set_bkp(0));
+    ResetCause::from_registers(csr, backup)
+}
+
+/// Records the task the watchdog is about to reset the chip for.
+pub fn record_culprit(task: SupervisedTask) {
+    pac::RTC
+        .bkpr(CULPRIT_REGISTER)
+        .write(|w| w.set_bkp(culprit_record(task)));
+}
+
+/// Stops the watchdog while the debugger halts the core.
+#[cfg(feature = "debug")]
+pub fn freeze_on_halt() {
+    pac::DBGMCU.apb1fzr().modify(|w| w.set_iwdg(true));
+}